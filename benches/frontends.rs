@@ -0,0 +1,145 @@
+//! Compares the throughput and allocation behaviour of the crate's front-ends when reading the
+//! same ADTS data:
+//!
+//!  - `callback`: `AdtsParser` driving an `AdtsConsumer`, given the whole input at once, and in
+//!    1316 byte chunks (the payload size of 7 MPEG-TS packets, typical of UDP delivery)
+//!  - `reader`: `FollowingAdtsReader`, iterating over owned frames from an `io::Read`
+//!  - `async`: `AsyncAdtsReader` reading from a tokio `AsyncRead` (with the `tokio` feature)
+//!
+//! Run with `cargo bench --all-features --bench frontends`.  Allocation counts per frame are
+//! printed before the timings.
+
+use adts_reader::follow::FollowingAdtsReader;
+use adts_reader::testing::StreamBuilder;
+use adts_reader::*;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counts allocations made through the global allocator
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const FRAMES: usize = 10_000;
+
+/// Frames of 48kHz stereo AAC-LC, with payload sizes varying as they would at around 128kbit/s
+fn input() -> Vec<u8> {
+    (0..FRAMES)
+        .fold(StreamBuilder::new(), |builder, i| {
+            builder.frame(&vec![i as u8; 300 + (i * 37) % 100])
+        })
+        .build()
+}
+
+/// Totals the payload bytes seen, so that the work can't be optimised away
+#[derive(Default)]
+struct Summing {
+    bytes: usize,
+}
+impl AdtsConsumer for Summing {
+    fn new_config(
+        &mut self,
+        _: MpegVersion,
+        _: ProtectionIndicator,
+        _: AudioObjectType,
+        _: SamplingFrequency,
+        _: u8,
+        _: ChannelConfiguration,
+        _: Originality,
+        _: u8,
+    ) {
+    }
+    fn payload(&mut self, _: u16, _: u8, buf: &[u8]) {
+        self.bytes += buf.len();
+    }
+    fn error(&mut self, err: AdtsParseError) {
+        panic!("unexpected error {:?}", err);
+    }
+}
+
+fn callback(data: &[u8], chunk_size: usize) -> usize {
+    let mut parser = AdtsParser::new(Summing::default());
+    for chunk in data.chunks(chunk_size) {
+        parser.push(chunk);
+    }
+    parser.consumer.bytes
+}
+
+fn reader(data: &[u8]) -> usize {
+    FollowingAdtsReader::new(io::Cursor::new(data))
+        .idle_timeout(Some(Duration::ZERO))
+        .map(|frame| frame.unwrap().len())
+        .sum()
+}
+
+#[cfg(feature = "tokio")]
+fn async_reader(runtime: &tokio::runtime::Runtime, data: &[u8]) -> usize {
+    runtime.block_on(async {
+        let mut reader = adts_reader::async_reader::AsyncAdtsReader::new(data);
+        let mut bytes = 0;
+        while let Some(frame) = reader.next_frame().await.unwrap() {
+            bytes += frame.len();
+        }
+        bytes
+    })
+}
+
+/// Prints the number of allocations per frame made by the given front-end
+fn report_allocations(name: &str, f: impl FnOnce() -> usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    criterion::black_box(f());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{:<20} {:>8.3} allocations/frame",
+        name,
+        allocations as f64 / FRAMES as f64
+    );
+}
+
+fn frontends(c: &mut Criterion) {
+    let data = input();
+    #[cfg(feature = "tokio")]
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    report_allocations("callback/whole", || callback(&data, data.len()));
+    report_allocations("callback/chunked", || callback(&data, 1316));
+    report_allocations("reader", || reader(&data));
+    #[cfg(feature = "tokio")]
+    report_allocations("async", || async_reader(&runtime, &data));
+
+    let mut group = c.benchmark_group("frontends");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("callback/whole", |b| b.iter(|| callback(&data, data.len())));
+    group.bench_function("callback/chunked", |b| b.iter(|| callback(&data, 1316)));
+    group.bench_function("reader", |b| b.iter(|| reader(&data)));
+    #[cfg(feature = "tokio")]
+    group.bench_function("async", |b| b.iter(|| async_reader(&runtime, &data)));
+    group.finish();
+}
+
+criterion_group!(benches, frontends);
+criterion_main!(benches);
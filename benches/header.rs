@@ -0,0 +1,108 @@
+//! Compares reading every header field of every frame of a long recording with `AdtsHeader`,
+//! whose accessors each extract their field from the buffer, and with `DecodedAdtsHeader`, which
+//! decodes all the fields in one pass:
+//!
+//!  - `fields`: reading each field once through the accessors
+//!  - `debug`: formatting each header with `Debug`, which for both types decodes the fields in one
+//!    pass
+//!
+//! Run with `cargo bench --features testing --bench header`.  The input is two hours of 48kHz stereo AAC-LC at
+//! around 96kbit/s, some 337,500 frames.
+
+use adts_reader::testing::StreamBuilder;
+use adts_reader::*;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::fmt::Write;
+
+const FRAMES: usize = 2 * 60 * 60 * 48000 / 1024;
+
+/// Frames with payload sizes varying as they would at around 96kbit/s
+fn input() -> Vec<u8> {
+    (0..FRAMES)
+        .fold(StreamBuilder::new(), |builder, i| {
+            builder.frame(&vec![i as u8; 200 + (i * 37) % 100])
+        })
+        .build()
+}
+
+/// Combines the values of every field, so that none of the work can be optimised away
+macro_rules! fold_fields {
+    ($h:expr) => {
+        $h.mpeg_version() as u64
+            + $h.protection() as u64
+            + $h.audio_object_type() as u64
+            + $h.sampling_frequency() as u64
+            + u64::from($h.private_bit())
+            + $h.channel_configuration() as u64
+            + $h.originality() as u64
+            + u64::from($h.home())
+            + u64::from($h.copyright_identification_bit())
+            + $h.copyright_identification_start() as u64
+            + u64::from($h.frame_length())
+            + u64::from($h.adts_buffer_fullness())
+            + u64::from($h.crc().unwrap_or(0))
+            + u64::from($h.number_of_raw_data_blocks_in_frame())
+            + $h.payload().unwrap().len() as u64
+    };
+}
+
+fn lazy_fields(data: &[u8]) -> u64 {
+    AdtsFrameIter::new(data)
+        .map(|h| {
+            let h = h.unwrap();
+            fold_fields!(h)
+        })
+        .sum()
+}
+
+fn decoded_fields(data: &[u8]) -> u64 {
+    AdtsFrameIter::new(data)
+        .map(|h| {
+            let h = DecodedAdtsHeader::from(h.unwrap());
+            fold_fields!(h)
+        })
+        .sum()
+}
+
+fn lazy_debug(data: &[u8], out: &mut String) -> usize {
+    let mut len = 0;
+    for h in AdtsFrameIter::new(data) {
+        out.clear();
+        write!(out, "{:?}", h.unwrap()).unwrap();
+        len += out.len();
+    }
+    len
+}
+
+fn decoded_debug(data: &[u8], out: &mut String) -> usize {
+    let mut len = 0;
+    for h in AdtsFrameIter::new(data) {
+        out.clear();
+        write!(out, "{:?}", DecodedAdtsHeader::from(h.unwrap())).unwrap();
+        len += out.len();
+    }
+    len
+}
+
+fn header(c: &mut Criterion) {
+    let data = input();
+    assert_eq!(lazy_fields(&data), decoded_fields(&data));
+
+    let mut group = c.benchmark_group("fields");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    group.bench_function("lazy", |b| b.iter(|| lazy_fields(&data)));
+    group.bench_function("decoded", |b| b.iter(|| decoded_fields(&data)));
+    group.finish();
+
+    let mut out = String::new();
+    let mut group = c.benchmark_group("debug");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    group.bench_function("lazy", |b| b.iter(|| lazy_debug(&data, &mut out)));
+    group.bench_function("decoded", |b| b.iter(|| decoded_debug(&data, &mut out)));
+    group.finish();
+}
+
+criterion_group!(benches, header);
+criterion_main!(benches);
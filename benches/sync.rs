@@ -0,0 +1,70 @@
+//! Measures the speed of searching for sync-words through data that holds no frames, as when
+//! skipping junk at the start of a recording, or resynchronising after corruption:
+//!
+//!  - `find_syncword`: the search alone
+//!  - `resync`: `AdtsParser` skipping the junk in 1316 byte chunks, before the frame at its end
+//!
+//! Run with `cargo bench --bench sync`, adding `--features memchr` to compare the faster search.
+//! The input is 16MiB of pseudo-random bytes without any `0xfff` sync-word, of which roughly one
+//! byte in 256 is `0xff`, so every candidate must be checked and rejected.  For comparison, a
+//! 500Mbit/s input arrives at about 60MiB/s.
+
+use adts_reader::*;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const LEN: usize = 16 * 1024 * 1024;
+
+/// Junk with no sync-word, followed by a single frame
+fn input() -> Vec<u8> {
+    let mut state = 0x1234_5678u32;
+    let mut data: Vec<u8> = (0..LEN)
+        .map(|_| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 24) as u8
+        })
+        .collect();
+    for i in 1..data.len() {
+        if data[i - 1] == 0xff && data[i] & 0xf0 == 0xf0 {
+            data[i] = 0;
+        }
+    }
+    data.extend_from_slice(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+    data
+}
+
+/// Counts the frames found, so that the work can't be optimised away
+#[derive(Default)]
+struct Counting {
+    frames: usize,
+}
+impl AdtsConsumer2 for Counting {
+    fn on_frame(&mut self, _frame: &FrameContext<'_>) {
+        self.frames += 1;
+    }
+}
+
+fn resync(data: &[u8]) -> usize {
+    let mut parser = AdtsParser::new(Counting::default());
+    for chunk in data.chunks(1316) {
+        parser.push(chunk);
+    }
+    parser.consumer.frames
+}
+
+fn sync(c: &mut Criterion) {
+    let data = input();
+    assert_eq!(find_syncword(&data), Some(LEN));
+    assert_eq!(resync(&data), 1);
+
+    let mut group = c.benchmark_group("sync");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("find_syncword", |b| b.iter(|| find_syncword(&data)));
+    group.bench_function("resync", |b| b.iter(|| resync(&data)));
+    group.finish();
+}
+
+criterion_group!(benches, sync);
+criterion_main!(benches);
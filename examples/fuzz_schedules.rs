@@ -0,0 +1,40 @@
+//! Writes variants of an ADTS fixture, damaged and divided into `push()` buffers in many
+//! different ways, into the given directory; for seeding the corpus of the `push_schedule` fuzz
+//! target in `fuzz/`:
+//!
+//!     cargo run --features testing --example fuzz_schedules fixture.aac fuzz/corpus/push_schedule [count] [seed]
+
+use adts_reader::testing::boundary_corpus;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("usage: fuzz_schedules <fixture> <corpus-directory> [count] [seed]");
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 || args.len() > 4 {
+        usage();
+    }
+    let number = |i: usize, default: u64| match args.get(i) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| usage()),
+        None => default,
+    };
+    let count = number(2, 240) as usize;
+    let seed = number(3, 1);
+    let fixture = fs::read(&args[0]).unwrap_or_else(|e| {
+        eprintln!("{}: {}", args[0], e);
+        process::exit(1);
+    });
+    for (i, schedule) in boundary_corpus(&fixture, count, seed).iter().enumerate() {
+        let path = Path::new(&args[1]).join(format!("schedule-{:04}", i));
+        if let Err(e) = fs::write(&path, schedule.to_bytes()) {
+            eprintln!("{}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Writes a set of synthetic ADTS streams, exercising resync and error handling, into the given
+//! directory; for seeding the corpus of the fuzz targets in `fuzz/`:
+//!
+//!     cargo run --features testing --example fuzz_seeds fuzz/corpus/fuzz_target_1
+
+use adts_reader::testing::StreamBuilder;
+use adts_reader::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let dir = match env::args().nth(1) {
+        Some(dir) => dir,
+        None => {
+            eprintln!("usage: fuzz_seeds <corpus-directory>");
+            process::exit(2);
+        }
+    };
+    let cfg = StreamBuilder::DEFAULT_CONFIG;
+    let mono = AdtsStreamConfig {
+        channel_configuration: ChannelConfiguration::Mono,
+        sampling_frequency: SamplingFrequency::Freq44100,
+        ..cfg
+    };
+    let crc = FrameOptions {
+        crc: true,
+        ..FrameOptions::default()
+    };
+    let seeds = [
+        ("seed-clean", StreamBuilder::new().frames(4, cfg).build()),
+        (
+            "seed-junk",
+            StreamBuilder::new()
+                .frames(2, cfg)
+                .junk(5)
+                .frames(2, cfg)
+                .build(),
+        ),
+        (
+            "seed-config-change",
+            StreamBuilder::new()
+                .frames(2, cfg)
+                .config_change(mono)
+                .frames(1, mono)
+                .build(),
+        ),
+        (
+            "seed-truncated",
+            StreamBuilder::new()
+                .frames(3, cfg)
+                .truncate_last_frame()
+                .build(),
+        ),
+        (
+            "seed-crc",
+            StreamBuilder::new().options(crc).frames(3, cfg).build(),
+        ),
+        (
+            "seed-false-sync",
+            StreamBuilder::new()
+                .frames(1, cfg)
+                .bytes(&[0xff, 0xf1, 0, 0])
+                .frames(1, cfg)
+                .build(),
+        ),
+    ];
+    for (name, data) in seeds {
+        let path = Path::new(&dir).join(name);
+        if let Err(e) = fs::write(&path, data) {
+            eprintln!("{}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate adts_reader;
+
+use adts_reader::testing::PushSchedule;
+use adts_reader::*;
+
+struct NullConsumer;
+
+impl AdtsConsumer2 for NullConsumer {
+    fn on_frame(&mut self, _frame: &FrameContext<'_>) {}
+}
+
+fuzz_target!(|data: &[u8]| {
+    let schedule = PushSchedule::from_bytes(data);
+    let mut p = AdtsParser::new(NullConsumer).with_crc_policy(crc::CrcPolicy::Drop);
+    for buf in schedule.pushes() {
+        p.push(buf);
+    }
+    assert!(p.pending_bytes() <= 8191);
+});
@@ -0,0 +1,568 @@
+//! Health analysis of large ADTS archives, optionally applying expensive checks to only a sample
+//! of the frames.
+//!
+//! Cheap framing checks are always applied to every frame, but CRC verification and (with the
+//! `elements` feature) channel layout checks cost time in proportion to the payload size.  For
+//! huge archives, [`Sampling`](enum.Sampling.html) allows those checks to be limited to every Nth
+//! frame, or to one frame per interval of media time, with the
+//! [`HealthReport`](struct.HealthReport.html) giving failure rates over the frames actually
+//! checked.
+//!
+//! Each problem found is also listed as an [`Anomaly`](struct.Anomaly.html), giving both its byte
+//! offset and its media time, so that it can be matched up with what a listener would have heard.
+//!
+//! Optionally, [`LintOptions`](struct.LintOptions.html) enables checks for encoder settings which
+//! are legal, but not best practice, reported as [`Warning`s](enum.Warning.html).
+
+use crate::crc::{self, CrcAlgorithm};
+use crate::encryption::PayloadEncryption;
+use crate::sync::find_possible_sync;
+use crate::{maybe_sync, AdtsHeader, FrameLength, MediaTime, ProtectionIndicator};
+use std::fmt;
+use std::time::Duration;
+
+/// Selects which frames [`analyze()`](fn.analyze.html) applies its expensive checks to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sampling {
+    /// Check every frame
+    All,
+    /// Check the first frame, and every Nth frame thereafter.  A value of `0` is treated as `1`.
+    EveryNthFrame(u64),
+    /// Check the first frame starting at or after each multiple of the given interval of media
+    /// time.  Frame start times are calculated exactly from the count of preceding samples.
+    Interval(Duration),
+}
+
+/// Options for [`analyze()`](fn.analyze.html).
+///
+/// Further fields may be added in future releases, so start from `AnalysisOptions::default()` and
+/// change the fields of interest.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AnalysisOptions {
+    /// (default `Sampling::All`)
+    pub sampling: Sampling,
+    /// Verify the CRC of sampled frames which carry one (default `true`)
+    pub check_crc: bool,
+    /// Scan the payload of sampled frames, checking that the channel elements present match the
+    /// `channel_configuration` header field (default `true`)
+    #[cfg(feature = "elements")]
+    pub check_channel_layout: bool,
+    /// Best practice checks to apply to every frame, or `None` to skip them (default `None`)
+    pub lints: Option<LintOptions>,
+    /// Number of samples per `raw_data_block`, used to calculate media time (default
+    /// `FrameLength::Samples1024`)
+    pub frame_length: FrameLength,
+    /// The CRC implementation used when checking frames (default
+    /// [`crc::DEFAULT`](../crc/static.DEFAULT.html))
+    pub crc_algorithm: &'static dyn CrcAlgorithm,
+    /// How frame payloads are encrypted; checks which depend on the payload content (the CRC and
+    /// channel layout checks) are skipped for encrypted payloads (default
+    /// `PayloadEncryption::Clear`)
+    pub encryption: PayloadEncryption,
+    /// The most anomalies to list in `HealthReport::anomalies`; any more are still counted, but
+    /// not listed (default `1000`)
+    pub max_anomalies: usize,
+}
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            sampling: Sampling::All,
+            check_crc: true,
+            #[cfg(feature = "elements")]
+            check_channel_layout: true,
+            lints: None,
+            frame_length: FrameLength::default(),
+            crc_algorithm: &crc::DEFAULT,
+            encryption: PayloadEncryption::Clear,
+            max_anomalies: 1000,
+        }
+    }
+}
+
+/// Options for the best practice checks made by [`analyze()`](fn.analyze.html).
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    /// Warn about frames without CRC protection, as expected by many broadcast delivery profiles
+    /// (default `false`)
+    pub expect_crc: bool,
+}
+
+/// Stream properties which don't break the specification, but suggest that the encoder is
+/// misconfigured.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum Warning {
+    /// Every frame has the same `adts_buffer_fullness`, but not the value `0x7FF` that signals a
+    /// variable rate stream.  A constant rate encoder's buffer fullness would vary from frame to
+    /// frame, so this stream is most likely variable rate, mis-signalled.
+    ConstantBufferFullness { value: u16 },
+    /// Frames lacking a CRC were found when `LintOptions::expect_crc` was set
+    CrcAbsent { frames: u64 },
+    /// The `number_of_raw_data_blocks_in_frame` varies within the stream, which some decoders
+    /// handle poorly
+    InconsistentRawDataBlocks { min: u8, max: u8 },
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::ConstantBufferFullness { value } => write!(
+                f,
+                "adts_buffer_fullness is always {:#x}; variable rate streams should use 0x7ff",
+                value
+            ),
+            Warning::CrcAbsent { frames } => write!(f, "{} frames have no CRC", frames),
+            Warning::InconsistentRawDataBlocks { min, max } => write!(
+                f,
+                "number_of_raw_data_blocks_in_frame varies between {} and {}",
+                min, max
+            ),
+        }
+    }
+}
+
+/// Gathers the stream properties needed by the best practice checks
+#[derive(Default)]
+struct Linter {
+    /// the buffer fullness of the first frame, and whether any later frame differed
+    fullness: Option<(u16, bool)>,
+    frames_without_crc: u64,
+    blocks: Option<(u8, u8)>,
+}
+impl Linter {
+    fn frame(&mut self, header: &AdtsHeader<'_>) {
+        let fullness = header.adts_buffer_fullness();
+        match self.fullness {
+            None => self.fullness = Some((fullness, false)),
+            Some((first, _)) if first != fullness => self.fullness = Some((first, true)),
+            Some(_) => (),
+        }
+        if header.protection() == ProtectionIndicator::CrcAbsent {
+            self.frames_without_crc += 1;
+        }
+        let blocks = header.number_of_raw_data_blocks_in_frame();
+        self.blocks = Some(match self.blocks {
+            None => (blocks, blocks),
+            Some((min, max)) => (min.min(blocks), max.max(blocks)),
+        });
+    }
+
+    fn warnings(&self, options: &LintOptions, frames: u64) -> Vec<Warning> {
+        let mut warnings = vec![];
+        // a single frame says nothing about whether the value is constant
+        if let Some((value, false)) = self.fullness {
+            if frames > 1 && value != 0x7ff {
+                warnings.push(Warning::ConstantBufferFullness { value });
+            }
+        }
+        if options.expect_crc && self.frames_without_crc > 0 {
+            warnings.push(Warning::CrcAbsent {
+                frames: self.frames_without_crc,
+            });
+        }
+        if let Some((min, max)) = self.blocks {
+            if min != max {
+                warnings.push(Warning::InconsistentRawDataBlocks { min, max });
+            }
+        }
+        warnings
+    }
+}
+
+/// The kinds of problem listed in [`HealthReport::anomalies`](struct.HealthReport.html#structfield.anomalies).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum AnomalyKind {
+    /// A run of bytes that weren't part of any frame
+    Garbage { len: u64 },
+    /// A sampled frame whose CRC did not match
+    CrcMismatch,
+    /// A sampled frame whose channel elements didn't match its channel configuration
+    #[cfg(feature = "elements")]
+    LayoutMismatch,
+    /// An incomplete frame at the end of the input
+    Truncated { len: u64 },
+}
+
+/// A problem found by [`analyze()`](fn.analyze.html), and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Anomaly {
+    /// Byte offset of the problem within the input
+    pub offset: u64,
+    /// Media time of the problem; the total duration of the frames preceding it
+    pub time: Duration,
+    pub kind: AnomalyKind,
+}
+
+/// Summary of the health of an ADTS stream, produced by [`analyze()`](fn.analyze.html).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HealthReport {
+    /// Total number of complete frames found
+    pub frames: u64,
+    /// Number of frames selected for the expensive checks
+    pub frames_sampled: u64,
+    /// Total media duration of all frames found
+    pub duration: Duration,
+    /// Number of runs of bytes that weren't part of any frame
+    pub garbage_runs: u64,
+    /// Total number of bytes that weren't part of any frame
+    pub garbage_bytes: u64,
+    /// Bytes at the end of the input holding an incomplete frame
+    pub truncated_bytes: u64,
+    /// Number of sampled frames whose CRC was verified
+    pub crc_checked: u64,
+    /// Number of sampled frames whose CRC did not match
+    pub crc_failures: u64,
+    /// Number of sampled frames whose channel layout was checked
+    #[cfg(feature = "elements")]
+    pub layout_checked: u64,
+    /// Number of sampled frames whose channel elements didn't match their channel configuration
+    #[cfg(feature = "elements")]
+    pub layout_mismatches: u64,
+    /// Results of the best practice checks, if enabled by `AnalysisOptions::lints`
+    pub warnings: Vec<Warning>,
+    /// The problems found, in stream order, up to `AnalysisOptions::max_anomalies` of them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anomalies: Vec<Anomaly>,
+}
+impl HealthReport {
+    /// The proportion of CRC-checked frames that failed, as an estimate of the failure rate
+    /// across the whole stream, or `None` if no CRCs were checked
+    pub fn crc_failure_rate(&self) -> Option<f64> {
+        rate(self.crc_failures, self.crc_checked)
+    }
+
+    /// The proportion of layout-checked frames that failed, as an estimate of the failure rate
+    /// across the whole stream, or `None` if no layouts were checked
+    #[cfg(feature = "elements")]
+    pub fn layout_mismatch_rate(&self) -> Option<f64> {
+        rate(self.layout_mismatches, self.layout_checked)
+    }
+}
+
+/// Where the frame or garbage currently being examined starts
+#[derive(Clone, Copy)]
+struct Position {
+    offset: u64,
+    time: Duration,
+}
+
+fn anomaly(report: &mut HealthReport, options: &AnalysisOptions, at: Position, kind: AnomalyKind) {
+    if report.anomalies.len() < options.max_anomalies {
+        report.anomalies.push(Anomaly {
+            offset: at.offset,
+            time: at.time,
+            kind,
+        });
+    }
+}
+
+fn rate(failures: u64, checked: u64) -> Option<f64> {
+    if checked == 0 {
+        None
+    } else {
+        Some(failures as f64 / checked as f64)
+    }
+}
+
+/// Decides which frames are sampled, given the stream position of each in turn
+struct Sampler<'a> {
+    sampling: &'a Sampling,
+    /// start time, in nanoseconds, of the next interval to be sampled
+    next_interval_nanos: u128,
+}
+impl<'a> Sampler<'a> {
+    fn select(&mut self, index: u64, start: MediaTime) -> bool {
+        match *self.sampling {
+            Sampling::All => true,
+            Sampling::EveryNthFrame(n) => index % n.max(1) == 0,
+            Sampling::Interval(interval) => {
+                // compare start.samples / start.rate against nanos / 10^9, exactly
+                let start_scaled = u128::from(start.samples()) * 1_000_000_000;
+                let rate = u128::from(start.rate());
+                if start_scaled < self.next_interval_nanos * rate {
+                    return false;
+                }
+                // skip over any intervals which contained no frame start
+                let interval = interval.as_nanos().max(1);
+                self.next_interval_nanos = (start_scaled / (interval * rate) + 1) * interval;
+                true
+            }
+        }
+    }
+}
+
+/// Analyses the ADTS data in the given buffer, applying the checks selected by `options`.
+///
+/// Data which is not part of a frame is skipped (and counted in the report) so that analysis of
+/// damaged archives can continue past the damage.
+///
+/// ```rust
+/// use adts_reader::analysis::{analyze, AnalysisOptions, Sampling};
+/// # let archive = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let mut options = AnalysisOptions::default();
+/// options.sampling = Sampling::EveryNthFrame(100);
+/// let report = analyze(&archive, &options);
+/// println!("{} frames, estimated CRC failure rate {:?}", report.frames, report.crc_failure_rate());
+/// ```
+pub fn analyze(buf: &[u8], options: &AnalysisOptions) -> HealthReport {
+    let mut report = HealthReport::default();
+    let mut sampler = Sampler {
+        sampling: &options.sampling,
+        next_interval_nanos: 0,
+    };
+    let mut linter = Linter::default();
+    let mut elapsed = MediaTime::ZERO;
+    // the offset at which the current run of garbage started
+    let mut garbage_start = None;
+    let mut pos = 0;
+    while pos < buf.len() {
+        let rest = &buf[pos..];
+        let at = Position {
+            offset: pos as u64,
+            time: elapsed.to_duration(),
+        };
+        let header = match AdtsHeader::from_bytes(rest) {
+            Ok(header) if header.payload().is_ok() => header,
+            Ok(_) => {
+                report.truncated_bytes = rest.len() as u64;
+                let kind = AnomalyKind::Truncated {
+                    len: report.truncated_bytes,
+                };
+                anomaly(&mut report, options, at, kind);
+                break;
+            }
+            Err(crate::AdtsHeaderError::NotEnoughData { .. }) if maybe_sync(rest) => {
+                report.truncated_bytes = rest.len() as u64;
+                let kind = AnomalyKind::Truncated {
+                    len: report.truncated_bytes,
+                };
+                anomaly(&mut report, options, at, kind);
+                break;
+            }
+            Err(_) => {
+                let len = find_possible_sync(&rest[1..]).map_or(rest.len(), |i| i + 1);
+                if garbage_start.is_none() {
+                    report.garbage_runs += 1;
+                    garbage_start = Some(at.offset);
+                    anomaly(&mut report, options, at, AnomalyKind::Garbage { len: 0 });
+                }
+                // the run's anomaly, if it was listed, is the latest
+                if let Some(Anomaly {
+                    offset,
+                    kind: AnomalyKind::Garbage { len: run },
+                    ..
+                }) = report.anomalies.last_mut()
+                {
+                    if Some(*offset) == garbage_start {
+                        *run += len as u64;
+                    }
+                }
+                report.garbage_bytes += len as u64;
+                pos += len;
+                continue;
+            }
+        };
+        garbage_start = None;
+        linter.frame(&header);
+        if sampler.select(report.frames, elapsed) {
+            report.frames_sampled += 1;
+            check_frame(&header, options, at, &mut report);
+        }
+        // frames with a reserved sampling frequency have unknown duration, and are taken to have
+        // none
+        if let Some(rate) = header.sampling_frequency().freq() {
+            let blocks = header.number_of_raw_data_blocks_in_frame();
+            elapsed =
+                elapsed + MediaTime::from_blocks_of(blocks.into(), options.frame_length, rate);
+        }
+        report.frames += 1;
+        pos += header.frame_len();
+    }
+    report.duration = elapsed.to_duration();
+    if let Some(ref lints) = options.lints {
+        report.warnings = linter.warnings(lints, report.frames);
+    }
+    report
+}
+
+fn check_frame(
+    header: &AdtsHeader<'_>,
+    options: &AnalysisOptions,
+    at: Position,
+    report: &mut HealthReport,
+) {
+    let payload = match header.payload() {
+        Ok(payload) => payload,
+        Err(_) => return,
+    };
+    if options.encryption.is_encrypted() {
+        return;
+    }
+    // as with validate_frame(), only single-block frames are covered
+    if options.check_crc
+        && header.protection() == ProtectionIndicator::CrcPresent
+        && header.number_of_raw_data_blocks_in_frame() == 1
+    {
+        report.crc_checked += 1;
+        if header.crc() != Some(options.crc_algorithm.frame_crc(header.buf, payload)) {
+            report.crc_failures += 1;
+            anomaly(report, options, at, AnomalyKind::CrcMismatch);
+        }
+    }
+    #[cfg(feature = "elements")]
+    if options.check_channel_layout {
+        let scan = crate::elements::scan_frame(header, payload);
+        report.layout_checked += 1;
+        if crate::elements::check_channel_layout(header, &scan).is_err() {
+            report.layout_mismatches += 1;
+            anomaly(report, options, at, AnomalyKind::LayoutMismatch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    fn stream(frames: usize, crc_fail_every: usize) -> Vec<u8> {
+        let mut builder = StreamBuilder::new()
+            .config(AdtsStreamConfig {
+                sampling_frequency: SamplingFrequency::Freq8000,
+                channel_configuration: ChannelConfiguration::Mono,
+                ..StreamBuilder::DEFAULT_CONFIG
+            })
+            .crc(true);
+        for _ in 0..frames {
+            builder = builder.frame(&[0x00, 0x01]);
+        }
+        let offsets = builder.offsets().to_vec();
+        let mut data = builder.build();
+        for offset in offsets.into_iter().step_by(crc_fail_every) {
+            data[offset + 8] ^= 1;
+        }
+        data
+    }
+
+    #[test]
+    fn every_frame() {
+        let mut data = stream(10, 5);
+        data.extend_from_slice(&[1, 2, 3]);
+        data.extend(stream(10, 100));
+        data.extend_from_slice(&[0xff, 0xf1]);
+        let report = analyze(&data, &AnalysisOptions::default());
+        assert_eq!(report.frames, 20);
+        assert_eq!(report.frames_sampled, 20);
+        assert_eq!(report.garbage_runs, 1);
+        assert_eq!(report.garbage_bytes, 3);
+        assert_eq!(report.truncated_bytes, 2);
+        assert_eq!(report.crc_checked, 20);
+        assert_eq!(report.crc_failures, 3);
+        // 20 frames of 1024 samples at 8kHz
+        assert_eq!(report.duration, Duration::from_millis(2560));
+        let at = |offset, millis| (offset, Duration::from_millis(millis));
+        assert_eq!(
+            report
+                .anomalies
+                .iter()
+                .map(|a| ((a.offset, a.time), a.kind.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (at(0, 0), AnomalyKind::CrcMismatch),
+                (at(55, 640), AnomalyKind::CrcMismatch),
+                (at(110, 1280), AnomalyKind::Garbage { len: 3 }),
+                (at(113, 1280), AnomalyKind::CrcMismatch),
+                (at(223, 2560), AnomalyKind::Truncated { len: 2 }),
+            ]
+        );
+        let options = AnalysisOptions {
+            max_anomalies: 2,
+            ..AnalysisOptions::default()
+        };
+        let report = analyze(&data, &options);
+        assert_eq!(report.anomalies.len(), 2);
+        assert_eq!(report.crc_failures, 3);
+
+        let options = AnalysisOptions {
+            encryption: PayloadEncryption::SampleAes,
+            ..AnalysisOptions::default()
+        };
+        let report = analyze(&data, &options);
+        assert_eq!(report.frames_sampled, 20);
+        assert_eq!(report.crc_checked, 0);
+        assert_eq!(report.crc_failure_rate(), None);
+    }
+
+    #[test]
+    fn sparse() {
+        let data = stream(1000, 10);
+        let options = AnalysisOptions {
+            sampling: Sampling::EveryNthFrame(10),
+            ..AnalysisOptions::default()
+        };
+        let report = analyze(&data, &options);
+        assert_eq!(report.frames, 1000);
+        assert_eq!(report.frames_sampled, 100);
+        // the sampled frames happen to be exactly the corrupted ones
+        assert_eq!(report.crc_failure_rate(), Some(1.0));
+
+        // each frame is 128ms, so a frame starts exactly on every whole second only every 125
+        // frames, but one frame is sampled per second regardless
+        let options = AnalysisOptions {
+            sampling: Sampling::Interval(Duration::from_secs(1)),
+            ..AnalysisOptions::default()
+        };
+        let report = analyze(&data, &options);
+        assert_eq!(report.duration, Duration::from_millis(128_000));
+        assert_eq!(report.frames_sampled, 128);
+    }
+
+    #[test]
+    fn lints() {
+        let options = AnalysisOptions {
+            lints: Some(LintOptions { expect_crc: true }),
+            ..AnalysisOptions::default()
+        };
+        // stream() writes CRC-protected frames with the default buffer fullness of 0x7ff
+        let data = stream(10, 100);
+        assert_eq!(analyze(&data, &options).warnings, vec![]);
+        // not reported unless enabled
+        assert_eq!(analyze(&data, &AnalysisOptions::default()).warnings, vec![]);
+
+        let mut frames = data.chunks(11).map(|f| f.to_vec()).collect::<Vec<_>>();
+        for frame in &mut frames {
+            fields::ADTS_BUFFER_FULLNESS.set(frame, 0x100);
+        }
+        fields::NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.set(&mut frames[3], 2);
+        frames.push(
+            crate::write_frame(
+                &AdtsHeader::from_bytes(&data).unwrap().stream_config(),
+                &[0],
+                &FrameOptions {
+                    buffer_fullness: 0x100,
+                    ..FrameOptions::default()
+                },
+            )
+            .unwrap(),
+        );
+        let report = analyze(&frames.concat(), &options);
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::ConstantBufferFullness { value: 0x100 },
+                Warning::CrcAbsent { frames: 1 },
+                Warning::InconsistentRawDataBlocks { min: 1, max: 3 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Assembly of frames into memory supplied by the caller, for frameworks which require all media
+//! memory to come from their own pools (shared-memory IPC transports, for example).
+//!
+//! [`ArenaParser`](struct.ArenaParser.html) copies every frame, whether it arrives in one
+//! `push()` or is split over several, into a buffer allocated from a
+//! [`FrameArena`](trait.FrameArena.html), and hands the consumer the arena's handle to the
+//! completed frame.  The only frame data held by the parser itself is up to 9 bytes of a header
+//! whose frame length is not yet known.
+
+use crate::sync::find_possible_sync;
+use crate::{AdtsHeader, AdtsHeaderError, AdtsParseError};
+
+/// Caller-owned memory from which an [`ArenaParser`](struct.ArenaParser.html) allocates a buffer
+/// for each frame.
+pub trait FrameArena {
+    /// Identifies an allocated buffer
+    type Handle;
+
+    /// Allocates a buffer of exactly `len` bytes, or returns `None` if the arena is exhausted
+    fn allocate(&mut self, len: usize) -> Option<Self::Handle>;
+
+    /// Gives access to a buffer previously returned by `allocate()`
+    fn bytes_mut(&mut self, handle: &Self::Handle) -> &mut [u8];
+}
+impl<A: FrameArena> FrameArena for &mut A {
+    type Handle = A::Handle;
+
+    fn allocate(&mut self, len: usize) -> Option<Self::Handle> {
+        (**self).allocate(len)
+    }
+
+    fn bytes_mut(&mut self, handle: &Self::Handle) -> &mut [u8] {
+        (**self).bytes_mut(handle)
+    }
+}
+
+/// Errors reported by an [`ArenaParser`](struct.ArenaParser.html).
+#[derive(Debug, PartialEq)]
+pub enum ArenaError {
+    /// An error in the ADTS bitstream; as for `AdtsParser`, a single run of garbage produces one
+    /// error however long it is
+    Bitstream(AdtsParseError),
+    /// The arena could not supply a buffer for a frame of the given length, so the frame was
+    /// skipped
+    Exhausted { len: usize },
+}
+
+/// Receives the frames assembled by an [`ArenaParser`](struct.ArenaParser.html).
+pub trait ArenaConsumer<H> {
+    /// Called with the handle of a buffer holding a complete frame, header included.  The buffer
+    /// now belongs to the consumer.
+    fn on_frame(&mut self, frame: H);
+
+    /// Called if the parser encounters an error
+    fn on_error(&mut self, err: ArenaError) {
+        let _ = err;
+    }
+}
+
+enum State<H> {
+    /// collecting header bytes until the frame length is known
+    Header { bytes: [u8; 9], len: usize },
+    /// copying the rest of the frame into its arena buffer
+    Body { frame: H, filled: usize, len: usize },
+    /// discarding the rest of a frame which the arena had no room for
+    Skip { remaining: usize },
+    /// searching for a sync-word following a bitstream error
+    Sync,
+}
+impl<H> State<H> {
+    fn start() -> State<H> {
+        State::Header {
+            bytes: [0; 9],
+            len: 0,
+        }
+    }
+}
+
+/// Parses ADTS data pushed to it, assembling each frame into a buffer from a caller-owned
+/// [`FrameArena`](trait.FrameArena.html).
+///
+/// ```rust
+/// use adts_reader::arena::{ArenaConsumer, ArenaParser, FrameArena};
+/// use std::ops::Range;
+///
+/// /// hands out consecutive ranges of a fixed block of memory
+/// struct Pool {
+///     memory: Vec<u8>,
+///     used: usize,
+/// }
+/// impl FrameArena for Pool {
+///     type Handle = Range<usize>;
+///     fn allocate(&mut self, len: usize) -> Option<Range<usize>> {
+///         let start = self.used;
+///         self.used = self.used.checked_add(len).filter(|&end| end <= self.memory.len())?;
+///         Some(start..self.used)
+///     }
+///     fn bytes_mut(&mut self, handle: &Range<usize>) -> &mut [u8] {
+///         &mut self.memory[handle.clone()]
+///     }
+/// }
+///
+/// struct Frames(Vec<Range<usize>>);
+/// impl ArenaConsumer<Range<usize>> for Frames {
+///     fn on_frame(&mut self, frame: Range<usize>) {
+///         self.0.push(frame);
+///     }
+/// }
+///
+/// let pool = Pool { memory: vec![0; 4096], used: 0 };
+/// let mut parser = ArenaParser::new(pool, Frames(vec![]));
+/// let data = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// parser.push(&data[..3]);
+/// parser.push(&data[3..]);
+/// assert_eq!(parser.consumer.0, vec![0..8]);
+/// assert_eq!(&parser.arena.memory[0..8], data);
+/// ```
+pub struct ArenaParser<A: FrameArena, C: ArenaConsumer<A::Handle>> {
+    pub arena: A,
+    pub consumer: C,
+    state: State<A::Handle>,
+    /// `true` from an error until the next frame is found
+    resyncing: bool,
+}
+impl<A: FrameArena, C: ArenaConsumer<A::Handle>> ArenaParser<A, C> {
+    pub fn new(arena: A, consumer: C) -> ArenaParser<A, C> {
+        ArenaParser {
+            arena,
+            consumer,
+            state: State::start(),
+            resyncing: false,
+        }
+    }
+
+    /// Abandons any partially received frame, so that the next `push()` is expected to start
+    /// with a new frame, returning the handle of the abandoned frame's buffer, if one had been
+    /// allocated, so that the caller can release it.
+    pub fn start(&mut self) -> Option<A::Handle> {
+        self.resyncing = false;
+        match std::mem::replace(&mut self.state, State::start()) {
+            State::Body { frame, .. } => Some(frame),
+            _ => None,
+        }
+    }
+
+    /// Parses the given data, passing each frame completed by it to the consumer
+    pub fn push(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            match self.state {
+                State::Sync => match find_possible_sync(buf) {
+                    Some(i) => {
+                        buf = &buf[i..];
+                        self.state = State::start();
+                    }
+                    None => return,
+                },
+                State::Skip { ref mut remaining } => {
+                    let n = (*remaining).min(buf.len());
+                    *remaining -= n;
+                    buf = &buf[n..];
+                    if *remaining == 0 {
+                        self.state = State::start();
+                    }
+                }
+                State::Header {
+                    ref mut bytes,
+                    ref mut len,
+                } => {
+                    // take no more than the header needs, since a frame without a CRC may be
+                    // shorter than the largest header
+                    let wanted = if *len < 7 { 7 } else { 9 };
+                    let n = (wanted - *len).min(buf.len());
+                    bytes[*len..*len + n].copy_from_slice(&buf[..n]);
+                    *len += n;
+                    buf = &buf[n..];
+                    let (bytes, len) = (*bytes, *len);
+                    match AdtsHeader::from_bytes(&bytes[..len]) {
+                        Ok(header) => self.begin_frame(&bytes[..len], header.frame_len()),
+                        Err(AdtsHeaderError::NotEnoughData { .. }) => (),
+                        Err(e) => {
+                            self.error(ArenaError::Bitstream(match e {
+                                AdtsHeaderError::BadFrameLength { .. } => {
+                                    AdtsParseError::BadFrameLength
+                                }
+                                _ => AdtsParseError::BadSyncWord,
+                            }));
+                            self.state = State::Sync;
+                            // look for a sync-word following the false one, before carrying on
+                            // with buf
+                            self.push(&bytes[1..len]);
+                        }
+                    }
+                }
+                State::Body {
+                    ref frame,
+                    ref mut filled,
+                    len,
+                } => {
+                    let n = (len - *filled).min(buf.len());
+                    self.arena.bytes_mut(frame)[*filled..*filled + n].copy_from_slice(&buf[..n]);
+                    *filled += n;
+                    buf = &buf[n..];
+                    if *filled == len {
+                        self.finish_frame();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Allocates the buffer for a frame of the given length, given its header
+    fn begin_frame(&mut self, header: &[u8], len: usize) {
+        match self.arena.allocate(len) {
+            Some(frame) => {
+                self.arena.bytes_mut(&frame)[..header.len()].copy_from_slice(header);
+                self.state = State::Body {
+                    frame,
+                    filled: header.len(),
+                    len,
+                };
+                // a frame consisting only of a header is already complete
+                if header.len() == len {
+                    self.finish_frame();
+                }
+            }
+            None => {
+                self.consumer.on_error(ArenaError::Exhausted { len });
+                self.state = if header.len() == len {
+                    State::start()
+                } else {
+                    State::Skip {
+                        remaining: len - header.len(),
+                    }
+                };
+            }
+        }
+    }
+
+    /// Passes the frame whose buffer has just been filled to the consumer
+    fn finish_frame(&mut self) {
+        if let State::Body { frame, .. } = std::mem::replace(&mut self.state, State::start()) {
+            self.resyncing = false;
+            self.consumer.on_frame(frame);
+        }
+    }
+
+    fn error(&mut self, err: ArenaError) {
+        if !self.resyncing {
+            self.resyncing = true;
+            self.consumer.on_error(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+
+    /// Allocates each frame separately, failing once `limit` frames have been allocated
+    #[derive(Default)]
+    struct Frames {
+        buffers: Vec<Vec<u8>>,
+        limit: usize,
+    }
+    impl FrameArena for Frames {
+        type Handle = usize;
+        fn allocate(&mut self, len: usize) -> Option<usize> {
+            if self.buffers.len() == self.limit {
+                return None;
+            }
+            self.buffers.push(vec![0; len]);
+            Some(self.buffers.len() - 1)
+        }
+        fn bytes_mut(&mut self, handle: &usize) -> &mut [u8] {
+            &mut self.buffers[*handle]
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        frames: Vec<usize>,
+        errors: Vec<ArenaError>,
+    }
+    impl ArenaConsumer<usize> for Recorder {
+        fn on_frame(&mut self, frame: usize) {
+            self.frames.push(frame);
+        }
+        fn on_error(&mut self, err: ArenaError) {
+            self.errors.push(err);
+        }
+    }
+
+    #[test]
+    fn split_pushes() {
+        let builder = StreamBuilder::new()
+            .payload_len(3)
+            .frames(1, StreamBuilder::DEFAULT_CONFIG)
+            // garbage including a false sync-word
+            .bytes(&[0, 0xff, 0xf1, 0, 0, 0, 0, 0, 0])
+            .options(crate::FrameOptions {
+                crc: true,
+                ..crate::FrameOptions::default()
+            })
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .junk(4)
+            .frame(&[9]);
+        let offsets = builder.offsets().to_vec();
+        let data = builder.build();
+        let expected: Vec<&[u8]> = vec![
+            &data[offsets[0]..10],
+            &data[offsets[1]..offsets[2]],
+            &data[offsets[2]..offsets[2] + 12],
+            &data[offsets[3]..],
+        ];
+        for split in 0..data.len() {
+            let arena = Frames {
+                limit: usize::MAX,
+                ..Frames::default()
+            };
+            let mut parser = ArenaParser::new(arena, Recorder::default());
+            parser.push(&data[..split]);
+            parser.push(&data[split..]);
+            assert_eq!(
+                parser.consumer.frames,
+                vec![0, 1, 2, 3],
+                "split at {}",
+                split
+            );
+            assert_eq!(parser.arena.buffers, expected, "split at {}", split);
+            assert_eq!(
+                parser.consumer.errors,
+                vec![
+                    ArenaError::Bitstream(AdtsParseError::BadSyncWord),
+                    ArenaError::Bitstream(AdtsParseError::BadSyncWord),
+                ],
+                "split at {}",
+                split
+            );
+        }
+    }
+
+    #[test]
+    fn exhausted() {
+        let data = StreamBuilder::new()
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let arena = Frames {
+            limit: 1,
+            ..Frames::default()
+        };
+        let mut parser = ArenaParser::new(arena, Recorder::default());
+        parser.push(&data);
+        assert_eq!(parser.consumer.frames, vec![0]);
+        assert_eq!(
+            parser.consumer.errors,
+            vec![
+                ArenaError::Exhausted { len: 11 },
+                ArenaError::Exhausted { len: 11 },
+            ]
+        );
+        // the buffer of a partial frame is returned when it is abandoned
+        assert_eq!(parser.start(), None);
+        parser.arena.limit = 2;
+        parser.push(&data[..9]);
+        assert_eq!(parser.start(), Some(1));
+    }
+}
@@ -0,0 +1,723 @@
+//! Parsing of the MPEG-4 `AudioSpecificConfig`, as carried in the `DecoderSpecificInfo` of an MP4
+//! `esds` box, and conversion to the equivalent ADTS stream configuration.
+//!
+//! ADTS can only describe a subset of what an `AudioSpecificConfig` can: its 2-bit `profile`
+//! field covers just the AAC Main, LC, SSR and LTP object types.  Rather than producing a header
+//! which misdescribes the audio, conversion of anything else fails with an
+//! [`AscError`](enum.AscError.html) saying why.
+//!
+//! ADTS also has no way to signal Spectral Band Replication (SBR), the tool that makes AAC-LC
+//! into HE-AAC.  An ADTS header gives the sampling frequency of the AAC-LC core, while the
+//! decoder's output runs at double that rate, so the output rate must be carried alongside the
+//! ADTS stream; mistaking one rate for the other halves or doubles the audio clock.
+
+use crate::bits::{BitReader, BitWriter};
+use crate::{
+    AdtsStreamConfig, AudioObjectType, ChannelConfiguration, FrameLength, MpegVersion, Originality,
+    SamplingFrequency,
+};
+
+/// Audio object type signalling explicit (hierarchical) SBR
+const AOT_SBR: u8 = 5;
+/// Audio object type signalling explicit (hierarchical) Parametric Stereo
+const AOT_PS: u8 = 29;
+/// `syncExtensionType` preceding backward compatible SBR signalling
+const SYNC_EXTENSION_SBR: u32 = 0x2b7;
+/// `syncExtensionType` preceding backward compatible PS signalling
+const SYNC_EXTENSION_PS: u32 = 0x548;
+/// Error resilient AAC Low Delay
+const AOT_ER_AAC_LD: u8 = 23;
+/// Error resilient AAC Enhanced Low Delay
+const AOT_ER_AAC_ELD: u8 = 39;
+
+/// Sampling frequencies indexed by `samplingFrequencyIndex` (0xf means the frequency is given
+/// explicitly instead)
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Error indicating that an `AudioSpecificConfig` could not be parsed, or has no ADTS equivalent.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AscError {
+    /// The data ended before the end of the `AudioSpecificConfig`
+    Truncated,
+    /// The `samplingFrequencyIndex` holds one of the reserved values 0xd or 0xe
+    ReservedSamplingFrequencyIndex(u8),
+    /// A low delay object type (AAC-LD or AAC-ELD), whose frame lengths of 480 or 512 samples
+    /// and error resilient syntax ADTS can't describe
+    LowDelay { audio_object_type: u8 },
+    /// An error resilient (ER) object type, whose bitstream syntax differs from the AAC
+    /// `raw_data_block` that ADTS frames carry
+    ErrorResilient { audio_object_type: u8 },
+    /// Some other object type that the ADTS `profile` field can't signal
+    UnsupportedObjectType { audio_object_type: u8 },
+    /// A sampling frequency which has no ADTS `sampling_frequency_index` (including the 7.35kHz
+    /// entry, index 0xc, which ADTS reserves)
+    UnsupportedSamplingFrequency(u32),
+    /// A `channelConfiguration` value which the 3-bit ADTS field can't hold (or, when writing an
+    /// `AudioSpecificConfig`, which its own 4-bit field can't hold)
+    UnsupportedChannelConfiguration(u8),
+    /// A `channelConfiguration` of 0, whose channel layout would have to be given by a
+    /// `program_config_element` within the `AudioSpecificConfig`, which isn't supported
+    ProgramConfigRequired,
+}
+
+/// The fields of an MPEG-4 `AudioSpecificConfig` (ISO/IEC 14496-3 1.6.2.1) relevant to ADTS.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AudioSpecificConfig {
+    /// The object type of the underlying codec.  Where SBR or PS is signalled explicitly (object
+    /// type 5 or 29), this is the object type that follows, rather than 5 or 29.
+    pub audio_object_type: u8,
+    /// The sampling frequency of the underlying codec
+    pub sampling_frequency: u32,
+    pub channel_configuration: u8,
+    /// From the `frameLengthFlag` of the `GASpecificConfig`, for object types which have one;
+    /// otherwise `FrameLength::Samples1024`
+    pub frame_length: FrameLength,
+    /// The output sampling frequency of the SBR tool, where SBR is signalled, either explicitly
+    /// or by the backward compatible extension following the `GASpecificConfig`
+    pub extension_sampling_frequency: Option<u32>,
+    /// Whether Parametric Stereo is signalled (HE-AAC v2)
+    pub ps_present: bool,
+}
+impl AudioSpecificConfig {
+    /// Parses an `AudioSpecificConfig` from the start of the given buffer.
+    ///
+    /// ```rust
+    /// use adts_reader::asc::AudioSpecificConfig;
+    /// // AAC-LC, 44.1kHz, stereo
+    /// let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+    /// assert_eq!(asc.audio_object_type, 2);
+    /// assert_eq!(asc.sampling_frequency, 44100);
+    /// assert_eq!(asc.channel_configuration, 2);
+    /// ```
+    pub fn parse(buf: &[u8]) -> Result<AudioSpecificConfig, AscError> {
+        let mut r = BitReader::new(buf);
+        let mut asc = read_start(&mut r)?;
+        let aot = asc.audio_object_type;
+        if is_general_audio(aot) {
+            asc.frame_length = FrameLength::from_frame_length_flag(read_bool(&mut r)?);
+            // backward compatible signalling follows the rest of the GASpecificConfig, which can't
+            // be located without parsing any program_config_element, so isn't looked for then
+            if asc.extension_sampling_frequency.is_none()
+                && asc.channel_configuration != 0
+                && !is_error_resilient(aot)
+            {
+                skip_ga_specific_config(&mut r, aot)?;
+                if let Some((freq, ps)) = read_sbr_sync_extension(&mut r)? {
+                    asc.extension_sampling_frequency = Some(freq);
+                    asc.ps_present = ps;
+                }
+            }
+        }
+        Ok(asc)
+    }
+
+    /// Describes the given ADTS stream configuration, with SBR signalled at double the core
+    /// sampling frequency if `sbr` is true.
+    ///
+    /// Since an ADTS header never says whether SBR is in use, the caller must know this from
+    /// elsewhere, or make a guess such as that given by
+    /// [`implicit_sbr_likely()`](fn.implicit_sbr_likely.html).  Fails only for reserved sampling
+    /// frequencies.
+    pub fn from_adts_config(
+        config: &AdtsStreamConfig,
+        sbr: bool,
+    ) -> Result<AudioSpecificConfig, AscError> {
+        let sampling_frequency =
+            config
+                .sampling_frequency
+                .freq()
+                .ok_or(AscError::ReservedSamplingFrequencyIndex(
+                    config.sampling_frequency.index(),
+                ))?;
+        let audio_object_type = u8::from(config.audio_object_type);
+        Ok(AudioSpecificConfig {
+            audio_object_type,
+            sampling_frequency,
+            channel_configuration: config.channel_configuration.into(),
+            frame_length: FrameLength::Samples1024,
+            extension_sampling_frequency: if sbr {
+                Some(sampling_frequency * 2)
+            } else {
+                None
+            },
+            ps_present: false,
+        })
+    }
+
+    /// The sampling frequency of the decoded audio: the SBR output rate if SBR is signalled,
+    /// and otherwise the core `sampling_frequency`.  This is the rate to use for timing the
+    /// decoded audio, while media time in ADTS frame counts follows the core rate.
+    pub fn output_sampling_frequency(&self) -> u32 {
+        self.extension_sampling_frequency
+            .unwrap_or(self.sampling_frequency)
+    }
+
+    /// The ADTS stream configuration describing the same audio, or an error if ADTS can't
+    /// represent it.
+    ///
+    /// Where SBR is signalled, the header describes the AAC-LC core, so its sampling frequency is
+    /// the core `sampling_frequency`, not the
+    /// [`output_sampling_frequency()`](#method.output_sampling_frequency), which callers need to
+    /// carry alongside the ADTS stream themselves (for example, in an HLS playlist).
+    ///
+    /// ADTS has no way to signal a `FrameLength` of 960 samples; if `frame_length` indicates
+    /// this, the consumer of the resulting stream needs to be told so out of band.
+    ///
+    /// ```rust
+    /// use adts_reader::asc::{AscError, AudioSpecificConfig};
+    /// // AAC-ELD, 48kHz, mono
+    /// let asc = AudioSpecificConfig::parse(&[0xf8, 0xe6, 0x20, 0x00]).unwrap();
+    /// assert_eq!(
+    ///     asc.to_adts_config(),
+    ///     Err(AscError::LowDelay { audio_object_type: 39 })
+    /// );
+    /// ```
+    pub fn to_adts_config(&self) -> Result<AdtsStreamConfig, AscError> {
+        let audio_object_type = match self.audio_object_type {
+            1 => AudioObjectType::AacMain,
+            2 => AudioObjectType::AacLC,
+            3 => AudioObjectType::AacSSR,
+            4 => AudioObjectType::AacLTP,
+            aot @ (AOT_ER_AAC_LD | AOT_ER_AAC_ELD) => {
+                return Err(AscError::LowDelay {
+                    audio_object_type: aot,
+                })
+            }
+            aot if is_error_resilient(aot) => {
+                return Err(AscError::ErrorResilient {
+                    audio_object_type: aot,
+                })
+            }
+            aot => {
+                return Err(AscError::UnsupportedObjectType {
+                    audio_object_type: aot,
+                })
+            }
+        };
+        // ADTS reserves index 0xc, which AudioSpecificConfig uses for 7.35kHz
+        let sampling_frequency = SAMPLING_FREQUENCIES[..0xc]
+            .iter()
+            .position(|&f| f == self.sampling_frequency)
+            .map(|index| SamplingFrequency::from_bits(index as u8))
+            .ok_or(AscError::UnsupportedSamplingFrequency(
+                self.sampling_frequency,
+            ))?;
+        if self.channel_configuration > 7 {
+            return Err(AscError::UnsupportedChannelConfiguration(
+                self.channel_configuration,
+            ));
+        }
+        Ok(AdtsStreamConfig {
+            mpeg_version: MpegVersion::Mpeg4,
+            audio_object_type,
+            sampling_frequency,
+            private_bit: 0,
+            channel_configuration: ChannelConfiguration::from_bits(self.channel_configuration),
+            originality: Originality::Original,
+            home: 0,
+        })
+    }
+
+    /// Serializes this configuration as an `AudioSpecificConfig`, as carried in the
+    /// `DecoderSpecificInfo` of an MP4 `esds` box.
+    ///
+    /// Any SBR or PS is signalled explicitly (with object type 5 or 29 preceding the underlying
+    /// object type), since decoders need not look for the backward compatible form.  Only object
+    /// types whose configuration is a `GASpecificConfig` can be written, and a
+    /// `channel_configuration` of 0 is refused, since the `program_config_element` that would
+    /// have to accompany it isn't modelled.
+    ///
+    /// ```rust
+    /// use adts_reader::asc::AudioSpecificConfig;
+    /// let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+    /// assert_eq!(asc.to_bytes(), Ok(vec![0x12, 0x10]));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AscError> {
+        let aot = self.audio_object_type;
+        if !is_general_audio(aot) {
+            return Err(AscError::UnsupportedObjectType {
+                audio_object_type: aot,
+            });
+        }
+        match self.channel_configuration {
+            0 => return Err(AscError::ProgramConfigRequired),
+            c if c > 0xf => return Err(AscError::UnsupportedChannelConfiguration(c)),
+            _ => (),
+        }
+        let mut w = BitWriter::new();
+        match self.extension_sampling_frequency {
+            Some(extension_freq) => {
+                write_object_type(&mut w, if self.ps_present { AOT_PS } else { AOT_SBR });
+                write_sampling_frequency(&mut w, self.sampling_frequency)?;
+                w.write(4, u32::from(self.channel_configuration));
+                write_sampling_frequency(&mut w, extension_freq)?;
+                write_object_type(&mut w, aot);
+            }
+            None => {
+                write_object_type(&mut w, aot);
+                write_sampling_frequency(&mut w, self.sampling_frequency)?;
+                w.write(4, u32::from(self.channel_configuration));
+            }
+        }
+        // GASpecificConfig
+        w.write_bool(self.frame_length.frame_length_flag());
+        // dependsOnCoreCoder
+        w.write_bool(false);
+        // extensionFlag, which error resilient object types must set
+        let extension_flag = is_error_resilient(aot);
+        w.write_bool(extension_flag);
+        if aot == 6 || aot == 20 {
+            // layerNr
+            w.write(3, 0);
+        }
+        if extension_flag {
+            if aot == 22 {
+                // numOfSubFrame, layer_length
+                w.write(16, 0);
+            }
+            if matches!(aot, 17 | 19 | 20 | 23) {
+                // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+                // aacSpectralDataResilienceFlag
+                w.write(3, 0);
+            }
+            // extensionFlag3
+            w.write_bool(false);
+        }
+        Ok(w.into_bytes())
+    }
+}
+
+/// `GetAudioObjectType()` syntax, escaping values of 32 and above
+fn write_object_type(w: &mut BitWriter, aot: u8) {
+    if aot >= 32 {
+        w.write(5, 31);
+        w.write(6, u32::from(aot - 32));
+    } else {
+        w.write(5, u32::from(aot));
+    }
+}
+
+/// Writes the `samplingFrequencyIndex` of the given frequency, or if it has none, the escape
+/// value 0xf followed by the frequency itself
+fn write_sampling_frequency(w: &mut BitWriter, freq: u32) -> Result<(), AscError> {
+    match SAMPLING_FREQUENCIES.iter().position(|&f| f == freq) {
+        Some(index) => w.write(4, index as u32),
+        None if freq < 1 << 24 => {
+            w.write(4, 0xf);
+            w.write(24, freq);
+        }
+        None => return Err(AscError::UnsupportedSamplingFrequency(freq)),
+    }
+    Ok(())
+}
+
+/// Reads an `AudioSpecificConfig` embedded in other syntax, such as a LATM `StreamMuxConfig`,
+/// leaving the reader at its end.  That end must be found exactly, so only configurations whose
+/// whole syntax is understood are accepted.
+///
+/// If the length of the configuration is given in `len` (in bits), the reader is moved to the end
+/// of that length, and any backward compatible SBR signalling found within it is used.  Without
+/// a length, such signalling can't be told apart from the syntax that follows, so isn't looked
+/// for.
+pub(crate) fn read_embedded(
+    r: &mut BitReader<'_>,
+    len: Option<usize>,
+) -> Result<AudioSpecificConfig, AscError> {
+    let end = len.map(|len| r.position() + len);
+    let mut asc = read_start(r)?;
+    let aot = asc.audio_object_type;
+    if is_error_resilient(aot) {
+        return Err(AscError::ErrorResilient {
+            audio_object_type: aot,
+        });
+    }
+    if !is_general_audio(aot) {
+        return Err(AscError::UnsupportedObjectType {
+            audio_object_type: aot,
+        });
+    }
+    if asc.channel_configuration == 0 {
+        return Err(AscError::ProgramConfigRequired);
+    }
+    asc.frame_length = FrameLength::from_frame_length_flag(read_bool(r)?);
+    skip_ga_specific_config(r, aot)?;
+    if let Some(end) = end {
+        if asc.extension_sampling_frequency.is_none() {
+            let mut ext = r.clone();
+            if let Ok(Some((freq, ps))) = read_sbr_sync_extension(&mut ext) {
+                if ext.position() <= end {
+                    asc.extension_sampling_frequency = Some(freq);
+                    asc.ps_present = ps;
+                }
+            }
+        }
+        let fill = end.checked_sub(r.position()).ok_or(AscError::Truncated)?;
+        r.skip(fill).ok_or(AscError::Truncated)?;
+    }
+    Ok(asc)
+}
+
+/// The fields common to all object types, including any explicit SBR or PS signalling
+fn read_start(r: &mut BitReader<'_>) -> Result<AudioSpecificConfig, AscError> {
+    let mut audio_object_type = read_object_type(r)?;
+    let sampling_frequency = read_sampling_frequency(r)?;
+    let channel_configuration = read(r, 4)? as u8;
+    let mut extension_sampling_frequency = None;
+    let mut ps_present = false;
+    if audio_object_type == AOT_SBR || audio_object_type == AOT_PS {
+        ps_present = audio_object_type == AOT_PS;
+        extension_sampling_frequency = Some(read_sampling_frequency(r)?);
+        audio_object_type = read_object_type(r)?;
+    }
+    Ok(AudioSpecificConfig {
+        audio_object_type,
+        sampling_frequency,
+        channel_configuration,
+        frame_length: FrameLength::Samples1024,
+        extension_sampling_frequency,
+        ps_present,
+    })
+}
+
+fn read(r: &mut BitReader<'_>, bits: u32) -> Result<u32, AscError> {
+    r.read(bits).ok_or(AscError::Truncated)
+}
+
+/// `GetAudioObjectType()`, with its escape for values of 32 and above
+fn read_object_type(r: &mut BitReader<'_>) -> Result<u8, AscError> {
+    match read(r, 5)? as u8 {
+        31 => Ok(32 + read(r, 6)? as u8),
+        aot => Ok(aot),
+    }
+}
+
+/// A `samplingFrequencyIndex`, followed by an explicit 24-bit `samplingFrequency` if the index
+/// is 0xf
+fn read_sampling_frequency(r: &mut BitReader<'_>) -> Result<u32, AscError> {
+    match read(r, 4)? as usize {
+        0xf => read(r, 24),
+        index => SAMPLING_FREQUENCIES
+            .get(index)
+            .copied()
+            .ok_or(AscError::ReservedSamplingFrequencyIndex(index as u8)),
+    }
+}
+
+fn read_bool(r: &mut BitReader<'_>) -> Result<bool, AscError> {
+    r.read_bool().ok_or(AscError::Truncated)
+}
+
+/// Skips the fields of a `GASpecificConfig` following `frameLengthFlag`, for a
+/// `channelConfiguration` other than 0
+fn skip_ga_specific_config(r: &mut BitReader<'_>, aot: u8) -> Result<(), AscError> {
+    // dependsOnCoreCoder
+    if read_bool(r)? {
+        // coreCoderDelay
+        read(r, 14)?;
+    }
+    let extension_flag = read_bool(r)?;
+    if aot == 6 || aot == 20 {
+        // layerNr
+        read(r, 3)?;
+    }
+    if extension_flag {
+        if aot == 22 {
+            // numOfSubFrame, layer_length
+            read(r, 16)?;
+        }
+        if matches!(aot, 17 | 19 | 20 | 23) {
+            // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+            // aacSpectralDataResilienceFlag
+            read(r, 3)?;
+        }
+        // extensionFlag3
+        read(r, 1)?;
+    }
+    Ok(())
+}
+
+/// Reads any backward compatible SBR signalling, giving the extension sampling frequency and
+/// whether PS is present.  Its absence is not an error, since it is optional.
+fn read_sbr_sync_extension(r: &mut BitReader<'_>) -> Result<Option<(u32, bool)>, AscError> {
+    if r.remaining() < 16 || read(r, 11)? != SYNC_EXTENSION_SBR {
+        return Ok(None);
+    }
+    if read_object_type(r)? != AOT_SBR || !read_bool(r)? {
+        return Ok(None);
+    }
+    let freq = read_sampling_frequency(r)?;
+    let ps = r.remaining() >= 12 && read(r, 11)? == SYNC_EXTENSION_PS && read_bool(r)?;
+    Ok(Some((freq, ps)))
+}
+
+/// Guesses whether an ADTS stream with the given configuration carries HE-AAC, with SBR
+/// signalled implicitly in the payload.
+///
+/// This is a heuristic: HE-AAC encoders run the AAC-LC core at half the output rate, so a core
+/// rate of 24kHz or less is taken to suggest SBR.  Low bitrate AAC-LC without SBR (for example
+/// narrowband speech at 8 or 16kHz) will be misjudged, so where the payload can be inspected
+/// for SBR extension data, that is more reliable.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// use adts_reader::asc::{implicit_sbr_likely, AudioSpecificConfig};
+/// # let config = AdtsStreamConfig {
+/// #     mpeg_version: MpegVersion::Mpeg4,
+/// #     audio_object_type: AudioObjectType::AacLC,
+/// #     sampling_frequency: SamplingFrequency::Freq24000,
+/// #     private_bit: 0,
+/// #     channel_configuration: ChannelConfiguration::Stereo,
+/// #     originality: Originality::Original,
+/// #     home: 0,
+/// # };
+/// let asc = AudioSpecificConfig::from_adts_config(&config, implicit_sbr_likely(&config)).unwrap();
+/// assert_eq!(asc.output_sampling_frequency(), 48000);
+/// ```
+pub fn implicit_sbr_likely(config: &AdtsStreamConfig) -> bool {
+    config.audio_object_type == AudioObjectType::AacLC
+        && matches!(config.sampling_frequency.freq(), Some(freq) if freq <= 24000)
+}
+
+/// Whether a stream uses the SBR and PS tools of HE-AAC, and so the object type and sampling
+/// frequency that describe the decoded output, which are what an MP4 sample entry must give.
+///
+/// This comes either from an `AudioSpecificConfig`, which signals the tools exactly, or from
+/// inspecting the payload of an ADTS stream, as by
+/// [`elements::HeAacDetector`](../elements/struct.HeAacDetector.html).
+///
+/// ```rust
+/// use adts_reader::asc::{AudioSpecificConfig, HeAacConfig};
+/// // HE-AAC v2: AAC-LC at 24kHz, mono, with explicit PS and SBR to 48kHz
+/// let asc = AudioSpecificConfig::parse(&[0xeb, 0x09, 0x88, 0x00]).unwrap();
+/// let he_aac = HeAacConfig::from_asc(&asc);
+/// assert_eq!(he_aac.audio_object_type(), 29);
+/// assert_eq!(he_aac.output_sampling_frequency(), 48000);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HeAacConfig {
+    /// The object type of the core codec (2, for AAC-LC, in any HE-AAC stream)
+    pub core_audio_object_type: u8,
+    /// The sampling frequency of the core codec, which is what an ADTS header gives
+    pub core_sampling_frequency: u32,
+    /// The output sampling frequency of the SBR tool, where SBR is in use
+    pub extension_sampling_frequency: Option<u32>,
+    /// Whether Parametric Stereo is in use, or `None` where that isn't known.  Only a mono stream
+    /// with SBR can use PS.
+    pub ps_present: Option<bool>,
+}
+impl HeAacConfig {
+    /// The tools signalled by the given `AudioSpecificConfig`
+    pub fn from_asc(asc: &AudioSpecificConfig) -> HeAacConfig {
+        HeAacConfig {
+            core_audio_object_type: asc.audio_object_type,
+            core_sampling_frequency: asc.sampling_frequency,
+            extension_sampling_frequency: asc.extension_sampling_frequency,
+            ps_present: Some(asc.ps_present),
+        }
+    }
+
+    /// The object type describing the stream as a whole: 29 where PS is in use (HE-AAC v2), 5
+    /// where SBR is (HE-AAC), or otherwise that of the core codec.  Where SBR is in use but
+    /// whether PS is isn't known, this gives 5, which any HE-AAC v2 decoder accepts too.
+    pub fn audio_object_type(&self) -> u8 {
+        match (self.extension_sampling_frequency, self.ps_present) {
+            (None, _) => self.core_audio_object_type,
+            (Some(_), Some(true)) => AOT_PS,
+            (Some(_), _) => AOT_SBR,
+        }
+    }
+
+    /// The sampling frequency of the decoded audio
+    pub fn output_sampling_frequency(&self) -> u32 {
+        self.extension_sampling_frequency
+            .unwrap_or(self.core_sampling_frequency)
+    }
+}
+
+/// Object types whose configuration is a `GASpecificConfig`, starting with `frameLengthFlag`
+fn is_general_audio(aot: u8) -> bool {
+    matches!(aot, 1..=4 | 6 | 7 | 17 | 19..=23)
+}
+
+fn is_error_resilient(aot: u8) -> bool {
+    matches!(aot, 17..=27 | 39)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        // AAC-LC, 48kHz, stereo, 960 sample frames
+        let asc = AudioSpecificConfig::parse(&[0x11, 0x94]).unwrap();
+        assert_eq!(
+            asc,
+            AudioSpecificConfig {
+                audio_object_type: 2,
+                sampling_frequency: 48000,
+                channel_configuration: 2,
+                frame_length: FrameLength::Samples960,
+                extension_sampling_frequency: None,
+                ps_present: false,
+            }
+        );
+        // explicit SBR signalling: 24kHz AAC-LC core, 48kHz output
+        let asc = AudioSpecificConfig::parse(&[0x2b, 0x11, 0x88, 0x00]).unwrap();
+        assert_eq!(asc.audio_object_type, 2);
+        assert_eq!(asc.sampling_frequency, 24000);
+        assert_eq!(asc.channel_configuration, 2);
+        assert_eq!(asc.extension_sampling_frequency, Some(48000));
+        assert_eq!(asc.output_sampling_frequency(), 48000);
+        assert!(!asc.ps_present);
+        // explicit 24-bit sampling frequency
+        let asc = AudioSpecificConfig::parse(&[0x17, 0x80, 0x5d, 0xc0, 0x10]).unwrap();
+        assert_eq!(asc.sampling_frequency, 48000);
+        assert_eq!(
+            asc.to_adts_config().unwrap().sampling_frequency.freq(),
+            Some(48000)
+        );
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x12]),
+            Err(AscError::Truncated)
+        );
+        assert_eq!(AudioSpecificConfig::parse(&[]), Err(AscError::Truncated));
+        assert_eq!(
+            AudioSpecificConfig::parse(&[0x16, 0x90]),
+            Err(AscError::ReservedSamplingFrequencyIndex(0xd))
+        );
+    }
+
+    #[test]
+    fn backward_compatible_sbr() {
+        // AAC-LC at 22.05kHz, mono, then the SBR sync extension giving 44.1kHz output, then the
+        // PS sync extension
+        let asc = AudioSpecificConfig::parse(&[0x13, 0x88, 0x56, 0xe5, 0xa5, 0x48, 0x80]).unwrap();
+        assert_eq!(asc.sampling_frequency, 22050);
+        assert_eq!(asc.extension_sampling_frequency, Some(44100));
+        assert!(asc.ps_present);
+        // the ADTS header describes the core
+        let config = asc.to_adts_config().unwrap();
+        assert_eq!(config.sampling_frequency, SamplingFrequency::Freq22050);
+        assert!(implicit_sbr_likely(&config));
+        let round_trip = AudioSpecificConfig::from_adts_config(&config, true).unwrap();
+        assert_eq!(round_trip.output_sampling_frequency(), 44100);
+        // sbrPresentFlag of 0
+        let asc = AudioSpecificConfig::parse(&[0x13, 0x88, 0x56, 0xe5, 0x00]).unwrap();
+        assert_eq!(asc.extension_sampling_frequency, None);
+        assert_eq!(asc.output_sampling_frequency(), 22050);
+        // a full rate stream is not guessed to use SBR
+        let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+        assert!(!implicit_sbr_likely(&asc.to_adts_config().unwrap()));
+    }
+
+    #[test]
+    fn adts_conversion() {
+        let asc = AudioSpecificConfig {
+            audio_object_type: 2,
+            sampling_frequency: 44100,
+            channel_configuration: 2,
+            frame_length: FrameLength::Samples1024,
+            extension_sampling_frequency: None,
+            ps_present: false,
+        };
+        let config = asc.to_adts_config().unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLC);
+        assert_eq!(config.sampling_frequency, SamplingFrequency::Freq44100);
+        assert_eq!(config.channel_configuration, ChannelConfiguration::Stereo);
+        let with = |audio_object_type| {
+            AudioSpecificConfig {
+                audio_object_type,
+                ..asc
+            }
+            .to_adts_config()
+        };
+        assert_eq!(
+            with(23),
+            Err(AscError::LowDelay {
+                audio_object_type: 23
+            })
+        );
+        assert_eq!(
+            with(17),
+            Err(AscError::ErrorResilient {
+                audio_object_type: 17
+            })
+        );
+        assert_eq!(
+            with(8),
+            Err(AscError::UnsupportedObjectType {
+                audio_object_type: 8
+            })
+        );
+        let unsupported_rate = AudioSpecificConfig {
+            sampling_frequency: 7350,
+            ..asc
+        };
+        assert_eq!(
+            unsupported_rate.to_adts_config(),
+            Err(AscError::UnsupportedSamplingFrequency(7350))
+        );
+        let unsupported_channels = AudioSpecificConfig {
+            channel_configuration: 11,
+            ..asc
+        };
+        assert_eq!(
+            unsupported_channels.to_adts_config(),
+            Err(AscError::UnsupportedChannelConfiguration(11))
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        let lc = AudioSpecificConfig {
+            audio_object_type: 2,
+            sampling_frequency: 48000,
+            channel_configuration: 2,
+            frame_length: FrameLength::Samples960,
+            extension_sampling_frequency: None,
+            ps_present: false,
+        };
+        assert_eq!(lc.to_bytes(), Ok(vec![0x11, 0x94]));
+        let he_v2 = AudioSpecificConfig {
+            sampling_frequency: 24000,
+            frame_length: FrameLength::Samples1024,
+            extension_sampling_frequency: Some(48000),
+            ps_present: true,
+            ..lc
+        };
+        let unusual_rate = AudioSpecificConfig {
+            sampling_frequency: 50000,
+            ..lc
+        };
+        let low_delay = AudioSpecificConfig {
+            audio_object_type: 23,
+            ..lc
+        };
+        for asc in [lc, he_v2, unusual_rate, low_delay] {
+            let bytes = asc.to_bytes().unwrap();
+            assert_eq!(AudioSpecificConfig::parse(&bytes), Ok(asc));
+        }
+        assert_eq!(he_v2.to_bytes(), Ok(vec![0xeb, 0x11, 0x88, 0x00]));
+        assert_eq!(
+            AudioSpecificConfig {
+                channel_configuration: 0,
+                ..lc
+            }
+            .to_bytes(),
+            Err(AscError::ProgramConfigRequired)
+        );
+        assert_eq!(
+            AudioSpecificConfig {
+                audio_object_type: 39,
+                ..lc
+            }
+            .to_bytes(),
+            Err(AscError::UnsupportedObjectType {
+                audio_object_type: 39
+            })
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Reading frames from a tokio `AsyncRead`, such as a socket.
+//!
+//! Available when the `tokio` feature is enabled.  The `futures` feature adds
+//! [`AdtsFrameStream`](struct.AdtsFrameStream.html), giving the frames as a `futures::Stream`.
+
+use crate::follow::FrameBuffer;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "futures")]
+use {
+    crate::OwnedAdtsFrame,
+    futures_core::Stream,
+    std::pin::Pin,
+    std::task::{Context, Poll},
+    tokio::io::ReadBuf,
+};
+
+const READ_SIZE: usize = 64 * 1024;
+
+/// Yields complete ADTS frames read from an asynchronous input.
+///
+/// As with [`FollowingAdtsReader`](../follow/struct.FollowingAdtsReader.html), frames are only
+/// returned once complete, and bytes which aren't part of any frame are skipped and counted by
+/// [`skipped_bytes()`](#method.skipped_bytes).
+///
+/// # Cancellation safety
+///
+/// [`next_frame()`](#method.next_frame) is cancellation safe: if the future it returns is
+/// dropped before completing (for example, because another branch of a `tokio::select!` finished
+/// first), no data is lost.  Bytes already read from the input stay buffered in the reader, and
+/// the next call carries on from where the cancelled one left off.
+///
+/// ```rust
+/// use adts_reader::async_reader::AsyncAdtsReader;
+/// # async fn example(socket: tokio::io::DuplexStream) -> std::io::Result<()> {
+/// let mut reader = AsyncAdtsReader::new(socket);
+/// while let Some(frame) = reader.next_frame().await? {
+///     println!("frame of {} bytes", frame.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncAdtsReader<R: AsyncRead + Unpin> {
+    input: R,
+    buf: FrameBuffer,
+    skipped_bytes: u64,
+}
+impl<R: AsyncRead + Unpin> AsyncAdtsReader<R> {
+    pub fn new(input: R) -> AsyncAdtsReader<R> {
+        AsyncAdtsReader {
+            input,
+            buf: FrameBuffer::default(),
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Number of bytes read which were not part of any frame
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Number of bytes read but not yet returned as part of a frame.  Once `next_frame()` has
+    /// returned `None`, these are the bytes of an incomplete frame at the end of the input.
+    pub fn pending_bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the next complete frame, reading more of the input as required, or `None` at the
+    /// end of the input.
+    ///
+    /// This method is cancellation safe.
+    pub async fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(frame) = self.buf.take_frame(&mut self.skipped_bytes) {
+                return Ok(Some(frame));
+            }
+            // read_buf() only appends to buf once data has actually been read, and reading is
+            // the only await point, so cancellation can never leave buf partially updated
+            let buf = self.buf.spare();
+            buf.reserve(READ_SIZE);
+            if self.input.read_buf(buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Converts this reader into a `Stream` of frames, keeping any data already read
+    #[cfg(feature = "futures")]
+    pub fn into_stream(self) -> AdtsFrameStream<R> {
+        AdtsFrameStream {
+            reader: self,
+            scratch: vec![0; READ_SIZE].into_boxed_slice(),
+            ended: false,
+        }
+    }
+}
+
+/// A `futures::Stream` of the complete ADTS frames read from an asynchronous input, which ends at
+/// the end of the input.
+///
+/// Frames are found as by [`AsyncAdtsReader`](struct.AsyncAdtsReader.html), skipping any bytes
+/// which aren't part of a frame.  An I/O error is yielded as an item, after which the stream may
+/// be polled again to retry.
+///
+/// Available when the `futures` feature is enabled.
+///
+/// ```rust
+/// use adts_reader::async_reader::AdtsFrameStream;
+/// # use futures_core::Stream;
+/// # use std::pin::Pin;
+/// # async fn example(socket: tokio::io::DuplexStream) -> std::io::Result<()> {
+/// let mut frames = AdtsFrameStream::new(socket);
+/// // with futures::StreamExt, this would be `frames.next().await`
+/// while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut frames).poll_next(cx)).await {
+///     println!("frame of {} bytes", frame?.as_bytes().len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "futures")]
+pub struct AdtsFrameStream<R: AsyncRead + Unpin> {
+    reader: AsyncAdtsReader<R>,
+    /// data is read here before being appended to the reader's buffer
+    scratch: Box<[u8]>,
+    ended: bool,
+}
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> AdtsFrameStream<R> {
+    pub fn new(input: R) -> AdtsFrameStream<R> {
+        AsyncAdtsReader::new(input).into_stream()
+    }
+
+    /// As for [`AsyncAdtsReader::skipped_bytes()`](struct.AsyncAdtsReader.html#method.skipped_bytes)
+    pub fn skipped_bytes(&self) -> u64 {
+        self.reader.skipped_bytes()
+    }
+
+    /// As for [`AsyncAdtsReader::pending_bytes()`](struct.AsyncAdtsReader.html#method.pending_bytes)
+    pub fn pending_bytes(&self) -> usize {
+        self.reader.pending_bytes()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.input
+    }
+}
+#[cfg(feature = "futures")]
+impl<R: AsyncRead + Unpin> Stream for AdtsFrameStream<R> {
+    type Item = io::Result<OwnedAdtsFrame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let reader = &mut this.reader;
+            if let Some(frame) = reader.buf.take_frame(&mut reader.skipped_bytes) {
+                return Poll::Ready(Some(Ok(OwnedAdtsFrame::from_frame(frame))));
+            }
+            if this.ended {
+                return Poll::Ready(None);
+            }
+            let mut read_buf = ReadBuf::new(&mut this.scratch);
+            match Pin::new(&mut reader.input).poll_read(cx, &mut read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(())) => {
+                    let data = read_buf.filled();
+                    this.ended = data.is_empty();
+                    reader.buf.spare().extend_from_slice(data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// Gets the next frame if it's available without waiting, and otherwise cancels the read
+    async fn next_or_cancel<R: AsyncRead + Unpin>(
+        reader: &mut AsyncAdtsReader<R>,
+    ) -> Option<Vec<u8>> {
+        tokio::select! {
+            biased;
+            frame = reader.next_frame() => frame.unwrap(),
+            _ = tokio::task::yield_now() => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cancellation() {
+        let builder = StreamBuilder::new()
+            .bytes(b"junk")
+            .frame(&[1, 2, 3])
+            .frame(&[4])
+            .frame(&[5, 6]);
+        let mut bounds = builder.offsets().to_vec();
+        let data = builder.build();
+        bounds.push(data.len());
+        let frames: Vec<_> = bounds
+            .windows(2)
+            .map(|w| data[w[0]..w[1]].to_vec())
+            .collect();
+        for split in 0..data.len() {
+            let (mut tx, rx) = tokio::io::duplex(1024);
+            let mut reader = AsyncAdtsReader::new(rx);
+            let mut received = vec![];
+            tx.write_all(&data[..split]).await.unwrap();
+            // reads that are cancelled part way through a frame
+            for _ in 0..3 {
+                if let Some(frame) = next_or_cancel(&mut reader).await {
+                    received.push(frame);
+                }
+            }
+            assert_eq!(next_or_cancel(&mut reader).await, None);
+            tx.write_all(&data[split..]).await.unwrap();
+            drop(tx);
+            while let Some(frame) = reader.next_frame().await.unwrap() {
+                received.push(frame);
+            }
+            assert_eq!(received, frames, "split at {}", split);
+            assert_eq!(reader.skipped_bytes(), 4);
+            assert_eq!(reader.pending_bytes(), 0);
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    async fn next_item<R: AsyncRead + Unpin>(
+        frames: &mut AdtsFrameStream<R>,
+    ) -> Option<io::Result<OwnedAdtsFrame>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *frames).poll_next(cx)).await
+    }
+
+    #[cfg(feature = "futures")]
+    #[tokio::test]
+    async fn stream() {
+        // junk, then frames of 9, 8 and 8 bytes
+        let data = StreamBuilder::new()
+            .bytes(b"junk")
+            .frame(&[1, 2])
+            .frame(&[3])
+            .frame(&[4])
+            .build();
+        let (mut tx, rx) = tokio::io::duplex(1024);
+        let mut frames = AdtsFrameStream::new(rx);
+        tx.write_all(&data[..17]).await.unwrap();
+        let first = next_item(&mut frames).await.unwrap().unwrap();
+        assert_eq!(first.payload(), &[1, 2]);
+        // the rest of a frame arrives after the stream has returned Pending
+        let writer = tokio::spawn(async move {
+            tokio::task::yield_now().await;
+            tx.write_all(&data[17..23]).await.unwrap();
+        });
+        assert_eq!(
+            next_item(&mut frames).await.unwrap().unwrap().payload(),
+            &[3]
+        );
+        writer.await.unwrap();
+        assert!(next_item(&mut frames).await.is_none());
+        assert!(next_item(&mut frames).await.is_none());
+        assert_eq!(frames.skipped_bytes(), 4);
+        assert_eq!(frames.pending_bytes(), 2);
+    }
+
+    #[tokio::test]
+    async fn truncated_at_end() {
+        let data = StreamBuilder::new().frame(&[1]).frame(&[2, 3]).build();
+        let mut reader = AsyncAdtsReader::new(&data[..16]);
+        assert_eq!(reader.next_frame().await.unwrap(), Some(data[..8].to_vec()));
+        assert_eq!(reader.next_frame().await.unwrap(), None);
+        assert_eq!(reader.pending_bytes(), 8);
+    }
+}
@@ -0,0 +1,141 @@
+//! Applies common repairs to an ADTS stream (or several, joined end to end), writing the cleaned
+//! stream to a new file.
+
+use adts_reader::repair::{concat, CrcRepair, FullnessRepair, RepairOptions};
+use adts_reader::ChannelConfiguration;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: adts-fix [options] <input>... <output>
+
+Use '-' as <input> or <output> to read from stdin or write to stdout.  Several inputs are joined
+end to end, in the order given.
+
+Options:
+  --keep-id3            copy ID3v2 tags to the output, rather than dropping them
+  --keep-junk           copy bytes that are not part of any frame to the output
+  --keep-truncated      copy an incomplete final frame to the output
+  --strip-crc           remove CRC protection from frames
+  --add-crc             add CRC protection to frames that lack it
+  --force-channels <n>  set channel_configuration to <n> (0-7) in every frame
+  --vbr-fullness        set adts_buffer_fullness to 0x7ff (variable rate) in every frame
+  --cbr-fullness <bps>  recompute adts_buffer_fullness for a constant rate of <bps> bits/s
+  --drop-corrupt        drop frames whose CRC does not match
+  --start <seconds>     drop frames starting before this media time
+  --end <seconds>       drop frames starting at or after this media time
+  --splice-duplicates <n>
+                        drop up to <n> frames at the start of each input which repeat the
+                        last frames of the input before it";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+fn parse_args() -> (RepairOptions, Vec<String>, String) {
+    let mut options = RepairOptions::default();
+    let mut files = vec![];
+    let (mut start, mut end) = (None, None);
+    let seconds = |arg: Option<String>| {
+        arg.and_then(|s| Duration::try_from_secs_f64(s.parse().ok()?).ok())
+            .unwrap_or_else(|| usage())
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keep-id3" => options.skip_id3 = false,
+            "--keep-junk" => options.skip_junk = false,
+            "--keep-truncated" => options.trim_truncated = false,
+            "--strip-crc" => options.crc = CrcRepair::Strip,
+            "--add-crc" => options.crc = CrcRepair::Add,
+            "--force-channels" => {
+                let n = args.next().and_then(|n| n.parse::<u8>().ok());
+                match n.and_then(|n| ChannelConfiguration::try_from(n).ok()) {
+                    Some(channels) => options.force_channels = Some(channels),
+                    None => usage(),
+                }
+            }
+            "--vbr-fullness" => options.buffer_fullness = FullnessRepair::Vbr,
+            "--cbr-fullness" => match args.next().and_then(|n| n.parse::<u32>().ok()) {
+                Some(bitrate) if bitrate > 0 => {
+                    options.buffer_fullness = FullnessRepair::Reservoir { bitrate }
+                }
+                _ => usage(),
+            },
+            "--drop-corrupt" => options.drop_corrupt = true,
+            "--start" => start = Some(seconds(args.next())),
+            "--end" => end = Some(seconds(args.next())),
+            "--splice-duplicates" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) => options.splice_duplicates = n,
+                None => usage(),
+            },
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with("--") => usage(),
+            _ => files.push(arg),
+        }
+    }
+    if files.len() < 2 {
+        usage();
+    }
+    if start.is_some() || end.is_some() {
+        options.time_range = Some(start.unwrap_or(Duration::ZERO)..end.unwrap_or(Duration::MAX));
+    }
+    let output = files.pop().unwrap();
+    (options, files, output)
+}
+
+fn open_input(name: &str) -> io::Result<Box<dyn Read>> {
+    if name == "-" {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(File::open(name)?))
+    }
+}
+
+fn open_output(name: &str) -> io::Result<Box<dyn Write>> {
+    if name == "-" {
+        Ok(Box::new(io::stdout().lock()))
+    } else {
+        Ok(Box::new(io::BufWriter::new(File::create(name)?)))
+    }
+}
+
+fn main() {
+    let (options, input_names, output_name) = parse_args();
+    let result = input_names
+        .iter()
+        .map(|name| open_input(name))
+        .collect::<io::Result<Vec<_>>>()
+        .and_then(|inputs| Ok((inputs, open_output(&output_name)?)))
+        .and_then(|(inputs, output)| concat(inputs, output, &options));
+    match result {
+        Ok(report) => {
+            let mut dropped = String::new();
+            if options.splice_duplicates > 0 {
+                dropped += &format!(", {} duplicate frames", report.duplicate_frames_dropped);
+            }
+            if options.drop_corrupt {
+                dropped += &format!(", {} corrupt frames", report.corrupt_frames_dropped);
+            }
+            if options.time_range.is_some() {
+                dropped += &format!(", {} frames out of range", report.frames_outside_range);
+            }
+            eprintln!(
+                "{} frames written ({} rewritten); dropped {} bytes of ID3 tags, {} bytes of junk, {} bytes of truncated frame{}",
+                report.frames,
+                report.frames_rewritten,
+                report.id3_bytes_skipped,
+                report.junk_bytes_skipped,
+                report.truncated_bytes_trimmed,
+                dropped
+            );
+        }
+        Err(e) => {
+            eprintln!("adts-fix: {}", e);
+            process::exit(1);
+        }
+    }
+}
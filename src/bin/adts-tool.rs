@@ -0,0 +1,191 @@
+//! Inspects an ADTS stream: summarising it, listing its frames, and checking it for damage.
+
+use adts_reader::crc::{CrcError, CrcPolicy};
+use adts_reader::inspect::{self, FrameRow};
+use adts_reader::{AdtsConsumer2, AdtsParseError, AdtsParser, AdtsStreamStats, FrameContext};
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+const USAGE: &str = "usage: adts-tool <command> [options] <input>
+
+Use '-' as <input> to read from stdin.
+
+Commands:
+  info      summarise the stream's configuration, duration and bitrate
+  frames    list every frame, with its offset, length and CRC status
+
+Options:
+  --json    write JSON: a single object for 'info', or one object per line for 'frames'
+  --verify  check CRCs and continuity, exiting with status 1 if problems are found";
+
+const READ_SIZE: usize = 64 * 1024;
+
+#[derive(PartialEq)]
+enum Command {
+    Info,
+    Frames,
+}
+
+struct Args {
+    command: Command,
+    json: bool,
+    verify: bool,
+    input: String,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+fn parse_args() -> Args {
+    let mut args = env::args().skip(1);
+    let command = match args.next().as_deref() {
+        Some("info") => Command::Info,
+        Some("frames") => Command::Frames,
+        _ => usage(),
+    };
+    let (mut json, mut verify) = (false, false);
+    let mut inputs = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--verify" => verify = true,
+            "-h" | "--help" => usage(),
+            _ if arg.starts_with("--") => usage(),
+            _ => inputs.push(arg),
+        }
+    }
+    match <[String; 1]>::try_from(inputs) {
+        Ok([input]) => Args {
+            command,
+            json,
+            verify,
+            input,
+        },
+        Err(_) => usage(),
+    }
+}
+
+/// Collects statistics on every frame, listing each one if `listing` is given
+struct Inspector<W: Write> {
+    stats: AdtsStreamStats,
+    listing: Option<W>,
+    json: bool,
+    /// the first error writing the listing, after which no more is written
+    error: Option<io::Error>,
+}
+impl<W: Write> Inspector<W> {
+    fn list(&mut self, row: &FrameRow) -> io::Result<()> {
+        let out = match self.listing.as_mut() {
+            Some(out) => out,
+            None => return Ok(()),
+        };
+        if self.json {
+            serde_json::to_writer(&mut *out, row)?;
+            writeln!(out)
+        } else {
+            writeln!(out, "{}", row.format())
+        }
+    }
+}
+impl<W: Write> AdtsConsumer2 for Inspector<W> {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        self.stats.frame(&frame.header);
+        if self.error.is_none() {
+            let row = FrameRow::new(frame.index, frame.offset, &frame.header);
+            if let Err(e) = self.list(&row) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn on_error(&mut self, err: AdtsParseError) {
+        self.stats.on_error(err);
+    }
+
+    fn on_crc_error(&mut self, frame: &FrameContext<'_>, err: CrcError) {
+        self.stats.on_crc_error(frame, err);
+    }
+}
+
+fn open_input(name: &str) -> io::Result<Box<dyn Read>> {
+    if name == "-" {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(File::open(name)?))
+    }
+}
+
+/// Inspects the whole input, returning the statistics and the length of any incomplete frame
+/// left at its end
+fn run(
+    args: &Args,
+    mut input: impl Read,
+    out: &mut impl Write,
+) -> io::Result<(AdtsStreamStats, usize)> {
+    let listing = args.command == Command::Frames;
+    if listing && !args.json {
+        writeln!(out, "{}", FrameRow::headings())?;
+    }
+    let inspector = Inspector {
+        stats: AdtsStreamStats::new(),
+        listing: if listing { Some(&mut *out) } else { None },
+        json: args.json,
+        error: None,
+    };
+    let policy = if args.verify {
+        CrcPolicy::Flag
+    } else {
+        CrcPolicy::Ignore
+    };
+    let mut parser = AdtsParser::new(inspector).with_crc_policy(policy);
+    let mut buf = vec![0; READ_SIZE];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        parser.push(&buf[..n]);
+        if let Some(e) = parser.consumer.error.take() {
+            return Err(e);
+        }
+    }
+    let truncated = parser.pending_bytes();
+    let stats = parser.consumer.stats;
+    if args.command == Command::Info {
+        if args.json {
+            writeln!(out, "{}", inspect::summary_json(&stats))?;
+        } else {
+            write!(out, "{}", inspect::format_summary(&stats))?;
+        }
+    }
+    out.flush()?;
+    Ok((stats, truncated))
+}
+
+fn main() {
+    let args = parse_args();
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let result = open_input(&args.input).and_then(|input| run(&args, input, &mut out));
+    let (stats, truncated) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("adts-tool: {}", e);
+            process::exit(1);
+        }
+    };
+    if args.verify {
+        eprintln!(
+            "verify: {} CRC mismatches, {} bitstream errors, {} configuration changes, {} bytes of truncated frame",
+            stats.crc_errors, stats.errors, stats.config_changes, truncated
+        );
+        if stats.crc_errors > 0 || stats.errors > 0 || stats.config_changes > 0 || truncated > 0 {
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Minimal MSB-first bit reader, used where syntax isn't aligned to byte boundaries.
+
+#[derive(Clone)]
+pub(crate) struct BitReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+// some methods are only needed by the elements feature
+#[cfg_attr(not(feature = "elements"), allow(dead_code))]
+impl<'a> BitReader<'a> {
+    pub fn new(buf: &'a [u8]) -> BitReader<'a> {
+        BitReader { buf, pos: 0 }
+    }
+
+    /// Current position, in bits from the start of the buffer
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() * 8 - self.pos
+    }
+
+    /// Reads an unsigned value of up to 32 bits, or `None` if the buffer holds too few bits
+    pub fn read(&mut self, bits: u32) -> Option<u32> {
+        debug_assert!(bits <= 32);
+        if self.remaining() < bits as usize {
+            return None;
+        }
+        let mut val = 0u32;
+        for _ in 0..bits {
+            let byte = self.buf[self.pos / 8];
+            let bit = byte >> (7 - self.pos % 8) & 1;
+            val = val << 1 | u32::from(bit);
+            self.pos += 1;
+        }
+        Some(val)
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read(1).map(|b| b != 0)
+    }
+
+    pub fn skip(&mut self, bits: usize) -> Option<()> {
+        if self.remaining() < bits {
+            return None;
+        }
+        self.pos += bits;
+        Some(())
+    }
+
+    /// Advances to the next byte boundary, if not already at one
+    pub fn byte_align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+/// MSB-first counterpart to `BitReader`, padding the final byte with zero bits.
+pub(crate) struct BitWriter {
+    buf: Vec<u8>,
+    pos: usize,
+}
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        BitWriter {
+            buf: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Writes the least significant `bits` bits of `val`
+    pub fn write(&mut self, bits: u32, val: u32) {
+        debug_assert!(bits <= 32);
+        for i in (0..bits).rev() {
+            if self.pos % 8 == 0 {
+                self.buf.push(0);
+            }
+            let bit = (val >> i & 1) as u8;
+            *self.buf.last_mut().unwrap() |= bit << (7 - self.pos % 8);
+            self.pos += 1;
+        }
+    }
+
+    pub fn write_bool(&mut self, val: bool) {
+        self.write(1, u32::from(val));
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
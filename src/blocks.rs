@@ -0,0 +1,450 @@
+//! Location of the individual `raw_data_block`s within a frame holding more than one.
+//!
+//! A frame may carry up to four `raw_data_block`s.  When a frame has a CRC, its header is
+//! followed by a `raw_data_block_position` table giving where each block after the first starts
+//! (and each block is followed by its own 16-bit CRC).  Without a CRC there is no table, and
+//! blocks can only be located by identifying every syntactic element in the blocks before them,
+//! which (with the `elements` feature) is attempted as a fallback.
+//!
+//! Knowing the size of each block allows a single damaged block to be localised, since its size
+//! will usually stand out from those of its neighbours; see
+//! [`BlockLayout::implausible_blocks()`](struct.BlockLayout.html#method.implausible_blocks).
+//!
+//! Each block is a separate access unit for a decoder, so
+//! [`AdtsHeader::raw_data_blocks()`](../struct.AdtsHeader.html#method.raw_data_blocks) gives the
+//! bytes of each in turn, and [`AdtsParser`](../struct.AdtsParser.html) can deliver them
+//! individually.
+
+use crate::{AdtsHeader, AdtsHeaderError, ProtectionIndicator};
+use std::iter::{Enumerate, FusedIterator};
+
+/// Length of the fixed and variable headers, preceding any position table
+const HEADER_LEN: usize = 7;
+
+/// Blocks whose size differs from the frame's median block size by more than this factor are
+/// judged implausible.  The blocks of one frame cover only a few tens of milliseconds of audio
+/// between them, so even a sharp change in the signal rarely gives sizes this far apart.
+const IMPLAUSIBLE_RATIO: usize = 4;
+
+/// The extent of one `raw_data_block` within a frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlockExtent {
+    /// Position of the start of the block, in bytes from the start of the frame
+    pub offset: usize,
+    /// Length of the block in bytes, excluding the CRC which follows it in protected frames
+    pub len: usize,
+}
+
+/// How the extents of the blocks were determined.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LayoutSource {
+    /// The frame holds a single block, spanning the whole payload
+    SingleBlock,
+    /// From the `raw_data_block_position` table in the header of a frame with a CRC
+    PositionTable,
+    /// By identifying every syntactic element of each block (with the `elements` feature)
+    ElementScan,
+}
+
+/// Why [`block_layout()`](fn.block_layout.html) could not locate the blocks of a frame.
+#[derive(Debug, PartialEq)]
+pub enum BlockLayoutError {
+    /// The header fields could not be parsed at all
+    Header(AdtsHeaderError),
+    /// The buffer is shorter than the `frame_length` header field says the frame should be
+    Truncated { expected: usize, actual: usize },
+    /// The frame has no CRC, so no position table, and the blocks could not be located by
+    /// scanning their elements (which requires the `elements` feature, and is only possible when
+    /// the blocks preceding the last hold no channel elements)
+    NoPositionTable,
+    /// The position table gives a start for the block of the given index which leaves no room
+    /// for the previous block's CRC, or is beyond the end of the frame
+    BadPosition { block: usize, position: u16 },
+}
+
+/// The extents of every `raw_data_block` in a frame.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BlockLayout {
+    pub blocks: Vec<BlockExtent>,
+    pub source: LayoutSource,
+}
+impl BlockLayout {
+    /// Indexes of blocks whose size is implausible: blocks of zero length (since every block
+    /// must hold at least an `END` element), and blocks more than four times larger or smaller
+    /// than the median block size of the frame.
+    ///
+    /// Frames with fewer than three blocks have no meaningful median, so only the zero length
+    /// check applies to them.
+    pub fn implausible_blocks(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self.blocks.iter().map(|b| b.len).collect();
+        sizes.sort_unstable();
+        let median = if sizes.len() >= 3 {
+            Some(sizes[sizes.len() / 2])
+        } else {
+            None
+        };
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| {
+                b.len == 0
+                    || median.is_some_and(|m| {
+                        b.len > m * IMPLAUSIBLE_RATIO || b.len * IMPLAUSIBLE_RATIO < m
+                    })
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// The ratio of the largest block size to the smallest, as a simple measure of how
+    /// consistent the block sizes are, or `None` if any block is empty
+    pub fn size_ratio(&self) -> Option<f64> {
+        let min = self.blocks.iter().map(|b| b.len).min()?;
+        let max = self.blocks.iter().map(|b| b.len).max()?;
+        if min == 0 {
+            None
+        } else {
+            Some(max as f64 / min as f64)
+        }
+    }
+}
+
+/// Locates each `raw_data_block` in the complete frame at the start of the given buffer.
+///
+/// ```rust
+/// use adts_reader::blocks::{block_layout, LayoutSource};
+/// let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let layout = block_layout(&frame).unwrap();
+/// assert_eq!(layout.source, LayoutSource::SingleBlock);
+/// assert_eq!(layout.blocks[0].len, 1);
+/// ```
+pub fn block_layout(frame: &[u8]) -> Result<BlockLayout, BlockLayoutError> {
+    if frame.is_empty() {
+        return Err(BlockLayoutError::Truncated {
+            expected: HEADER_LEN,
+            actual: 0,
+        });
+    }
+    let header = AdtsHeader::from_bytes(frame).map_err(BlockLayoutError::Header)?;
+    let frame_len = header.frame_len();
+    if frame.len() < frame_len {
+        return Err(BlockLayoutError::Truncated {
+            expected: frame_len,
+            actual: frame.len(),
+        });
+    }
+    let frame = &frame[..frame_len];
+    let blocks = usize::from(header.number_of_raw_data_blocks_in_frame());
+    let crc = header.protection() == ProtectionIndicator::CrcPresent;
+    if blocks == 1 {
+        let offset = if crc { 9 } else { 7 };
+        return Ok(BlockLayout {
+            blocks: vec![BlockExtent {
+                offset,
+                len: frame_len.saturating_sub(offset),
+            }],
+            source: LayoutSource::SingleBlock,
+        });
+    }
+    if crc {
+        position_table(frame, blocks)
+    } else {
+        scan_blocks(&header, frame)
+    }
+}
+
+fn position_table(frame: &[u8], blocks: usize) -> Result<BlockLayout, BlockLayoutError> {
+    // raw_data_block_position[1..blocks] then crc_check, each 16 bits
+    let first = HEADER_LEN + 2 * blocks;
+    if frame.len() < first {
+        return Err(BlockLayoutError::Truncated {
+            expected: first,
+            actual: frame.len(),
+        });
+    }
+    let data_len = frame.len() - first;
+    // positions are measured from the start of the first block
+    let mut starts = vec![0];
+    for i in 1..blocks {
+        let at = HEADER_LEN + 2 * (i - 1);
+        let position = u16::from_be_bytes([frame[at], frame[at + 1]]);
+        let previous = starts[i - 1];
+        // an empty block is reported by implausible_blocks(), rather than rejected here
+        if usize::from(position) < previous + 2 || usize::from(position) + 2 > data_len {
+            return Err(BlockLayoutError::BadPosition { block: i, position });
+        }
+        starts.push(usize::from(position));
+    }
+    starts.push(data_len);
+    let blocks = starts
+        .windows(2)
+        .map(|w| BlockExtent {
+            offset: first + w[0],
+            len: w[1] - w[0] - 2,
+        })
+        .collect();
+    Ok(BlockLayout {
+        blocks,
+        source: LayoutSource::PositionTable,
+    })
+}
+
+/// The bytes of one `raw_data_block`, as yielded by [`RawDataBlocks`](struct.RawDataBlocks.html).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RawDataBlock<'buf> {
+    /// The position of this block within the frame, counting from zero
+    pub index: usize,
+    /// The block itself, excluding any CRC
+    pub data: &'buf [u8],
+    /// The CRC protecting this block: the `crc_check` header field for a frame's only block, or
+    /// the `adts_raw_data_block_error_check` following each block of a frame with several.
+    /// `None` in frames without a CRC.
+    pub crc: Option<u16>,
+}
+
+/// Iterator over the `raw_data_block`s of a frame, returned by
+/// [`AdtsHeader::raw_data_blocks()`](../struct.AdtsHeader.html#method.raw_data_blocks).
+#[derive(Debug, Clone)]
+pub struct RawDataBlocks<'buf> {
+    frame: &'buf [u8],
+    blocks: Enumerate<std::vec::IntoIter<BlockExtent>>,
+    source: LayoutSource,
+    /// the header's `crc_check`, which protects a single block
+    header_crc: Option<u16>,
+}
+impl<'buf> RawDataBlocks<'buf> {
+    /// Locates the blocks of the complete frame at the start of the given buffer, as for
+    /// [`block_layout()`](fn.block_layout.html)
+    pub fn new(frame: &'buf [u8]) -> Result<RawDataBlocks<'buf>, BlockLayoutError> {
+        let layout = block_layout(frame)?;
+        let header_crc = AdtsHeader::from_bytes(frame)
+            .map_err(BlockLayoutError::Header)?
+            .crc();
+        Ok(RawDataBlocks {
+            frame,
+            blocks: layout.blocks.into_iter().enumerate(),
+            source: layout.source,
+            header_crc,
+        })
+    }
+
+    /// How the blocks were located
+    pub fn source(&self) -> LayoutSource {
+        self.source
+    }
+
+    fn block(&self, (index, extent): (usize, BlockExtent)) -> RawDataBlock<'buf> {
+        let end = extent.offset + extent.len;
+        let crc = match self.source {
+            LayoutSource::SingleBlock => self.header_crc,
+            LayoutSource::PositionTable => {
+                Some(u16::from_be_bytes([self.frame[end], self.frame[end + 1]]))
+            }
+            LayoutSource::ElementScan => None,
+        };
+        RawDataBlock {
+            index,
+            data: &self.frame[extent.offset..end],
+            crc,
+        }
+    }
+}
+impl<'buf> Iterator for RawDataBlocks<'buf> {
+    type Item = RawDataBlock<'buf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.blocks.next().map(|b| self.block(b))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.blocks.size_hint()
+    }
+}
+impl DoubleEndedIterator for RawDataBlocks<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.blocks.next_back().map(|b| self.block(b))
+    }
+}
+impl ExactSizeIterator for RawDataBlocks<'_> {}
+impl FusedIterator for RawDataBlocks<'_> {}
+
+#[cfg(feature = "elements")]
+fn scan_blocks(header: &AdtsHeader<'_>, frame: &[u8]) -> Result<BlockLayout, BlockLayoutError> {
+    let payload = &frame[HEADER_LEN..];
+    let scan = crate::elements::scan_frame(header, payload);
+    let blocks = usize::from(header.number_of_raw_data_blocks_in_frame());
+    // every block before the last must be fully identified to find where the next begins
+    if scan.blocks.len() < blocks {
+        return Err(BlockLayoutError::NoPositionTable);
+    }
+    let mut extents = vec![];
+    let mut start = 0;
+    for (i, block) in scan.blocks.iter().enumerate() {
+        let end = if i + 1 == blocks {
+            payload.len()
+        } else {
+            let end = block
+                .elements
+                .last()
+                .expect("a complete block ends with END");
+            (end.bit_offset + end.bit_len.expect("END has a known length")).div_ceil(8)
+        };
+        extents.push(BlockExtent {
+            offset: HEADER_LEN + start,
+            len: end - start,
+        });
+        start = end;
+    }
+    Ok(BlockLayout {
+        blocks: extents,
+        source: LayoutSource::ElementScan,
+    })
+}
+
+#[cfg(not(feature = "elements"))]
+fn scan_blocks(_: &AdtsHeader<'_>, _: &[u8]) -> Result<BlockLayout, BlockLayoutError> {
+    Err(BlockLayoutError::NoPositionTable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    /// Builds a protected frame holding blocks of the given sizes, each followed by a (dummy)
+    /// block CRC, preceded by the position table
+    fn protected_frame(sizes: &[usize]) -> Vec<u8> {
+        let mut table = vec![];
+        let mut data = vec![];
+        for (i, &size) in sizes.iter().enumerate() {
+            if i > 0 {
+                table.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            }
+            data.extend(std::iter::repeat_n(i as u8 + 1, size));
+            data.extend_from_slice(&[0, 0]);
+        }
+        let options = FrameOptions {
+            number_of_raw_data_blocks: sizes.len() as u8,
+            ..FrameOptions::default()
+        };
+        // written unprotected, then converted, since the writer won't write multi-block CRCs
+        let mut payload = table;
+        payload.extend_from_slice(&[0, 0]); // crc_check
+        payload.extend(data);
+        let mut frame = StreamBuilder::new()
+            .options(options)
+            .frame(&payload)
+            .build();
+        fields::PROTECTION_ABSENT.set(&mut frame, 0);
+        frame
+    }
+
+    #[test]
+    fn position_table() {
+        let frame = protected_frame(&[10, 12, 0, 11]);
+        let layout = block_layout(&frame).unwrap();
+        assert_eq!(layout.source, LayoutSource::PositionTable);
+        let first = 7 + 2 * 4;
+        assert_eq!(
+            layout.blocks,
+            vec![
+                BlockExtent {
+                    offset: first,
+                    len: 10
+                },
+                BlockExtent {
+                    offset: first + 12,
+                    len: 12
+                },
+                BlockExtent {
+                    offset: first + 26,
+                    len: 0
+                },
+                BlockExtent {
+                    offset: first + 28,
+                    len: 11
+                },
+            ]
+        );
+        assert_eq!(frame[first + 12], 2);
+        assert_eq!(layout.implausible_blocks(), vec![2]);
+
+        let mut blocks = RawDataBlocks::new(&frame).unwrap();
+        assert_eq!(blocks.source(), LayoutSource::PositionTable);
+        assert_eq!(blocks.len(), 4);
+        let sizes: Vec<_> = blocks.clone().map(|b| (b.index, b.data.len())).collect();
+        assert_eq!(sizes, vec![(0, 10), (1, 12), (2, 0), (3, 11)]);
+        assert!(blocks
+            .clone()
+            .all(|b| b.data.iter().all(|&d| d == b.index as u8 + 1) && b.crc == Some(0)));
+        assert_eq!(blocks.next_back().unwrap().index, 3);
+        assert_eq!(layout.size_ratio(), None);
+
+        let layout = block_layout(&protected_frame(&[100, 90, 10, 110])).unwrap();
+        assert_eq!(layout.implausible_blocks(), vec![2]);
+        assert_eq!(layout.size_ratio(), Some(11.0));
+
+        let mut frame = protected_frame(&[10, 12]);
+        frame[8] = 200;
+        assert_eq!(
+            block_layout(&frame),
+            Err(BlockLayoutError::BadPosition {
+                block: 1,
+                position: 200
+            })
+        );
+    }
+
+    #[test]
+    fn unprotected() {
+        let options = FrameOptions {
+            number_of_raw_data_blocks: 2,
+            ..FrameOptions::default()
+        };
+        // a CPE first, whose length can't be found
+        let frame = StreamBuilder::new()
+            .options(options)
+            .frame(&[0x21, 0x00, 0x21, 0x00])
+            .build();
+        assert_eq!(block_layout(&frame), Err(BlockLayoutError::NoPositionTable));
+        assert_eq!(
+            RawDataBlocks::new(&frame).err(),
+            Some(BlockLayoutError::NoPositionTable)
+        );
+
+        let frame = StreamBuilder::new().frame(&[0x21, 0x00]).build();
+        let blocks: Vec<_> = RawDataBlocks::new(&frame).unwrap().collect();
+        assert_eq!(
+            blocks,
+            vec![RawDataBlock {
+                index: 0,
+                data: &[0x21, 0x00],
+                crc: None
+            }]
+        );
+    }
+
+    #[cfg(feature = "elements")]
+    #[test]
+    fn element_scan() {
+        let options = FrameOptions {
+            number_of_raw_data_blocks: 2,
+            ..FrameOptions::default()
+        };
+        // a block holding just END (0b111 then alignment), then a block with a CPE
+        let frame = StreamBuilder::new()
+            .options(options)
+            .frame(&[0xe0, 0x21, 0x00, 0x00])
+            .build();
+        let layout = block_layout(&frame).unwrap();
+        assert_eq!(layout.source, LayoutSource::ElementScan);
+        assert_eq!(
+            layout.blocks,
+            vec![
+                BlockExtent { offset: 7, len: 1 },
+                BlockExtent { offset: 8, len: 3 },
+            ]
+        );
+    }
+}
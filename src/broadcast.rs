@@ -0,0 +1,193 @@
+//! Fan-out of frames from one ingest stream to many subscribers.
+//!
+//! Available when the `tokio` feature is enabled.
+
+use crate::async_reader::AsyncAdtsReader;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+use tokio::sync::broadcast;
+
+/// A complete ADTS frame, shared between all the subscribers it is sent to.
+pub type SharedFrame = Arc<[u8]>;
+
+/// What a [`Subscriber`](struct.Subscriber.html) does on falling so far behind that frames it
+/// had not yet received have been discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Skip the discarded frames, and carry on from the oldest frame still held.  Since whole
+    /// frames are dropped, the stream the subscriber receives remains decodable.
+    DropOldest,
+    /// Report the lag to the caller as an error, which might (for example) disconnect the client.
+    /// Calling `recv()` again after the error carries on from the oldest frame still held.
+    Error,
+}
+
+/// Reported by [`Subscriber::recv()`](struct.Subscriber.html#method.recv), under
+/// `LagPolicy::Error`, when frames were discarded before the subscriber received them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lagged {
+    /// The number of frames missed
+    pub missed: u64,
+}
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "subscriber lagged, missing {} frames", self.missed)
+    }
+}
+impl std::error::Error for Lagged {}
+
+/// Distributes frames to any number of [`Subscriber`s](struct.Subscriber.html), for example to
+/// relay one ingest stream to many clients.
+///
+/// Sending never waits for subscribers.  Instead, the broadcaster holds the most recent
+/// `capacity` frames, and a subscriber which falls further behind than that misses frames,
+/// handling this according to its own [`LagPolicy`](enum.LagPolicy.html).  One slow client
+/// therefore can't hold up ingest, or the other clients.
+///
+/// ```rust
+/// use adts_reader::broadcast::{Broadcaster, LagPolicy};
+/// # async fn example(ingest: tokio::io::DuplexStream) -> std::io::Result<()> {
+/// let broadcaster = Broadcaster::new(64);
+/// let mut client = broadcaster.subscribe(LagPolicy::DropOldest);
+/// tokio::spawn(async move {
+///     while let Ok(Some(frame)) = client.recv().await {
+///         // client_socket.write_all(&frame).await
+///     }
+/// });
+/// let mut reader = adts_reader::async_reader::AsyncAdtsReader::new(ingest);
+/// broadcaster.relay(&mut reader).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Broadcaster {
+    sender: broadcast::Sender<SharedFrame>,
+}
+impl Broadcaster {
+    /// Creates a broadcaster holding up to `capacity` frames for subscribers which have yet to
+    /// receive them.  Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Broadcaster {
+        let (sender, _) = broadcast::channel(capacity);
+        Broadcaster { sender }
+    }
+
+    /// Adds a subscriber, which will receive the frames sent from now on
+    pub fn subscribe(&self, policy: LagPolicy) -> Subscriber {
+        Subscriber {
+            receiver: self.sender.subscribe(),
+            policy,
+            dropped_frames: 0,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Sends a frame to all current subscribers, returning the number of subscribers it was sent
+    /// to
+    pub fn send(&self, frame: impl Into<SharedFrame>) -> usize {
+        self.sender.send(frame.into()).unwrap_or(0)
+    }
+
+    /// Sends every frame read from the given reader, until the end of its input, returning the
+    /// number of frames sent
+    pub async fn relay<R: AsyncRead + Unpin>(
+        &self,
+        reader: &mut AsyncAdtsReader<R>,
+    ) -> io::Result<u64> {
+        let mut frames = 0;
+        while let Some(frame) = reader.next_frame().await? {
+            self.send(frame);
+            frames += 1;
+        }
+        Ok(frames)
+    }
+}
+
+/// Receives frames sent by a [`Broadcaster`](struct.Broadcaster.html).
+#[derive(Debug)]
+pub struct Subscriber {
+    receiver: broadcast::Receiver<SharedFrame>,
+    policy: LagPolicy,
+    dropped_frames: u64,
+}
+impl Subscriber {
+    /// Waits for the next frame, returning `None` once the `Broadcaster` has been dropped and all
+    /// frames sent before that have been received.
+    ///
+    /// This method is cancellation safe.
+    pub async fn recv(&mut self) -> Result<Option<SharedFrame>, Lagged> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(frame) => return Ok(Some(frame)),
+                Err(broadcast::error::RecvError::Closed) => return Ok(None),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    self.dropped_frames += missed;
+                    if self.policy == LagPolicy::Error {
+                        return Err(Lagged { missed });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Total number of frames this subscriber has missed by lagging
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(sub: &mut Subscriber) -> Vec<u8> {
+        let mut received = vec![];
+        while let Ok(frame) = sub.receiver.try_recv() {
+            received.push(frame[0]);
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn lag_policies() {
+        let broadcaster = Broadcaster::new(2);
+        let mut dropping = broadcaster.subscribe(LagPolicy::DropOldest);
+        let mut failing = broadcaster.subscribe(LagPolicy::Error);
+        assert_eq!(broadcaster.subscriber_count(), 2);
+        for i in 0..5u8 {
+            assert_eq!(broadcaster.send(vec![i]), 2);
+        }
+        assert_eq!(dropping.recv().await, Ok(Some(vec![3].into())));
+        assert_eq!(dropping.dropped_frames(), 3);
+        assert_eq!(failing.recv().await, Err(Lagged { missed: 3 }));
+        assert_eq!(failing.recv().await, Ok(Some(vec![3].into())));
+        assert_eq!(failing.dropped_frames(), 3);
+        assert_eq!(frames(&mut dropping), vec![4]);
+        assert_eq!(frames(&mut failing), vec![4]);
+        drop(broadcaster);
+        assert_eq!(dropping.recv().await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn relay() {
+        use crate::testing::StreamBuilder;
+        let data = StreamBuilder::new()
+            .payload_len(1)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let broadcaster = Broadcaster::new(16);
+        let mut subscriber = broadcaster.subscribe(LagPolicy::Error);
+        let mut reader = AsyncAdtsReader::new(&data[..]);
+        assert_eq!(broadcaster.relay(&mut reader).await.unwrap(), 3);
+        drop(broadcaster);
+        let mut received = vec![];
+        while let Some(frame) = subscriber.recv().await.unwrap() {
+            received.extend_from_slice(&frame);
+        }
+        assert_eq!(received, data);
+    }
+}
@@ -0,0 +1,148 @@
+//! A consumer made of closures, for small tools and tests which don't warrant a named type.
+
+use crate::{
+    AdtsConsumer2, AdtsParseError, AdtsParser, AdtsStreamConfig, FrameContext, ProtectionIndicator,
+};
+
+/// An [`AdtsConsumer2`](trait.AdtsConsumer2.html) passing configurations, frames and errors to
+/// the given closures, as created by
+/// [`AdtsParser::with_callbacks()`](struct.AdtsParser.html#method.with_callbacks).  Other parser
+/// events are ignored.
+pub struct Callbacks<Fc, Ff, Fe>
+where
+    Fc: FnMut(AdtsStreamConfig, ProtectionIndicator),
+    Ff: FnMut(&FrameContext<'_>),
+    Fe: FnMut(AdtsParseError),
+{
+    pub on_config: Fc,
+    pub on_frame: Ff,
+    pub on_error: Fe,
+}
+impl<Fc, Ff, Fe> AdtsConsumer2 for Callbacks<Fc, Ff, Fe>
+where
+    Fc: FnMut(AdtsStreamConfig, ProtectionIndicator),
+    Ff: FnMut(&FrameContext<'_>),
+    Fe: FnMut(AdtsParseError),
+{
+    fn on_config(&mut self, config: AdtsStreamConfig, protection: ProtectionIndicator) {
+        (self.on_config)(config, protection);
+    }
+
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        (self.on_frame)(frame);
+    }
+
+    fn on_error(&mut self, err: AdtsParseError) {
+        (self.on_error)(err);
+    }
+}
+
+impl<Fc, Ff, Fe> AdtsParser<Callbacks<Fc, Ff, Fe>>
+where
+    Fc: FnMut(AdtsStreamConfig, ProtectionIndicator),
+    Ff: FnMut(&FrameContext<'_>),
+    Fe: FnMut(AdtsParseError),
+{
+    /// Creates a parser passing each new configuration, frame and error to the given closures.
+    ///
+    /// ```rust
+    /// use adts_reader::AdtsParser;
+    /// let mut frames = 0;
+    /// let mut parser = AdtsParser::with_callbacks(
+    ///     |config, _protection| println!("{:?}", config),
+    ///     |frame| frames += frame.header.number_of_raw_data_blocks_in_frame(),
+    ///     |err| eprintln!("{:?}", err),
+    /// );
+    /// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+    /// drop(parser);
+    /// assert_eq!(frames, 1);
+    /// ```
+    pub fn with_callbacks(on_config: Fc, on_frame: Ff, on_error: Fe) -> Self {
+        AdtsParser::new(Callbacks {
+            on_config,
+            on_frame,
+            on_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn callbacks() {
+        let data = StreamBuilder::new()
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .junk(3)
+            .frames(1, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut configs = vec![];
+        let mut indexes = vec![];
+        let mut errors = vec![];
+        let mut parser = AdtsParser::with_callbacks(
+            |config, _| configs.push(config),
+            |frame| indexes.push(frame.index),
+            |err| errors.push(err),
+        );
+        parser.push(&data);
+        drop(parser);
+        assert_eq!(configs, [StreamBuilder::DEFAULT_CONFIG]);
+        assert_eq!(indexes, [0, 1, 2]);
+        assert_eq!(errors, [AdtsParseError::BadSyncWord]);
+    }
+
+    #[test]
+    fn trait_objects() {
+        struct Count<'a>(&'a mut u64);
+        impl AdtsConsumer2 for Count<'_> {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {
+                *self.0 += 1;
+            }
+            // a default method which the box must forward, not fall back on
+            fn on_error_at(&mut self, _: AdtsParseError, _: &ErrorContext<'_>) {
+                *self.0 += 100;
+            }
+        }
+        struct Old(u64);
+        impl AdtsConsumer for Old {
+            fn new_config(
+                &mut self,
+                _: MpegVersion,
+                _: ProtectionIndicator,
+                _: AudioObjectType,
+                _: SamplingFrequency,
+                _: u8,
+                _: ChannelConfiguration,
+                _: Originality,
+                _: u8,
+            ) {
+            }
+            fn payload(&mut self, _: u16, _: u8, _: &[u8]) {
+                self.0 += 1;
+            }
+            fn error(&mut self, _: AdtsParseError) {}
+        }
+
+        let data = StreamBuilder::new()
+            .junk(3)
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut count = 0;
+        let consumers: Vec<Box<dyn AdtsConsumer2 + '_>> = vec![
+            Box::new(Count(&mut count)),
+            Box::new(OwnedFrames::new(|_| ())),
+        ];
+        for consumer in consumers {
+            let mut parser: AdtsParser<Box<dyn AdtsConsumer2>> = AdtsParser::new(consumer);
+            parser.push(&data);
+        }
+        assert_eq!(count, 102);
+
+        let mut parser: AdtsParser<Box<dyn AdtsConsumer + Send>> =
+            AdtsParser::new(Box::new(Old(0)));
+        parser.push(&data);
+        assert_eq!(parser.frames(), 2);
+    }
+}
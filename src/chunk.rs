@@ -0,0 +1,194 @@
+//! Grouping of frames into packets for sending over the network, with bounded size and latency.
+
+use crate::{AdtsHeader, AdtsHeaderError, FrameLength, MediaTime};
+use std::time::Duration;
+
+/// Limits on the packets produced by [`Chunker`](struct.Chunker.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkLimits {
+    /// Largest number of bytes a packet may hold.  A single frame larger than this is sent as a
+    /// packet on its own.
+    pub max_bytes: usize,
+    /// Greatest media duration a packet may hold, bounding the latency added by waiting for
+    /// frames to fill a packet.  A single frame longer than this is sent as a packet on its own.
+    pub max_duration: Duration,
+}
+
+/// A group of whole frames produced by [`Chunker`](struct.Chunker.html).
+#[derive(Debug, PartialEq)]
+pub struct Chunk<'a> {
+    /// The complete frames, concatenated
+    pub data: &'a [u8],
+    pub frames: usize,
+    /// Total media duration of the frames
+    pub duration: Duration,
+}
+
+/// Groups complete ADTS frames into packets no larger than `max_bytes` and no longer than
+/// `max_duration`, whichever limit is reached first, preserving frame boundaries.
+///
+/// Each packet is passed to the given closure as soon as it's known that the next frame won't
+/// fit.  To decide this without waiting for the next frame to arrive (which would add a frame's
+/// worth of latency), the next frame is assumed to have the same duration as the last.  If input
+/// stalls, call [`flush()`](#method.flush) to send whatever has been gathered so far.
+///
+/// ```rust
+/// use adts_reader::chunk::{ChunkLimits, Chunker};
+/// use std::time::Duration;
+/// # let frames: Vec<Vec<u8>> = vec![];
+/// let limits = ChunkLimits {
+///     max_bytes: 1400,
+///     max_duration: Duration::from_millis(100),
+/// };
+/// let mut chunker = Chunker::new(limits, |chunk| {
+///     // socket.send(chunk.data)
+///     println!("sending {} frames in {} bytes", chunk.frames, chunk.data.len());
+/// });
+/// for frame in frames {
+///     chunker.push_frame(&frame).unwrap();
+/// }
+/// chunker.flush();
+/// ```
+pub struct Chunker<F>
+where
+    F: FnMut(Chunk<'_>),
+{
+    limits: ChunkLimits,
+    on_chunk: F,
+    buf: Vec<u8>,
+    frames: usize,
+    duration: MediaTime,
+    frame_length: FrameLength,
+}
+impl<F> Chunker<F>
+where
+    F: FnMut(Chunk<'_>),
+{
+    pub fn new(limits: ChunkLimits, on_chunk: F) -> Chunker<F> {
+        Chunker {
+            limits,
+            on_chunk,
+            buf: Vec::new(),
+            frames: 0,
+            duration: MediaTime::ZERO,
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to calculate frame durations (by
+    /// default, 1024)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Adds the given frame, which must be a single, complete ADTS frame, sending any packets
+    /// which this completes.
+    pub fn push_frame(&mut self, frame: &[u8]) -> Result<(), AdtsHeaderError> {
+        let header = AdtsHeader::from_bytes(frame)?;
+        let frame_duration = match header.sampling_frequency().freq() {
+            Some(rate) => MediaTime::from_blocks_of(
+                header.number_of_raw_data_blocks_in_frame().into(),
+                self.frame_length,
+                rate,
+            ),
+            None => MediaTime::ZERO,
+        };
+        let frame = &frame[..frame.len().min(header.frame_len())];
+        if self.frames > 0
+            && (self.buf.len() + frame.len() > self.limits.max_bytes
+                || (self.duration + frame_duration).to_duration() > self.limits.max_duration)
+        {
+            self.flush();
+        }
+        self.buf.extend_from_slice(frame);
+        self.frames += 1;
+        self.duration = self.duration + frame_duration;
+        // send now if a further frame like this one couldn't be added
+        if self.buf.len() + frame.len() > self.limits.max_bytes
+            || (self.duration + frame_duration).to_duration() > self.limits.max_duration
+        {
+            self.flush();
+        }
+        Ok(())
+    }
+
+    /// Sends any frames gathered so far as a packet, even though the limits have not been
+    /// reached.
+    pub fn flush(&mut self) {
+        if self.frames == 0 {
+            return;
+        }
+        (self.on_chunk)(Chunk {
+            data: &self.buf,
+            frames: self.frames,
+            duration: self.duration.to_duration(),
+        });
+        self.buf.clear();
+        self.frames = 0;
+        self.duration = MediaTime::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+    use std::cell::RefCell;
+
+    /// 8kHz frames, of 128ms each
+    const CONFIG: AdtsStreamConfig = AdtsStreamConfig {
+        sampling_frequency: SamplingFrequency::Freq8000,
+        ..StreamBuilder::DEFAULT_CONFIG
+    };
+
+    fn chunk_sizes(limits: ChunkLimits, frames: &[usize]) -> Vec<(usize, usize)> {
+        let mut chunks = vec![];
+        let mut chunker = Chunker::new(limits, |c| chunks.push((c.frames, c.data.len())));
+        for &len in frames {
+            let frame = StreamBuilder::new()
+                .config(CONFIG)
+                .frame(&vec![0; len - 7])
+                .build();
+            chunker.push_frame(&frame).unwrap();
+        }
+        chunker.flush();
+        chunks
+    }
+
+    #[test]
+    fn byte_limit() {
+        // each frame is 128ms, so the duration limit isn't reached
+        let limits = ChunkLimits {
+            max_bytes: 100,
+            max_duration: Duration::from_secs(10),
+        };
+        assert_eq!(
+            chunk_sizes(limits, &[40, 40, 30, 50, 150, 20]),
+            vec![(2, 80), (2, 80), (1, 150), (1, 20)]
+        );
+    }
+
+    #[test]
+    fn duration_limit() {
+        let limits = ChunkLimits {
+            max_bytes: 1000,
+            max_duration: Duration::from_millis(300),
+        };
+        let durations = RefCell::new(vec![]);
+        let mut chunker = Chunker::new(limits, |c| {
+            durations.borrow_mut().push((c.frames, c.duration))
+        });
+        let frame = StreamBuilder::new().config(CONFIG).frame(&[0; 3]).build();
+        // the second frame of each chunk is sent as soon as it arrives, since a third would
+        // exceed the duration limit
+        chunker.push_frame(&frame).unwrap();
+        assert!(durations.borrow().is_empty());
+        chunker.push_frame(&frame).unwrap();
+        assert_eq!(*durations.borrow(), vec![(2, Duration::from_millis(256))]);
+        chunker.push_frame(&frame).unwrap();
+        chunker.flush();
+        assert_eq!(durations.borrow()[1], (1, Duration::from_millis(128)));
+    }
+}
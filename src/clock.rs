@@ -0,0 +1,104 @@
+//! Sources of the current time for the crate's live features.
+//!
+//! [`AdtsParser`](../struct.AdtsParser.html),
+//! [`CadenceMonitor`](../monitor/struct.CadenceMonitor.html) and
+//! [`FollowingAdtsReader`](../follow/struct.FollowingAdtsReader.html) all read the time (and the
+//! latter also waits) through the [`Clock`](trait.Clock.html) trait.  They use
+//! [`SystemClock`](struct.SystemClock.html) unless told otherwise, while
+//! [`ManualClock`](struct.ManualClock.html) lets tests control the passage of time exactly.
+//!
+//! Under tokio with paused time, a clock can be built on `tokio::time::Instant`:
+//!
+//! ```rust
+//! # mod tokio { pub mod time { pub struct Instant(std::time::Instant); impl Instant {
+//! #     pub fn now() -> Instant { Instant(std::time::Instant::now()) }
+//! #     pub fn into_std(self) -> std::time::Instant { self.0 } } } }
+//! use adts_reader::clock::Clock;
+//! struct TokioClock;
+//! impl Clock for TokioClock {
+//!     fn now(&self) -> std::time::Instant {
+//!         tokio::time::Instant::now().into_std()
+//!     }
+//! }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub trait Clock {
+    /// The current time
+    fn now(&self) -> Instant;
+
+    /// Blocks the current thread until the given time has passed, according to this clock.
+    /// Defaults to `std::thread::sleep()`.
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+impl<K: Clock + ?Sized> Clock for &K {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// The system's monotonic clock, `std::time::Instant::now()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock which only moves when advanced explicitly, or when asked to sleep, for deterministic
+/// tests.
+///
+/// Clones share the same time, so one clone can be given to the component under test while
+/// another is used to advance time.
+///
+/// ```rust
+/// use adts_reader::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.clone().advance(Duration::from_secs(5));
+/// assert_eq!(clock.now() - start, Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+impl ManualClock {
+    /// Creates a clock which starts at the current system time
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves time forward by the given amount
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    /// Advances the clock by the given duration, returning immediately
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+}
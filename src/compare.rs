@@ -0,0 +1,358 @@
+//! Frame-by-frame comparison of two ADTS streams, for example the output of two versions of an
+//! encoder given the same input.
+//!
+//! [`compare_streams()`](fn.compare_streams.html) produces a
+//! [`DiffReport`](struct.DiffReport.html), which can be rendered for humans with its `Display`
+//! implementation, or (with the `serde` feature enabled) serialised for further processing.
+//!
+//! ```rust
+//! use adts_reader::compare::compare_streams;
+//! let a = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let b = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x00, 0x00, 0x22];
+//! let report = compare_streams(&a, &b);
+//! assert_eq!(report.differences[0].fields[0].field, "adts_buffer_fullness");
+//! println!("{}", report);
+//! ```
+
+use crate::{fields, AdtsHeader, FrameLength, MediaTime};
+use std::fmt;
+use std::iter::FusedIterator;
+use std::ops::Range;
+use std::time::Duration;
+
+/// A header field whose value differs between the two streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FieldDiff {
+    /// The field name, as given in ISO/IEC 14496-3 (e.g. `"channel_configuration"`)
+    pub field: String,
+    /// The raw field value in the first stream
+    pub a: u32,
+    /// The raw field value in the second stream
+    pub b: u32,
+}
+
+/// The differences found between a pair of frames at the same position in both streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FrameDiff {
+    /// Zero-based position of the frame within both streams
+    pub index: u64,
+    /// Byte offset of the frame within the first stream
+    pub offset_a: usize,
+    /// Byte offset of the frame within the second stream
+    pub offset_b: usize,
+    /// Media time at which the frame starts in the first stream, taking each `raw_data_block` to
+    /// hold 1024 samples
+    pub time_a: Duration,
+    /// Media time at which the frame starts in the second stream
+    pub time_b: Duration,
+    pub fields: Vec<FieldDiff>,
+    /// Ranges of byte positions within the payloads at which the two payloads differ.  Where one
+    /// payload is longer than the other, the excess bytes are included in the final range.
+    pub payload_ranges: Vec<Range<usize>>,
+}
+
+/// The result of [`compare_streams()`](fn.compare_streams.html).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiffReport {
+    /// Number of frames found in the first stream
+    pub frames_a: u64,
+    /// Number of frames found in the second stream
+    pub frames_b: u64,
+    /// Bytes at the end of the first stream which could not be parsed as complete frames
+    pub unparsed_bytes_a: usize,
+    /// Bytes at the end of the second stream which could not be parsed as complete frames
+    pub unparsed_bytes_b: usize,
+    /// One entry for each frame index, present in both streams, at which the frames differ
+    pub differences: Vec<FrameDiff>,
+}
+impl DiffReport {
+    /// `true` if the streams hold identical frames
+    pub fn is_identical(&self) -> bool {
+        self.frames_a == self.frames_b
+            && self.unparsed_bytes_a == self.unparsed_bytes_b
+            && self.differences.is_empty()
+    }
+}
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identical() {
+            return writeln!(f, "streams identical ({} frames)", self.frames_a);
+        }
+        writeln!(
+            f,
+            "{} differing frames of {} compared",
+            self.differences.len(),
+            self.frames_a.min(self.frames_b)
+        )?;
+        if self.frames_a != self.frames_b {
+            writeln!(
+                f,
+                "frame count differs: a={} b={}",
+                self.frames_a, self.frames_b
+            )?;
+        }
+        if self.unparsed_bytes_a != self.unparsed_bytes_b {
+            writeln!(
+                f,
+                "unparsed trailing bytes differ: a={} b={}",
+                self.unparsed_bytes_a, self.unparsed_bytes_b
+            )?;
+        }
+        for diff in &self.differences {
+            writeln!(
+                f,
+                "frame {} (a@{:#x} {:?}, b@{:#x} {:?})",
+                diff.index, diff.offset_a, diff.time_a, diff.offset_b, diff.time_b
+            )?;
+            for field in &diff.fields {
+                writeln!(f, "  {}: {:#x} -> {:#x}", field.field, field.a, field.b)?;
+            }
+            for range in &diff.payload_ranges {
+                writeln!(f, "  payload bytes {}..{} differ", range.start, range.end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a buffer into complete frames, stopping at the first data which is not one
+struct Frames<'buf> {
+    buf: &'buf [u8],
+    pos: usize,
+    /// total duration of the frames returned so far
+    elapsed: MediaTime,
+    frame_length: FrameLength,
+}
+impl<'buf> Frames<'buf> {
+    fn new(buf: &'buf [u8], frame_length: FrameLength) -> Frames<'buf> {
+        Frames {
+            buf,
+            pos: 0,
+            elapsed: MediaTime::ZERO,
+            frame_length,
+        }
+    }
+}
+impl<'buf> Iterator for Frames<'buf> {
+    type Item = (FramePosition, AdtsHeader<'buf>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.buf[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        let header = AdtsHeader::from_bytes(rest).ok()?;
+        header.payload().ok()?;
+        let position = FramePosition {
+            offset: self.pos,
+            time: self.elapsed.to_duration(),
+        };
+        self.pos += header.frame_len();
+        if let Some(duration) = header.frame_duration_of(self.frame_length) {
+            self.elapsed = self.elapsed + duration;
+        }
+        Some((position, header))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // no frame is shorter than its 7 byte header
+        (0, Some((self.buf.len() - self.pos) / 7))
+    }
+}
+// once data that isn't a complete frame is reached, it stays unconsumed, so every later call to
+// next() returns None too
+impl<'buf> FusedIterator for Frames<'buf> {}
+
+/// Where a frame was found within its stream
+struct FramePosition {
+    offset: usize,
+    time: Duration,
+}
+
+/// Compares two buffers of ADTS data frame by frame, listing differing header fields and payload
+/// byte ranges for each pair of frames at the same position in both streams.
+///
+/// Each buffer is expected to start with a frame; parsing of a buffer stops at the first data
+/// which is not a complete frame, and the number of remaining bytes is given in the report.
+pub fn compare_streams(a: &[u8], b: &[u8]) -> DiffReport {
+    compare_streams_with(a, b, FrameLength::Samples1024)
+}
+
+/// As for [`compare_streams()`](fn.compare_streams.html), for streams whose `raw_data_block`s
+/// have the given number of samples, which determines the times given for differing frames.
+pub fn compare_streams_with(a: &[u8], b: &[u8], frame_length: FrameLength) -> DiffReport {
+    let mut report = DiffReport::default();
+    let mut frames_a = Frames::new(a, frame_length);
+    let mut frames_b = Frames::new(b, frame_length);
+    loop {
+        match (frames_a.next(), frames_b.next()) {
+            (Some((pos_a, header_a)), Some((pos_b, header_b))) => {
+                if let Some(diff) =
+                    compare_frames(report.frames_a, pos_a, &header_a, pos_b, &header_b)
+                {
+                    report.differences.push(diff);
+                }
+                report.frames_a += 1;
+                report.frames_b += 1;
+            }
+            (Some(_), None) => {
+                report.frames_a += 1;
+                report.frames_a += frames_a.by_ref().count() as u64;
+                break;
+            }
+            (None, Some(_)) => {
+                report.frames_b += 1;
+                report.frames_b += frames_b.by_ref().count() as u64;
+                break;
+            }
+            (None, None) => break,
+        }
+    }
+    report.unparsed_bytes_a = a.len() - frames_a.pos;
+    report.unparsed_bytes_b = b.len() - frames_b.pos;
+    report
+}
+
+fn compare_frames(
+    index: u64,
+    pos_a: FramePosition,
+    a: &AdtsHeader<'_>,
+    pos_b: FramePosition,
+    b: &AdtsHeader<'_>,
+) -> Option<FrameDiff> {
+    let mut field_diffs: Vec<FieldDiff> = fields::HEADER_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let (va, vb) = (field.get(a.buf), field.get(b.buf));
+            if va == vb {
+                None
+            } else {
+                Some(FieldDiff {
+                    field: field.name.to_string(),
+                    a: va,
+                    b: vb,
+                })
+            }
+        })
+        .collect();
+    if let (Some(crc_a), Some(crc_b)) = (a.crc(), b.crc()) {
+        if crc_a != crc_b {
+            field_diffs.push(FieldDiff {
+                field: fields::CRC_CHECK.name.to_string(),
+                a: crc_a.into(),
+                b: crc_b.into(),
+            });
+        }
+    }
+    // both frames were checked to be complete as they were found
+    let payload_ranges = differing_ranges(a.payload().ok()?, b.payload().ok()?);
+    if field_diffs.is_empty() && payload_ranges.is_empty() {
+        return None;
+    }
+    Some(FrameDiff {
+        index,
+        offset_a: pos_a.offset,
+        offset_b: pos_b.offset,
+        time_a: pos_a.time,
+        time_b: pos_b.time,
+        fields: field_diffs,
+        payload_ranges,
+    })
+}
+
+fn differing_ranges(a: &[u8], b: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let len = a.len().max(b.len());
+    for i in 0..len {
+        if a.get(i) != b.get(i) {
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end = i + 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn differences() {
+        let a = StreamBuilder::new()
+            .frame(&[1, 2, 3, 4, 5])
+            .frame(&[1, 2, 3, 4, 5])
+            .frame(&[1])
+            .build();
+        let b = StreamBuilder::new()
+            .frame(&[1, 2, 3, 4, 5])
+            .config(AdtsStreamConfig {
+                channel_configuration: ChannelConfiguration::Mono,
+                ..StreamBuilder::DEFAULT_CONFIG
+            })
+            .frame(&[0, 2, 0, 0, 5, 6])
+            .build();
+        let report = compare_streams(&a, &b);
+        assert!(!report.is_identical());
+        assert_eq!(report.frames_a, 3);
+        assert_eq!(report.frames_b, 2);
+        assert_eq!(
+            report.differences,
+            vec![FrameDiff {
+                index: 1,
+                offset_a: 12,
+                offset_b: 12,
+                time_a: Duration::from_nanos(21_333_333),
+                time_b: Duration::from_nanos(21_333_333),
+                fields: vec![
+                    FieldDiff {
+                        field: "channel_configuration".to_string(),
+                        a: 2,
+                        b: 1
+                    },
+                    FieldDiff {
+                        field: "frame_length".to_string(),
+                        a: 12,
+                        b: 13
+                    },
+                ],
+                payload_ranges: vec![0..1, 2..4, 5..6],
+            }]
+        );
+        let text = report.to_string();
+        assert!(text.contains("frame count differs: a=3 b=2"));
+        assert!(text.contains("frame 1 (a@0xc 21.333333ms, b@0xc 21.333333ms)"));
+        assert!(text.contains("  channel_configuration: 0x2 -> 0x1"));
+        assert!(text.contains("  payload bytes 2..4 differ"));
+
+        let report = compare_streams_with(&a, &b, FrameLength::Samples960);
+        assert_eq!(report.differences[0].time_a, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn identical() {
+        let a = StreamBuilder::new().frame(&[1, 2, 3]).build();
+        let report = compare_streams(&a, &a);
+        assert!(report.is_identical());
+        assert_eq!(report.to_string(), "streams identical (1 frames)\n");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let a = StreamBuilder::new().frame(&[1, 2, 3]).build();
+        let b = StreamBuilder::new().frame(&[1, 2, 4]).build();
+        let report = compare_streams(&a, &b);
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<DiffReport>(&json).unwrap(), report);
+    }
+}
@@ -0,0 +1,398 @@
+//! Checks of a single ADTS frame against the normative constraints of ISO/IEC 13818-7 and
+//! ISO/IEC 14496-3.
+//!
+//! Where [`validate_frame()`](../fn.validate_frame.html) stops at the first problem it finds,
+//! [`spec_conformance_check()`](fn.spec_conformance_check.html) reports every constraint that a
+//! frame violates, each identifying the clause of the specification that imposes it, so that
+//! reports can be traced back to the standard.
+//!
+//! Clause numbers refer to ISO/IEC 14496-3:2009 Annex 1.A unless noted otherwise.  Constraints
+//! that only apply to MPEG-2 (`ID` = 1) streams come from ISO/IEC 13818-7.
+
+use crate::encryption::PayloadEncryption;
+use crate::{crc, fields, AdtsHeader, MpegVersion};
+use std::fmt;
+
+/// A normative constraint violated by a frame.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum Violation {
+    /// The buffer is too short to hold the header, or the whole frame that `frame_length` implies
+    Incomplete { expected: usize, actual: usize },
+    /// The `syncword` is not `0xFFF`
+    SyncWord(u16),
+    /// `layer` is not `0`
+    Layer(u8),
+    /// An MPEG-2 frame uses `profile` value `3`, which is reserved in ISO/IEC 13818-7
+    ReservedProfile(u8),
+    /// `sampling_frequency_index` holds a value reserved for the given MPEG version (including the
+    /// escape value `0xF`, which ADTS has no way to follow with an explicit frequency)
+    ReservedSamplingFrequency {
+        index: u8,
+        mpeg_version: MpegVersion,
+    },
+    /// `frame_length` is too small to hold the headers, error check fields and at least one byte
+    /// for each of the `raw_data_block`s that the header declares
+    FrameLength { frame_length: u16, minimum: u16 },
+    /// The `crc_check` value doesn't match the CRC calculated over the 7 header bytes and the
+    /// whole of the payload (only checked for frames with a single `raw_data_block`).
+    ///
+    /// The cited clause protects only the leading bits of some syntactic elements within the
+    /// `raw_data_block`, but since this crate doesn't parse AAC syntax it can't find those
+    /// bits, and checks the whole payload instead, as
+    /// [`CrcAlgorithm::frame_crc()`](../crc/trait.CrcAlgorithm.html#method.frame_crc) describes.
+    /// A frame whose encoder protected only the bits the specification requires may therefore
+    /// be reported here, even though it conforms.
+    CrcMismatch { expected: u16, actual: u16 },
+}
+impl Violation {
+    /// The specification clause imposing the violated constraint
+    pub fn clause(&self) -> &'static str {
+        match self {
+            Violation::Incomplete { .. } => "ISO/IEC 14496-3 1.A.3.2.2 (frame_length)",
+            Violation::SyncWord(_) => "ISO/IEC 14496-3 1.A.3.2.1 (syncword)",
+            Violation::Layer(_) => "ISO/IEC 14496-3 1.A.3.2.1 (layer)",
+            Violation::ReservedProfile(_) => "ISO/IEC 13818-7 adts_fixed_header (profile)",
+            Violation::ReservedSamplingFrequency {
+                mpeg_version: MpegVersion::Mpeg2,
+                ..
+            } => "ISO/IEC 13818-7 adts_fixed_header (sampling_frequency_index)",
+            Violation::ReservedSamplingFrequency { .. } => {
+                "ISO/IEC 14496-3 1.6.3.4, Table 1.18 (samplingFrequencyIndex)"
+            }
+            Violation::FrameLength { .. } => "ISO/IEC 14496-3 1.A.3.2.2 (frame_length)",
+            Violation::CrcMismatch { .. } => "ISO/IEC 14496-3 1.A.3.2.3 (crc_check)",
+        }
+    }
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Incomplete { expected, actual } => {
+                write!(
+                    f,
+                    "frame needs {} bytes, but only {} given",
+                    expected, actual
+                )
+            }
+            Violation::SyncWord(value) => write!(f, "syncword {:#x} is not 0xfff", value),
+            Violation::Layer(value) => write!(f, "layer {} is not 0", value),
+            Violation::ReservedProfile(value) => write!(f, "profile {} is reserved", value),
+            Violation::ReservedSamplingFrequency {
+                index,
+                mpeg_version,
+            } => write!(
+                f,
+                "sampling_frequency_index {:#x} is reserved for {:?}",
+                index, mpeg_version
+            ),
+            Violation::FrameLength {
+                frame_length,
+                minimum,
+            } => write!(
+                f,
+                "frame_length {} is less than the minimum {}",
+                frame_length, minimum
+            ),
+            Violation::CrcMismatch { expected, actual } => write!(
+                f,
+                "crc_check {:#06x} does not match calculated {:#06x}",
+                expected, actual
+            ),
+        }?;
+        write!(f, " [{}]", self.clause())
+    }
+}
+
+/// Checks the ADTS frame at the start of the given buffer against the normative constraints of
+/// the specifications, returning every violation found (an empty result means the frame
+/// conforms).
+///
+/// Data following the end of the frame is ignored.  Constraints on the content of the
+/// `raw_data_block`s themselves are not checked.
+///
+/// ```rust
+/// use adts_reader::conformance::{spec_conformance_check, Violation};
+/// let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// assert!(spec_conformance_check(&frame).is_empty());
+/// // set the layer field to 1
+/// let frame = [0xff, 0xf3, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let violations = spec_conformance_check(&frame);
+/// assert_eq!(violations, vec![Violation::Layer(1)]);
+/// println!("{}", violations[0]);
+/// ```
+pub fn spec_conformance_check(frame: &[u8]) -> Vec<Violation> {
+    spec_conformance_check_with(frame, PayloadEncryption::Clear)
+}
+
+/// As for [`spec_conformance_check()`](fn.spec_conformance_check.html), for a frame whose
+/// payload may be encrypted.  The CRC, which covers the payload as it was before encryption, is
+/// not checked for encrypted payloads.
+pub fn spec_conformance_check_with(frame: &[u8], encryption: PayloadEncryption) -> Vec<Violation> {
+    let mut violations = vec![];
+    if frame.len() < 7 {
+        violations.push(Violation::Incomplete {
+            expected: 7,
+            actual: frame.len(),
+        });
+        return violations;
+    }
+    let sync_word = fields::SYNCWORD.get(frame) as u16;
+    if sync_word != 0xfff {
+        violations.push(Violation::SyncWord(sync_word));
+    }
+    let layer = fields::LAYER.get(frame) as u8;
+    if layer != 0 {
+        violations.push(Violation::Layer(layer));
+    }
+    let mpeg_version = if fields::ID.get(frame) == 1 {
+        MpegVersion::Mpeg2
+    } else {
+        MpegVersion::Mpeg4
+    };
+    let profile = fields::PROFILE.get(frame) as u8;
+    if mpeg_version == MpegVersion::Mpeg2 && profile == 3 {
+        violations.push(Violation::ReservedProfile(profile));
+    }
+    let index = fields::SAMPLING_FREQUENCY_INDEX.get(frame) as u8;
+    let last_index = match mpeg_version {
+        MpegVersion::Mpeg2 => 0xb,
+        MpegVersion::Mpeg4 => 0xc,
+    };
+    if index > last_index {
+        violations.push(Violation::ReservedSamplingFrequency {
+            index,
+            mpeg_version,
+        });
+    }
+
+    let crc_present = fields::PROTECTION_ABSENT.get(frame) == 0;
+    let blocks = fields::NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.get(frame) as u16 + 1;
+    let minimum = minimum_frame_length(crc_present, blocks);
+    let frame_length = fields::FRAME_LENGTH.get(frame) as u16;
+    if frame_length < minimum {
+        violations.push(Violation::FrameLength {
+            frame_length,
+            minimum,
+        });
+    }
+    let expected = usize::from(frame_length.max(minimum));
+    if frame.len() < expected {
+        violations.push(Violation::Incomplete {
+            expected,
+            actual: frame.len(),
+        });
+        return violations;
+    }
+
+    if crc_present && blocks == 1 && frame_length >= minimum && !encryption.is_encrypted() {
+        let header = AdtsHeader { buf: frame };
+        let payload = header.payload().expect("length already checked");
+        let expected = header.crc().unwrap();
+        let actual = crc::frame_crc(frame, payload);
+        if expected != actual {
+            violations.push(Violation::CrcMismatch { expected, actual });
+        }
+    }
+    violations
+}
+
+/// The smallest `frame_length` able to hold the fixed and variable headers, the error check
+/// fields, and the given number of `raw_data_block`s, each of which must hold at least its
+/// `ID_END` element.
+fn minimum_frame_length(crc_present: bool, blocks: u16) -> u16 {
+    let headers = 7;
+    let error_check = match (crc_present, blocks) {
+        (false, _) => 0,
+        // adts_error_check()
+        (true, 1) => 2,
+        // adts_header_error_check() holding raw_data_block_position[] and crc_check, plus an
+        // adts_raw_data_block_error_check() following each raw_data_block()
+        (true, n) => 2 * (n - 1) + 2 + 2 * n,
+    };
+    headers + error_check + blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn field_constraints() {
+        // (description, field to change, value, expected violations)
+        let cases: &[(&str, &Field, u32, &[Violation])] = &[
+            ("conforming", &LAYER, 0, &[]),
+            ("syncword", &SYNCWORD, 0xffe, &[Violation::SyncWord(0xffe)]),
+            ("layer", &LAYER, 2, &[Violation::Layer(2)]),
+            ("mpeg-4 profile 3 (LTP)", &PROFILE, 3, &[]),
+            ("mpeg-4 7350Hz", &SAMPLING_FREQUENCY_INDEX, 0xc, &[]),
+            (
+                "reserved sampling frequency",
+                &SAMPLING_FREQUENCY_INDEX,
+                0xd,
+                &[Violation::ReservedSamplingFrequency {
+                    index: 0xd,
+                    mpeg_version: MpegVersion::Mpeg4,
+                }],
+            ),
+            (
+                "escape sampling frequency",
+                &SAMPLING_FREQUENCY_INDEX,
+                0xf,
+                &[Violation::ReservedSamplingFrequency {
+                    index: 0xf,
+                    mpeg_version: MpegVersion::Mpeg4,
+                }],
+            ),
+            (
+                "frame_length shorter than headers",
+                &FRAME_LENGTH,
+                6,
+                &[Violation::FrameLength {
+                    frame_length: 6,
+                    minimum: 8,
+                }],
+            ),
+            (
+                "frame_length leaving no room for a raw_data_block",
+                &FRAME_LENGTH,
+                7,
+                &[Violation::FrameLength {
+                    frame_length: 7,
+                    minimum: 8,
+                }],
+            ),
+            (
+                "frame_length beyond the data",
+                &FRAME_LENGTH,
+                20,
+                &[Violation::Incomplete {
+                    expected: 20,
+                    actual: 10,
+                }],
+            ),
+        ];
+        for (description, field, value, expected) in cases {
+            let mut buf = StreamBuilder::new().frame(&[0x1c; 3]).build();
+            field.set(&mut buf, *value);
+            assert_eq!(
+                spec_conformance_check(&buf),
+                *expected,
+                "case: {}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn mpeg2_constraints() {
+        let mut buf = StreamBuilder::new().frame(&[0x1c; 3]).build();
+        ID.set(&mut buf, 1);
+        assert!(spec_conformance_check(&buf).is_empty());
+        PROFILE.set(&mut buf, 3);
+        SAMPLING_FREQUENCY_INDEX.set(&mut buf, 0xc);
+        assert_eq!(
+            spec_conformance_check(&buf),
+            vec![
+                Violation::ReservedProfile(3),
+                Violation::ReservedSamplingFrequency {
+                    index: 0xc,
+                    mpeg_version: MpegVersion::Mpeg2,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn crc_coverage() {
+        let mut buf = StreamBuilder::new().crc(true).frame(&[0x1c; 3]).build();
+        assert!(spec_conformance_check(&buf).is_empty());
+        // the CRC covers the header fields preceding crc_check (changing any of those that don't
+        // alter the frame structure) and the payload
+        let header_fields = [
+            &ID,
+            &PROFILE,
+            &PRIVATE_BIT,
+            &CHANNEL_CONFIGURATION,
+            &ORIGINAL_COPY,
+            &HOME,
+            &COPYRIGHT_IDENTIFICATION_BIT,
+            &COPYRIGHT_IDENTIFICATION_START,
+            &ADTS_BUFFER_FULLNESS,
+        ];
+        let crc_fails = |damaged: &[u8]| {
+            spec_conformance_check(damaged)
+                .iter()
+                .any(|v| matches!(v, Violation::CrcMismatch { .. }))
+        };
+        for field in header_fields {
+            let mut damaged = buf.clone();
+            field.set(&mut damaged, field.get(&buf) ^ 1);
+            assert!(crc_fails(&damaged), "field {}", field.name);
+        }
+        for i in 9..buf.len() {
+            let mut damaged = buf.clone();
+            damaged[i] ^= 0x01;
+            assert!(crc_fails(&damaged), "payload byte {}", i);
+        }
+        buf[9] ^= 0x01;
+        assert_eq!(
+            spec_conformance_check(&buf)[0].clause(),
+            "ISO/IEC 14496-3 1.A.3.2.3 (crc_check)"
+        );
+        assert!(spec_conformance_check_with(&buf, PayloadEncryption::SampleAes).is_empty());
+    }
+
+    #[test]
+    fn multiple_blocks() {
+        // (crc, blocks, minimum frame_length)
+        let cases = [
+            (false, 1, 8),
+            (false, 4, 11),
+            (true, 1, 10),
+            // 7 header + 2 * 1 positions + 2 crc_check + 2 * 2 block CRCs + 2 blocks
+            (true, 2, 17),
+            (true, 4, 27),
+        ];
+        for (crc, blocks, minimum) in cases {
+            assert_eq!(minimum_frame_length(crc, blocks), minimum);
+            // the writer can't produce CRC-protected multi-block frames, so adjust the fields of
+            // a larger frame instead
+            let mut buf = StreamBuilder::new().frame(&[0x1c; 30]).build();
+            PROTECTION_ABSENT.set(&mut buf, u32::from(!crc));
+            NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.set(&mut buf, u32::from(blocks - 1));
+            let check = |buf: &[u8]| -> Vec<Violation> {
+                spec_conformance_check(buf)
+                    .into_iter()
+                    .filter(|v| !matches!(v, Violation::CrcMismatch { .. }))
+                    .collect()
+            };
+            FRAME_LENGTH.set(&mut buf, u32::from(minimum));
+            assert_eq!(check(&buf), vec![]);
+            FRAME_LENGTH.set(&mut buf, u32::from(minimum - 1));
+            assert_eq!(
+                check(&buf),
+                vec![Violation::FrameLength {
+                    frame_length: minimum - 1,
+                    minimum
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn incomplete_header() {
+        assert_eq!(
+            spec_conformance_check(&[0xff, 0xf1]),
+            vec![Violation::Incomplete {
+                expected: 7,
+                actual: 2
+            }]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Assembly of the 72-bit copyright identifier which ISO/IEC 13818-7 spreads over successive
+//! frames, one `copyright_identification_bit` per frame, with `copyright_identification_start`
+//! marking the frame carrying the first bit.
+
+use crate::{AdtsHeader, CopyrightIdErr, CopyrightIdentification, CopyrightIdentificationStart};
+
+/// Number of bits in a complete identifier
+const ID_BITS: u8 = 72;
+
+/// Collects the bits of a copyright identifier from the headers of consecutive frames.
+#[derive(Default)]
+pub(crate) struct Accumulator {
+    bits: u128,
+    /// bits collected since the last start, or `None` when not collecting (before the first
+    /// start, or after an error)
+    count: Option<u8>,
+}
+impl Accumulator {
+    /// Adds the bit carried by the given frame, returning the identifier if this completes it,
+    /// or the error if the identifier turns out to have the wrong length
+    pub fn frame(
+        &mut self,
+        header: &AdtsHeader<'_>,
+    ) -> Option<Result<CopyrightIdentification, CopyrightIdErr>> {
+        let bit = u128::from(header.copyright_identification_bit());
+        if header.copyright_identification_start() == CopyrightIdentificationStart::Start {
+            let previous = self.count.replace(1);
+            self.bits = bit;
+            return match previous {
+                Some(n) if n < ID_BITS => Some(Err(CopyrightIdErr::TooFewBits)),
+                _ => None,
+            };
+        }
+        let count = self.count.as_mut()?;
+        if *count == ID_BITS {
+            self.count = None;
+            return Some(Err(CopyrightIdErr::TooManyBits));
+        }
+        *count += 1;
+        self.bits = self.bits << 1 | bit;
+        if *count < ID_BITS {
+            return None;
+        }
+        Some(Ok(CopyrightIdentification {
+            copyright_identifier: (self.bits >> 64) as u8,
+            copyright_number: self.bits as u64,
+        }))
+    }
+
+    /// Abandons any identifier being collected, as when frames may have been lost
+    pub fn reset(&mut self) {
+        self.count = None;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::{AdtsFrameIter, FrameOptions};
+
+    /// A stream of frames carrying the bits of the given identifier, followed by `extra` frames
+    /// without the start of another
+    pub(crate) fn stream(id: u128, bits: u8, extra: usize) -> Vec<u8> {
+        let mut builder = StreamBuilder::new();
+        for i in 0..bits + extra as u8 {
+            let bit = if i < bits {
+                (id >> (bits - 1 - i)) & 1
+            } else {
+                0
+            };
+            builder = builder
+                .options(FrameOptions {
+                    copyright_identification_bit: bit as u8,
+                    copyright_identification_start: if i == 0 {
+                        CopyrightIdentificationStart::Start
+                    } else {
+                        CopyrightIdentificationStart::Other
+                    },
+                    ..FrameOptions::default()
+                })
+                .frames(1, StreamBuilder::DEFAULT_CONFIG);
+        }
+        builder.build()
+    }
+
+    fn accumulate(data: &[u8]) -> Vec<Result<CopyrightIdentification, CopyrightIdErr>> {
+        let mut acc = Accumulator::default();
+        AdtsFrameIter::new(data)
+            .filter_map(|frame| acc.frame(&frame.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn identifier() {
+        let id = 0x2a_0123_4567_89ab_cdef;
+        assert_eq!(
+            accumulate(&stream(id, 72, 0)),
+            [Ok(CopyrightIdentification {
+                copyright_identifier: 0x2a,
+                copyright_number: 0x0123_4567_89ab_cdef,
+            })]
+        );
+        // identifiers repeated back to back
+        let mut data = stream(id, 72, 0);
+        data.extend(stream(id, 72, 0));
+        assert_eq!(accumulate(&data).len(), 2);
+        assert!(accumulate(&data).iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn wrong_length() {
+        let mut data = stream(0, 40, 0);
+        data.extend(stream(0, 72, 1));
+        assert_eq!(
+            accumulate(&data),
+            [
+                Err(CopyrightIdErr::TooFewBits),
+                Ok(CopyrightIdentification {
+                    copyright_identifier: 0,
+                    copyright_number: 0,
+                }),
+                Err(CopyrightIdErr::TooManyBits),
+            ]
+        );
+        // streams which don't use the mechanism give nothing
+        let data = StreamBuilder::new()
+            .frames(100, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        assert!(accumulate(&data).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+//! The CRC-16 variant used by the ADTS `crc_check` field.
+//!
+//! ISO/IEC 13818-7 specifies the generator polynomial `x^16 + x^15 + x^2 + 1` (`0x8005`), with
+//! the shift register initialised to all '1' bits and no bit-reflection of input or output.
+//!
+//! The calculation is available in several implementations of the
+//! [`CrcAlgorithm`](trait.CrcAlgorithm.html) trait, trading code and data size against speed:
+//!
+//!  - [`TableCrc`](struct.TableCrc.html), the default, using a 512 byte lookup table
+//!  - [`BitwiseCrc`](struct.BitwiseCrc.html), processing one bit at a time with no table, for
+//!    targets where memory is scarcer than cycles
+//!  - `SliceBy16Crc`, behind the `crc` feature, using the `crc` crate's 8KiB slice-by-16 tables
+//!    for hot paths checking many frames
+//!
+//! The implementation is chosen with `ValidateOptions::crc_algorithm`,
+//! `AnalysisOptions::crc_algorithm` or
+//! [`EncodedHeader::fill_crc_with()`](../struct.EncodedHeader.html#method.fill_crc_with).
+//!
+//! The CRCs stored in a frame are checked with
+//! [`AdtsHeader::verify_crc()`](../struct.AdtsHeader.html#method.verify_crc), or by an
+//! `AdtsParser` given a [`CrcPolicy`](enum.CrcPolicy.html).
+
+use crate::blocks::{self, BlockLayout, BlockLayoutError};
+use crate::{AdtsHeader, ProtectionIndicator};
+use std::fmt;
+
+const POLY: u16 = 0x8005;
+
+/// The value of the CRC shift register before any data has been processed.
+pub const INITIAL: u16 = 0xffff;
+
+const TABLE: [u16; 256] = make_table();
+
+const fn make_table() -> [u16; 256] {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// An implementation of the ADTS CRC-16 calculation.
+///
+/// All implementations produce identical results, so the choice between them is purely one of
+/// performance.
+///
+/// ```rust
+/// use adts_reader::crc::{BitwiseCrc, CrcAlgorithm, TableCrc, INITIAL};
+/// // the data may be supplied in several pieces
+/// let crc = TableCrc.update(INITIAL, b"1234");
+/// let crc = TableCrc.update(crc, b"56789");
+/// assert_eq!(crc, 0xaee7);
+/// assert_eq!(BitwiseCrc.update(INITIAL, b"123456789"), crc);
+/// ```
+pub trait CrcAlgorithm: fmt::Debug + Sync {
+    /// Continues a calculation from the given intermediate value (or from
+    /// [`INITIAL`](constant.INITIAL.html)), returning the value after the given data
+    fn update(&self, crc: u16, data: &[u8]) -> u16;
+
+    /// Calculates the CRC for a frame having a single `raw_data_block`, given the 7 bytes of
+    /// header that precede the `crc_check` field, and the frame payload.
+    ///
+    /// ISO/IEC 13818-7 limits the protected region of some syntactic elements within the
+    /// `raw_data_block` to their initial bits; since this crate doesn't parse AAC syntax, the
+    /// whole payload is covered.
+    fn frame_crc(&self, header: &[u8], payload: &[u8]) -> u16 {
+        let crc = self.update(INITIAL, &header[..7]);
+        self.update(crc, payload)
+    }
+}
+
+/// Table-driven implementation, processing a byte at a time.  This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableCrc;
+impl CrcAlgorithm for TableCrc {
+    fn update(&self, mut crc: u16, data: &[u8]) -> u16 {
+        for &b in data {
+            crc = crc << 8 ^ TABLE[usize::from((crc >> 8) as u8 ^ b)];
+        }
+        crc
+    }
+}
+
+/// Implementation processing a bit at a time, needing no lookup table.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BitwiseCrc;
+impl CrcAlgorithm for BitwiseCrc {
+    fn update(&self, mut crc: u16, data: &[u8]) -> u16 {
+        for &b in data {
+            crc ^= u16::from(b) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    crc << 1 ^ POLY
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        crc
+    }
+}
+
+/// Implementation backed by the `crc` crate, processing 16 bytes at a time.  Available when the
+/// `crc` feature is enabled.
+#[cfg(feature = "crc")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SliceBy16Crc;
+#[cfg(feature = "crc")]
+const SLICE_BY_16: ::crc::Crc<u16, ::crc::Table<16>> =
+    ::crc::Crc::<u16, ::crc::Table<16>>::new(&::crc::CRC_16_CMS);
+#[cfg(feature = "crc")]
+impl CrcAlgorithm for SliceBy16Crc {
+    fn update(&self, crc: u16, data: &[u8]) -> u16 {
+        let mut digest = SLICE_BY_16.digest_with_initial(crc);
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+/// The implementation used where none is specified.
+pub static DEFAULT: TableCrc = TableCrc;
+
+pub(crate) fn frame_crc(header: &[u8], payload: &[u8]) -> u16 {
+    DEFAULT.frame_crc(header, payload)
+}
+
+/// Why [`AdtsHeader::verify_crc()`](../struct.AdtsHeader.html#method.verify_crc) did not succeed.
+#[derive(Debug, PartialEq)]
+pub enum CrcError {
+    /// The frame has no CRC to verify
+    Absent,
+    /// The frame is incomplete, or (in a frame with several `raw_data_block`s) its
+    /// `raw_data_block_position` table is unusable
+    Layout(BlockLayoutError),
+    /// The `crc_check` following the header differs from the value calculated.  In a frame with
+    /// a single `raw_data_block`, this covers the block too.
+    Mismatch { expected: u16, actual: u16 },
+    /// In a frame with several `raw_data_block`s, the CRC following the block of the given index
+    /// differs from the value calculated
+    BlockMismatch {
+        block: usize,
+        expected: u16,
+        actual: u16,
+    },
+}
+
+/// Calculates the CRC following the header of the given complete frame, over the given 7 bytes
+/// of header, returning its offset within the frame along with its value.
+///
+/// In a frame with a single `raw_data_block`, the CRC directly follows the header, and covers the
+/// block too.  In a frame with several, it follows, and covers, the `raw_data_block_position`
+/// table instead.
+pub(crate) fn header_crc(
+    sent: &[u8],
+    frame: &[u8],
+    layout: &BlockLayout,
+    algorithm: &dyn CrcAlgorithm,
+) -> (usize, u16) {
+    if let [block] = layout.blocks[..] {
+        (7, algorithm.frame_crc(sent, &frame[block.offset..]))
+    } else {
+        let table_end = 7 + 2 * (layout.blocks.len() - 1);
+        let crc = algorithm.update(INITIAL, &sent[..7]);
+        (table_end, algorithm.update(crc, &frame[7..table_end]))
+    }
+}
+
+/// What an [`AdtsParser`](../struct.AdtsParser.html) does with frames whose CRC doesn't match,
+/// set with [`AdtsParser::with_crc_policy()`](../struct.AdtsParser.html#method.with_crc_policy).
+///
+/// Under `Flag` and `Drop`, mismatches are reported to
+/// [`AdtsConsumer2::on_crc_error()`](../trait.AdtsConsumer2.html#method.on_crc_error), and
+/// counted in `ParserStats::crc_mismatches`.  Frames whose payload is encrypted are not checked.
+/// The header of a frame corrected for a [quirk](../quirks/index.html) is checked as it was
+/// written, before correction, since that is what the encoder's CRC covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPolicy {
+    /// Don't check CRCs
+    #[default]
+    Ignore,
+    /// Report the mismatch, then pass the frame to the consumer as usual
+    Flag,
+    /// Report the mismatch, and don't pass the frame to the consumer
+    Drop,
+}
+
+/// Checks every CRC stored in the complete frame that `header` belongs to
+pub(crate) fn verify(
+    header: &AdtsHeader<'_>,
+    algorithm: &dyn CrcAlgorithm,
+) -> Result<(), CrcError> {
+    verify_as_sent(&header.buf[..7], header, algorithm)
+}
+
+/// As for [`verify()`](fn.verify.html), but calculating the CRC which covers the header over
+/// the given 7 bytes: the header as the encoder wrote it, before an `AdtsParser` corrected any
+/// quirks
+pub(crate) fn verify_as_sent(
+    sent: &[u8],
+    header: &AdtsHeader<'_>,
+    algorithm: &dyn CrcAlgorithm,
+) -> Result<(), CrcError> {
+    if header.protection() == ProtectionIndicator::CrcAbsent {
+        return Err(CrcError::Absent);
+    }
+    let layout = blocks::block_layout(header.buf).map_err(CrcError::Layout)?;
+    let frame = &header.buf[..header.frame_len()];
+    let (pos, actual) = header_crc(sent, frame, &layout, algorithm);
+    let expected = u16::from_be_bytes([frame[pos], frame[pos + 1]]);
+    if actual != expected {
+        return Err(CrcError::Mismatch { expected, actual });
+    }
+    if layout.blocks.len() == 1 {
+        return Ok(());
+    }
+    // in a frame with several blocks, each is followed by a CRC of its own
+    for (i, block) in layout.blocks.iter().enumerate() {
+        let end = block.offset + block.len;
+        let expected = u16::from_be_bytes([frame[end], frame[end + 1]]);
+        let actual = algorithm.update(INITIAL, &frame[block.offset..end]);
+        if actual != expected {
+            return Err(CrcError::BlockMismatch {
+                block: i,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn algorithms() -> Vec<&'static dyn CrcAlgorithm> {
+        vec![
+            &TableCrc,
+            &BitwiseCrc,
+            #[cfg(feature = "crc")]
+            &SliceBy16Crc,
+        ]
+    }
+
+    #[test]
+    fn check_value() {
+        // the standard check value for CRC-16/CMS (poly=0x8005 init=0xffff refin=false
+        // refout=false xorout=0x0000)
+        for algorithm in algorithms() {
+            let crc = algorithm.update(INITIAL, b"1234");
+            assert_eq!(algorithm.update(crc, b"56789"), 0xaee7, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn implementations_agree() {
+        let data: Vec<u8> = (0..1000u32).map(|i| ((i * 7919) >> 3) as u8).collect();
+        let expected = TableCrc.frame_crc(&data[..7], &data[7..]);
+        for algorithm in algorithms() {
+            assert_eq!(
+                algorithm.frame_crc(&data, &data[7..]),
+                expected,
+                "{:?}",
+                algorithm
+            );
+            // split at an offset that isn't a multiple of the slice-by-16 stride
+            let crc = algorithm.update(INITIAL, &data[..7]);
+            let crc = algorithm.update(crc, &data[7..500]);
+            assert_eq!(
+                algorithm.update(crc, &data[500..]),
+                expected,
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn verify_frames() {
+        use crate::testing::StreamBuilder;
+        use crate::*;
+        let verify = |frame: &[u8]| AdtsHeader::from_bytes(frame).unwrap().verify_crc();
+        let unprotected = StreamBuilder::new().frame(&[1, 2, 3]).build();
+        assert_eq!(verify(&unprotected), Err(CrcError::Absent));
+        let mut frame = StreamBuilder::new().crc(true).frame(&[1, 2, 3]).build();
+        assert_eq!(verify(&frame), Ok(()));
+        assert!(matches!(
+            verify(&frame[..frame.len() - 1]),
+            Err(CrcError::Layout(BlockLayoutError::Truncated { .. }))
+        ));
+        frame[10] ^= 1;
+        assert!(matches!(verify(&frame), Err(CrcError::Mismatch { .. })));
+
+        // two blocks, with a position table, a header CRC, and a CRC following each block
+        let blocks: [&[u8]; 2] = [&[1, 2, 3], &[4, 5]];
+        let mut frame = StreamBuilder::new()
+            .options(FrameOptions {
+                number_of_raw_data_blocks: 2,
+                ..FrameOptions::default()
+            })
+            .frame(&[0; 2 + 2 + 3 + 2 + 2 + 2])
+            .build();
+        fields::PROTECTION_ABSENT.set(&mut frame, 0);
+        frame[7..9].copy_from_slice(&5u16.to_be_bytes());
+        let header_crc = TableCrc.update(INITIAL, &frame[..9]);
+        frame[9..11].copy_from_slice(&header_crc.to_be_bytes());
+        let mut at = 11;
+        for block in blocks {
+            frame[at..at + block.len()].copy_from_slice(block);
+            at += block.len();
+            let crc = TableCrc.update(INITIAL, block);
+            frame[at..at + 2].copy_from_slice(&crc.to_be_bytes());
+            at += 2;
+        }
+        assert_eq!(verify(&frame), Ok(()));
+        let mut damaged = frame.clone();
+        damaged[16] ^= 1;
+        assert!(matches!(
+            verify(&damaged),
+            Err(CrcError::BlockMismatch { block: 1, .. })
+        ));
+        let mut damaged = frame.clone();
+        damaged[8] += 1;
+        assert!(matches!(verify(&damaged), Err(CrcError::Mismatch { .. })));
+        for algorithm in algorithms() {
+            let header = AdtsHeader::from_bytes(&frame).unwrap();
+            assert_eq!(header.verify_crc_with(algorithm), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Headers decoded into plain values in a single pass, for loops which read most of the fields of
+//! every frame.
+
+use crate::{
+    fields, AdtsHeader, AdtsHeaderError, AdtsHeaderInfo, AdtsStreamConfig, AudioObjectType,
+    ChannelConfiguration, CopyrightIdentificationStart, MpegVersion, Originality, PayloadError,
+    ProtectionIndicator, SamplingFrequency,
+};
+use std::fmt;
+
+impl AdtsHeaderInfo {
+    /// Decodes every field of the header at the start of the given buffer, which must hold the
+    /// whole header (CRC included, if signalled)
+    pub(crate) fn decode(buf: &[u8]) -> AdtsHeaderInfo {
+        const AUDIO_OBJECT_TYPES: [AudioObjectType; 4] = [
+            AudioObjectType::AacMain,
+            AudioObjectType::AacLC,
+            AudioObjectType::AacSSR,
+            AudioObjectType::AacLTP,
+        ];
+        let bits = fields::load(buf);
+        let protection = if fields::PROTECTION_ABSENT.extract(bits) == 1 {
+            ProtectionIndicator::CrcAbsent
+        } else {
+            ProtectionIndicator::CrcPresent
+        };
+        AdtsHeaderInfo {
+            mpeg_version: if fields::ID.extract(bits) == 1 {
+                MpegVersion::Mpeg2
+            } else {
+                MpegVersion::Mpeg4
+            },
+            protection,
+            audio_object_type: AUDIO_OBJECT_TYPES[fields::PROFILE.extract(bits) as usize],
+            sampling_frequency: SamplingFrequency::from_bits(
+                fields::SAMPLING_FREQUENCY_INDEX.extract(bits) as u8,
+            ),
+            private_bit: fields::PRIVATE_BIT.extract(bits) as u8,
+            channel_configuration: ChannelConfiguration::from_bits(
+                fields::CHANNEL_CONFIGURATION.extract(bits) as u8,
+            ),
+            originality: if fields::ORIGINAL_COPY.extract(bits) == 1 {
+                Originality::Copy
+            } else {
+                Originality::Original
+            },
+            home: fields::HOME.extract(bits) as u8,
+            copyright_identification_bit: fields::COPYRIGHT_IDENTIFICATION_BIT.extract(bits) as u8,
+            copyright_identification_start: if fields::COPYRIGHT_IDENTIFICATION_START.extract(bits)
+                == 1
+            {
+                CopyrightIdentificationStart::Start
+            } else {
+                CopyrightIdentificationStart::Other
+            },
+            frame_length: fields::FRAME_LENGTH.extract(bits) as u16,
+            buffer_fullness: fields::ADTS_BUFFER_FULLNESS.extract(bits) as u16,
+            number_of_raw_data_blocks: fields::NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.extract(bits)
+                as u8
+                + 1,
+            crc: match protection {
+                ProtectionIndicator::CrcPresent => Some(fields::CRC_CHECK.get(buf) as u16),
+                ProtectionIndicator::CrcAbsent => None,
+            },
+        }
+    }
+}
+
+/// A frame header whose fields are all decoded up front, so that its accessors are plain field
+/// reads.
+///
+/// [`AdtsHeader`](struct.AdtsHeader.html) extracts each field from the buffer whenever its
+/// accessor is called, which is cheapest for code looking at only a few fields.  Code reading
+/// most fields of every frame (or formatting headers with `Debug`) does less work decoding the
+/// header once, with this type.  Run `cargo bench --bench header` to compare the two.
+///
+/// ```rust
+/// use adts_reader::DecodedAdtsHeader;
+/// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let header = DecodedAdtsHeader::from_bytes(&buf).unwrap();
+/// assert_eq!(header.frame_length(), 8);
+/// assert_eq!(header.payload(), Ok(&[0x21][..]));
+/// ```
+#[derive(Clone, Copy)]
+pub struct DecodedAdtsHeader<'buf> {
+    buf: &'buf [u8],
+    info: AdtsHeaderInfo,
+}
+impl<'buf> DecodedAdtsHeader<'buf> {
+    /// Checks and decodes the header at the start of the given buffer, as for
+    /// [`AdtsHeader::from_bytes()`](struct.AdtsHeader.html#method.from_bytes)
+    pub fn from_bytes(buf: &'buf [u8]) -> Result<DecodedAdtsHeader<'buf>, AdtsHeaderError> {
+        AdtsHeader::from_bytes(buf).map(DecodedAdtsHeader::from)
+    }
+
+    /// The header, for the methods that only `AdtsHeader` provides
+    pub fn header(&self) -> AdtsHeader<'buf> {
+        AdtsHeader { buf: self.buf }
+    }
+
+    /// All of the header's values
+    pub fn info(&self) -> &AdtsHeaderInfo {
+        &self.info
+    }
+
+    pub fn mpeg_version(&self) -> MpegVersion {
+        self.info.mpeg_version
+    }
+
+    pub fn protection(&self) -> ProtectionIndicator {
+        self.info.protection
+    }
+
+    pub fn audio_object_type(&self) -> AudioObjectType {
+        self.info.audio_object_type
+    }
+
+    pub fn sampling_frequency(&self) -> SamplingFrequency {
+        self.info.sampling_frequency
+    }
+
+    /// either 1 or 0
+    pub fn private_bit(&self) -> u8 {
+        self.info.private_bit
+    }
+
+    pub fn channel_configuration(&self) -> ChannelConfiguration {
+        self.info.channel_configuration
+    }
+
+    pub fn originality(&self) -> Originality {
+        self.info.originality
+    }
+
+    /// either 1 or 0
+    pub fn home(&self) -> u8 {
+        self.info.home
+    }
+
+    /// either 1 or 0
+    pub fn copyright_identification_bit(&self) -> u8 {
+        self.info.copyright_identification_bit
+    }
+
+    pub fn copyright_identification_start(&self) -> CopyrightIdentificationStart {
+        self.info.copyright_identification_start
+    }
+
+    /// length of the frame, including the header
+    pub fn frame_length(&self) -> u16 {
+        self.info.frame_length
+    }
+
+    pub fn adts_buffer_fullness(&self) -> u16 {
+        self.info.buffer_fullness
+    }
+
+    /// the `crc_check` value, if present
+    pub fn crc(&self) -> Option<u16> {
+        self.info.crc
+    }
+
+    /// between 1 and 4 inclusive
+    pub fn number_of_raw_data_blocks_in_frame(&self) -> u8 {
+        self.info.number_of_raw_data_blocks
+    }
+
+    /// The fixed-header values of this frame
+    pub fn stream_config(&self) -> AdtsStreamConfig {
+        self.info.stream_config()
+    }
+
+    /// The frame's payload, following the header and any CRC, as for
+    /// [`AdtsHeader::payload()`](struct.AdtsHeader.html#method.payload)
+    pub fn payload(&self) -> Result<&'buf [u8], PayloadError> {
+        let len = usize::from(self.info.frame_length);
+        let header_len = match self.info.protection {
+            ProtectionIndicator::CrcPresent => 9,
+            ProtectionIndicator::CrcAbsent => 7,
+        };
+        if self.buf.len() < len {
+            Err(PayloadError {
+                expected: len,
+                actual: self.buf.len(),
+            })
+        } else {
+            Ok(&self.buf[header_len..len])
+        }
+    }
+}
+impl<'buf> From<AdtsHeader<'buf>> for DecodedAdtsHeader<'buf> {
+    fn from(header: AdtsHeader<'buf>) -> Self {
+        DecodedAdtsHeader {
+            buf: header.buf,
+            info: AdtsHeaderInfo::decode(header.buf),
+        }
+    }
+}
+impl<'buf> fmt::Debug for DecodedAdtsHeader<'buf> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt_info(&self.info, f)
+    }
+}
+
+/// Formats the header's values in the manner of a derived `Debug` implementation for
+/// `AdtsHeader`, shared by both header types so that their output is the same
+pub(crate) fn fmt_info(info: &AdtsHeaderInfo, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AdtsHeader")
+        .field("mpeg_version", &info.mpeg_version)
+        .field("protection", &info.protection)
+        .field("audio_object_type", &info.audio_object_type)
+        .field("sampling_frequency", &info.sampling_frequency)
+        .field("private_bit", &info.private_bit)
+        .field("channel_configuration", &info.channel_configuration)
+        .field("originality", &info.originality)
+        .field("home", &info.home)
+        .field(
+            "copyright_identification_bit",
+            &info.copyright_identification_bit,
+        )
+        .field(
+            "copyright_identification_start",
+            &info.copyright_identification_start,
+        )
+        .field("frame_length", &info.frame_length)
+        .field("adts_buffer_fullness", &info.buffer_fullness)
+        .field("crc", &info.crc)
+        .field(
+            "number_of_raw_data_blocks_in_frame",
+            &info.number_of_raw_data_blocks,
+        )
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::{AdtsFrameIter, FrameOptions};
+
+    #[test]
+    fn same_as_header() {
+        let mut mpeg2 = StreamBuilder::DEFAULT_CONFIG;
+        mpeg2.mpeg_version = MpegVersion::Mpeg2;
+        mpeg2.audio_object_type = AudioObjectType::AacSSR;
+        mpeg2.private_bit = 1;
+        mpeg2.channel_configuration = ChannelConfiguration::FiveOne;
+        mpeg2.originality = Originality::Copy;
+        mpeg2.home = 1;
+        let data = StreamBuilder::new()
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .options(FrameOptions {
+                crc: true,
+                buffer_fullness: 0x123,
+                number_of_raw_data_blocks: 1,
+                copyright_identification_bit: 1,
+                copyright_identification_start: CopyrightIdentificationStart::Start,
+            })
+            .frames(2, mpeg2)
+            .options(FrameOptions {
+                number_of_raw_data_blocks: 3,
+                ..FrameOptions::default()
+            })
+            .frames(2, mpeg2)
+            .build();
+        for header in AdtsFrameIter::new(&data) {
+            let header = header.unwrap();
+            let decoded = DecodedAdtsHeader::from(AdtsHeader { buf: header.buf });
+            let expected = AdtsHeaderInfo {
+                mpeg_version: header.mpeg_version(),
+                protection: header.protection(),
+                audio_object_type: header.audio_object_type(),
+                sampling_frequency: header.sampling_frequency(),
+                private_bit: header.private_bit(),
+                channel_configuration: header.channel_configuration(),
+                originality: header.originality(),
+                home: header.home(),
+                copyright_identification_bit: header.copyright_identification_bit(),
+                copyright_identification_start: header.copyright_identification_start(),
+                frame_length: header.frame_length(),
+                buffer_fullness: header.adts_buffer_fullness(),
+                number_of_raw_data_blocks: header.number_of_raw_data_blocks_in_frame(),
+                crc: header.crc(),
+            };
+            assert_eq!(decoded.info(), &expected);
+            assert_eq!(decoded.payload(), header.payload());
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", header));
+        }
+    }
+}
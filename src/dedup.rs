@@ -0,0 +1,150 @@
+//! Detection of duplicated input chunks, as produced by naive retransmission over UDP-like
+//! transports.
+//!
+//! If the same datagram is delivered twice in succession, pushing both copies into a parser would
+//! duplicate whatever frames (or parts of frames) it carries, giving a glitch in the audio
+//! downstream.  [`DuplicateFilter`](struct.DuplicateFilter.html) sits in front of the parser and
+//! drops such repeats.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+/// Counts kept by a [`DuplicateFilter`](struct.DuplicateFilter.html).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DuplicateStats {
+    /// Number of chunks checked
+    pub chunks: u64,
+    /// Number of chunks dropped for repeating the chunk before them
+    pub duplicates: u64,
+    /// Total size of the dropped chunks
+    pub duplicate_bytes: u64,
+}
+
+/// Drops chunks which exactly repeat the immediately preceding chunk.
+///
+/// Only a hash and the length of the previous chunk are kept, rather than a copy of it; with a
+/// 64-bit hash, the chance of mistaking a different chunk for a duplicate is negligible.  Empty
+/// chunks are never treated as duplicates.
+///
+/// Content alone can't tell a retransmission from a legitimate repeat, though: an encoder
+/// producing digital silence may well emit identical datagrams in succession, and
+/// [`is_new()`](#method.is_new) drops all but the first of them.  Where the transport numbers its
+/// datagrams (an RTP sequence number or timestamp, say), use
+/// [`is_new_in_sequence()`](#method.is_new_in_sequence) instead, so that only repeats of the same
+/// datagram are dropped.
+///
+/// ```rust
+/// use adts_reader::dedup::DuplicateFilter;
+/// # use adts_reader::*;
+/// # struct MyConsumer;
+/// # impl AdtsConsumer for MyConsumer {
+/// #     fn new_config(&mut self, mpeg_version: MpegVersion, protection: ProtectionIndicator, aot: AudioObjectType, freq: SamplingFrequency, private_bit: u8, channels: ChannelConfiguration, originality: Originality, home: u8) { }
+/// #     fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) { }
+/// #     fn error(&mut self, err: AdtsParseError) { }
+/// # }
+/// # let datagrams: Vec<Vec<u8>> = vec![];
+/// let mut filter = DuplicateFilter::new();
+/// let mut parser = AdtsParser::new(MyConsumer);
+/// for datagram in datagrams {
+///     if filter.is_new(&datagram) {
+///         parser.push(&datagram);
+///     }
+/// }
+/// println!("dropped {} duplicates", filter.stats().duplicates);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateFilter {
+    /// sequence number (if given), hash and length of the previous chunk
+    previous: Option<(Option<u64>, u64, usize)>,
+    stats: DuplicateStats,
+}
+impl DuplicateFilter {
+    pub fn new() -> DuplicateFilter {
+        DuplicateFilter::default()
+    }
+
+    /// Returns `false` if the given chunk is identical to the chunk given in the previous call,
+    /// in which case it should be dropped, or `true` otherwise
+    pub fn is_new(&mut self, chunk: &[u8]) -> bool {
+        self.check(None, chunk)
+    }
+
+    /// As for [`is_new()`](#method.is_new), but a chunk is only taken to be a duplicate if it
+    /// also carries the same `sequence` value as the previous chunk, so that identical chunks
+    /// sent one after another (repeated frames of silence, for example) are kept.
+    ///
+    /// ```rust
+    /// use adts_reader::dedup::DuplicateFilter;
+    /// let silence = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let mut filter = DuplicateFilter::new();
+    /// assert!(filter.is_new_in_sequence(1, &silence));
+    /// assert!(filter.is_new_in_sequence(2, &silence));
+    /// // a retransmission of datagram 2
+    /// assert!(!filter.is_new_in_sequence(2, &silence));
+    /// ```
+    pub fn is_new_in_sequence(&mut self, sequence: u64, chunk: &[u8]) -> bool {
+        self.check(Some(sequence), chunk)
+    }
+
+    fn check(&mut self, sequence: Option<u64>, chunk: &[u8]) -> bool {
+        self.stats.chunks += 1;
+        if chunk.is_empty() {
+            self.previous = None;
+            return true;
+        }
+        let mut hasher = DefaultHasher::new();
+        hasher.write(chunk);
+        let current = (sequence, hasher.finish(), chunk.len());
+        if self.previous == Some(current) {
+            self.stats.duplicates += 1;
+            self.stats.duplicate_bytes += chunk.len() as u64;
+            return false;
+        }
+        self.previous = Some(current);
+        true
+    }
+
+    pub fn stats(&self) -> &DuplicateStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_duplicates() {
+        let a = [0xff, 0xf1, 1, 2];
+        let b = [0xff, 0xf1, 1, 3];
+        let chunks: [&[u8]; 8] = [&a, &a, &b, &a, &b, &b, &b, &[]];
+        let mut filter = DuplicateFilter::new();
+        let passed: Vec<&[u8]> = chunks.into_iter().filter(|c| filter.is_new(c)).collect();
+        assert_eq!(passed, [&a[..], &b, &a, &b, &[]]);
+        assert_eq!(
+            filter.stats(),
+            &DuplicateStats {
+                chunks: 8,
+                duplicates: 3,
+                duplicate_bytes: 12,
+            }
+        );
+        // an empty chunk breaks a run, and is itself never a duplicate
+        assert!(filter.is_new(&[]));
+        assert!(filter.is_new(&b));
+    }
+
+    #[test]
+    fn sequenced_duplicates() {
+        let a = [0xff, 0xf1, 1, 2];
+        let mut filter = DuplicateFilter::new();
+        let passed: Vec<bool> = [(1, a), (2, a), (2, a), (3, a)]
+            .iter()
+            .map(|(seq, c)| filter.is_new_in_sequence(*seq, c))
+            .collect();
+        assert_eq!(passed, [true, true, false, true]);
+        assert_eq!(filter.stats().duplicates, 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! Plain descriptions of where frames lie within a buffer, for exchanging between processes which
+//! share that buffer (a memory-mapped capture file, for example) without copying any payload.
+//!
+//! A [`FrameDescriptor`](struct.FrameDescriptor.html) holds no references, only offsets, lengths
+//! and timing, so it can be sent over any IPC channel and resolved against the receiver's own
+//! mapping of the buffer.  [`Descriptors`](struct.Descriptors.html) produces them from a buffer
+//! of ADTS data.
+//!
+//! ```rust
+//! use adts_reader::descriptor::Descriptors;
+//! # let capture = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! // in the process scanning the capture
+//! let descriptors: Vec<_> = Descriptors::new(&capture).collect();
+//! // in the process holding its own mapping of the same capture
+//! for descriptor in &descriptors {
+//!     let frame = descriptor.resolve(&capture).expect("capture unchanged");
+//!     assert_eq!(frame.payload(), Ok(&[0x21][..]));
+//! }
+//! ```
+
+use crate::{AdtsFrameIter, AdtsHeader, AdtsStreamConfig, FrameLength, MediaTime};
+use std::iter::FusedIterator;
+use std::ops::Range;
+use std::time::Duration;
+
+/// The location of a frame within a shared buffer, along with its timing and configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameDescriptor {
+    /// Position of the start of the frame's header within the buffer
+    pub offset: u64,
+    /// Length of the whole frame, header included
+    pub len: u32,
+    /// Length of the header, `7` or `9` depending on whether a CRC is present
+    pub header_len: u8,
+    /// Media time of the start of the frame, counted from the first frame described
+    pub pts: Duration,
+    /// Identifies the frame's stream configuration; see
+    /// [`Descriptors::configs()`](struct.Descriptors.html#method.configs)
+    pub config_id: u32,
+}
+impl FrameDescriptor {
+    /// The range of the buffer holding the whole frame
+    pub fn range(&self) -> Range<usize> {
+        let start = self.offset as usize;
+        start..start + self.len as usize
+    }
+
+    /// The range of the buffer holding the frame's payload (the data following the header)
+    pub fn payload_range(&self) -> Range<usize> {
+        let range = self.range();
+        range.start + usize::from(self.header_len)..range.end
+    }
+
+    /// The bytes of the whole frame, or `None` if the buffer is too short to hold it
+    pub fn bytes<'buf>(&self, base: &'buf [u8]) -> Option<&'buf [u8]> {
+        base.get(self.range())
+    }
+
+    /// The bytes of the frame's payload, or `None` if the buffer is too short to hold it
+    pub fn payload<'buf>(&self, base: &'buf [u8]) -> Option<&'buf [u8]> {
+        base.get(self.payload_range())
+    }
+
+    /// Parses the frame's header from the buffer, checking that the buffer still holds a frame of
+    /// the described length there.  Returns `None` if it does not, as happens when the writer of
+    /// a ring buffer has since overwritten the frame.
+    pub fn resolve<'buf>(&self, base: &'buf [u8]) -> Option<AdtsHeader<'buf>> {
+        let header = AdtsHeader::from_bytes(self.bytes(base)?).ok()?;
+        if header.frame_len() == self.len as usize
+            && header.header_length() == u16::from(self.header_len)
+        {
+            Some(header)
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator yielding a [`FrameDescriptor`](struct.FrameDescriptor.html) for each complete
+/// frame in a buffer, skipping any data which can't be parsed as a frame.
+///
+/// Each distinct stream configuration is given a `config_id`, counting from zero in the order
+/// the configurations are first seen, so that a frame returning to an earlier configuration is
+/// given that configuration's id again.
+#[derive(Debug, Clone)]
+pub struct Descriptors<'buf> {
+    frames: AdtsFrameIter<'buf>,
+    frame_length: FrameLength,
+    elapsed: MediaTime,
+    configs: Vec<AdtsStreamConfig>,
+}
+impl<'buf> Descriptors<'buf> {
+    pub fn new(buf: &'buf [u8]) -> Descriptors<'buf> {
+        Descriptors {
+            frames: AdtsFrameIter::new(buf),
+            frame_length: FrameLength::default(),
+            elapsed: MediaTime::ZERO,
+            configs: vec![],
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` used for `pts` (default 1024), since ADTS
+    /// headers don't say
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// The stream configurations seen so far, indexed by `config_id`
+    pub fn configs(&self) -> &[AdtsStreamConfig] {
+        &self.configs
+    }
+
+    /// As for [`AdtsFrameIter::remainder()`](../struct.AdtsFrameIter.html#method.remainder)
+    pub fn remainder(&self) -> &'buf [u8] {
+        self.frames.remainder()
+    }
+
+    fn config_id(&mut self, config: AdtsStreamConfig) -> u32 {
+        let index = match self.configs.iter().position(|c| *c == config) {
+            Some(index) => index,
+            None => {
+                self.configs.push(config);
+                self.configs.len() - 1
+            }
+        };
+        index as u32
+    }
+}
+impl<'buf> Iterator for Descriptors<'buf> {
+    type Item = FrameDescriptor;
+
+    fn next(&mut self) -> Option<FrameDescriptor> {
+        loop {
+            let offset = self.frames.position();
+            let header = match self.frames.next()? {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+            let descriptor = FrameDescriptor {
+                offset: offset as u64,
+                len: header.frame_len() as u32,
+                header_len: header.header_length() as u8,
+                pts: self.elapsed.to_duration(),
+                config_id: self.config_id(header.stream_config()),
+            };
+            // frames with a reserved sampling frequency have unknown duration, and are taken to
+            // have none
+            if let Some(duration) = header.frame_duration_of(self.frame_length) {
+                self.elapsed = self.elapsed + duration;
+            }
+            return Some(descriptor);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+impl<'buf> FusedIterator for Descriptors<'buf> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn describe_and_resolve() {
+        let mono = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Mono,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let builder = StreamBuilder::new()
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .junk(3)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .config_change(mono)
+            .frames(1, StreamBuilder::DEFAULT_CONFIG);
+        let offsets = builder.offsets().to_vec();
+        let data = builder.build();
+        let mut descriptors = Descriptors::new(&data);
+        let all: Vec<_> = descriptors.by_ref().collect();
+        assert_eq!(
+            all.iter().map(|d| d.offset as usize).collect::<Vec<_>>(),
+            offsets
+        );
+        assert_eq!(
+            all.iter().map(|d| d.config_id).collect::<Vec<_>>(),
+            vec![0, 0, 1, 0]
+        );
+        assert_eq!(descriptors.configs(), [StreamBuilder::DEFAULT_CONFIG, mono]);
+        assert_eq!(all[3].pts, MediaTime::from_blocks(3, 48000).to_duration());
+        assert_eq!((all[2].len, all[2].header_len), (13, 9));
+        assert_eq!(all[2].payload(&data), Some(&[2; 4][..]));
+        let header = all[2].resolve(&data).unwrap();
+        assert_eq!(header.channel_configuration(), ChannelConfiguration::Mono);
+
+        // a descriptor doesn't resolve against data that no longer holds its frame
+        let mut overwritten = data.clone();
+        overwritten[offsets[1]..offsets[1] + 9].copy_from_slice(&data[offsets[2]..offsets[2] + 9]);
+        assert!(all[1].resolve(&overwritten).is_none());
+        assert!(all[3].resolve(&data[..data.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+//! A single checksum over the sequence of frames in a stream, for checking that two pipelines
+//! (say, an old tool and its replacement) produce equivalent output without comparing the
+//! streams byte for byte.
+//!
+//! Header fields which legitimately differ between equivalent streams, such as
+//! `adts_buffer_fullness`, can be excluded with [`Normalise`](struct.Normalise.html), so that the
+//! rules for what counts as "the same" live in one place rather than in each pipeline's test
+//! scripts.  Where the digests do differ, [`compare`](../compare/index.html) can show why.
+//!
+//! ```rust
+//! use adts_reader::digest::{FrameDigest, Normalise};
+//! let a = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! // the same frame, but with an adts_buffer_fullness of 0
+//! let b = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x00, 0x00, 0x21];
+//! let normalise = Normalise {
+//!     buffer_fullness: true,
+//!     ..Normalise::default()
+//! };
+//! assert_eq!(
+//!     FrameDigest::of_stream(&a, normalise),
+//!     FrameDigest::of_stream(&b, normalise)
+//! );
+//! assert_ne!(
+//!     FrameDigest::of_stream(&a, Normalise::default()),
+//!     FrameDigest::of_stream(&b, Normalise::default())
+//! );
+//! ```
+
+use crate::{fields, AdtsConsumer2, AdtsFrameIter, AdtsHeader, FrameContext};
+
+/// Selects the header fields which [`FrameDigest`](struct.FrameDigest.html) ignores.  Each field
+/// is included in the digest unless its flag is set (by default, none are set).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalise {
+    /// Ignore `adts_buffer_fullness`
+    pub buffer_fullness: bool,
+    /// Ignore whether frames carry a CRC (along with the CRC value itself, and the effect of its
+    /// presence on `frame_length`)
+    pub crc: bool,
+    /// Ignore `copyright_identification_bit` and `copyright_identification_start`
+    pub copyright_identification: bool,
+    /// Ignore `private_bit`
+    pub private_bit: bool,
+    /// Ignore `original_copy` and `home`
+    pub originality: bool,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Accumulates a 64-bit digest over a sequence of complete frames.
+///
+/// The digest covers each frame's header (less any fields ignored by the given
+/// [`Normalise`](struct.Normalise.html) settings) and payload, and the position of the boundaries
+/// between frames.  Data which isn't part of a frame is not included.  The algorithm (64-bit
+/// FNV-1a) will not change between releases, so digests may be stored and compared later; it is
+/// not a cryptographic hash, and is no defence against deliberately crafted collisions.
+///
+/// `FrameDigest` implements [`AdtsConsumer2`](../trait.AdtsConsumer2.html), so it can be given
+/// to an `AdtsParser` directly.
+#[derive(Debug, Clone)]
+pub struct FrameDigest {
+    normalise: Normalise,
+    state: u64,
+    frames: u64,
+}
+impl FrameDigest {
+    pub fn new(normalise: Normalise) -> FrameDigest {
+        FrameDigest {
+            normalise,
+            state: FNV_OFFSET_BASIS,
+            frames: 0,
+        }
+    }
+
+    /// The digest of all the complete frames found in the given buffer
+    pub fn of_stream(buf: &[u8], normalise: Normalise) -> u64 {
+        let mut digest = FrameDigest::new(normalise);
+        for header in AdtsFrameIter::new(buf).flatten() {
+            digest.update(&header);
+        }
+        digest.value()
+    }
+
+    /// Adds the frame with the given header to the digest.  Panics if the header's buffer
+    /// doesn't hold the whole frame.
+    pub fn update(&mut self, header: &AdtsHeader<'_>) {
+        let payload = header.payload().expect("complete frame");
+        self.update_frame(header, payload);
+    }
+
+    fn update_frame(&mut self, header: &AdtsHeader<'_>, payload: &[u8]) {
+        let mut fixed = [0; 7];
+        fixed.copy_from_slice(&header.buf[..7]);
+        let n = self.normalise;
+        if n.buffer_fullness {
+            fields::ADTS_BUFFER_FULLNESS.set(&mut fixed, 0);
+        }
+        if n.copyright_identification {
+            fields::COPYRIGHT_IDENTIFICATION_BIT.set(&mut fixed, 0);
+            fields::COPYRIGHT_IDENTIFICATION_START.set(&mut fixed, 0);
+        }
+        if n.private_bit {
+            fields::PRIVATE_BIT.set(&mut fixed, 0);
+        }
+        if n.originality {
+            fields::ORIGINAL_COPY.set(&mut fixed, 0);
+            fields::HOME.set(&mut fixed, 0);
+        }
+        let crc = if n.crc {
+            // describe the frame as it would be without a CRC
+            fields::PROTECTION_ABSENT.set(&mut fixed, 1);
+            fields::FRAME_LENGTH.set(&mut fixed, (7 + payload.len()) as u32);
+            &[][..]
+        } else {
+            &header.buf[7..usize::from(header.header_length())]
+        };
+        // the length prefix keeps the boundaries between frames significant
+        self.write(&(payload.len() as u32).to_be_bytes());
+        self.write(&fixed);
+        self.write(crc);
+        self.write(payload);
+        self.frames += 1;
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state ^= u64::from(b);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// The digest of the frames added so far
+    pub fn value(&self) -> u64 {
+        self.state
+    }
+
+    /// Number of frames added so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+impl AdtsConsumer2 for FrameDigest {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        self.update_frame(&frame.header, frame.payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    fn stream(options: FrameOptions) -> Vec<u8> {
+        StreamBuilder::new()
+            .options(options)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .build()
+    }
+
+    #[test]
+    fn normalisation() {
+        let plain = stream(FrameOptions::default());
+        let varied = stream(FrameOptions {
+            crc: true,
+            buffer_fullness: 0x100,
+            copyright_identification_bit: 1,
+            ..FrameOptions::default()
+        });
+        let all = Normalise {
+            buffer_fullness: true,
+            crc: true,
+            copyright_identification: true,
+            private_bit: true,
+            originality: true,
+        };
+        let digest = |buf: &[u8], n| FrameDigest::of_stream(buf, n);
+        assert_eq!(digest(&plain, all), digest(&varied, all));
+        assert_ne!(
+            digest(&plain, Normalise::default()),
+            digest(&varied, Normalise::default())
+        );
+        // each field left in makes a difference
+        for n in [
+            Normalise {
+                buffer_fullness: false,
+                ..all
+            },
+            Normalise { crc: false, ..all },
+            Normalise {
+                copyright_identification: false,
+                ..all
+            },
+        ] {
+            assert_ne!(digest(&plain, n), digest(&varied, n), "{:?}", n);
+        }
+
+        // payloads always count, but data outside of frames doesn't
+        let mut changed = plain.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert_ne!(digest(&plain, all), digest(&changed, all));
+        let mut junk = plain.clone();
+        junk.extend_from_slice(&[0x55; 10]);
+        assert_eq!(digest(&plain, all), digest(&junk, all));
+        assert_eq!(
+            FrameDigest::new(all).value(),
+            digest(&[0x55; 10], Normalise::default())
+        );
+    }
+
+    #[test]
+    fn as_consumer() {
+        let data = stream(FrameOptions::default());
+        let mut parser = AdtsParser::new(FrameDigest::new(Normalise::default()));
+        for chunk in data.chunks(5) {
+            parser.push(chunk);
+        }
+        assert_eq!(parser.consumer.frames(), 3);
+        assert_eq!(
+            parser.consumer.value(),
+            FrameDigest::of_stream(&data, Normalise::default())
+        );
+    }
+}
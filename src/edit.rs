@@ -0,0 +1,190 @@
+//! Rewriting header fields of existing frames in place.
+
+use crate::blocks::{self, BlockLayoutError};
+use crate::crc::{self, CrcAlgorithm};
+use crate::write::check_range;
+use crate::{
+    fields, AdtsHeader, AdtsHeaderError, CopyrightIdentificationStart, Originality,
+    ProtectionIndicator, WriteError,
+};
+
+/// Mutable access to the header of an ADTS frame at the start of the given buffer, for changing
+/// the values of header fields without writing the frame out again.
+///
+/// Only the fields which don't affect the layout of the frame can be changed.  If the frame has
+/// a CRC, it will no longer match once a field has changed, until updated with
+/// [`fill_crc()`](#method.fill_crc).
+///
+/// ```rust
+/// use adts_reader::{AdtsHeaderMut, CopyrightIdentificationStart};
+/// let mut buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x00, 0x00, 0x21];
+/// let mut header = AdtsHeaderMut::from_bytes(&mut buf).unwrap();
+/// header.set_adts_buffer_fullness(0x7ff).unwrap();
+/// header.set_copyright_identification_start(CopyrightIdentificationStart::Start);
+/// assert_eq!(header.header().adts_buffer_fullness(), 0x7ff);
+/// assert_eq!(buf, [0xff, 0xf1, 0x4c, 0x84, 0x01, 0x1f, 0xfc, 0x21]);
+/// ```
+pub struct AdtsHeaderMut<'buf> {
+    buf: &'buf mut [u8],
+}
+impl<'buf> AdtsHeaderMut<'buf> {
+    /// Borrows the given buffer, which must start with a valid header, as for
+    /// [`AdtsHeader::from_bytes()`](struct.AdtsHeader.html#method.from_bytes).  The buffer may
+    /// be longer than the frame, in which case the rest of it is ignored.
+    pub fn from_bytes(buf: &'buf mut [u8]) -> Result<AdtsHeaderMut<'buf>, AdtsHeaderError> {
+        AdtsHeader::from_bytes(buf)?;
+        Ok(AdtsHeaderMut { buf })
+    }
+
+    /// Read access to all the header fields, with the values as changed so far
+    pub fn header(&self) -> AdtsHeader<'_> {
+        AdtsHeader { buf: self.buf }
+    }
+
+    /// Sets the 11-bit `adts_buffer_fullness` field, where `0x7ff` signals a variable rate stream
+    pub fn set_adts_buffer_fullness(&mut self, fullness: u16) -> Result<(), WriteError> {
+        self.set(&fields::ADTS_BUFFER_FULLNESS, fullness.into())
+    }
+
+    /// Sets `private_bit`, to either 1 or 0
+    pub fn set_private_bit(&mut self, private_bit: u8) -> Result<(), WriteError> {
+        self.set(&fields::PRIVATE_BIT, private_bit.into())
+    }
+
+    pub fn set_originality(&mut self, originality: Originality) {
+        let value = match originality {
+            Originality::Original => 0,
+            Originality::Copy => 1,
+        };
+        fields::ORIGINAL_COPY.set(self.buf, value);
+    }
+
+    /// Sets `home`, to either 1 or 0
+    pub fn set_home(&mut self, home: u8) -> Result<(), WriteError> {
+        self.set(&fields::HOME, home.into())
+    }
+
+    /// Sets `copyright_identification_bit`, to either 1 or 0
+    pub fn set_copyright_identification_bit(&mut self, bit: u8) -> Result<(), WriteError> {
+        self.set(&fields::COPYRIGHT_IDENTIFICATION_BIT, bit.into())
+    }
+
+    pub fn set_copyright_identification_start(&mut self, start: CopyrightIdentificationStart) {
+        let value = match start {
+            CopyrightIdentificationStart::Start => 1,
+            CopyrightIdentificationStart::Other => 0,
+        };
+        fields::COPYRIGHT_IDENTIFICATION_START.set(self.buf, value);
+    }
+
+    fn set(&mut self, field: &fields::Field, value: u32) -> Result<(), WriteError> {
+        check_range(field, value)?;
+        field.set(self.buf, value);
+        Ok(())
+    }
+
+    /// Recalculates the `crc_check` value following the header, so that it matches the changed
+    /// header fields.  Has no effect on frames without a CRC.
+    ///
+    /// In a frame with a single `raw_data_block` the CRC also covers the payload, so the whole
+    /// frame must be available.  In a frame with several, only the header CRC is updated; the
+    /// CRCs following each block don't cover the header, so stay valid.
+    pub fn fill_crc(&mut self) -> Result<(), BlockLayoutError> {
+        self.fill_crc_with(&crc::DEFAULT)
+    }
+
+    /// As for [`fill_crc()`](#method.fill_crc), using the given CRC implementation
+    pub fn fill_crc_with(&mut self, algorithm: &dyn CrcAlgorithm) -> Result<(), BlockLayoutError> {
+        if self.header().protection() == ProtectionIndicator::CrcAbsent {
+            return Ok(());
+        }
+        let layout = blocks::block_layout(self.buf)?;
+        let frame = &self.buf[..self.header().frame_len()];
+        let (pos, crc) = crc::header_crc(&frame[..7], frame, &layout, algorithm);
+        self.buf[pos..pos + 2].copy_from_slice(&crc.to_be_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::FrameOptions;
+
+    #[test]
+    fn edit_fields() {
+        let mut frame = StreamBuilder::new()
+            .options(FrameOptions {
+                crc: true,
+                buffer_fullness: 0x123,
+                ..FrameOptions::default()
+            })
+            .frames(1, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let original = frame.clone();
+        let mut header = AdtsHeaderMut::from_bytes(&mut frame).unwrap();
+        header.set_adts_buffer_fullness(0x7ff).unwrap();
+        assert_eq!(
+            header.set_adts_buffer_fullness(0x800),
+            Err(WriteError::FieldOutOfRange {
+                field: "adts_buffer_fullness",
+                value: 0x800
+            })
+        );
+        header.set_private_bit(1).unwrap();
+        header.set_home(1).unwrap();
+        header.set_originality(Originality::Copy);
+        header.set_copyright_identification_bit(1).unwrap();
+        assert!(header.set_copyright_identification_bit(2).is_err());
+        header.set_copyright_identification_start(CopyrightIdentificationStart::Start);
+
+        let h = header.header();
+        assert_eq!(h.adts_buffer_fullness(), 0x7ff);
+        assert_eq!(h.private_bit(), 1);
+        assert_eq!(h.home(), 1);
+        assert_eq!(h.originality(), Originality::Copy);
+        assert_eq!(h.copyright_identification_bit(), 1);
+        assert_eq!(
+            h.copyright_identification_start(),
+            CopyrightIdentificationStart::Start
+        );
+        // everything else is untouched
+        let o = AdtsHeader::from_bytes(&original).unwrap();
+        assert_eq!(
+            h.stream_config().channel_configuration,
+            o.channel_configuration()
+        );
+        assert_eq!(h.frame_length(), o.frame_length());
+        assert_eq!(h.payload(), o.payload());
+
+        assert!(matches!(
+            header.header().verify_crc(),
+            Err(crc::CrcError::Mismatch { .. })
+        ));
+        header.fill_crc().unwrap();
+        assert_eq!(header.header().verify_crc(), Ok(()));
+        assert!(AdtsHeaderMut::from_bytes(&mut frame[..9])
+            .unwrap()
+            .fill_crc()
+            .is_err());
+    }
+
+    #[test]
+    fn multi_block_crc() {
+        // the header CRC of a frame with several blocks covers the position table, ahead of two
+        // blocks of 3 bytes, each followed by its own CRC
+        let blocks = [[1u8, 2, 3], [4, 5, 6]];
+        let mut frame = vec![0xff, 0xf0, 0x4c, 0x80, 0x00, 0x00, 0x01, 0x00, 0x05, 0, 0];
+        for block in &blocks {
+            frame.extend_from_slice(block);
+            frame.extend_from_slice(&crc::DEFAULT.update(crc::INITIAL, block).to_be_bytes());
+        }
+        let len = frame.len() as u32;
+        fields::FRAME_LENGTH.set(&mut frame, len);
+        let mut header = AdtsHeaderMut::from_bytes(&mut frame).unwrap();
+        header.set_adts_buffer_fullness(0x7ff).unwrap();
+        header.fill_crc().unwrap();
+        assert_eq!(header.header().verify_crc(), Ok(()));
+    }
+}
@@ -0,0 +1,795 @@
+//! Identification of the top-level syntactic elements within the `raw_data_block`s of a frame
+//! payload.
+//!
+//! Available when the `elements` feature is enabled.
+//!
+//! This doesn't decode AAC.  The channel elements (SCE, CPE, CCE and LFE) carry no length field,
+//! and finding where one ends would require entropy-decoding its spectral data, so a scan stops
+//! at the first channel element it finds, reporting [`ScanEnd::Opaque`](enum.ScanEnd.html).  The
+//! extent of other elements (PCE, DSE, FIL and END) is found from their own syntax.
+//!
+//! The data of DSE and FIL elements is located, and the `extension_type` of each FIL element is
+//! given, so that (for instance) DSE metadata can be extracted.  Since SBR data is carried in FIL
+//! elements following the channel elements it extends, a scan finds it only in the unusual case
+//! that no channel element precedes it in the block; [`find_sbr()`](fn.find_sbr.html) instead
+//! searches back from the end of the block, and [`HeAacDetector`](struct.HeAacDetector.html)
+//! uses that to tell HE-AAC streams, whose ADTS headers claim plain AAC-LC, from the rest.
+//!
+//! ```rust
+//! use adts_reader::{elements, AdtsHeader};
+//! let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let header = AdtsHeader::from_bytes(&buf).unwrap();
+//! let scan = elements::scan_frame(&header, &buf[7..]);
+//! assert_eq!(scan.counts().cpe, 1);
+//! ```
+
+use crate::asc::HeAacConfig;
+use crate::bits::BitReader;
+use crate::{AdtsConsumer2, AdtsHeader, AdtsStreamConfig, ChannelConfiguration, FrameContext};
+use std::ops::Range;
+
+/// The value of the 3-bit `id_syn_ele` field which starts each syntactic element.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ElementId {
+    /// single_channel_element
+    Sce = 0,
+    /// channel_pair_element
+    Cpe = 1,
+    /// coupling_channel_element
+    Cce = 2,
+    /// lfe_channel_element
+    Lfe = 3,
+    /// data_stream_element
+    Dse = 4,
+    /// program_config_element
+    Pce = 5,
+    /// fill_element
+    Fil = 6,
+    /// terminates the raw_data_block
+    End = 7,
+}
+impl ElementId {
+    fn from_bits(id: u32) -> ElementId {
+        match id & 0b111 {
+            0 => ElementId::Sce,
+            1 => ElementId::Cpe,
+            2 => ElementId::Cce,
+            3 => ElementId::Lfe,
+            4 => ElementId::Dse,
+            5 => ElementId::Pce,
+            6 => ElementId::Fil,
+            _ => ElementId::End,
+        }
+    }
+
+    /// `true` for the element types carrying audio channel data, whose length can't be determined
+    /// without decoding
+    pub fn is_channel_element(self) -> bool {
+        matches!(
+            self,
+            ElementId::Sce | ElementId::Cpe | ElementId::Cce | ElementId::Lfe
+        )
+    }
+}
+
+/// The `extension_type` of the `extension_payload` carried by a FIL element.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ExtensionType {
+    /// EXT_FILL, padding
+    Fill,
+    /// EXT_FILL_DATA, padding
+    FillData,
+    /// EXT_DATA_ELEMENT
+    DataElement,
+    /// EXT_DYNAMIC_RANGE, dynamic range control information
+    DynamicRange,
+    /// EXT_SAC_DATA, MPEG Surround data
+    SacData,
+    /// EXT_SBR_DATA, spectral band replication data (as in HE-AAC)
+    SbrData,
+    /// EXT_SBR_DATA_CRC, spectral band replication data with a CRC
+    SbrDataCrc,
+    /// A value reserved by the specification
+    Reserved(u8),
+}
+impl ExtensionType {
+    fn from_bits(ext: u32) -> ExtensionType {
+        match ext & 0b1111 {
+            0b0000 => ExtensionType::Fill,
+            0b0001 => ExtensionType::FillData,
+            0b0010 => ExtensionType::DataElement,
+            0b1011 => ExtensionType::DynamicRange,
+            0b1100 => ExtensionType::SacData,
+            0b1101 => ExtensionType::SbrData,
+            0b1110 => ExtensionType::SbrDataCrc,
+            v => ExtensionType::Reserved(v as u8),
+        }
+    }
+
+    /// `true` for the extension types carrying SBR data
+    pub fn is_sbr(self) -> bool {
+        matches!(self, ExtensionType::SbrData | ExtensionType::SbrDataCrc)
+    }
+}
+
+/// A syntactic element found in a `raw_data_block`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+    pub id: ElementId,
+    /// The `element_instance_tag`, for those element types which have one
+    pub instance_tag: Option<u8>,
+    /// Position of the start of the element, in bits from the start of the payload
+    pub bit_offset: usize,
+    /// Length of the element in bits, including the `id_syn_ele` field, if it could be determined
+    pub bit_len: Option<usize>,
+    /// Position of the data carried by a DSE or FIL element (the `data_stream_byte`s, or the
+    /// `extension_payload`), in bits from the start of the payload
+    pub data: Option<Range<usize>>,
+    /// The type of a FIL element's `extension_payload`, if it has one
+    pub extension_type: Option<ExtensionType>,
+}
+
+/// Why the scan of a `raw_data_block` finished.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScanEnd {
+    /// An `END` element was found, so every element in the block has been identified
+    End,
+    /// A channel element was found, whose length can't be determined without decoding it, so
+    /// any further elements in the block are unknown
+    Opaque,
+    /// The payload ended part way through an element
+    Truncated,
+}
+
+/// The elements identified in a single `raw_data_block`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockScan {
+    pub elements: Vec<Element>,
+    pub end: ScanEnd,
+}
+
+/// The elements identified in the payload of a frame.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameScan {
+    /// One entry per `raw_data_block` that could be located.  Blocks after the first can only be
+    /// located if every element of the preceding block was identified.
+    pub blocks: Vec<BlockScan>,
+}
+impl FrameScan {
+    /// Counts of each element type found, across all scanned blocks
+    pub fn counts(&self) -> ElementCounts {
+        let mut counts = ElementCounts {
+            complete: self.blocks.iter().all(|b| b.end == ScanEnd::End),
+            ..ElementCounts::default()
+        };
+        for e in self.blocks.iter().flat_map(|b| b.elements.iter()) {
+            *match e.id {
+                ElementId::Sce => &mut counts.sce,
+                ElementId::Cpe => &mut counts.cpe,
+                ElementId::Cce => &mut counts.cce,
+                ElementId::Lfe => &mut counts.lfe,
+                ElementId::Dse => &mut counts.dse,
+                ElementId::Pce => &mut counts.pce,
+                ElementId::Fil => &mut counts.fil,
+                ElementId::End => &mut counts.end,
+            } += 1;
+        }
+        counts
+    }
+}
+
+/// Number of each type of element in a frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ElementCounts {
+    pub sce: usize,
+    pub cpe: usize,
+    pub cce: usize,
+    pub lfe: usize,
+    pub dse: usize,
+    pub pce: usize,
+    pub fil: usize,
+    pub end: usize,
+    /// `true` if every scanned block was identified up to its `END` element (otherwise the counts
+    /// are only a lower bound)
+    pub complete: bool,
+}
+
+/// Identifies the elements in each `raw_data_block` of the given frame payload.
+pub fn scan_frame(header: &AdtsHeader<'_>, payload: &[u8]) -> FrameScan {
+    let mut r = BitReader::new(payload);
+    let mut blocks = vec![];
+    for _ in 0..header.number_of_raw_data_blocks_in_frame() {
+        let block = scan_block(&mut r);
+        let end = block.end;
+        blocks.push(block);
+        if end != ScanEnd::End {
+            break;
+        }
+        r.byte_align();
+    }
+    FrameScan { blocks }
+}
+
+/// Identifies the elements of a single `raw_data_block` at the start of the given buffer.
+pub fn scan_raw_data_block(buf: &[u8]) -> BlockScan {
+    scan_block(&mut BitReader::new(buf))
+}
+
+fn scan_block(r: &mut BitReader<'_>) -> BlockScan {
+    let mut elements = vec![];
+    loop {
+        let bit_offset = r.position();
+        let id = match r.read(3) {
+            Some(id) => ElementId::from_bits(id),
+            None => {
+                return BlockScan {
+                    elements,
+                    end: ScanEnd::Truncated,
+                }
+            }
+        };
+        let instance_tag = if matches!(id, ElementId::Fil | ElementId::End) {
+            None
+        } else {
+            match r.read(4) {
+                Some(tag) => Some(tag as u8),
+                None => {
+                    return BlockScan {
+                        elements,
+                        end: ScanEnd::Truncated,
+                    }
+                }
+            }
+        };
+        let mut extension_type = None;
+        let skipped = match id {
+            ElementId::Dse => skip_dse(r).map(Some),
+            ElementId::Pce => skip_pce(r).map(|()| None),
+            ElementId::Fil => skip_fil(r, &mut extension_type).map(Some),
+            _ => Some(None),
+        };
+        let end = if skipped.is_none() {
+            Some(ScanEnd::Truncated)
+        } else if id == ElementId::End {
+            Some(ScanEnd::End)
+        } else if id.is_channel_element() {
+            Some(ScanEnd::Opaque)
+        } else {
+            None
+        };
+        let bit_len = match end {
+            Some(ScanEnd::Opaque) | Some(ScanEnd::Truncated) => None,
+            _ => Some(r.position() - bit_offset),
+        };
+        let data = skipped.flatten();
+        elements.push(Element {
+            id,
+            instance_tag,
+            bit_offset,
+            bit_len,
+            data,
+            extension_type,
+        });
+        if let Some(end) = end {
+            return BlockScan { elements, end };
+        }
+    }
+}
+
+fn skip_dse(r: &mut BitReader<'_>) -> Option<Range<usize>> {
+    let align = r.read_bool()?;
+    let mut count = r.read(8)? as usize;
+    if count == 255 {
+        count += r.read(8)? as usize;
+    }
+    if align {
+        r.byte_align();
+    }
+    let start = r.position();
+    r.skip(count * 8)?;
+    Some(start..r.position())
+}
+
+fn skip_fil(
+    r: &mut BitReader<'_>,
+    extension_type: &mut Option<ExtensionType>,
+) -> Option<Range<usize>> {
+    let mut count = r.read(4)? as usize;
+    if count == 15 {
+        // esc_count may be 0, giving a count of 14
+        count = count + r.read(8)? as usize - 1;
+    }
+    let start = r.position();
+    if count > 0 {
+        *extension_type = r.clone().read(4).map(ExtensionType::from_bits);
+    }
+    r.skip(count * 8)?;
+    Some(start..r.position())
+}
+
+fn skip_pce(r: &mut BitReader<'_>) -> Option<()> {
+    // object_type, sampling_frequency_index
+    r.skip(2 + 4)?;
+    let front = r.read(4)? as usize;
+    let side = r.read(4)? as usize;
+    let back = r.read(4)? as usize;
+    let lfe = r.read(2)? as usize;
+    let assoc_data = r.read(3)? as usize;
+    let valid_cc = r.read(4)? as usize;
+    if r.read_bool()? {
+        // mono_mixdown_element_number
+        r.skip(4)?;
+    }
+    if r.read_bool()? {
+        // stereo_mixdown_element_number
+        r.skip(4)?;
+    }
+    if r.read_bool()? {
+        // matrix_mixdown_idx, pseudo_surround_enable
+        r.skip(3)?;
+    }
+    // is_cpe + element_tag_select for front/side/back, tag only for lfe/assoc_data, and
+    // cc_e_is_ind_sw + valid_cc_element_tag_select for coupling channels
+    r.skip((front + side + back) * 5 + (lfe + assoc_data) * 4 + valid_cc * 5)?;
+    r.byte_align();
+    let comment_bytes = r.read(8)? as usize;
+    r.skip(comment_bytes * 8)
+}
+
+/// Looks for a FIL element carrying SBR data among the elements which end the payload of a frame
+/// with a single `raw_data_block`, returning it (with its position in bits from the start of the
+/// payload) if found.
+///
+/// The channel elements ahead of the SBR data can't be skipped without decoding them, so instead
+/// each possible starting position is tried, looking for a FIL element with an SBR
+/// `extension_type` from which the remaining elements of the block can be followed exactly to an
+/// `END` element at the end of the payload.  By chance, spectral data will sometimes look like
+/// that, so a single frame is weak evidence; [`HeAacDetector`](struct.HeAacDetector.html)
+/// weighs up several.
+pub fn find_sbr(header: &AdtsHeader<'_>, payload: &[u8]) -> Option<Element> {
+    if header.number_of_raw_data_blocks_in_frame() != 1 {
+        return None;
+    }
+    let len = payload.len() * 8;
+    // at least one channel element precedes the SBR data, which its id_syn_ele and
+    // element_instance_tag alone make 7 bits long
+    (7..len).find_map(|start| {
+        let mut r = BitReader::new(payload);
+        r.skip(start)?;
+        if r.clone().read(3)? != ElementId::Fil as u32 {
+            return None;
+        }
+        let scan = scan_block(&mut r);
+        let first = scan.elements.first()?;
+        let ends_payload = scan.end == ScanEnd::End && len - r.position() < 8;
+        if ends_payload && first.extension_type.is_some_and(ExtensionType::is_sbr) {
+            Some(first.clone())
+        } else {
+            None
+        }
+    })
+}
+
+/// Works out whether an ADTS stream is HE-AAC from the payloads of its first few frames, since
+/// its headers will say only that it is AAC-LC.
+///
+/// A stream is judged to use SBR if [`find_sbr()`](fn.find_sbr.html) finds SBR data in most of
+/// the frames examined.  Whether Parametric Stereo is also in use can't be told without decoding
+/// the SBR data, so for a mono stream with SBR that is reported as unknown.  Where the stream's
+/// `AudioSpecificConfig` is available, [`HeAacConfig::from_asc()`](../asc/struct.HeAacConfig.html#method.from_asc)
+/// gives a definite answer instead.
+///
+/// ```rust
+/// use adts_reader::elements::HeAacDetector;
+/// use adts_reader::AdtsParser;
+/// let mut parser = AdtsParser::new(HeAacDetector::new());
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// let he_aac = parser.consumer.result().unwrap();
+/// assert_eq!(he_aac.extension_sampling_frequency, None);
+/// assert_eq!(he_aac.audio_object_type(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeAacDetector {
+    config: Option<AdtsStreamConfig>,
+    /// frames examined since the configuration was found
+    frames: u32,
+    /// frames found to hold SBR data
+    sbr_frames: u32,
+    limit: u32,
+}
+impl Default for HeAacDetector {
+    fn default() -> Self {
+        HeAacDetector::new()
+    }
+}
+impl HeAacDetector {
+    pub fn new() -> HeAacDetector {
+        HeAacDetector {
+            config: None,
+            frames: 0,
+            sbr_frames: 0,
+            limit: 16,
+        }
+    }
+
+    /// Sets the number of frames to examine, after which further frames are ignored (default 16)
+    pub fn frames(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Examines the given frame.  A change of stream configuration starts the detection again.
+    /// Frames with more than one `raw_data_block` are not examined.
+    pub fn frame(&mut self, header: &AdtsHeader<'_>, payload: &[u8]) {
+        let config = header.stream_config();
+        if self.config != Some(config) {
+            *self = HeAacDetector::new().frames(self.limit);
+            self.config = Some(config);
+        }
+        if self.is_complete() || header.number_of_raw_data_blocks_in_frame() != 1 {
+            return;
+        }
+        self.frames += 1;
+        if find_sbr(header, payload).is_some() {
+            self.sbr_frames += 1;
+        }
+    }
+
+    /// `true` once enough frames have been examined
+    pub fn is_complete(&self) -> bool {
+        self.frames >= self.limit
+    }
+
+    /// The tools judged to be in use from the frames examined so far, or `None` if no frame has
+    /// been examined, or the stream's sampling frequency is reserved
+    pub fn result(&self) -> Option<HeAacConfig> {
+        let config = self.config.filter(|_| self.frames > 0)?;
+        let core_sampling_frequency = config.sampling_frequency.freq()?;
+        let sbr = self.sbr_frames * 2 > self.frames;
+        let ps_present = if sbr && config.channel_configuration == ChannelConfiguration::Mono {
+            None
+        } else {
+            Some(false)
+        };
+        Some(HeAacConfig {
+            core_audio_object_type: config.audio_object_type.into(),
+            core_sampling_frequency,
+            // with implicit signalling, SBR always runs at double the core rate
+            extension_sampling_frequency: Some(core_sampling_frequency * 2).filter(|_| sbr),
+            ps_present,
+        })
+    }
+}
+impl AdtsConsumer2 for HeAacDetector {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        self.frame(&frame.header, frame.payload);
+    }
+}
+
+/// Describes a frame whose channel elements are inconsistent with the `channel_configuration`
+/// header field, as found by [`check_channel_layout()`](fn.check_channel_layout.html).
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutMismatch {
+    pub channel_configuration: ChannelConfiguration,
+    /// Index of the `raw_data_block` within the frame
+    pub block: usize,
+    /// Index of the channel element within the block's sequence of channel elements
+    pub position: usize,
+    /// The element the channel configuration calls for at this position, or `None` if it calls
+    /// for no further elements
+    pub expected: Option<ElementId>,
+    /// The element actually present at this position, or `None` if the block had no further
+    /// channel elements
+    pub found: Option<ElementId>,
+}
+
+/// The sequence of channel elements in a raw_data_block, for the default channel configurations
+fn expected_layout(channels: ChannelConfiguration) -> Option<&'static [ElementId]> {
+    use ElementId::*;
+    match channels {
+        ChannelConfiguration::ObjectTypeSpecificConfig => None,
+        ChannelConfiguration::Mono => Some(&[Sce]),
+        ChannelConfiguration::Stereo => Some(&[Cpe]),
+        ChannelConfiguration::Three => Some(&[Sce, Cpe]),
+        ChannelConfiguration::Four => Some(&[Sce, Cpe, Sce]),
+        ChannelConfiguration::Five => Some(&[Sce, Cpe, Cpe]),
+        ChannelConfiguration::FiveOne => Some(&[Sce, Cpe, Cpe, Lfe]),
+        ChannelConfiguration::SevenOne => Some(&[Sce, Cpe, Cpe, Cpe, Lfe]),
+    }
+}
+
+/// Checks that the channel elements found by [`scan_frame()`](fn.scan_frame.html) match those
+/// required by the header's `channel_configuration`.
+///
+/// A header claiming stereo for a payload holding a single SCE, for example, is invisible at the
+/// framing layer but breaks many decoders.  Only the channel elements that the scan was able to
+/// identify are checked (and coupling channel elements are ignored); frames with
+/// `ObjectTypeSpecificConfig` always pass, since their layout is given by a PCE.
+///
+/// ```rust
+/// use adts_reader::{elements, AdtsHeader};
+/// // header signals stereo, but the payload starts with an SCE
+/// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x01];
+/// let header = AdtsHeader::from_bytes(&buf).unwrap();
+/// let scan = elements::scan_frame(&header, &buf[7..]);
+/// assert!(elements::check_channel_layout(&header, &scan).is_err());
+/// ```
+pub fn check_channel_layout(
+    header: &AdtsHeader<'_>,
+    scan: &FrameScan,
+) -> Result<(), LayoutMismatch> {
+    let channels = header.channel_configuration();
+    let expected = match expected_layout(channels) {
+        Some(layout) => layout,
+        None => return Ok(()),
+    };
+    for (block_index, block) in scan.blocks.iter().enumerate() {
+        let mut found = block
+            .elements
+            .iter()
+            .map(|e| e.id)
+            .filter(|id| id.is_channel_element() && *id != ElementId::Cce);
+        for position in 0.. {
+            let mismatch = match (expected.get(position), found.next()) {
+                (None, None) => break,
+                (Some(_), None) if block.end != ScanEnd::End => break,
+                (Some(e), Some(f)) if e == &f => continue,
+                (e, f) => LayoutMismatch {
+                    channel_configuration: channels,
+                    block: block_index,
+                    position,
+                    expected: e.copied(),
+                    found: f,
+                },
+            };
+            return Err(mismatch);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::tests::make_test_data;
+    use bitstream_io::BitWrite;
+
+    #[test]
+    fn fill_and_end() {
+        let payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Fil as u8)?;
+            w.write(4, 2)?; // count
+            w.write(16, 0xa5a5)?;
+            w.write(3, ElementId::End as u8)?;
+            w.byte_align()
+        });
+        let scan = scan_raw_data_block(&payload);
+        assert_eq!(scan.end, ScanEnd::End);
+        assert_eq!(
+            scan.elements,
+            [
+                Element {
+                    id: ElementId::Fil,
+                    instance_tag: None,
+                    bit_offset: 0,
+                    bit_len: Some(3 + 4 + 16),
+                    data: Some(7..23),
+                    extension_type: Some(ExtensionType::Reserved(0xa)),
+                },
+                Element {
+                    id: ElementId::End,
+                    instance_tag: None,
+                    bit_offset: 23,
+                    bit_len: Some(3),
+                    data: None,
+                    extension_type: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_escape_count() {
+        let payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Fil as u8)?;
+            w.write(4, 15)?; // count
+            w.write(8, 0)?; // esc_count
+            for _ in 0..14 {
+                w.write(8, 0)?;
+            }
+            w.write(3, ElementId::End as u8)?;
+            w.byte_align()
+        });
+        let scan = scan_raw_data_block(&payload);
+        assert_eq!(scan.end, ScanEnd::End);
+        assert_eq!(scan.elements[0].data, Some(15..15 + 14 * 8));
+        // truncated, rather than panicking
+        let scan = scan_raw_data_block(&[0xde, 0x00, 0x00]);
+        assert_eq!(scan.elements.len(), 1);
+    }
+
+    #[test]
+    fn dse_then_channel_element() {
+        let payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Dse as u8)?;
+            w.write(4, 3)?; // element_instance_tag
+            w.write(1, 1)?; // data_byte_align_flag
+            w.write(8, 1)?; // count
+            w.byte_align()?;
+            w.write(8, 0xff)?;
+            w.write(3, ElementId::Sce as u8)?;
+            w.write(4, 0)?; // element_instance_tag
+            w.write(8, 0x12)?; // (start of ICS)
+            w.byte_align()
+        });
+        let scan = scan_raw_data_block(&payload);
+        assert_eq!(scan.end, ScanEnd::Opaque);
+        assert_eq!(scan.elements.len(), 2);
+        assert_eq!(scan.elements[0].bit_len, Some(16 + 8));
+        assert_eq!(scan.elements[0].data, Some(16..24));
+        assert_eq!(payload[2], 0xff);
+        assert_eq!(scan.elements[1].id, ElementId::Sce);
+        assert_eq!(scan.elements[1].instance_tag, Some(0));
+        assert_eq!(scan.elements[1].bit_offset, 24);
+        assert_eq!(scan.elements[1].bit_len, None);
+    }
+
+    #[test]
+    fn pce() {
+        let payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Pce as u8)?;
+            w.write(4, 0)?; // element_instance_tag
+            w.write(2, 1)?; // object_type
+            w.write(4, 3)?; // sampling_frequency_index
+            w.write(4, 1)?; // num_front_channel_elements
+            w.write(4, 0)?; // num_side_channel_elements
+            w.write(4, 0)?; // num_back_channel_elements
+            w.write(2, 1)?; // num_lfe_channel_elements
+            w.write(3, 0)?; // num_assoc_data_elements
+            w.write(4, 0)?; // num_valid_cc_elements
+            w.write(1, 0)?; // mono_mixdown_present
+            w.write(1, 0)?; // stereo_mixdown_present
+            w.write(1, 0)?; // matrix_mixdown_idx_present
+            w.write(5, 0b10000)?; // front element: is_cpe, tag
+            w.write(4, 0)?; // lfe element tag
+            w.byte_align()?;
+            w.write(8, 2)?; // comment_field_bytes
+            w.write(16, 0x6869)?;
+            w.write(3, ElementId::End as u8)?;
+            w.byte_align()
+        });
+        let scan = scan_raw_data_block(&payload);
+        assert_eq!(scan.end, ScanEnd::End);
+        assert_eq!(scan.elements[0].id, ElementId::Pce);
+        assert_eq!(scan.elements[0].bit_len, Some(48 + 8 + 16));
+        assert_eq!(scan.elements[1].id, ElementId::End);
+    }
+
+    #[test]
+    fn layout() {
+        use crate::*;
+        let check = |channels, payload: &[u8]| {
+            let config = AdtsStreamConfig {
+                channel_configuration: channels,
+                ..StreamBuilder::DEFAULT_CONFIG
+            };
+            let buf = StreamBuilder::new().config(config).frame(payload).build();
+            let header = AdtsHeader::from_bytes(&buf).unwrap();
+            check_channel_layout(&header, &scan_frame(&header, &buf[7..]))
+        };
+        let sce = (ElementId::Sce as u8) << 5;
+        let cpe = (ElementId::Cpe as u8) << 5;
+        let end = (ElementId::End as u8) << 5;
+        assert_eq!(check(ChannelConfiguration::Stereo, &[cpe, 0]), Ok(()));
+        assert_eq!(check(ChannelConfiguration::FiveOne, &[sce, 0]), Ok(()));
+        assert_eq!(
+            check(ChannelConfiguration::Stereo, &[sce, 0]),
+            Err(LayoutMismatch {
+                channel_configuration: ChannelConfiguration::Stereo,
+                block: 0,
+                position: 0,
+                expected: Some(ElementId::Cpe),
+                found: Some(ElementId::Sce),
+            })
+        );
+        // a block complete without any channel elements
+        assert_eq!(
+            check(ChannelConfiguration::Mono, &[end]).unwrap_err().found,
+            None
+        );
+        assert_eq!(
+            check(ChannelConfiguration::ObjectTypeSpecificConfig, &[sce, 0]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn sbr_fill() {
+        let payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Fil as u8)?;
+            w.write(4, 1)?; // count
+            w.write(4, 0b1101)?; // extension_type: EXT_SBR_DATA
+            w.write(4, 0)?;
+            w.write(3, ElementId::Fil as u8)?;
+            w.write(4, 0)?; // count
+            w.write(3, ElementId::End as u8)?;
+            w.byte_align()
+        });
+        let scan = scan_raw_data_block(&payload);
+        assert_eq!(scan.end, ScanEnd::End);
+        let sbr = scan.elements[0].extension_type.unwrap();
+        assert_eq!(sbr, ExtensionType::SbrData);
+        assert!(sbr.is_sbr());
+        assert_eq!(scan.elements[1].data, Some(22..22));
+        assert_eq!(scan.elements[1].extension_type, None);
+    }
+
+    #[test]
+    fn he_aac() {
+        use crate::*;
+        let sbr_payload = make_test_data(|mut w| {
+            w.write(3, ElementId::Cpe as u8)?;
+            w.write(4, 0)?; // element_instance_tag
+            w.write(21, 0x1a2b3c)?; // (stand-in for the ICS data)
+            w.write(3, ElementId::Fil as u8)?;
+            w.write(4, 3)?; // count
+            w.write(4, 0b1101)?; // extension_type: EXT_SBR_DATA
+            w.write(20, 0xabcde)?;
+            w.write(3, ElementId::End as u8)?;
+            w.byte_align()
+        });
+        let config = AdtsStreamConfig {
+            sampling_frequency: SamplingFrequency::Freq24000,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let frame = StreamBuilder::new()
+            .config(config)
+            .frame(&sbr_payload)
+            .build();
+        let header = AdtsHeader::from_bytes(&frame).unwrap();
+        let sbr = find_sbr(&header, &frame[7..]).unwrap();
+        assert_eq!(sbr.bit_offset, 28);
+        assert_eq!(sbr.extension_type, Some(ExtensionType::SbrData));
+        let plain = StreamBuilder::new()
+            .config(config)
+            .frame(&[0x21, 0x10, 0x05])
+            .build();
+        assert_eq!(find_sbr(&header, &plain[7..]), None);
+
+        let detect = |frames: &[&[u8]]| {
+            let mut parser = AdtsParser::new(HeAacDetector::new().frames(3));
+            for frame in frames {
+                parser.push(frame);
+            }
+            parser.consumer.result().unwrap()
+        };
+        let he_aac = detect(&[&frame, &plain, &frame, &frame]);
+        assert_eq!(he_aac.extension_sampling_frequency, Some(48000));
+        assert_eq!(he_aac.ps_present, Some(false));
+        assert_eq!(he_aac.audio_object_type(), 5);
+        assert_eq!(he_aac.output_sampling_frequency(), 48000);
+        // the fourth frame is beyond the limit
+        let lc = detect(&[&frame, &plain, &plain, &frame]);
+        assert_eq!(lc.extension_sampling_frequency, None);
+        assert_eq!(lc.output_sampling_frequency(), 24000);
+
+        let mono = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Mono,
+            ..config
+        };
+        let frame = StreamBuilder::new()
+            .config(mono)
+            .frame(&sbr_payload)
+            .build();
+        let he_aac = detect(&[&frame]);
+        assert_eq!(he_aac.ps_present, None);
+        assert_eq!(he_aac.audio_object_type(), 5);
+    }
+
+    #[test]
+    fn truncated() {
+        let scan = scan_raw_data_block(&[(ElementId::Fil as u8) << 5 | 0b11110]);
+        assert_eq!(scan.end, ScanEnd::Truncated);
+    }
+}
@@ -0,0 +1,79 @@
+//! Handling of streams whose frame payloads are encrypted while the ADTS headers stay in the
+//! clear, as with HLS SAMPLE-AES.
+//!
+//! Nothing in an ADTS header signals that the payload is encrypted, so the caller must supply
+//! that knowledge (for example from the `METHOD` attribute of an HLS `EXT-X-KEY` tag).  Given it,
+//! checks which depend on the payload content, such as CRC verification and channel layout
+//! checks, are skipped rather than reporting a failure for every frame.
+
+use std::ops::Range;
+
+/// Size of the clear leader at the start of each SAMPLE-AES payload, and of each encrypted block
+const SAMPLE_AES_BLOCK: usize = 16;
+
+/// How the payload of each frame is protected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PayloadEncryption {
+    /// Payloads are not encrypted (the default)
+    #[default]
+    Clear,
+    /// HLS SAMPLE-AES: the first 16 bytes of each payload are clear, followed by as many whole
+    /// 16 byte blocks as fit, encrypted, with any remaining bytes at the end left clear
+    SampleAes,
+}
+impl PayloadEncryption {
+    /// `true` unless payloads are in the clear
+    pub fn is_encrypted(self) -> bool {
+        self != PayloadEncryption::Clear
+    }
+
+    /// The part of a payload of the given length which is encrypted (possibly empty, for short
+    /// payloads).
+    ///
+    /// ```rust
+    /// # use adts_reader::encryption::PayloadEncryption;
+    /// assert_eq!(PayloadEncryption::SampleAes.encrypted_range(100), 16..96);
+    /// assert_eq!(PayloadEncryption::SampleAes.encrypted_range(20), 16..16);
+    /// assert_eq!(PayloadEncryption::Clear.encrypted_range(100), 0..0);
+    /// ```
+    pub fn encrypted_range(self, payload_len: usize) -> Range<usize> {
+        match self {
+            PayloadEncryption::Clear => 0..0,
+            PayloadEncryption::SampleAes => {
+                let blocks = payload_len.saturating_sub(SAMPLE_AES_BLOCK) / SAMPLE_AES_BLOCK;
+                let start = SAMPLE_AES_BLOCK.min(payload_len);
+                start..start + blocks * SAMPLE_AES_BLOCK
+            }
+        }
+    }
+
+    /// The leading part of the given payload which can be read without decryption; the whole
+    /// payload if it is not encrypted.
+    pub fn clear_prefix(self, payload: &[u8]) -> &[u8] {
+        let range = self.encrypted_range(payload.len());
+        if range.is_empty() {
+            payload
+        } else {
+            &payload[..range.start]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_aes_layout() {
+        let enc = PayloadEncryption::SampleAes;
+        assert_eq!(enc.encrypted_range(0), 0..0);
+        assert_eq!(enc.encrypted_range(16), 16..16);
+        assert_eq!(enc.encrypted_range(32), 16..32);
+        assert_eq!(enc.encrypted_range(47), 16..32);
+        assert_eq!(enc.encrypted_range(48), 16..48);
+        assert_eq!(enc.clear_prefix(&[1; 40]), &[1; 16]);
+        assert_eq!(enc.clear_prefix(&[1; 20]), &[1; 20]);
+        assert_eq!(PayloadEncryption::Clear.clear_prefix(&[1; 40]), &[1; 40]);
+    }
+}
@@ -0,0 +1,285 @@
+//! Production of the `ES_Descriptor` (ISO/IEC 14496-1 7.2.6.5) carried in the `esds` box of an
+//! MP4 audio sample entry, for remuxing an ADTS stream into MP4.
+//!
+//! Besides the [`AudioSpecificConfig`](../asc/struct.AudioSpecificConfig.html), the descriptor
+//! gives the decoder buffer size and the maximum and average bitrates of the stream, which need a
+//! pass over all of its frames.  [`EsdsAnalyzer`](struct.EsdsAnalyzer.html) takes that pass as
+//! the consumer of an `AdtsParser`.
+//!
+//! ```rust
+//! use adts_reader::esds::EsdsAnalyzer;
+//! let stream = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let descriptor = EsdsAnalyzer::of_stream(&stream).unwrap();
+//! assert_eq!(descriptor.buffer_size_db, 1);
+//! // the tag of the ES_Descriptor, and its length
+//! assert_eq!(descriptor.to_bytes().unwrap()[..2], [0x03, 0x19]);
+//! ```
+
+use crate::asc::{AscError, AudioSpecificConfig};
+use crate::{AdtsConsumer2, AdtsParser, AdtsStreamConfig, FrameContext, FrameLength};
+use std::collections::VecDeque;
+
+/// The `objectTypeIndication` for MPEG-4 Audio
+pub const OBJECT_TYPE_MPEG4_AUDIO: u8 = 0x40;
+/// The `streamType` for audio streams
+const STREAM_TYPE_AUDIO: u8 = 0x05;
+
+const ES_DESCRIPTOR_TAG: u8 = 0x03;
+const DECODER_CONFIG_DESCRIPTOR_TAG: u8 = 0x04;
+const DECODER_SPECIFIC_INFO_TAG: u8 = 0x05;
+const SL_CONFIG_DESCRIPTOR_TAG: u8 = 0x06;
+
+/// Error indicating that an `ES_Descriptor` can't describe the stream analysed.
+#[derive(Debug, PartialEq)]
+pub enum EsdsError {
+    /// No frames were found
+    NoFrames,
+    /// The stream configuration changed part way through, which a single sample entry can't
+    /// describe
+    ConfigChanged,
+    /// The stream has no `AudioSpecificConfig` equivalent
+    Asc(AscError),
+}
+impl From<AscError> for EsdsError {
+    fn from(e: AscError) -> Self {
+        EsdsError::Asc(e)
+    }
+}
+
+/// The values of an `ES_Descriptor` with a `DecoderConfigDescriptor` for MPEG-4 Audio.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EsDescriptor {
+    /// The `ES_ID`, which is 0 within MP4 files, where the track gives the stream's identity
+    pub es_id: u16,
+    /// Size of the decoder buffer in bytes, being the largest access unit
+    pub buffer_size_db: u32,
+    /// The highest bitrate over any one second of the stream, in bits per second
+    pub max_bitrate: u32,
+    /// The average bitrate of the stream, in bits per second
+    pub avg_bitrate: u32,
+    /// Carried as the `DecoderSpecificInfo`
+    pub asc: AudioSpecificConfig,
+}
+impl EsDescriptor {
+    /// The bytes of the `ES_Descriptor`, which form the body of an `esds` box after its version
+    /// and flags.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AscError> {
+        let mut decoder_config = vec![OBJECT_TYPE_MPEG4_AUDIO, STREAM_TYPE_AUDIO << 2 | 1];
+        decoder_config.extend_from_slice(&self.buffer_size_db.min(0xff_ffff).to_be_bytes()[1..]);
+        decoder_config.extend_from_slice(&self.max_bitrate.to_be_bytes());
+        decoder_config.extend_from_slice(&self.avg_bitrate.to_be_bytes());
+        write_descriptor(
+            &mut decoder_config,
+            DECODER_SPECIFIC_INFO_TAG,
+            &self.asc.to_bytes()?,
+        );
+
+        let mut es = self.es_id.to_be_bytes().to_vec();
+        // no streamDependenceFlag, URL_Flag or OCRstreamFlag, and a streamPriority of 0
+        es.push(0);
+        write_descriptor(&mut es, DECODER_CONFIG_DESCRIPTOR_TAG, &decoder_config);
+        // the predefined SLConfigDescriptor required within MP4 files
+        write_descriptor(&mut es, SL_CONFIG_DESCRIPTOR_TAG, &[0x02]);
+
+        let mut result = vec![];
+        write_descriptor(&mut result, ES_DESCRIPTOR_TAG, &es);
+        Ok(result)
+    }
+
+    /// The complete `esds` box, header included.
+    pub fn to_box(&self) -> Result<Vec<u8>, AscError> {
+        let es = self.to_bytes()?;
+        let mut result = Vec::with_capacity(12 + es.len());
+        result.extend_from_slice(&(12 + es.len() as u32).to_be_bytes());
+        result.extend_from_slice(b"esds");
+        // version and flags
+        result.extend_from_slice(&[0; 4]);
+        result.extend_from_slice(&es);
+        Ok(result)
+    }
+}
+
+/// Appends a descriptor with the given tag and body, preceded by its length in the expandable
+/// form of ISO/IEC 14496-1 8.3.3, taking as few bytes as the length needs
+fn write_descriptor(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    out.push(tag);
+    let len = body.len();
+    let mut shift = 0;
+    while len >> (shift + 7) != 0 {
+        shift += 7;
+    }
+    while shift > 0 {
+        out.push(0x80 | (len >> shift) as u8 & 0x7f);
+        shift -= 7;
+    }
+    out.push(len as u8 & 0x7f);
+    out.extend_from_slice(body);
+}
+
+/// Measures the frames of a stream to give the [`EsDescriptor`](struct.EsDescriptor.html) that
+/// describes it.
+///
+/// The bitrates count only the frame payloads, which are the samples of the MP4 track, and not
+/// the ADTS headers that remuxing removes.
+#[derive(Debug, Default)]
+pub struct EsdsAnalyzer {
+    config: Option<AdtsStreamConfig>,
+    config_changed: bool,
+    frame_length: FrameLength,
+    sbr: bool,
+    bytes: u64,
+    samples: u64,
+    max_payload_len: usize,
+    /// The start time and length of the most recent frames covering one second of media
+    window: VecDeque<(u64, usize)>,
+    window_bytes: u64,
+    max_window_bytes: u64,
+}
+impl EsdsAnalyzer {
+    pub fn new() -> EsdsAnalyzer {
+        EsdsAnalyzer::default()
+    }
+
+    /// Sets the number of samples per `raw_data_block` (by default, 1024), which is also
+    /// signalled by the `AudioSpecificConfig`
+    pub fn with_frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Signals SBR in the `AudioSpecificConfig`, as for HE-AAC streams; see
+    /// [`AudioSpecificConfig::from_adts_config()`](../asc/struct.AudioSpecificConfig.html#method.from_adts_config)
+    pub fn with_sbr(mut self, sbr: bool) -> Self {
+        self.sbr = sbr;
+        self
+    }
+
+    /// Analyses all the frames in the given buffer
+    pub fn of_stream(buf: &[u8]) -> Result<EsDescriptor, EsdsError> {
+        let mut parser = AdtsParser::new(EsdsAnalyzer::new());
+        parser.push(buf);
+        parser.consumer.es_descriptor()
+    }
+
+    /// The descriptor of the frames seen so far
+    pub fn es_descriptor(&self) -> Result<EsDescriptor, EsdsError> {
+        let config = self.config.as_ref().ok_or(EsdsError::NoFrames)?;
+        if self.config_changed {
+            return Err(EsdsError::ConfigChanged);
+        }
+        let mut asc = AudioSpecificConfig::from_adts_config(config, self.sbr)?;
+        asc.frame_length = self.frame_length;
+        if self.samples == 0 {
+            return Err(EsdsError::NoFrames);
+        }
+        let rate = u64::from(asc.sampling_frequency);
+        let avg_bitrate = self.bytes * 8 * rate / self.samples;
+        // streams shorter than a second have no complete window, so just give their average
+        let max_bitrate = (self.max_window_bytes * 8).max(avg_bitrate);
+        Ok(EsDescriptor {
+            es_id: 0,
+            buffer_size_db: self.max_payload_len as u32,
+            max_bitrate: max_bitrate.min(u64::from(u32::MAX)) as u32,
+            avg_bitrate: avg_bitrate.min(u64::from(u32::MAX)) as u32,
+            asc,
+        })
+    }
+}
+impl AdtsConsumer2 for EsdsAnalyzer {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        let Some(rate) = frame.header.sampling_frequency().freq() else {
+            return;
+        };
+        let config = frame.header.stream_config();
+        match self.config {
+            None => self.config = Some(config),
+            Some(ref c) if *c != config => self.config_changed = true,
+            Some(_) => (),
+        }
+        let len = frame.payload.len();
+        let start = self.samples;
+        self.samples += u64::from(frame.header.number_of_raw_data_blocks_in_frame())
+            * self.frame_length.samples();
+        self.bytes += len as u64;
+        self.max_payload_len = self.max_payload_len.max(len);
+
+        self.window.push_back((start, len));
+        self.window_bytes += len as u64;
+        while let Some(&(front_start, front_len)) = self.window.front() {
+            if front_start + u64::from(rate) >= self.samples {
+                break;
+            }
+            self.window.pop_front();
+            self.window_bytes -= front_len as u64;
+        }
+        if self.samples >= u64::from(rate) {
+            self.max_window_bytes = self.max_window_bytes.max(self.window_bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+
+    #[test]
+    fn descriptor() {
+        let data = StreamBuilder::new()
+            .payload_len(100)
+            .frames(50, StreamBuilder::DEFAULT_CONFIG)
+            .payload_len(300)
+            .frames(1, StreamBuilder::DEFAULT_CONFIG)
+            .payload_len(100)
+            .frames(49, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let descriptor = EsdsAnalyzer::of_stream(&data).unwrap();
+        assert_eq!(descriptor.buffer_size_db, 300);
+        // 10200 bytes over 102400 samples at 48kHz
+        assert_eq!(descriptor.avg_bitrate, 38250);
+        // any second wholly holds 46 frames, one of which may be the larger one
+        assert_eq!(descriptor.max_bitrate, (45 * 100 + 300) * 8);
+
+        assert_eq!(
+            descriptor.to_bytes().unwrap(),
+            [
+                0x03, 0x19, 0x00, 0x00, 0x00, // ES_Descriptor
+                0x04, 0x11, 0x40, 0x15, // DecoderConfigDescriptor
+                0x00, 0x01, 0x2c, // bufferSizeDB
+                0x00, 0x00, 0x96, 0x00, // maxBitrate
+                0x00, 0x00, 0x95, 0x6a, // avgBitrate
+                0x05, 0x02, 0x11, 0x90, // DecoderSpecificInfo
+                0x06, 0x01, 0x02, // SLConfigDescriptor
+            ]
+        );
+        let esds = descriptor.to_box().unwrap();
+        assert_eq!(
+            esds[..12],
+            [0, 0, 0, 39, b'e', b's', b'd', b's', 0, 0, 0, 0]
+        );
+        assert_eq!(esds.len(), 39);
+    }
+
+    #[test]
+    fn descriptor_length() {
+        let mut out = vec![];
+        write_descriptor(&mut out, 0x05, &[0; 200]);
+        assert_eq!(out[..3], [0x05, 0x81, 0x48]);
+        assert_eq!(out.len(), 203);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(EsdsAnalyzer::of_stream(&[]), Err(EsdsError::NoFrames));
+        let mut config = StreamBuilder::DEFAULT_CONFIG;
+        config.channel_configuration = crate::ChannelConfiguration::Mono;
+        let data = StreamBuilder::new()
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .frames(2, config)
+            .build();
+        assert_eq!(
+            EsdsAnalyzer::of_stream(&data),
+            Err(EsdsError::ConfigChanged)
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! The single description of where each ADTS header field lives, shared by the code that reads,
+//! writes and edits headers so that their idea of the bit layout can't diverge.
+//!
+//! Bit offsets count from the most significant bit of the first header byte, matching the order
+//! in which ISO/IEC 14496-3 lists the fields.  All multi-byte fields are big-endian.
+
+/// The position and size of one header field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub width: usize,
+}
+impl Field {
+    const fn new(name: &'static str, offset: usize, width: usize) -> Field {
+        Field {
+            name,
+            offset,
+            width,
+        }
+    }
+
+    /// The largest value the field can hold
+    pub const fn max(&self) -> u32 {
+        (1 << self.width) - 1
+    }
+
+    /// Index one past the last byte containing bits of this field
+    const fn end_byte(&self) -> usize {
+        (self.offset + self.width).div_ceil(8)
+    }
+
+    /// Index of the first byte containing bits of this field
+    const fn start_byte(&self) -> usize {
+        self.offset / 8
+    }
+
+    /// Distance of the field's least significant bit from the end of the bytes spanned
+    const fn shift(&self) -> usize {
+        self.end_byte() * 8 - (self.offset + self.width)
+    }
+
+    /// Extracts the field's value from the given header bytes, which must be at least
+    /// `end_byte()` long.
+    #[inline]
+    pub fn get(&self, buf: &[u8]) -> u32 {
+        let span = buf[self.start_byte()..self.end_byte()]
+            .iter()
+            .fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+        (span >> self.shift()) as u32 & self.max()
+    }
+
+    /// Extracts the field's value from header bytes already gathered by [`load()`](fn.load.html),
+    /// for reading many fields with a single pass over the buffer.  The field must lie within the
+    /// first 7 bytes.
+    #[inline]
+    pub fn extract(&self, bits: u64) -> u32 {
+        (bits >> (64 - self.offset - self.width)) as u32 & self.max()
+    }
+
+    /// Stores `value` into the field within the given header bytes, leaving the bits of all other
+    /// fields untouched.  Bits of `value` beyond the field's width are ignored; callers should
+    /// check against [`max()`](#method.max) first.
+    #[inline]
+    pub fn set(&self, buf: &mut [u8], value: u32) {
+        let bytes = &mut buf[self.start_byte()..self.end_byte()];
+        let mask = u64::from(self.max()) << self.shift();
+        let span = bytes.iter().fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+        let mut span = span & !mask | (u64::from(value) << self.shift()) & mask;
+        for b in bytes.iter_mut().rev() {
+            *b = span as u8;
+            span >>= 8;
+        }
+    }
+}
+
+/// Gathers the first 7 bytes of the given header, which must be at least that long, into the
+/// most significant bytes of a `u64` for [`Field::extract()`](struct.Field.html#method.extract)
+#[inline]
+pub(crate) fn load(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes[..7].copy_from_slice(&buf[..7]);
+    u64::from_be_bytes(bytes)
+}
+
+pub(crate) const SYNCWORD: Field = Field::new("syncword", 0, 12);
+pub(crate) const ID: Field = Field::new("id", 12, 1);
+pub(crate) const LAYER: Field = Field::new("layer", 13, 2);
+pub(crate) const PROTECTION_ABSENT: Field = Field::new("protection_absent", 15, 1);
+pub(crate) const PROFILE: Field = Field::new("profile", 16, 2);
+pub(crate) const SAMPLING_FREQUENCY_INDEX: Field = Field::new("sampling_frequency_index", 18, 4);
+pub(crate) const PRIVATE_BIT: Field = Field::new("private_bit", 22, 1);
+pub(crate) const CHANNEL_CONFIGURATION: Field = Field::new("channel_configuration", 23, 3);
+pub(crate) const ORIGINAL_COPY: Field = Field::new("original_copy", 26, 1);
+pub(crate) const HOME: Field = Field::new("home", 27, 1);
+pub(crate) const COPYRIGHT_IDENTIFICATION_BIT: Field =
+    Field::new("copyright_identification_bit", 28, 1);
+pub(crate) const COPYRIGHT_IDENTIFICATION_START: Field =
+    Field::new("copyright_identification_start", 29, 1);
+pub(crate) const FRAME_LENGTH: Field = Field::new("frame_length", 30, 13);
+pub(crate) const ADTS_BUFFER_FULLNESS: Field = Field::new("adts_buffer_fullness", 43, 11);
+pub(crate) const NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME: Field =
+    Field::new("number_of_raw_data_blocks_in_frame", 54, 2);
+/// Only present when `protection_absent` is 0
+pub(crate) const CRC_CHECK: Field = Field::new("crc_check", 56, 16);
+
+/// Every field of the 7-byte fixed and variable header, in bitstream order.
+pub(crate) const HEADER_FIELDS: [Field; 15] = [
+    SYNCWORD,
+    ID,
+    LAYER,
+    PROTECTION_ABSENT,
+    PROFILE,
+    SAMPLING_FREQUENCY_INDEX,
+    PRIVATE_BIT,
+    CHANNEL_CONFIGURATION,
+    ORIGINAL_COPY,
+    HOME,
+    COPYRIGHT_IDENTIFICATION_BIT,
+    COPYRIGHT_IDENTIFICATION_START,
+    FRAME_LENGTH,
+    ADTS_BUFFER_FULLNESS,
+    NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_is_contiguous() {
+        let mut offset = 0;
+        for field in HEADER_FIELDS.iter().chain(Some(&CRC_CHECK)) {
+            assert_eq!(field.offset, offset, "{}", field.name);
+            offset += field.width;
+        }
+        assert_eq!(offset, 9 * 8);
+    }
+
+    #[test]
+    fn set_then_get() {
+        // every value of every field, written over both all-zero and all-one backgrounds, must
+        // read back unchanged and leave all the other fields' bits alone
+        for field in HEADER_FIELDS.iter().chain(Some(&CRC_CHECK)) {
+            for background in [0x00, 0xff] {
+                for value in 0..=field.max() {
+                    let mut buf = [background; 9];
+                    field.set(&mut buf, value);
+                    assert_eq!(field.get(&buf), value, "{}", field.name);
+                    for other in HEADER_FIELDS.iter().chain(Some(&CRC_CHECK)) {
+                        if other != field {
+                            let expected = if background == 0 { 0 } else { other.max() };
+                            assert_eq!(
+                                other.get(&buf),
+                                expected,
+                                "{} / {}",
+                                field.name,
+                                other.name
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extract_matches_get() {
+        let mut state = 0x1234_5678u32;
+        for _ in 0..1000 {
+            let buf: Vec<u8> = (0..7)
+                .map(|_| {
+                    // xorshift32
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect();
+            let bits = load(&buf);
+            for field in &HEADER_FIELDS {
+                assert_eq!(field.extract(bits), field.get(&buf), "{}", field.name);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_bits_ignored() {
+        let mut buf = [0; 7];
+        HOME.set(&mut buf, 0b10);
+        assert_eq!(buf, [0; 7]);
+    }
+}
@@ -0,0 +1,336 @@
+//! Reading frames from a file which is still being written, in the manner of `tail -f`.
+
+use crate::clock::{Clock, SystemClock};
+use crate::sync::find_possible_sync;
+use crate::{id3, AdtsHeader, AdtsHeaderError};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+
+const READ_SIZE: usize = 64 * 1024;
+
+/// Yields complete ADTS frames from an input that may continue to grow, such as a file being
+/// written by a live recorder.
+///
+/// When the end of the input is reached, the reader waits (polling at the configured interval)
+/// for more data to be appended.  A frame only partially written so far is held back until the
+/// rest of it arrives, so callers only ever see whole frames.  Bytes that aren't part of a frame
+/// (e.g. an ID3 tag at the start of the file) are skipped, and counted by
+/// [`skipped_bytes()`](#method.skipped_bytes).
+///
+/// The iterator ends (returns `None`) when the idle timeout expires, but since the input may grow
+/// again afterwards, iteration can usefully be resumed; for that reason this type does not
+/// implement `FusedIterator`.
+///
+/// ```rust,no_run
+/// use adts_reader::follow::FollowingAdtsReader;
+/// use std::time::Duration;
+/// let reader = FollowingAdtsReader::open("recording.aac")?
+///     .idle_timeout(Some(Duration::from_secs(10)));
+/// for frame in reader {
+///     let frame = frame?;
+///     println!("frame of {} bytes", frame.len());
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct FollowingAdtsReader<R: Read, K: Clock = SystemClock> {
+    input: R,
+    clock: K,
+    buf: FrameBuffer,
+    poll_interval: Duration,
+    idle_timeout: Option<Duration>,
+    skipped_bytes: u64,
+}
+impl FollowingAdtsReader<fs::File> {
+    /// Opens the file at the given path, to be read from the start
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FollowingAdtsReader<fs::File>> {
+        Ok(FollowingAdtsReader::new(fs::File::open(path)?))
+    }
+}
+impl<R: Read> FollowingAdtsReader<R> {
+    pub fn new(input: R) -> FollowingAdtsReader<R> {
+        FollowingAdtsReader {
+            input,
+            clock: SystemClock,
+            buf: FrameBuffer::default(),
+            poll_interval: Duration::from_millis(100),
+            idle_timeout: None,
+            skipped_bytes: 0,
+        }
+    }
+}
+impl<R: Read, K: Clock> FollowingAdtsReader<R, K> {
+    /// Use the given clock for measuring the idle timeout, and for waiting between polls
+    pub fn clock<K2: Clock>(self, clock: K2) -> FollowingAdtsReader<R, K2> {
+        FollowingAdtsReader {
+            input: self.input,
+            clock,
+            buf: self.buf,
+            poll_interval: self.poll_interval,
+            idle_timeout: self.idle_timeout,
+            skipped_bytes: self.skipped_bytes,
+        }
+    }
+
+    /// How long to wait before checking again for new data, once the end of the input has been
+    /// reached (default 100ms)
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// How long to wait for new data before giving up and ending iteration, or `None` to wait
+    /// forever (default `None`)
+    pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Number of bytes read which were not part of any frame
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Number of bytes read but not yet returned as part of a frame, such as the start of a
+    /// frame which is still being written
+    pub fn pending_bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the next frame if it has been completely written, without waiting.
+    pub fn try_next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Ok(Some(frame));
+            }
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Returns the next frame, waiting for it to be written if necessary.  Returns `None` if no
+    /// new data arrives within the idle timeout.
+    pub fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut last_data = self.clock.now();
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Ok(Some(frame));
+            }
+            if self.fill()? {
+                last_data = self.clock.now();
+                continue;
+            }
+            if let Some(timeout) = self.idle_timeout {
+                if self.clock.now().saturating_duration_since(last_data) >= timeout {
+                    return Ok(None);
+                }
+            }
+            self.clock.sleep(self.poll_interval);
+        }
+    }
+
+    /// Reads whatever data is currently available, returning `false` at the end of the input
+    fn fill(&mut self) -> io::Result<bool> {
+        self.buf.read_from(&mut self.input).map(|n| n > 0)
+    }
+
+    fn take_frame(&mut self) -> Option<Vec<u8>> {
+        self.buf.take_frame(&mut self.skipped_bytes)
+    }
+}
+impl<R: Read, K: Clock> Iterator for FollowingAdtsReader<R, K> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+/// Data read from an input but not yet returned as part of a frame.
+///
+/// Frames and junk are consumed from the front by advancing a read offset, rather than by moving
+/// the rest of the data down, so that taking each of the many frames from a large read doesn't
+/// copy all those after it.  The consumed space is reclaimed before the next read.
+#[derive(Default)]
+pub(crate) struct FrameBuffer {
+    data: Vec<u8>,
+    /// the offset within `data` of the first unconsumed byte
+    start: usize,
+}
+impl FrameBuffer {
+    /// The unconsumed data
+    pub fn pending(&self) -> &[u8] {
+        &self.data[self.start..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.start = 0;
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.start += len;
+        if self.start == self.data.len() {
+            self.clear();
+        }
+    }
+
+    /// The underlying buffer, with any consumed data removed, ready for more to be appended
+    pub fn spare(&mut self) -> &mut Vec<u8> {
+        if self.start > 0 {
+            self.data.drain(..self.start);
+            self.start = 0;
+        }
+        &mut self.data
+    }
+
+    /// Appends the result of a single read from the given input, returning its length
+    pub fn read_from<R: Read>(&mut self, input: &mut R) -> io::Result<usize> {
+        let data = self.spare();
+        let start = data.len();
+        data.resize(start + READ_SIZE, 0);
+        let res = loop {
+            match input.read(&mut data[start..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                res => break res,
+            }
+        };
+        data.truncate(start + *res.as_ref().unwrap_or(&0));
+        res
+    }
+
+    /// Removes the first frame from the buffer, if it is complete, discarding (and counting) any
+    /// junk before it
+    pub fn take_frame(&mut self, skipped_bytes: &mut u64) -> Option<Vec<u8>> {
+        loop {
+            let pending = self.pending();
+            let junk = find_possible_sync(pending).unwrap_or(pending.len());
+            if junk > 0 {
+                *skipped_bytes += junk as u64;
+                self.consume(junk);
+            }
+            if self.is_empty() {
+                return None;
+            }
+            match AdtsHeader::from_bytes(self.pending()) {
+                Ok(header) => {
+                    let len = header.frame_len();
+                    if self.len() < len {
+                        return None;
+                    }
+                    let frame = self.pending()[..len].to_vec();
+                    self.consume(len);
+                    return Some(frame);
+                }
+                Err(AdtsHeaderError::NotEnoughData { .. }) => return None,
+                Err(_) => {
+                    // a false sync-word; look for another after it
+                    *skipped_bytes += 1;
+                    self.consume(1);
+                }
+            }
+        }
+    }
+
+    /// Removes any complete ID3v2 tags from the start of the buffer, counting them as skipped,
+    /// and giving the HLS timestamp of the last.  Returns `false` if the buffer ends part way
+    /// through a tag, which needs more data before any frame following it can be taken.
+    pub fn skip_id3_tags(&mut self, skipped_bytes: &mut u64, timestamp: &mut Option<u64>) -> bool {
+        if !self.is_empty() && id3::maybe_tag_prefix(self.pending()) {
+            return false;
+        }
+        while let Some(len) = id3::buffered_len(self.pending(), id3::MAX_TAG_LEN) {
+            if self.len() < len {
+                return false;
+            }
+            *timestamp = id3::transport_stream_timestamp(&self.pending()[..len]);
+            *skipped_bytes += len as u64;
+            self.consume(len);
+            if !self.is_empty() && id3::maybe_tag_prefix(self.pending()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::StreamBuilder;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A file-like input which can be appended to while it's being read
+    struct Growing {
+        data: Rc<RefCell<Vec<u8>>>,
+        pos: usize,
+    }
+    impl Read for Growing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let data = self.data.borrow();
+            let n = buf.len().min(data.len() - self.pos);
+            buf[..n].copy_from_slice(&data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn partial_frames() {
+        let data = Rc::new(RefCell::new(b"ID3".to_vec()));
+        let mut reader = FollowingAdtsReader::new(Growing {
+            data: data.clone(),
+            pos: 0,
+        });
+        assert_eq!(reader.try_next_frame().unwrap(), None);
+        let builder = StreamBuilder::new()
+            .frame(&[1, 2, 3])
+            .frame(&[4])
+            .frame(&[5, 6]);
+        let offsets = builder.offsets().to_vec();
+        let stream = builder.build();
+        let first = &stream[..offsets[1]];
+        let middle = &stream[offsets[1]..offsets[2]];
+        let second = &stream[offsets[2]..];
+        data.borrow_mut().extend_from_slice(&first[..5]);
+        assert_eq!(reader.try_next_frame().unwrap(), None);
+        assert_eq!(reader.pending_bytes(), 5);
+        data.borrow_mut().extend_from_slice(&first[5..]);
+        data.borrow_mut().extend_from_slice(middle);
+        data.borrow_mut().extend_from_slice(&second[..8]);
+        assert_eq!(reader.try_next_frame().unwrap(), Some(first.to_vec()));
+        assert_eq!(reader.try_next_frame().unwrap(), Some(middle.to_vec()));
+        assert_eq!(reader.try_next_frame().unwrap(), None);
+        data.borrow_mut().extend_from_slice(&second[8..]);
+        assert_eq!(reader.try_next_frame().unwrap(), Some(second.to_vec()));
+        assert_eq!(reader.skipped_bytes(), 3);
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let frame = StreamBuilder::new().frame(&[1]).build();
+        let data = Rc::new(RefCell::new(frame.clone()));
+        let reader = FollowingAdtsReader::new(Growing { data, pos: 0 })
+            .clock(clock.clone())
+            .poll_interval(Duration::from_millis(100))
+            .idle_timeout(Some(Duration::from_secs(10)));
+        let frames: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(frames, vec![frame]);
+        // waited one poll interval at a time until the timeout passed
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+    }
+}
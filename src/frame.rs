@@ -0,0 +1,30 @@
+//! Single frames: header parsing and writing, and other pure functions of a frame's bytes.
+//!
+//! Nothing here holds state between frames, performs I/O or depends on an async runtime.
+//!
+//! ```rust
+//! use adts_reader::frame::AdtsHeader;
+//! # let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let header = AdtsHeader::from_bytes(&buf).unwrap();
+//! assert_eq!(header.frame_length(), 8);
+//! ```
+
+pub use crate::{
+    encode_header, find_syncword, validate_frame, validate_frame_with, write_encrypted_frame,
+    write_frame, AdtsHeader, AdtsHeaderBuilder, AdtsHeaderError, AdtsHeaderMut, AdtsStreamConfig,
+    AudioObjectType, ChannelConfiguration, CopyrightIdErr, CopyrightIdentification,
+    CopyrightIdentificationStart, DecodedAdtsHeader, EncodedHeader, FieldValueError, FrameError,
+    FrameLength, FrameMetadata, FrameOptions, MediaTime, Mpeg2Profile, MpegVersion, Originality,
+    PayloadError, PayloadHeuristics, PayloadProfile, PayloadSuspicion, Profile,
+    ProtectionIndicator, Rounding, SamplingFrequency, SamplingFrequencyError, ValidFrame,
+    ValidateOptions, WriteError,
+};
+
+pub use crate::blocks;
+pub use crate::conformance;
+pub use crate::crc;
+#[cfg(feature = "elements")]
+pub use crate::elements;
+pub use crate::encryption;
+#[cfg(feature = "inspect")]
+pub use crate::inspect;
@@ -0,0 +1,238 @@
+//! Cheap checks on whether a frame payload looks like it really holds AAC data.
+//!
+//! A coincidental sync-word in noisy data can produce a header which parses successfully; these
+//! checks help tell such false frames from real ones.  They are heuristics, and a real frame
+//! might occasionally fail them, so they are never used to reject frames during normal parsing.
+//!
+//! [`PayloadProfile`](struct.PayloadProfile.html) gives cheap statistics on the payload bytes
+//! themselves, which can flag frames that are likely encoded silence or padding (for example to
+//! detect dead air in a monitored feed) without running a decoder.
+
+use crate::{AdtsHeader, ChannelConfiguration};
+
+// values of the 3-bit id_syn_ele field starting each syntactic element in a raw_data_block
+const ID_SCE: u8 = 0;
+const ID_CPE: u8 = 1;
+const ID_CCE: u8 = 2;
+const ID_LFE: u8 = 3;
+const ID_END: u8 = 7;
+
+/// Reasons that a payload might be judged implausible by
+/// [`PayloadHeuristics::check()`](struct.PayloadHeuristics.html#method.check).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PayloadSuspicion {
+    /// The frame has no payload at all.  This is reported whichever checks are enabled, even
+    /// none.
+    Empty,
+    /// Every byte of the payload has the given value (typically `0x00` or `0xff` padding)
+    UniformBytes(u8),
+    /// The 3-bit syntactic element id at the start of the payload isn't one that should begin a
+    /// `raw_data_block` for the header's `channel_configuration`
+    UnexpectedFirstElement { element_id: u8 },
+}
+
+/// Selects which payload plausibility checks are applied.
+#[derive(Debug, Clone)]
+pub struct PayloadHeuristics {
+    /// Flag payloads consisting of a single repeated byte value (default `true`)
+    pub uniform_bytes: bool,
+    /// Flag payloads whose first syntactic element doesn't fit the channel configuration
+    /// (default `true`)
+    pub first_element: bool,
+}
+impl Default for PayloadHeuristics {
+    fn default() -> Self {
+        PayloadHeuristics {
+            uniform_bytes: true,
+            first_element: true,
+        }
+    }
+}
+impl PayloadHeuristics {
+    /// Applies the enabled checks to the given frame payload, returning the first suspicious
+    /// property found, if any.
+    ///
+    /// An empty payload gives none of the checks anything to examine, so is always reported as
+    /// `PayloadSuspicion::Empty`, even with every check disabled; with a non-empty payload and
+    /// every check disabled, the result is always `Ok(())`.
+    pub fn check(&self, header: &AdtsHeader<'_>, payload: &[u8]) -> Result<(), PayloadSuspicion> {
+        let first = *payload.first().ok_or(PayloadSuspicion::Empty)?;
+        if self.uniform_bytes && payload.len() > 1 && payload.iter().all(|&b| b == first) {
+            return Err(PayloadSuspicion::UniformBytes(first));
+        }
+        if self.first_element {
+            let element_id = first >> 5;
+            if !first_element_plausible(header.channel_configuration(), element_id) {
+                return Err(PayloadSuspicion::UnexpectedFirstElement { element_id });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entropy, in bits per byte, below which a payload is judged
+/// [`likely_silence()`](struct.PayloadProfile.html#method.likely_silence).  Coded audio
+/// typically measures well above 6 bits per byte, while an encoder's output for digital silence
+/// is both tiny and highly repetitive.
+const SILENCE_ENTROPY: f64 = 3.0;
+
+/// Cheap statistics on the bytes of a frame payload.
+///
+/// ```rust
+/// # use adts_reader::PayloadProfile;
+/// let profile = PayloadProfile::of(&[0x21, 0x00, 0x49, 0x90, 0x02, 0x19, 0x00, 0x23, 0x80]);
+/// assert!(profile.likely_silence());
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PayloadProfile {
+    /// Payload length in bytes
+    pub len: usize,
+    /// Shannon entropy of the byte values in the payload, in bits per byte (between `0.0` and
+    /// `8.0`).  Note that a payload of `n` bytes can measure no more than `log2(n)`, so short
+    /// payloads always measure low.
+    pub entropy: f64,
+    /// Length of the longest run of consecutive `0x00` bytes
+    pub longest_zero_run: usize,
+}
+impl PayloadProfile {
+    /// Measures the given frame payload
+    pub fn of(payload: &[u8]) -> PayloadProfile {
+        let mut counts = [0u32; 256];
+        let mut longest_zero_run = 0;
+        let mut zero_run = 0;
+        for &b in payload {
+            counts[b as usize] += 1;
+            if b == 0 {
+                zero_run += 1;
+                longest_zero_run = longest_zero_run.max(zero_run);
+            } else {
+                zero_run = 0;
+            }
+        }
+        let len = payload.len() as f64;
+        let entropy = counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = f64::from(c) / len;
+                -p * p.log2()
+            })
+            .sum::<f64>()
+            .max(0.0);
+        PayloadProfile {
+            len: payload.len(),
+            entropy,
+            longest_zero_run,
+        }
+    }
+
+    /// True if the payload's low entropy suggests the frame encodes silence.  Encoders
+    /// typically represent silence with very small, repetitive frames.
+    pub fn likely_silence(&self) -> bool {
+        self.entropy < SILENCE_ENTROPY
+    }
+
+    /// True if at least half of the payload is a single run of zero bytes, as when a damaged or
+    /// truncated frame has been padded out to its signalled length.  Real coded audio rarely
+    /// contains long zero runs.
+    pub fn likely_padding(&self) -> bool {
+        self.len > 1 && self.longest_zero_run * 2 >= self.len
+    }
+}
+
+fn first_element_plausible(channels: ChannelConfiguration, element_id: u8) -> bool {
+    match (channels, element_id) {
+        // a raw_data_block carrying no channel elements at all
+        (_, ID_END) => false,
+        // the layout is given by a PCE, so can't judge
+        (ChannelConfiguration::ObjectTypeSpecificConfig, _) => true,
+        // coupling and LFE channels never lead the default layouts
+        (_, ID_CCE) | (_, ID_LFE) => false,
+        (ChannelConfiguration::Stereo, ID_SCE) => false,
+        (ChannelConfiguration::Stereo, ID_CPE) => true,
+        // all other default layouts lead with a (centre or mono) SCE
+        (_, ID_CPE) => false,
+        // SCE, or DSE/PCE/FIL which might legitimately precede the channel elements
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    fn check(channels: ChannelConfiguration, payload: &[u8]) -> Result<(), PayloadSuspicion> {
+        let config = AdtsStreamConfig {
+            channel_configuration: channels,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let buf = StreamBuilder::new().config(config).frame(payload).build();
+        let header = AdtsHeader::from_bytes(&buf).unwrap();
+        PayloadHeuristics::default().check(&header, &buf[7..])
+    }
+
+    #[test]
+    fn suspicions() {
+        use ChannelConfiguration::*;
+        assert_eq!(check(Stereo, &[]), Err(PayloadSuspicion::Empty));
+        assert_eq!(
+            check(Stereo, &[0xff; 10]),
+            Err(PayloadSuspicion::UniformBytes(0xff))
+        );
+        assert_eq!(check(Stereo, &[ID_CPE << 5, 0x12, 0x34]), Ok(()));
+        assert_eq!(
+            check(Stereo, &[ID_SCE << 5, 0x12, 0x34]),
+            Err(PayloadSuspicion::UnexpectedFirstElement { element_id: ID_SCE })
+        );
+        assert_eq!(check(Mono, &[ID_SCE << 5, 0x12, 0x34]), Ok(()));
+        assert_eq!(check(FiveOne, &[6 << 5, 0x12, 0x34]), Ok(()));
+        assert_eq!(
+            check(ObjectTypeSpecificConfig, &[ID_END << 5, 0x12]),
+            Err(PayloadSuspicion::UnexpectedFirstElement { element_id: ID_END })
+        );
+
+        let none = PayloadHeuristics {
+            uniform_bytes: false,
+            first_element: false,
+        };
+        let buf = StreamBuilder::new().frame(&[]).frame(&[0xff; 10]).build();
+        let empty = AdtsHeader::from_bytes(&buf).unwrap();
+        assert_eq!(none.check(&empty, &[]), Err(PayloadSuspicion::Empty));
+        let uniform = AdtsHeader::from_bytes(&buf[7..]).unwrap();
+        assert_eq!(none.check(&uniform, &buf[14..]), Ok(()));
+    }
+
+    #[test]
+    fn profile() {
+        let empty = PayloadProfile::of(&[]);
+        assert_eq!(
+            (empty.len, empty.entropy, empty.longest_zero_run),
+            (0, 0.0, 0)
+        );
+        assert!(!empty.likely_padding());
+
+        let uniform = PayloadProfile::of(&[0x55; 100]);
+        assert_eq!(uniform.entropy, 0.0);
+        assert!(uniform.likely_silence());
+        assert!(!uniform.likely_padding());
+
+        // every byte value once: the maximum possible entropy
+        let all: Vec<u8> = (0..=255).collect();
+        let noisy = PayloadProfile::of(&all);
+        assert!((noisy.entropy - 8.0).abs() < 1e-9);
+        assert_eq!(noisy.longest_zero_run, 1);
+        assert!(!noisy.likely_silence());
+        assert!(!noisy.likely_padding());
+
+        let mut padded = all[..100].to_vec();
+        padded.extend_from_slice(&[0; 100]);
+        padded.push(1);
+        let padded = PayloadProfile::of(&padded);
+        assert_eq!(padded.longest_zero_run, 100);
+        assert!(!padded.likely_silence());
+        assert!(!padded.likely_padding());
+        assert!(PayloadProfile::of(&[1, 0, 0, 2]).likely_padding());
+    }
+}
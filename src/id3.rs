@@ -0,0 +1,273 @@
+//! Recognition of ID3v2 tags, which some recorders (and HLS packed audio segments) place ahead of
+//! ADTS data.
+
+/// Size of the fixed ID3v2 tag header, and of the header of each frame within the tag.
+pub(crate) const HEADER_LEN: usize = 10;
+
+/// The largest tag which is buffered in order to be examined by default; larger tags (which HLS
+/// segments never carry) are treated as junk
+pub(crate) const MAX_TAG_LEN: usize = 64 * 1024;
+
+/// The owner identifier of the `PRIV` frame in which HLS packed audio segments give the MPEG-TS
+/// timestamp of their first frame
+const TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp";
+
+/// Decodes the 'synchsafe' integers of ID3v2 headers, which hold 7 bits in each byte
+fn synchsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0usize, |acc, &b| acc << 7 | usize::from(b & 0x7f))
+}
+
+/// Checks for an ID3v2 tag header at the start of the given buffer.
+///
+/// Returns `None` if the buffer doesn't start with an ID3v2 tag, or `Some(len)` giving the total
+/// length of the tag, including its header and any footer.  At least `HEADER_LEN` bytes of input
+/// are needed to decide.
+pub(crate) fn tag_len(buf: &[u8]) -> Option<usize> {
+    if buf.len() < HEADER_LEN || &buf[..3] != b"ID3" || buf[3] == 0xff || buf[4] == 0xff {
+        return None;
+    }
+    let size = &buf[6..10];
+    if size.iter().any(|b| b & 0x80 != 0) {
+        // 'synchsafe' integer bytes never have the top bit set
+        return None;
+    }
+    let size = synchsafe(size);
+    let footer = if buf[5] & 0x10 != 0 { HEADER_LEN } else { 0 };
+    Some(HEADER_LEN + size + footer)
+}
+
+/// `true` if the given bytes could be the start of an ID3v2 tag, but there aren't yet enough of
+/// them to tell for sure.
+pub(crate) fn maybe_tag_prefix(buf: &[u8]) -> bool {
+    buf.len() < HEADER_LEN && b"ID3".starts_with(&buf[..buf.len().min(3)])
+}
+
+/// `true` if the given bytes start with what looks like an ID3v2 tag, judging by as much of the
+/// tag header as is present.
+pub(crate) fn starts_tag(buf: &[u8]) -> bool {
+    buf.starts_with(b"ID3") && (buf.len() < HEADER_LEN || tag_len(buf).is_some())
+}
+
+/// If the given bytes start with an ID3v2 tag no larger than `max`, the number of bytes to
+/// collect in order to examine it: the whole tag, or just its header until that's complete.
+pub(crate) fn buffered_len(buf: &[u8], max: usize) -> Option<usize> {
+    if !starts_tag(buf) {
+        return None;
+    }
+    match tag_len(buf) {
+        None => Some(HEADER_LEN),
+        Some(len) if len <= max => Some(len),
+        Some(_) => None,
+    }
+}
+
+/// The 33-bit MPEG-TS timestamp (in 90kHz ticks) of the first frame following the given tag, if
+/// the tag has the `com.apple.streaming.transportStreamTimestamp` `PRIV` frame with which HLS
+/// packed audio segments start.
+pub(crate) fn transport_stream_timestamp(tag: &[u8]) -> Option<u64> {
+    tag_len(tag)?;
+    let version = tag[3];
+    let flags = tag[5];
+    // frames of version 2.2 tags have a different layout, and HLS doesn't unsynchronise tags
+    if !(3..=4).contains(&version) || flags & 0x80 != 0 {
+        return None;
+    }
+    let frame_size = |bytes: &[u8]| {
+        if version == 4 {
+            synchsafe(bytes)
+        } else {
+            bytes
+                .iter()
+                .fold(0usize, |acc, &b| acc << 8 | usize::from(b))
+        }
+    };
+    let end = tag.len().min(HEADER_LEN + synchsafe(&tag[6..10]));
+    let mut pos = HEADER_LEN;
+    if flags & 0x40 != 0 {
+        // an extended header, whose size doesn't count itself in version 2.3
+        let size = frame_size(tag.get(pos..pos + 4)?);
+        pos += if version == 4 { size } else { 4 + size };
+    }
+    while pos + HEADER_LEN <= end {
+        let id = &tag[pos..pos + 4];
+        if id[0] == 0 {
+            // padding
+            break;
+        }
+        let body = pos + HEADER_LEN;
+        let next = body + frame_size(&tag[pos + 4..pos + 8]);
+        if next > end {
+            break;
+        }
+        if id == b"PRIV" {
+            let timestamp = tag[body..next]
+                .strip_prefix(TIMESTAMP_OWNER)
+                .and_then(|data| data.strip_prefix(&[0]))
+                .and_then(|data| <[u8; 8]>::try_from(data).ok());
+            if let Some(timestamp) = timestamp {
+                return Some(u64::from_be_bytes(timestamp) & ((1 << 33) - 1));
+            }
+        }
+        pos = next;
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn lengths() {
+        assert_eq!(tag_len(b"ID3\x04\x00\x00\x00\x00\x01\x7f"), Some(10 + 255));
+        assert_eq!(
+            tag_len(b"ID3\x04\x00\x10\x00\x00\x00\x01"),
+            Some(10 + 1 + 10)
+        );
+        assert_eq!(tag_len(b"ID3\x04\x00\x00\x00\x00\x00\x80"), None);
+        assert_eq!(tag_len(b"\xff\xf1ID3\x00\x00\x00\x00\x00"), None);
+        assert!(maybe_tag_prefix(b"ID"));
+        assert!(!maybe_tag_prefix(b"IE"));
+        assert!(starts_tag(b"ID3\x04\x00\x00\x00"));
+        assert!(!starts_tag(b"ID3\x04\x00\x00\x00\x00\x00\x80"));
+    }
+
+    /// An ID3v2.4 tag holding a `PRIV` frame with the given owner and data
+    pub(crate) fn priv_tag(owner: &[u8], data: &[u8]) -> Vec<u8> {
+        let len = owner.len() + 1 + data.len();
+        let mut tag = b"ID3\x04\x00\x00\x00\x00\x00".to_vec();
+        tag.push((HEADER_LEN + len) as u8);
+        tag.extend_from_slice(b"PRIV\x00\x00\x00");
+        tag.push(len as u8);
+        tag.extend_from_slice(&[0, 0]);
+        tag.extend_from_slice(owner);
+        tag.push(0);
+        tag.extend_from_slice(data);
+        tag
+    }
+
+    pub(crate) fn timestamp_tag(timestamp: u64) -> Vec<u8> {
+        priv_tag(TIMESTAMP_OWNER, &timestamp.to_be_bytes())
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            transport_stream_timestamp(&timestamp_tag(0x1_2345_6789)),
+            Some(0x1_2345_6789)
+        );
+        // the timestamp is only 33 bits
+        assert_eq!(
+            transport_stream_timestamp(&timestamp_tag(0xfe_0000_0001)),
+            Some(1)
+        );
+        assert_eq!(
+            transport_stream_timestamp(&priv_tag(b"com.example", &[0; 8])),
+            None
+        );
+        // a truncated timestamp
+        let mut tag = timestamp_tag(1);
+        assert_eq!(transport_stream_timestamp(&tag[..tag.len() - 1]), None);
+        // a PRIV frame following another frame, in a version 2.3 tag with padding
+        tag[3] = 3;
+        tag.splice(10..10, b"TXXX\x00\x00\x00\x02\x00\x00ab".iter().copied());
+        tag.extend_from_slice(&[0; 4]);
+        tag[9] += 12 + 4;
+        assert_eq!(transport_stream_timestamp(&tag), Some(1));
+    }
+
+    #[derive(Default)]
+    struct Segments {
+        frames: Vec<Option<u64>>,
+        tags: Vec<(u64, u64, usize, Option<u64>)>,
+        boundaries: Vec<(u64, u64, bool)>,
+        errors: usize,
+    }
+    impl AdtsConsumer2 for Segments {
+        fn on_frame(&mut self, frame: &FrameContext<'_>) {
+            self.frames.push(frame.pts);
+        }
+        fn on_error(&mut self, _: AdtsParseError) {
+            self.errors += 1;
+        }
+        fn on_stream_boundary(&mut self, boundary: &StreamBoundary) {
+            self.boundaries
+                .push((boundary.offset, boundary.discarded, boundary.id3));
+        }
+        fn on_id3_tag(&mut self, tag: &Id3Tag<'_>) {
+            self.tags
+                .push((tag.index, tag.offset, tag.tag.len(), tag.timestamp));
+        }
+    }
+
+    #[test]
+    fn hls_segments() {
+        // two segments of 11 byte frames, each starting with a tag of 73 bytes, the second
+        // segment's frames being at 44.1kHz, so lasting 2089 ticks rather than 1920
+        let mut data = timestamp_tag(9000);
+        data.extend(
+            StreamBuilder::new()
+                .frames(2, StreamBuilder::DEFAULT_CONFIG)
+                .build(),
+        );
+        data.extend(timestamp_tag(20000));
+        let config = AdtsStreamConfig {
+            sampling_frequency: SamplingFrequency::Freq44100,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        data.extend(StreamBuilder::new().frames(2, config).build());
+        for chunk in [1, 5, 8, 20, data.len()] {
+            let mut parser = AdtsParser::new(Segments::default()).with_id3_tags(true);
+            for buf in data.chunks(chunk) {
+                parser.push(buf);
+            }
+            let segments = &parser.consumer;
+            assert_eq!(
+                segments.frames,
+                [Some(9000), Some(10920), Some(20000), Some(22089)],
+                "chunks of {}",
+                chunk
+            );
+            assert_eq!(
+                segments.tags,
+                [(0, 0, 73, Some(9000)), (2, 95, 73, Some(20000))]
+            );
+            assert_eq!(segments.boundaries, [(95, 73, true)]);
+            assert_eq!(segments.errors, 0);
+            assert_eq!(parser.stats().id3_tags, 2);
+            assert_eq!(parser.stats().garbage_bytes, 0);
+            assert_eq!(parser.offset(), data.len() as u64);
+        }
+
+        // without the option, tags are garbage
+        let mut parser = AdtsParser::new(Segments::default());
+        parser.push(&data);
+        assert_eq!(parser.consumer.frames, [None; 4]);
+        assert!(parser.consumer.tags.is_empty());
+        assert_eq!(parser.consumer.errors, 2);
+    }
+
+    #[test]
+    fn not_a_tag() {
+        // "ID3" followed by an invalid size is junk, even with the option
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x80".to_vec();
+        data.extend(
+            StreamBuilder::new()
+                .frames(1, StreamBuilder::DEFAULT_CONFIG)
+                .build(),
+        );
+        for chunk in [1, data.len()] {
+            let mut parser = AdtsParser::new(Segments::default()).with_id3_tags(true);
+            for buf in data.chunks(chunk) {
+                parser.push(buf);
+            }
+            assert_eq!(parser.consumer.frames, [None]);
+            assert_eq!(parser.consumer.errors, 1);
+            assert_eq!(parser.stats().garbage_bytes, 10);
+        }
+    }
+}
@@ -0,0 +1,427 @@
+//! An index of the frames in a stream, for seeking by time or sample position within long files
+//! without parsing everything before the seek point.
+//!
+//! ```rust
+//! use adts_reader::index::AdtsIndex;
+//! use std::time::Duration;
+//! # let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! # let file = frame.repeat(100);
+//! let index = AdtsIndex::builder().every(10).scan(&file[..])?;
+//! // the indexed frame at or before 1 second, from which to resume parsing the file
+//! let entry = index.seek_to_time(Duration::from_secs(1)).unwrap();
+//! assert_eq!(entry.sample, 40 * 1024);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! With the `serde` feature enabled, an index may be serialized, to cache it alongside the file.
+//! Deserializing a cached index fails if any entry's header is not a valid ADTS header.
+
+use crate::descriptor::FrameDescriptor;
+use crate::{
+    AdtsFrameIter, AdtsFrameReader, AdtsHeader, AdtsReadError, AdtsStreamConfig, FrameLength,
+    MediaTime,
+};
+use std::io;
+use std::iter::FusedIterator;
+use std::time::Duration;
+
+/// One frame recorded in an [`AdtsIndex`](struct.AdtsIndex.html).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawIndexEntry"))]
+pub struct IndexEntry {
+    /// Position of the start of the frame's header within the stream
+    pub offset: u64,
+    /// Number of samples in the frames preceding this one
+    pub sample: u64,
+    /// Media time of the start of the frame, counted from the first frame in the stream
+    pub pts: Duration,
+    /// Identifies the frame's stream configuration; see
+    /// [`AdtsIndex::configs()`](struct.AdtsIndex.html#method.configs)
+    pub config_id: u32,
+    /// the frame's header bytes, with the last two zero unless it has a CRC
+    header: [u8; 9],
+}
+impl IndexEntry {
+    /// The frame's header, as found when the index was built
+    pub fn header(&self) -> AdtsHeader<'_> {
+        AdtsHeader::from_bytes(&self.header).expect("header checked when indexed")
+    }
+
+    /// Describes the frame's location, as if produced by
+    /// [`Descriptors`](../descriptor/struct.Descriptors.html)
+    pub fn descriptor(&self) -> FrameDescriptor {
+        let header = self.header();
+        FrameDescriptor {
+            offset: self.offset,
+            len: header.frame_len() as u32,
+            header_len: header.header_length() as u8,
+            pts: self.pts,
+            config_id: self.config_id,
+        }
+    }
+}
+
+/// An `IndexEntry` as deserialized, before its header is checked, so that a corrupt cache can't
+/// give entries whose `header()` would fail
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawIndexEntry {
+    offset: u64,
+    sample: u64,
+    pts: Duration,
+    config_id: u32,
+    header: [u8; 9],
+}
+#[cfg(feature = "serde")]
+impl TryFrom<RawIndexEntry> for IndexEntry {
+    type Error = String;
+
+    fn try_from(raw: RawIndexEntry) -> Result<Self, Self::Error> {
+        AdtsHeader::from_bytes(&raw.header)
+            .map_err(|e| format!("invalid frame header in index entry: {:?}", e))?;
+        Ok(IndexEntry {
+            offset: raw.offset,
+            sample: raw.sample,
+            pts: raw.pts,
+            config_id: raw.config_id,
+            header: raw.header,
+        })
+    }
+}
+
+/// Configures the building of an [`AdtsIndex`](struct.AdtsIndex.html), created by
+/// [`AdtsIndex::builder()`](struct.AdtsIndex.html#method.builder).
+#[derive(Debug, Clone)]
+pub struct IndexBuilder {
+    every: u64,
+    frame_length: FrameLength,
+}
+impl IndexBuilder {
+    /// Records only every `n`th frame, starting with the first, to make the index smaller
+    /// (default `1`, recording every frame).  A seek then finds the recorded frame at or before
+    /// the target, leaving up to `n - 1` frames to be skipped by the caller.  Panics if `n` is
+    /// zero.
+    pub fn every(mut self, n: u64) -> Self {
+        assert!(n > 0, "index interval must be at least 1");
+        self.every = n;
+        self
+    }
+
+    /// Sets the number of samples per `raw_data_block` (default 1024), since ADTS headers don't
+    /// say
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Builds an index by reading the whole of the given input.  Data which isn't part of any
+    /// frame is skipped, as is an incomplete frame at the end of the input.
+    pub fn scan<R: io::Read>(self, input: R) -> io::Result<AdtsIndex> {
+        let mut indexer = self.start();
+        let mut reader = AdtsFrameReader::new(input);
+        let mut consumed = 0;
+        while let Some(frame) = reader.next() {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(AdtsReadError::Truncated { .. }) => break,
+                Err(e) => return Err(e.into()),
+            };
+            indexer.add(consumed + reader.skipped_bytes(), &frame.header());
+            consumed += frame.as_bytes().len() as u64;
+        }
+        Ok(indexer.finish())
+    }
+
+    /// Builds an index of the complete frames within the given buffer
+    pub fn build(self, buf: &[u8]) -> AdtsIndex {
+        let mut indexer = self.start();
+        for (offset, frame) in AdtsFrameIter::new(buf).with_offsets() {
+            if let Ok(header) = frame {
+                indexer.add(offset as u64, &header);
+            }
+        }
+        indexer.finish()
+    }
+
+    fn start(self) -> Indexer {
+        Indexer {
+            index: AdtsIndex {
+                entries: vec![],
+                configs: vec![],
+                every: self.every,
+                frame_length: self.frame_length,
+                frames: 0,
+                samples: 0,
+                duration: Duration::ZERO,
+            },
+            elapsed: MediaTime::ZERO,
+        }
+    }
+}
+
+/// An index under construction
+struct Indexer {
+    index: AdtsIndex,
+    /// kept exactly, to avoid accumulating rounding errors in `pts`
+    elapsed: MediaTime,
+}
+impl Indexer {
+    fn add(&mut self, offset: u64, header: &AdtsHeader<'_>) {
+        let index = &mut self.index;
+        if index.frames % index.every == 0 {
+            let mut bytes = [0; 9];
+            let len = usize::from(header.header_length());
+            bytes[..len].copy_from_slice(&header.buf[..len]);
+            let config_id = index.config_id(header.stream_config());
+            index.entries.push(IndexEntry {
+                offset,
+                sample: index.samples,
+                pts: self.elapsed.to_duration(),
+                config_id,
+                header: bytes,
+            });
+        }
+        index.frames += 1;
+        index.samples += header.samples_per_frame_of(index.frame_length);
+        // frames with a reserved sampling frequency have unknown duration, and are taken to have
+        // none
+        if let Some(duration) = header.frame_duration_of(index.frame_length) {
+            self.elapsed = self.elapsed + duration;
+        }
+    }
+
+    fn finish(mut self) -> AdtsIndex {
+        self.index.duration = self.elapsed.to_duration();
+        self.index
+    }
+}
+
+/// The position, timing and header of the frames of a stream, which can be searched to find
+/// where in the stream to start parsing in order to reach a given time or sample.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdtsIndex {
+    entries: Vec<IndexEntry>,
+    configs: Vec<AdtsStreamConfig>,
+    every: u64,
+    #[cfg_attr(feature = "serde", serde(with = "frame_length"))]
+    frame_length: FrameLength,
+    frames: u64,
+    samples: u64,
+    duration: Duration,
+}
+impl AdtsIndex {
+    pub fn builder() -> IndexBuilder {
+        IndexBuilder {
+            every: 1,
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Builds an index of every frame in the given input; see
+    /// [`IndexBuilder::scan()`](struct.IndexBuilder.html#method.scan)
+    pub fn scan<R: io::Read>(input: R) -> io::Result<AdtsIndex> {
+        AdtsIndex::builder().scan(input)
+    }
+
+    /// The recorded frames, in stream order
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Iterates over the recorded frames, in either direction
+    pub fn iter(&self) -> std::slice::Iter<'_, IndexEntry> {
+        self.entries.iter()
+    }
+
+    /// A [`FrameDescriptor`](../descriptor/struct.FrameDescriptor.html) for each recorded frame
+    pub fn descriptors(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = FrameDescriptor> + ExactSizeIterator + FusedIterator + '_
+    {
+        self.entries.iter().map(IndexEntry::descriptor)
+    }
+
+    /// The stream configurations seen, indexed by `config_id`
+    pub fn configs(&self) -> &[AdtsStreamConfig] {
+        &self.configs
+    }
+
+    /// Total number of frames in the stream, including those not recorded
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Total number of samples in the stream
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Total media time of the stream
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The last recorded frame starting at or before `time`, or `None` if no frames were found.
+    /// Times beyond the end of the stream give the last recorded frame.
+    pub fn seek_to_time(&self, time: Duration) -> Option<&IndexEntry> {
+        let after = self.entries.partition_point(|e| e.pts <= time);
+        self.entries.get(after.checked_sub(1)?)
+    }
+
+    /// The last recorded frame starting at or before the given sample, or `None` if no frames
+    /// were found
+    pub fn seek_to_sample(&self, sample: u64) -> Option<&IndexEntry> {
+        let after = self.entries.partition_point(|e| e.sample <= sample);
+        self.entries.get(after.checked_sub(1)?)
+    }
+
+    fn config_id(&mut self, config: AdtsStreamConfig) -> u32 {
+        let index = match self.configs.iter().position(|c| *c == config) {
+            Some(index) => index,
+            None => {
+                self.configs.push(config);
+                self.configs.len() - 1
+            }
+        };
+        index as u32
+    }
+}
+impl<'a> IntoIterator for &'a AdtsIndex {
+    type Item = &'a IndexEntry;
+    type IntoIter = std::slice::Iter<'a, IndexEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// `FrameLength` has no serde support of its own, so is stored as its number of samples
+#[cfg(feature = "serde")]
+mod frame_length {
+    use crate::FrameLength;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &FrameLength, s: S) -> Result<S::Ok, S::Error> {
+        value.samples().serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<FrameLength, D::Error> {
+        match u64::deserialize(d)? {
+            1024 => Ok(FrameLength::Samples1024),
+            960 => Ok(FrameLength::Samples960),
+            n => Err(serde::de::Error::custom(format!(
+                "unsupported frame length {}",
+                n
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::Descriptors;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    fn stream() -> (Vec<u8>, Vec<usize>) {
+        let mono = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Mono,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let builder = StreamBuilder::new()
+            .frames(4, StreamBuilder::DEFAULT_CONFIG)
+            .junk(5)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .frames(3, mono)
+            .options(FrameOptions {
+                number_of_raw_data_blocks: 2,
+                ..FrameOptions::default()
+            })
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .truncate_last_frame();
+        let offsets = builder.offsets().to_vec();
+        (builder.build(), offsets)
+    }
+
+    #[test]
+    fn build_and_seek() {
+        let (data, offsets) = stream();
+        let index = AdtsIndex::scan(&data[..]).unwrap();
+        assert_eq!(index, AdtsIndex::builder().build(&data));
+        assert_eq!(index.frames(), 9);
+        assert_eq!(index.samples(), (4 + 3 + 2 * 2) * 1024);
+        assert_eq!(
+            index.duration(),
+            MediaTime::from_blocks(11, 48000).to_duration()
+        );
+        assert_eq!(
+            index.iter().map(|e| e.offset as usize).collect::<Vec<_>>(),
+            offsets[..9]
+        );
+        let entry = &index.entries()[5];
+        assert_eq!(entry.sample, 5 * 1024);
+        assert_eq!(entry.config_id, 1);
+        assert_eq!(entry.header().protection(), ProtectionIndicator::CrcPresent);
+        let last = &index.entries()[8];
+        assert_eq!(last.sample, (4 + 3 + 2) * 1024);
+        assert_eq!(last.header().number_of_raw_data_blocks_in_frame(), 2);
+
+        assert_eq!(index.seek_to_sample(5 * 1024), Some(entry));
+        assert_eq!(index.seek_to_sample(6 * 1024 - 1), Some(entry));
+        assert_eq!(index.seek_to_time(entry.pts), Some(entry));
+        assert_eq!(index.seek_to_time(Duration::ZERO), index.entries().first());
+        assert_eq!(index.seek_to_time(Duration::MAX), index.entries().last());
+        assert_eq!(AdtsIndex::builder().build(&[]).seek_to_sample(0), None);
+
+        // the index describes frames just as Descriptors does
+        let descriptors: Vec<_> = Descriptors::new(&data).collect();
+        assert_eq!(index.descriptors().collect::<Vec<_>>(), descriptors);
+        assert_eq!(index.descriptors().next_back(), descriptors.last().copied());
+        assert_eq!(index.configs().len(), 2);
+    }
+
+    #[test]
+    fn sparse() {
+        let (data, offsets) = stream();
+        let index = AdtsIndex::builder().every(4).build(&data);
+        assert_eq!(index.frames(), 9);
+        assert_eq!(
+            index.iter().map(|e| e.offset as usize).collect::<Vec<_>>(),
+            vec![offsets[0], offsets[4], offsets[8]]
+        );
+        assert_eq!(index.seek_to_sample(8 * 1024).unwrap().sample, 4 * 1024);
+
+        let index = AdtsIndex::builder()
+            .frame_length(FrameLength::Samples960)
+            .build(&data);
+        assert_eq!(index.samples(), 11 * 960);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let (data, _) = stream();
+        let index = AdtsIndex::builder().every(2).build(&data);
+        let json = serde_json::to_string(&index).unwrap();
+        let cached: AdtsIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(cached, index);
+        assert_eq!(cached.entries()[2].header().frame_len(), 13);
+
+        // entries whose header isn't valid are rejected, rather than panicking when used
+        let mut corrupt = serde_json::to_value(&index).unwrap();
+        corrupt["entries"][1]["header"][0] = 0.into();
+        let err = serde_json::from_value::<AdtsIndex>(corrupt).unwrap_err();
+        assert!(err.to_string().contains("BadSyncWord"), "{}", err);
+        let mut corrupt = serde_json::to_value(&index).unwrap();
+        // frame_length of 1
+        corrupt["entries"][0]["header"][4] = 0.into();
+        corrupt["entries"][0]["header"][5] = 0x3f.into();
+        assert!(serde_json::from_value::<AdtsIndex>(corrupt).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+//! Human-readable descriptions of ADTS frames, for debugging output.
+//!
+//! Available when the `inspect` feature is enabled.
+//!
+//! ```rust
+//! use adts_reader::{inspect, AdtsHeader};
+//! let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let header = AdtsHeader::from_bytes(&buf).unwrap();
+//! let text = inspect::format_frame(&header, &buf[7..], &inspect::InspectOptions::default());
+//! assert!(text.contains("sampling_frequency"));
+//! ```
+//!
+//! A whole stream can be described one frame per line, with [`FrameRow`](struct.FrameRow.html),
+//! or summarised from its [`AdtsStreamStats`](../struct.AdtsStreamStats.html) with
+//! [`format_summary()`](fn.format_summary.html).  These produce the output of the `adts-tool`
+//! command.
+
+use crate::crc::CrcError;
+use crate::{AdtsHeader, AdtsStreamStats};
+use std::fmt::Write;
+
+/// Controls what [`format_frame()`](fn.format_frame.html) includes in its output.
+#[derive(Debug, Clone)]
+pub struct InspectOptions {
+    /// Annotate header fields with their interpretation, e.g. the sampling rate in Hz (default
+    /// `true`)
+    pub annotate: bool,
+    /// Append a hexdump of the payload (default `true`)
+    pub hexdump: bool,
+    /// Limit the hexdump to at most this many bytes of payload (default `None`, no limit)
+    pub hexdump_limit: Option<usize>,
+}
+impl Default for InspectOptions {
+    fn default() -> Self {
+        InspectOptions {
+            annotate: true,
+            hexdump: true,
+            hexdump_limit: None,
+        }
+    }
+}
+
+/// Describes the header fields of the given frame one per line, optionally followed by a hexdump
+/// of the payload.
+pub fn format_frame(header: &AdtsHeader<'_>, payload: &[u8], options: &InspectOptions) -> String {
+    let mut out = String::new();
+    // writing to a String can't fail, so the fmt::Result values are ignored throughout
+    let _ = writeln!(
+        out,
+        "ADTS frame: {} bytes (header {}, payload {})",
+        header.frame_length(),
+        header.header_length(),
+        payload.len()
+    );
+    let mut field = |name: &str, value: String, note: Option<String>| {
+        let _ = write!(out, "  {:<32} {}", format!("{}:", name), value);
+        if let (true, Some(note)) = (options.annotate, note) {
+            let _ = write!(out, " ({})", note);
+        }
+        out.push('\n');
+    };
+    field("mpeg_version", format!("{:?}", header.mpeg_version()), None);
+    field("protection", format!("{:?}", header.protection()), None);
+    field(
+        "audio_object_type",
+        format!("{:?}", header.audio_object_type()),
+        None,
+    );
+    let freq = header.sampling_frequency();
+    field(
+        "sampling_frequency",
+        format!("{:?}", freq),
+        Some(match freq.freq() {
+            Some(hz) => format!("{} Hz", hz),
+            None => "reserved value".to_string(),
+        }),
+    );
+    field("private_bit", header.private_bit().to_string(), None);
+    field(
+        "channel_configuration",
+        format!("{:?}", header.channel_configuration()),
+        None,
+    );
+    field("originality", format!("{:?}", header.originality()), None);
+    field("home", header.home().to_string(), None);
+    field(
+        "copyright_identification_bit",
+        header.copyright_identification_bit().to_string(),
+        None,
+    );
+    field(
+        "copyright_identification_start",
+        format!("{:?}", header.copyright_identification_start()),
+        None,
+    );
+    field("frame_length", header.frame_length().to_string(), None);
+    let fullness = header.adts_buffer_fullness();
+    field(
+        "adts_buffer_fullness",
+        format!("{:#x}", fullness),
+        if fullness == 0x7ff {
+            Some("variable bitrate".to_string())
+        } else {
+            None
+        },
+    );
+    field(
+        "crc",
+        match header.crc() {
+            Some(crc) => format!("{:#06x}", crc),
+            None => "-".to_string(),
+        },
+        None,
+    );
+    field(
+        "number_of_raw_data_blocks_in_frame",
+        header.number_of_raw_data_blocks_in_frame().to_string(),
+        None,
+    );
+    if options.hexdump {
+        let len = options
+            .hexdump_limit
+            .map_or(payload.len(), |limit| limit.min(payload.len()));
+        out.push_str(&hexdump(&payload[..len]));
+        if len < payload.len() {
+            let _ = writeln!(out, "... {} more bytes", payload.len() - len);
+        }
+    }
+    out
+}
+
+/// Formats the given bytes as a classic 16-bytes-per-line hexdump, with offsets and an ASCII
+/// column.
+pub fn hexdump(buf: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in buf.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", i * 16);
+        for j in 0..16 {
+            if j == 8 {
+                out.push(' ');
+            }
+            match line.get(j) {
+                Some(b) => {
+                    let _ = write!(out, " {:02x}", b);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str("  |");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
+
+/// Whether a frame's CRC matched its contents, as listed in a [`FrameRow`](struct.FrameRow.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CrcStatus {
+    /// The frame has no CRC
+    Absent,
+    Ok,
+    Mismatch,
+    /// The frame has a CRC, but it couldn't be checked, since the frame is incomplete or its
+    /// `raw_data_block_position` table is unusable
+    Unverifiable,
+}
+impl CrcStatus {
+    /// Checks the CRC of the complete frame that `header` belongs to
+    pub fn of(header: &AdtsHeader<'_>) -> CrcStatus {
+        match header.verify_crc() {
+            Ok(()) => CrcStatus::Ok,
+            Err(CrcError::Absent) => CrcStatus::Absent,
+            Err(CrcError::Mismatch { .. }) | Err(CrcError::BlockMismatch { .. }) => {
+                CrcStatus::Mismatch
+            }
+            Err(CrcError::Layout(_)) => CrcStatus::Unverifiable,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CrcStatus::Absent => "-",
+            CrcStatus::Ok => "ok",
+            CrcStatus::Mismatch => "MISMATCH",
+            CrcStatus::Unverifiable => "unverifiable",
+        }
+    }
+}
+
+/// The values listed for one frame in a per-frame table of a stream.
+///
+/// ```rust
+/// use adts_reader::inspect::FrameRow;
+/// use adts_reader::AdtsHeader;
+/// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let row = FrameRow::new(0, 0, &AdtsHeader::from_bytes(&buf).unwrap());
+/// assert_eq!(FrameRow::headings(), "   index       offset  length  blocks  fullness  crc");
+/// assert_eq!(row.format(),         "       0            0       8       1     0x7ff  -");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrameRow {
+    /// The number of frames preceding this one
+    pub index: u64,
+    /// The position of the frame within the stream
+    pub offset: u64,
+    pub frame_length: u16,
+    pub blocks: u8,
+    pub buffer_fullness: u16,
+    pub crc: CrcStatus,
+}
+impl FrameRow {
+    /// Describes the complete frame with the given header.  `index` and `offset` give its
+    /// position in the stream, as in [`FrameContext`](../struct.FrameContext.html).
+    pub fn new(index: u64, offset: u64, header: &AdtsHeader<'_>) -> FrameRow {
+        FrameRow {
+            index,
+            offset,
+            frame_length: header.frame_length(),
+            blocks: header.number_of_raw_data_blocks_in_frame(),
+            buffer_fullness: header.adts_buffer_fullness(),
+            crc: CrcStatus::of(header),
+        }
+    }
+
+    /// The column headings for the lines produced by [`format()`](#method.format)
+    pub fn headings() -> String {
+        format!(
+            "{:>8}  {:>11}  {:>6}  {:>6}  {:>8}  crc",
+            "index", "offset", "length", "blocks", "fullness"
+        )
+    }
+
+    /// The values as a line of a fixed-width table, without a line ending
+    pub fn format(&self) -> String {
+        format!(
+            "{:>8}  {:>11}  {:>6}  {:>6}  {:>#8x}  {}",
+            self.index,
+            self.offset,
+            self.frame_length,
+            self.blocks,
+            self.buffer_fullness,
+            self.crc.name()
+        )
+    }
+}
+
+/// Summarises the stream described by the given statistics, one item per line: its
+/// configuration, duration and bitrate, the range of frame lengths, and the errors found.
+pub fn format_summary(stats: &AdtsStreamStats) -> String {
+    let mut out = String::new();
+    let mut item = |name: &str, value: String| {
+        let _ = writeln!(out, "{:<18}{}", format!("{}:", name), value);
+    };
+    item(
+        "configuration",
+        match stats.config {
+            Some(c) => format!(
+                "{:?} {:?} {:?} {:?}",
+                c.mpeg_version, c.audio_object_type, c.sampling_frequency, c.channel_configuration
+            ),
+            None => "-".to_string(),
+        },
+    );
+    item("frames", stats.frames.to_string());
+    item("bytes", stats.bytes.to_string());
+    item(
+        "duration",
+        format!("{:.3}s", stats.duration.to_duration().as_secs_f64()),
+    );
+    item(
+        "mean bitrate",
+        match stats.mean_bitrate() {
+            Some(bitrate) => format!("{:.0} bit/s", bitrate),
+            None => "-".to_string(),
+        },
+    );
+    item(
+        "frame length",
+        match (
+            stats.min_frame_length,
+            stats.max_frame_length,
+            stats.mean_frame_length(),
+        ) {
+            (Some(min), Some(max), Some(mean)) => {
+                format!("{} to {} bytes (mean {:.1})", min, max, mean)
+            }
+            _ => "-".to_string(),
+        },
+    );
+    item(
+        "buffer fullness",
+        format!(
+            "{} variable rate, {} constant rate",
+            stats.vbr_frames,
+            stats.frames - stats.vbr_frames
+        ),
+    );
+    item(
+        "errors",
+        format!(
+            "{} in bitstream, {} CRC mismatches",
+            stats.errors, stats.crc_errors
+        ),
+    );
+    item("config changes", stats.config_changes.to_string());
+    out
+}
+
+/// The same summary as [`format_summary()`](fn.format_summary.html), as a JSON object for
+/// scripting.  Available when the `serde` feature is also enabled.
+#[cfg(feature = "serde")]
+pub fn summary_json(stats: &AdtsStreamStats) -> serde_json::Value {
+    serde_json::json!({
+        "config": stats.config,
+        "frames": stats.frames,
+        "bytes": stats.bytes,
+        "samples": stats.samples,
+        "duration": stats.duration.to_duration().as_secs_f64(),
+        "mean_bitrate": stats.mean_bitrate(),
+        "min_frame_length": stats.min_frame_length,
+        "max_frame_length": stats.max_frame_length,
+        "mean_frame_length": stats.mean_frame_length(),
+        "vbr_frames": stats.vbr_frames,
+        "fullness_histogram": stats.fullness_histogram,
+        "errors": stats.errors,
+        "crc_errors": stats.crc_errors,
+        "config_changes": stats.config_changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexdump_lines() {
+        let buf: Vec<u8> = (0x20..0x32).collect();
+        assert_eq!(
+            hexdump(&buf),
+            "00000000  20 21 22 23 24 25 26 27  28 29 2a 2b 2c 2d 2e 2f  | !\"#$%&'()*+,-./|\n\
+             00000010  30 31                                             |01|\n"
+        );
+    }
+
+    #[test]
+    fn frame() {
+        let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+        let header = AdtsHeader::from_bytes(&buf).unwrap();
+        let options = InspectOptions {
+            hexdump_limit: Some(0),
+            ..InspectOptions::default()
+        };
+        let text = format_frame(&header, &buf[7..], &options);
+        assert!(text.starts_with("ADTS frame: 8 bytes (header 7, payload 1)\n"));
+        assert!(text.contains("  sampling_frequency:              Freq48000 (48000 Hz)\n"));
+        assert!(text.contains("  adts_buffer_fullness:            0x7ff (variable bitrate)\n"));
+        assert!(text.ends_with("... 1 more bytes\n"));
+    }
+
+    #[test]
+    fn rows_and_summary() {
+        use crate::testing::StreamBuilder;
+        use crate::{AdtsParser, FrameOptions};
+        let mut data = StreamBuilder::new()
+            .options(FrameOptions {
+                crc: true,
+                buffer_fullness: 0x100,
+                ..FrameOptions::default()
+            })
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        *data.last_mut().unwrap() ^= 1;
+        let second = AdtsHeader::from_bytes(&data[13..]).unwrap();
+        let row = FrameRow::new(1, 13, &second);
+        assert_eq!(row.crc, CrcStatus::Mismatch);
+        assert_eq!(
+            row.format(),
+            "       1           13      13       1     0x100  MISMATCH"
+        );
+        let truncated = AdtsHeader::from_bytes(&data[13..24]).unwrap();
+        assert_eq!(CrcStatus::of(&truncated), CrcStatus::Unverifiable);
+
+        let mut parser =
+            AdtsParser::new(AdtsStreamStats::new()).with_crc_policy(crate::crc::CrcPolicy::Flag);
+        parser.push(&data);
+        let text = format_summary(&parser.consumer);
+        assert!(text.starts_with("configuration:    Mpeg4 AacLC Freq48000 Stereo\n"));
+        assert!(text.contains("frames:           2\n"));
+        assert!(text.contains("frame length:     13 to 13 bytes (mean 13.0)\n"));
+        assert!(text.contains("errors:           0 in bitstream, 1 CRC mismatches\n"));
+        assert_eq!(
+            format_summary(&AdtsStreamStats::new()).lines().next(),
+            Some("configuration:    -")
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Exchange with other formats and systems: MPEG-4 `AudioSpecificConfig` and `esds`,
+//! timestamped packets for media frameworks, RFC 3640 RTP payloads, a Symphonia `FormatReader`,
+//! an mpeg2ts-reader `ElementaryStreamConsumer`, and serialised reports.
+//!
+//! [`packets`](packets/index.html) needs the `packets` feature,
+//! [`symphonia`](symphonia/index.html) needs the `symphonia` feature,
+//! [`mpeg2ts`](mpeg2ts/index.html) needs the `mpeg2ts` feature, and
+//! [`report`](report/index.html) and `JsonLinesConsumer` need the `serde` feature.
+//!
+//! ```rust
+//! use adts_reader::interop::asc::AudioSpecificConfig;
+//! let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+//! assert_eq!(asc.sampling_frequency, 44100);
+//! ```
+
+pub use crate::asc;
+pub use crate::esds;
+#[cfg(feature = "mpeg2ts")]
+pub use crate::mpeg2ts;
+#[cfg(feature = "packets")]
+pub use crate::packets;
+#[cfg(feature = "serde")]
+pub use crate::report;
+pub use crate::rtp;
+#[cfg(feature = "symphonia")]
+pub use crate::symphonia;
+#[cfg(feature = "serde")]
+pub use crate::JsonLinesConsumer;
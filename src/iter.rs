@@ -0,0 +1,188 @@
+//! Iteration over the frames in a buffer, as an alternative to the callbacks of `AdtsParser` when
+//! all the data is already in memory.
+
+use crate::sync::find_possible_sync;
+use crate::{AdtsHeader, AdtsHeaderError};
+use std::iter::FusedIterator;
+
+/// An iterator over the ADTS frames in a buffer, yielding the header of each complete frame.
+///
+/// Data which can't be parsed as a frame is reported as a single `Err` item for each run of such
+/// data, after which iteration carries on from the next frame found.  Iteration ends at the end
+/// of the buffer, at a trailing frame which the buffer holds only part of, or when fewer bytes
+/// than a header remain; any such incomplete data is available from [`remainder()`](#method.remainder), for example to be
+/// prepended to the next buffer read.
+///
+/// ```rust
+/// use adts_reader::AdtsFrameIter;
+/// # let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21, 0xff, 0xf1, 0x4c];
+/// let mut frames = AdtsFrameIter::new(&buf);
+/// let payload_bytes: usize = frames
+///     .by_ref()
+///     .filter_map(Result::ok)
+///     .map(|header| header.payload().unwrap().len())
+///     .sum();
+/// assert_eq!(payload_bytes, 1);
+/// // the start of a second frame was left over
+/// assert_eq!(frames.remainder(), &[0xff, 0xf1, 0x4c]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdtsFrameIter<'buf> {
+    buf: &'buf [u8],
+    pos: usize,
+}
+impl<'buf> AdtsFrameIter<'buf> {
+    pub fn new(buf: &'buf [u8]) -> AdtsFrameIter<'buf> {
+        AdtsFrameIter { buf, pos: 0 }
+    }
+
+    /// The position within the buffer of the start of the data not yet consumed, which is where
+    /// the frame returned by the next call to `next()` will start, unless there is an error
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// The data not yet consumed; once iteration has ended, this is any trailing incomplete frame
+    pub fn remainder(&self) -> &'buf [u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Adapts this iterator to yield the position of each item within the buffer along with it
+    ///
+    /// ```rust
+    /// use adts_reader::AdtsFrameIter;
+    /// # let buf = [0, 0, 0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// for (offset, frame) in AdtsFrameIter::new(&buf).with_offsets() {
+    ///     match frame {
+    ///         Ok(header) => println!("frame at {}, {} bytes", offset, header.frame_length()),
+    ///         Err(e) => println!("unexpected data at {}: {:?}", offset, e),
+    ///     }
+    /// }
+    /// ```
+    pub fn with_offsets(self) -> WithOffsets<'buf> {
+        WithOffsets { frames: self }
+    }
+}
+impl<'buf> Iterator for AdtsFrameIter<'buf> {
+    type Item = Result<AdtsHeader<'buf>, AdtsHeaderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut error = None;
+        loop {
+            let rest = &self.buf[self.pos..];
+            if rest.is_empty() {
+                return error.map(Err);
+            }
+            match AdtsHeader::from_bytes(rest) {
+                Ok(header) if header.payload().is_ok() => {
+                    if let Some(e) = error {
+                        // report the error now, and return this frame on the next call
+                        return Some(Err(e));
+                    }
+                    self.pos += header.frame_len();
+                    return Some(Ok(header));
+                }
+                // an incomplete frame at the end of the buffer, or too little data left to hold a
+                // header
+                Ok(_) | Err(AdtsHeaderError::NotEnoughData { .. }) => return error.map(Err),
+                Err(e) => {
+                    // the whole run of unparsable data is reported as the first error in it
+                    error.get_or_insert(e);
+                    let skip = find_possible_sync(&rest[1..]).map_or(rest.len(), |i| i + 1);
+                    self.pos += skip;
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // every frame is at least 7 bytes, and each error item covers at least one byte and is
+        // followed by a frame, or by the end of the data
+        let remaining = self.buf.len() - self.pos;
+        (0, Some(remaining.div_ceil(4)))
+    }
+}
+// once only an incomplete frame is left, it stays unconsumed, so every later call to next()
+// returns None too
+impl<'buf> FusedIterator for AdtsFrameIter<'buf> {}
+
+/// An iterator yielding the items of an [`AdtsFrameIter`](struct.AdtsFrameIter.html) along with
+/// their position in the buffer, created by
+/// [`AdtsFrameIter::with_offsets()`](struct.AdtsFrameIter.html#method.with_offsets).
+#[derive(Debug, Clone)]
+pub struct WithOffsets<'buf> {
+    frames: AdtsFrameIter<'buf>,
+}
+impl<'buf> WithOffsets<'buf> {
+    /// As for [`AdtsFrameIter::remainder()`](struct.AdtsFrameIter.html#method.remainder)
+    pub fn remainder(&self) -> &'buf [u8] {
+        self.frames.remainder()
+    }
+}
+impl<'buf> Iterator for WithOffsets<'buf> {
+    type Item = (usize, Result<AdtsHeader<'buf>, AdtsHeaderError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.frames.position();
+        self.frames.next().map(|item| (offset, item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+impl<'buf> FusedIterator for WithOffsets<'buf> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn frames_garbage_and_truncation() {
+        let data = StreamBuilder::new()
+            .frame(&[1])
+            // a run of garbage holding false sync-words is reported once
+            .bytes(&[0, 0xff, 0xf1, 0, 0, 0, 0, 0])
+            .frame(&[1; 2])
+            .frame(&[1; 3])
+            .frame(&[1; 4])
+            .truncate_last_frame()
+            .build();
+
+        let frames = AdtsFrameIter::new(&data);
+        assert!(frames.size_hint().1.unwrap() >= 4);
+        let mut frames = frames.with_offsets();
+        let items: Vec<_> = frames
+            .by_ref()
+            .map(|(offset, item)| (offset, item.map(|h| h.frame_length())))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (0, Ok(8)),
+                (8, Err(AdtsHeaderError::BadSyncWord(0x00f))),
+                (16, Ok(9)),
+                (25, Ok(10)),
+            ]
+        );
+        assert_eq!(frames.remainder(), &data[35..]);
+        assert!(frames.next().is_none());
+
+        // garbage at the end is reported, and a short tail left as the remainder
+        let mut frames = AdtsFrameIter::new(&[0xff, 0xf1, 0x4c, 0x80, 0x00, 0x1f, 0xfc, 0, 0]);
+        assert_eq!(
+            frames.next().and_then(Result::err),
+            Some(AdtsHeaderError::BadFrameLength {
+                minimum: 7,
+                actual: 0
+            })
+        );
+        assert!(frames.next().is_none());
+        assert!(AdtsFrameIter::new(&[]).next().is_none());
+        let mut frames = AdtsFrameIter::new(&[0, 0, 0]);
+        assert!(frames.next().is_none());
+        assert_eq!(frames.remainder(), &[0, 0, 0]);
+    }
+}
@@ -0,0 +1,196 @@
+//! An `AdtsConsumer2` writing each parser event as a line of JSON.
+
+use crate::{AdtsConsumer2, AdtsParseError, AdtsStreamConfig, FrameContext, ProtectionIndicator};
+use serde::Serialize;
+use std::io;
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Config {
+        protection: ProtectionIndicator,
+        #[serde(flatten)]
+        config: &'a AdtsStreamConfig,
+    },
+    Frame {
+        index: u64,
+        buffer_fullness: u16,
+        number_of_blocks: u8,
+        payload_length: usize,
+    },
+    Error {
+        error: &'a AdtsParseError,
+    },
+    /// The parser found a frame again after discarding data because of an error
+    Resync {
+        index: u64,
+        offset: u64,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Writes one JSON object per line to the given `io::Write` for every event
+/// [`AdtsParser`](struct.AdtsParser.html) produces, so that a stream can be analysed with tools
+/// like `jq`.
+///
+/// Available when the `serde` feature is enabled.
+///
+/// Each object has an `"event"` property naming the kind of event (`"config"`, `"frame"`,
+/// `"error"` or `"resync"`), plus properties specific to that kind of event.  A `"resync"` event
+/// precedes the first frame found after data was discarded because of an error, giving the
+/// `"index"` and byte `"offset"` of that frame, so that the gap in the audio can be located.  When the output of several
+/// streams is gathered together, [`with_label()`](#method.with_label) adds a `"stream"` property
+/// to every object, identifying the stream it came from.
+///
+/// ```rust
+/// use adts_reader::{AdtsParser, JsonLinesConsumer};
+/// let mut parser = AdtsParser::new(JsonLinesConsumer::new(vec![]));
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// let out = String::from_utf8(parser.consumer.into_inner()).unwrap();
+/// assert_eq!(out.lines().count(), 2);
+/// ```
+///
+/// Since `AdtsConsumer` methods can't return errors, the first error produced by the writer is
+/// held and no further output is attempted; check [`error()`](#method.error) once parsing is
+/// complete.
+pub struct JsonLinesConsumer<W: io::Write> {
+    writer: W,
+    error: Option<io::Error>,
+    label: Option<String>,
+}
+impl<W: io::Write> JsonLinesConsumer<W> {
+    pub fn new(writer: W) -> JsonLinesConsumer<W> {
+        JsonLinesConsumer {
+            writer,
+            error: None,
+            label: None,
+        }
+    }
+
+    /// Adds a `"stream"` property with the given value to every line of output, typically the
+    /// same label as given to
+    /// [`AdtsParser::with_label()`](struct.AdtsParser.html#method.with_label)
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// The first error encountered when writing output, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn emit(&mut self, event: &Event<'_>) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = Line {
+            stream: self.label.as_deref(),
+            event,
+        };
+        let res = serde_json::to_writer(&mut self.writer, &line)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+        if let Err(e) = res {
+            self.error = Some(e);
+        }
+    }
+}
+impl<W: io::Write> AdtsConsumer2 for JsonLinesConsumer<W> {
+    fn on_config(&mut self, config: AdtsStreamConfig, protection: ProtectionIndicator) {
+        self.emit(&Event::Config {
+            protection,
+            config: &config,
+        });
+    }
+
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        if frame.resynced {
+            self.emit(&Event::Resync {
+                index: frame.index,
+                offset: frame.offset,
+            });
+        }
+        self.emit(&Event::Frame {
+            index: frame.index,
+            buffer_fullness: frame.header.adts_buffer_fullness(),
+            number_of_blocks: frame.header.number_of_raw_data_blocks_in_frame(),
+            payload_length: frame.payload.len(),
+        });
+    }
+
+    fn on_error(&mut self, err: AdtsParseError) {
+        self.emit(&Event::Error { error: &err });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::tests::{make_test_data, write_frame};
+    use crate::AdtsParser;
+
+    #[test]
+    fn events() {
+        let mut buf = make_test_data(|mut w| {
+            write_frame(&mut w)?;
+            write_frame(&mut w)
+        });
+        buf.push(0);
+        let mut parser = AdtsParser::new(JsonLinesConsumer::new(vec![]));
+        parser.push(&buf);
+        // supply enough data for the trailing junk to be parsed as a header
+        parser.push(&[0; 6]);
+        let out = String::from_utf8(parser.consumer.into_inner()).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"config","protection":"CrcAbsent","mpeg_version":"Mpeg4","audio_object_type":"AacMain","sampling_frequency":"Freq48000","private_bit":1,"channel_configuration":"Stereo","originality":"Copy","home":0}"#,
+                r#"{"event":"frame","index":0,"buffer_fullness":123,"number_of_blocks":1,"payload_length":1}"#,
+                r#"{"event":"frame","index":1,"buffer_fullness":123,"number_of_blocks":1,"payload_length":1}"#,
+                r#"{"event":"error","error":"BadSyncWord"}"#,
+            ]
+        );
+
+        let mut parser = AdtsParser::new(JsonLinesConsumer::new(vec![]).with_label("cam1"));
+        parser.push(&buf[..buf.len() - 1]);
+        let out = String::from_utf8(parser.consumer.into_inner()).unwrap();
+        assert!(out
+            .lines()
+            .all(|line| line.starts_with(r#"{"stream":"cam1","event":"#)));
+    }
+
+    #[test]
+    fn resync() {
+        let builder = StreamBuilder::new()
+            .frames(1, StreamBuilder::DEFAULT_CONFIG)
+            .junk(3)
+            .frames(1, StreamBuilder::DEFAULT_CONFIG);
+        let offset = builder.offsets()[1];
+        let mut parser = AdtsParser::new(JsonLinesConsumer::new(vec![]));
+        parser.push(&builder.build());
+        let out = String::from_utf8(parser.consumer.into_inner()).unwrap();
+        let lines: Vec<_> = out.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"error","error":"BadSyncWord"}"#.to_string(),
+                format!(r#"{{"event":"resync","index":1,"offset":{}}}"#, offset),
+                r#"{"event":"frame","index":1,"buffer_fullness":2047,"number_of_blocks":1,"payload_length":4}"#.to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! Parsing of AAC carried in LOAS/LATM framing (ISO/IEC 14496-3 1.7), as used by many DVB
+//! broadcasts in place of ADTS.
+//!
+//! A LOAS `AudioSyncStream` frame is a sync-word and length, followed by an LATM
+//! `AudioMuxElement`.  The element may carry a `StreamMuxConfig`, describing the audio with an
+//! embedded `AudioSpecificConfig`, or may signal that the previous configuration still applies,
+//! so unlike ADTS frames, LOAS frames can't generally be understood in isolation.
+//! [`LoasParser`](struct.LoasParser.html) keeps track of this, passing each access unit to a
+//! [`LoasConsumer`](trait.LoasConsumer.html) in the manner of `AdtsParser`.
+//!
+//! Only the configurations found in practice are supported: a single program of a single layer
+//! carrying AAC, with all payloads in an element sharing the same time framing.  Elements using
+//! anything else are reported as errors.
+//!
+//! ```rust
+//! use adts_reader::latm::{LatmHeader, StreamMuxConfig};
+//! // AAC-LC 48kHz stereo configuration, followed by a 1 byte access unit
+//! let buf = [0x56, 0xe0, 0x08, 0x20, 0x00, 0x11, 0x90, 0x1f, 0xe0, 0x09, 0x08];
+//! let header = LatmHeader::from_bytes(&buf).unwrap();
+//! assert_eq!(header.frame_len(), 11);
+//! let element = header.decode(None).unwrap();
+//! let config = element.stream_mux_config.unwrap();
+//! assert_eq!(config.audio_specific_config.sampling_frequency, 48000);
+//! assert_eq!(element.payloads, vec![vec![0x21]]);
+//! ```
+
+use crate::asc::{self, AscError, AudioSpecificConfig};
+use crate::bits::BitReader;
+use crate::PayloadError;
+
+/// The 11-bit `syncword` starting every LOAS `AudioSyncStream` frame
+const SYNC_WORD: u16 = 0x2b7;
+/// Length of the sync-word and `audioMuxLengthBytes` fields
+const HEADER_LEN: usize = 3;
+
+/// Error indicating that the given data does not start with a LOAS frame header.
+#[derive(Debug, PartialEq)]
+pub enum LatmHeaderError {
+    /// Indicates that the given buffer did not start with the 11-bit sync-word `0x2b7`
+    BadSyncWord(u16),
+    NotEnoughData {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Error indicating that an `AudioMuxElement` could not be decoded.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LatmError {
+    /// The data ended before all of the fields the element signals, either because the frame is
+    /// incomplete or because `audioMuxLengthBytes` is too small
+    Truncated,
+    /// The element relies on an earlier `StreamMuxConfig` (`useSameStreamMux` is set), but none
+    /// has been seen
+    NoStreamMuxConfig,
+    /// `audioMuxVersionA` holds the reserved value 1
+    ReservedMuxVersion,
+    /// More than one program or layer is multiplexed into the stream
+    MultipleStreams,
+    /// `allStreamsSameTimeFraming` is not set
+    IndependentTimeFraming,
+    /// A `frameLengthType` other than 0, used for object types other than AAC (such as CELP)
+    UnsupportedFrameLengthType(u8),
+    /// The embedded `AudioSpecificConfig` could not be parsed, or its length could not be
+    /// determined
+    AudioSpecificConfig(AscError),
+}
+impl From<AscError> for LatmError {
+    fn from(err: AscError) -> LatmError {
+        LatmError::AudioSpecificConfig(err)
+    }
+}
+
+/// The fields of an LATM `StreamMuxConfig` describing a single AAC stream.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct StreamMuxConfig {
+    /// Either 0 or 1; version 1 gives the length of some fields explicitly
+    pub audio_mux_version: u8,
+    /// One less than the number of payloads (access units) in each `AudioMuxElement`
+    pub num_sub_frames: u8,
+    pub audio_specific_config: AudioSpecificConfig,
+    pub latm_buffer_fullness: u8,
+    /// The length in bits of the other data at the end of each `AudioMuxElement`, if present
+    pub other_data_len_bits: Option<u32>,
+    /// The `crcCheckSum` value, if present
+    pub crc_check_sum: Option<u8>,
+}
+impl StreamMuxConfig {
+    fn read(r: &mut BitReader<'_>) -> Result<StreamMuxConfig, LatmError> {
+        let audio_mux_version = read(r, 1)? as u8;
+        if audio_mux_version == 1 {
+            // audioMuxVersionA
+            if read_bool(r)? {
+                return Err(LatmError::ReservedMuxVersion);
+            }
+            // taraBufferFullness
+            latm_get_value(r)?;
+        }
+        if !read_bool(r)? {
+            return Err(LatmError::IndependentTimeFraming);
+        }
+        let num_sub_frames = read(r, 6)? as u8;
+        // numProgram, then numLayer of the first program, both one less than the count
+        if read(r, 4)? != 0 || read(r, 3)? != 0 {
+            return Err(LatmError::MultipleStreams);
+        }
+        // useSameConfig is absent for the first layer of the first program
+        let audio_specific_config = if audio_mux_version == 1 {
+            let asc_len = latm_get_value(r)?;
+            asc::read_embedded(r, Some(asc_len as usize))?
+        } else {
+            asc::read_embedded(r, None)?
+        };
+        let frame_length_type = read(r, 3)? as u8;
+        if frame_length_type != 0 {
+            return Err(LatmError::UnsupportedFrameLengthType(frame_length_type));
+        }
+        let latm_buffer_fullness = read(r, 8)? as u8;
+        let other_data_len_bits = if read_bool(r)? {
+            Some(if audio_mux_version == 1 {
+                latm_get_value(r)?
+            } else {
+                let mut bits = 0u32;
+                loop {
+                    let escape = read_bool(r)?;
+                    bits = bits.saturating_mul(256).saturating_add(read(r, 8)?);
+                    if !escape {
+                        break bits;
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        let crc_check_sum = if read_bool(r)? {
+            Some(read(r, 8)? as u8)
+        } else {
+            None
+        };
+        Ok(StreamMuxConfig {
+            audio_mux_version,
+            num_sub_frames,
+            audio_specific_config,
+            latm_buffer_fullness,
+            other_data_len_bits,
+            crc_check_sum,
+        })
+    }
+}
+
+/// The contents of a decoded `AudioMuxElement`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AudioMuxElement {
+    /// The configuration carried in the element, or `None` if the element relies on an earlier
+    /// one
+    pub stream_mux_config: Option<StreamMuxConfig>,
+    /// The payload of each sub-frame; each is one access unit
+    pub payloads: Vec<Vec<u8>>,
+}
+
+/// The header of a LOAS `AudioSyncStream` frame, along with the `AudioMuxElement` which follows
+/// it.
+pub struct LatmHeader<'buf> {
+    buf: &'buf [u8],
+}
+impl<'buf> LatmHeader<'buf> {
+    /// Parses the header at the start of the given buffer, which must hold at least the 3 header
+    /// bytes, but need not hold the whole frame
+    pub fn from_bytes(buf: &'buf [u8]) -> Result<LatmHeader<'buf>, LatmHeaderError> {
+        if buf.len() < HEADER_LEN {
+            return Err(LatmHeaderError::NotEnoughData {
+                expected: HEADER_LEN,
+                actual: buf.len(),
+            });
+        }
+        let sync_word = u16::from(buf[0]) << 3 | u16::from(buf[1] >> 5);
+        if sync_word != SYNC_WORD {
+            return Err(LatmHeaderError::BadSyncWord(sync_word));
+        }
+        Ok(LatmHeader { buf })
+    }
+
+    /// Length of the `AudioMuxElement` following the header
+    pub fn audio_mux_length_bytes(&self) -> u16 {
+        u16::from(self.buf[1] & 0x1f) << 8 | u16::from(self.buf[2])
+    }
+
+    /// Length of the whole frame, header included
+    pub fn frame_len(&self) -> usize {
+        HEADER_LEN + usize::from(self.audio_mux_length_bytes())
+    }
+
+    /// The bytes of the `AudioMuxElement`, or an error if the buffer is too short to hold it all
+    pub fn audio_mux_element(&self) -> Result<&'buf [u8], PayloadError> {
+        self.buf
+            .get(HEADER_LEN..self.frame_len())
+            .ok_or(PayloadError {
+                expected: self.frame_len(),
+                actual: self.buf.len(),
+            })
+    }
+
+    /// The `useSameStreamMux` flag, which if `true` means that the element has no
+    /// `StreamMuxConfig` of its own.  Returns `false` if the element is empty.
+    pub fn use_same_stream_mux(&self) -> bool {
+        self.buf.get(HEADER_LEN).is_some_and(|&b| b & 0x80 != 0)
+    }
+
+    /// Decodes the `AudioMuxElement`, given the configuration from earlier frames (if any), for
+    /// use where the element has no configuration of its own.
+    pub fn decode(&self, current: Option<&StreamMuxConfig>) -> Result<AudioMuxElement, LatmError> {
+        let element = self.audio_mux_element().map_err(|_| LatmError::Truncated)?;
+        let mut r = BitReader::new(element);
+        let stream_mux_config = if read_bool(&mut r)? {
+            None
+        } else {
+            Some(StreamMuxConfig::read(&mut r)?)
+        };
+        let config = stream_mux_config
+            .as_ref()
+            .or(current)
+            .ok_or(LatmError::NoStreamMuxConfig)?;
+        let mut payloads = Vec::with_capacity(usize::from(config.num_sub_frames) + 1);
+        for _ in 0..=config.num_sub_frames {
+            // PayloadLengthInfo, where each byte of 255 means that another follows
+            let mut len = 0;
+            loop {
+                let tmp = read(&mut r, 8)? as usize;
+                len += tmp;
+                if tmp != 255 {
+                    break;
+                }
+            }
+            // PayloadMux, which need not be byte aligned
+            if r.remaining() < len * 8 {
+                return Err(LatmError::Truncated);
+            }
+            let mut payload = Vec::with_capacity(len);
+            for _ in 0..len {
+                payload.push(read(&mut r, 8)? as u8);
+            }
+            payloads.push(payload);
+        }
+        if let Some(bits) = config.other_data_len_bits {
+            r.skip(bits as usize).ok_or(LatmError::Truncated)?;
+        }
+        Ok(AudioMuxElement {
+            stream_mux_config,
+            payloads,
+        })
+    }
+}
+
+fn read(r: &mut BitReader<'_>, bits: u32) -> Result<u32, LatmError> {
+    r.read(bits).ok_or(LatmError::Truncated)
+}
+
+fn read_bool(r: &mut BitReader<'_>) -> Result<bool, LatmError> {
+    r.read_bool().ok_or(LatmError::Truncated)
+}
+
+/// `LatmGetValue()`, a value of 1 to 4 bytes preceded by its length
+fn latm_get_value(r: &mut BitReader<'_>) -> Result<u32, LatmError> {
+    let bytes_for_value = read(r, 2)?;
+    read(r, 8 * (bytes_for_value + 1))
+}
+
+/// `true` if the buffer might start with a sync-word, given as much of it as the buffer holds
+fn maybe_sync(buf: &[u8]) -> bool {
+    buf[0] == 0x56 && !matches!(buf.get(1), Some(b) if b & 0xe0 != 0xe0)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoasParseError {
+    BadSyncWord,
+    /// A frame's `AudioMuxElement` could not be decoded, so its payloads were discarded
+    Latm(LatmError),
+}
+
+/// Receives the configuration and access units found by a
+/// [`LoasParser`](struct.LoasParser.html).
+pub trait LoasConsumer {
+    /// Called when a `StreamMuxConfig` differing from the previous one is found, which would
+    /// usually happen just once, at the start of the stream (although broadcasts typically
+    /// repeat the configuration periodically, repeats are not reported)
+    fn new_config(&mut self, config: &StreamMuxConfig);
+
+    /// Called with each access unit, which is the payload of a single sub-frame
+    fn payload(&mut self, buf: &[u8]);
+
+    /// Called if the parser encounters an error in the LOAS bitstream
+    fn error(&mut self, err: LoasParseError);
+}
+
+enum LoasState {
+    /// collecting the bytes of a frame, starting with its header
+    Frame,
+    /// searching for a sync-word following a bitstream error
+    Sync,
+}
+
+/// Find LOAS frames within provided buffers of data, in the manner of `AdtsParser`.
+///
+/// On encountering data which is not a LOAS frame, the parser reports a single error to the
+/// consumer for the whole run of such data, and resumes at the next sync-word.
+pub struct LoasParser<C: LoasConsumer> {
+    pub consumer: C,
+    config: Option<StreamMuxConfig>,
+    /// holds the frame being received, which is copied whole before decoding since payloads need
+    /// not be byte aligned
+    frame: Vec<u8>,
+    state: LoasState,
+    /// `true` from an error until the next frame is found
+    resyncing: bool,
+}
+impl<C: LoasConsumer> LoasParser<C> {
+    pub fn new(consumer: C) -> LoasParser<C> {
+        LoasParser {
+            consumer,
+            config: None,
+            frame: vec![],
+            state: LoasState::Frame,
+            resyncing: false,
+        }
+    }
+
+    /// Initialize or re-initialize parser state, dropping any partially received frame, so that
+    /// the next `push()` is expected to start with a new frame.  The last `StreamMuxConfig` is
+    /// kept, since later frames may rely on it.
+    pub fn start(&mut self) {
+        self.frame.clear();
+        self.state = LoasState::Frame;
+        self.resyncing = false;
+    }
+
+    /// The configuration in effect, if one has been found
+    pub fn stream_mux_config(&self) -> Option<&StreamMuxConfig> {
+        self.config.as_ref()
+    }
+
+    /// Extracts the access units from each frame completed by the given data, passing them to
+    /// the consumer
+    pub fn push(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            match self.state {
+                LoasState::Sync => match (0..buf.len()).find(|&i| maybe_sync(&buf[i..])) {
+                    Some(i) => {
+                        buf = &buf[i..];
+                        self.state = LoasState::Frame;
+                    }
+                    None => return,
+                },
+                LoasState::Frame => {
+                    let wanted = match LatmHeader::from_bytes(&self.frame) {
+                        Ok(header) => header.frame_len(),
+                        Err(_) => HEADER_LEN,
+                    };
+                    let n = (wanted - self.frame.len()).min(buf.len());
+                    self.frame.extend_from_slice(&buf[..n]);
+                    buf = &buf[n..];
+                    match LatmHeader::from_bytes(&self.frame) {
+                        Ok(header) if header.frame_len() == self.frame.len() => self.finish_frame(),
+                        Ok(_) | Err(LatmHeaderError::NotEnoughData { .. }) => (),
+                        Err(LatmHeaderError::BadSyncWord(_)) => {
+                            self.error(LoasParseError::BadSyncWord);
+                            let pending = std::mem::take(&mut self.frame);
+                            self.state = LoasState::Sync;
+                            // look for a sync-word following the false one, before carrying on
+                            // with buf
+                            self.push(&pending[1..]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes the frame just received, passing its contents to the consumer
+    fn finish_frame(&mut self) {
+        self.resyncing = false;
+        let result = LatmHeader::from_bytes(&self.frame)
+            .map_err(|_| LatmError::Truncated)
+            .and_then(|header| header.decode(self.config.as_ref()));
+        match result {
+            Ok(element) => {
+                if let Some(config) = element.stream_mux_config {
+                    if self.config.as_ref() != Some(&config) {
+                        self.consumer.new_config(&config);
+                        self.config = Some(config);
+                    }
+                }
+                for payload in &element.payloads {
+                    self.consumer.payload(payload);
+                }
+            }
+            Err(e) => self.consumer.error(LoasParseError::Latm(e)),
+        }
+        self.frame.clear();
+    }
+
+    fn error(&mut self, err: LoasParseError) {
+        if !self.resyncing {
+            self.resyncing = true;
+            self.consumer.error(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bits::BitWriter;
+
+    /// Writes a frame with the given payloads, and an AAC-LC 48kHz stereo configuration of the
+    /// given `audioMuxVersion` unless `config` is `None`
+    fn loas_frame(config: Option<u8>, payloads: &[&[u8]]) -> Vec<u8> {
+        let mut w = BitWriter::new();
+        w.write_bool(config.is_none());
+        if let Some(version) = config {
+            w.write(1, version.into());
+            if version == 1 {
+                // audioMuxVersionA, then taraBufferFullness as a 1 byte LatmGetValue()
+                w.write(1, 0);
+                w.write(2, 0);
+                w.write(8, 0xff);
+            }
+            // allStreamsSameTimeFraming, numSubFrames, numProgram, numLayer
+            w.write(1, 1);
+            w.write(6, payloads.len() as u32 - 1);
+            w.write(4, 0);
+            w.write(3, 0);
+            if version == 1 {
+                // ascLen, including 4 bits of fill
+                w.write(2, 0);
+                w.write(8, 20);
+            }
+            // AAC-LC, 48kHz, stereo, GASpecificConfig
+            w.write(5, 2);
+            w.write(4, 3);
+            w.write(4, 2);
+            w.write(3, 0);
+            if version == 1 {
+                w.write(4, 0);
+            }
+            // frameLengthType, latmBufferFullness
+            w.write(3, 0);
+            w.write(8, 0xff);
+            // otherDataPresent with 8 bits of other data, and crcCheckPresent
+            w.write(1, 1);
+            if version == 1 {
+                w.write(2, 0);
+                w.write(8, 8);
+            } else {
+                w.write(1, 0);
+                w.write(8, 8);
+            }
+            w.write(1, 1);
+            w.write(8, 0x5a);
+        }
+        for payload in payloads {
+            let mut len = payload.len();
+            while len >= 255 {
+                w.write(8, 255);
+                len -= 255;
+            }
+            w.write(8, len as u32);
+            for &b in payload.iter() {
+                w.write(8, b.into());
+            }
+        }
+        w.write(8, 0xaa);
+        let element = w.into_bytes();
+        let mut frame = vec![0x56, 0xe0 | (element.len() >> 8) as u8, element.len() as u8];
+        frame.extend(element);
+        frame
+    }
+
+    #[test]
+    fn decode() {
+        for version in [0, 1] {
+            let long = [7; 300];
+            let frame = loas_frame(Some(version), &[&[1, 2, 3], &long]);
+            let header = LatmHeader::from_bytes(&frame).unwrap();
+            assert_eq!(header.frame_len(), frame.len());
+            assert!(!header.use_same_stream_mux());
+            let element = header.decode(None).unwrap();
+            let config = element.stream_mux_config.unwrap();
+            assert_eq!(config.audio_mux_version, version);
+            assert_eq!(config.num_sub_frames, 1);
+            assert_eq!(config.audio_specific_config.audio_object_type, 2);
+            assert_eq!(config.audio_specific_config.channel_configuration, 2);
+            assert_eq!(config.other_data_len_bits, Some(8));
+            assert_eq!(config.crc_check_sum, Some(0x5a));
+            assert_eq!(element.payloads, vec![vec![1, 2, 3], long.to_vec()]);
+
+            let frame = loas_frame(None, &[&[4], &[5, 6]]);
+            let header = LatmHeader::from_bytes(&frame).unwrap();
+            assert!(header.use_same_stream_mux());
+            assert_eq!(header.decode(None), Err(LatmError::NoStreamMuxConfig));
+            let element = header.decode(Some(&config)).unwrap();
+            assert_eq!(element.payloads, vec![vec![4], vec![5, 6]]);
+            assert_eq!(
+                LatmHeader::from_bytes(&frame[..frame.len() - 1])
+                    .unwrap()
+                    .decode(Some(&config)),
+                Err(LatmError::Truncated)
+            );
+        }
+        assert_eq!(
+            LatmHeader::from_bytes(&[0x56, 0xc0, 0]).err(),
+            Some(LatmHeaderError::BadSyncWord(0x2b6))
+        );
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        configs: usize,
+        payloads: Vec<Vec<u8>>,
+        errors: Vec<LoasParseError>,
+    }
+    impl LoasConsumer for Recorder {
+        fn new_config(&mut self, _config: &StreamMuxConfig) {
+            self.configs += 1;
+        }
+        fn payload(&mut self, buf: &[u8]) {
+            self.payloads.push(buf.to_vec());
+        }
+        fn error(&mut self, err: LoasParseError) {
+            self.errors.push(err);
+        }
+    }
+
+    #[test]
+    fn parser() {
+        let mut data = loas_frame(None, &[&[1]]);
+        data.extend(loas_frame(Some(0), &[&[2]]));
+        data.extend(loas_frame(None, &[&[3]]));
+        // garbage including a false sync-word
+        data.extend_from_slice(&[0, 0x56, 0x00, 0x56]);
+        // a repeat of the configuration isn't reported, but a change is
+        data.extend(loas_frame(Some(0), &[&[4]]));
+        data.extend(loas_frame(Some(0), &[&[5], &[6]]));
+        for split in 0..data.len() {
+            let mut parser = LoasParser::new(Recorder::default());
+            parser.push(&data[..split]);
+            parser.push(&data[split..]);
+            let consumer = &parser.consumer;
+            assert_eq!(consumer.configs, 2, "split at {}", split);
+            assert_eq!(
+                consumer.payloads,
+                vec![vec![2], vec![3], vec![4], vec![5], vec![6]],
+                "split at {}",
+                split
+            );
+            assert_eq!(
+                consumer.errors,
+                vec![
+                    LoasParseError::Latm(LatmError::NoStreamMuxConfig),
+                    LoasParseError::BadSyncWord,
+                ],
+                "split at {}",
+                split
+            );
+        }
+    }
+}
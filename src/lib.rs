@@ -16,22 +16,130 @@
 //! }
 //! ```
 //!
+//! # Module organisation
+//!
+//! The crate's API is grouped into three modules:
+//!
+//!  - [`frame`](frame/index.html): the header, the writer, and other pure functions of a single
+//!    frame
+//!  - [`stream`](stream/index.html): the parser, readers (including async readers, with the
+//!    `tokio` feature) and the tools built on them for whole streams
+//!  - [`interop`](interop/index.html): conversion to and from other formats, such as
+//!    `AudioSpecificConfig` and timestamped packets
+//!
+//! Each feature flag enables items within just one of these.  The same items also remain
+//! available at their previous paths (at the crate root, and as top-level modules) to ease
+//! migration; new code should use the grouped paths, as the old ones will be removed in a
+//! future breaking release.
+//!
+//! # Long-running streams
+//!
+//! Cumulative counters maintained by this crate (frame, byte and sample counts, and the like) are
+//! all `u64`, so will not overflow even for centuries of continuous audio at the highest sampling
+//! rates ADTS supports.
+//!
 //! # Unsupported
 //!
-//!  - Resynchronising `AdtsParser` after encountering bitstream error (we could search for
-//!    sync-word)
-//!  - Copyright identifiers (I don't have any example bitstreams to try)
-//!  - CRC handling (probably needs to be implemented as part of AAC bitstream parsing)
+//!  - CRCs covering only part of a `raw_data_block`: ISO/IEC 13818-7 limits the protection of
+//!    some syntactic elements to their initial bits, but since this crate doesn't parse AAC
+//!    syntax, [`verify_crc()`](struct.AdtsHeader.html#method.verify_crc) and the CRCs written by
+//!    [`write_frame()`](fn.write_frame.html) cover whole blocks
 
 #![forbid(unsafe_code)]
 #![deny(rust_2018_idioms, future_incompatible)]
 
-// TODO: might be better to implement AdtsParser as an iterator, rather then doing callbacks into a
-// trait implementation -- it looked hard to implement though!
-
 use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+pub mod analysis;
+pub mod arena;
+pub mod asc;
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+mod bits;
+pub mod blocks;
+#[cfg(feature = "tokio")]
+pub mod broadcast;
+mod callbacks;
+pub mod chunk;
+pub mod clock;
+pub mod compare;
+pub mod conformance;
+mod copyright;
+pub mod crc;
+mod decoded;
+pub mod dedup;
+pub mod descriptor;
+pub mod digest;
+mod edit;
+#[cfg(feature = "elements")]
+pub mod elements;
+pub mod encryption;
+pub mod esds;
+mod fields;
+pub mod follow;
+pub mod frame;
+mod heuristics;
+mod id3;
+pub mod index;
+#[cfg(feature = "inspect")]
+pub mod inspect;
+pub mod interop;
+mod iter;
+#[cfg(feature = "serde")]
+mod json;
+pub mod latm;
+mod metadata;
+pub mod monitor;
+#[cfg(feature = "mpeg2ts")]
+pub mod mpeg2ts;
+pub mod overview;
+mod owned;
+#[cfg(feature = "packets")]
+pub mod packets;
+mod pes;
+pub mod quirks;
+mod reader;
+pub mod repair;
+#[cfg(feature = "serde")]
+pub mod report;
+pub mod rtp;
+pub mod segment;
+mod stats;
+pub mod stream;
+#[cfg(feature = "symphonia")]
+pub mod symphonia;
+mod sync;
+pub mod tagged;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod time;
+mod validate;
+mod write;
+mod writer;
 
-#[derive(Debug)]
+#[cfg(feature = "serde")]
+pub use json::JsonLinesConsumer;
+
+pub use callbacks::Callbacks;
+pub use decoded::DecodedAdtsHeader;
+pub use edit::AdtsHeaderMut;
+pub use heuristics::{PayloadHeuristics, PayloadProfile, PayloadSuspicion};
+pub use iter::{AdtsFrameIter, WithOffsets};
+pub use metadata::{FrameMetadata, StreamMetadata};
+pub use owned::{OwnedAdtsFrame, OwnedFrames};
+pub use reader::{AdtsFrameReader, AdtsReadError};
+pub use stats::{AdtsStreamStats, ParserStats};
+pub use sync::find_syncword;
+pub use time::{FrameLength, MediaTime, PtsCounter, Rounding};
+pub use validate::{validate_frame, validate_frame_with, FrameError, ValidFrame, ValidateOptions};
+pub use write::{
+    encode_header, write_encrypted_frame, write_frame, EncodedHeader, FrameOptions, WriteError,
+};
+pub use writer::{AdtsHeaderBuilder, AdtsWriter};
+
+#[derive(Debug, PartialEq)]
 pub enum AdtsHeaderError {
     /// Indicates that the given buffer did not start with the required sequence of 12 '1'-bits
     /// (`0xfff`).
@@ -56,13 +164,36 @@ pub struct PayloadError {
     pub actual: usize,
 }
 
-#[derive(Debug, PartialEq)]
+/// A value out of range for the header field it was to be converted for, as by
+/// `SamplingFrequency::try_from()`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FieldValueError {
+    /// The name of the header field
+    pub field: &'static str,
+    pub value: u8,
+}
+
+/// A sampling frequency which ADTS has no `sampling_frequency_index` for, given to
+/// `SamplingFrequency::try_from()`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SamplingFrequencyError {
+    /// The frequency, in Hz
+    pub freq: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum MpegVersion {
     Mpeg2,
     Mpeg4,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// The value of the `profile` field, read as an MPEG-4 Audio Object Type (of one more than the
+/// field value), regardless of the header's `ID` field.  See [`Profile`](enum.Profile.html) for
+/// an interpretation which takes account of the MPEG version.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AudioObjectType {
     /// 'Main' profile
     AacMain,
@@ -73,14 +204,61 @@ pub enum AudioObjectType {
     /// 'Long Term Prediction' profile
     AacLTP,
 }
+impl From<AudioObjectType> for u8 {
+    /// The MPEG-4 Audio Object Type number, from `1` for `AacMain` to `4` for `AacLTP`
+    fn from(aot: AudioObjectType) -> u8 {
+        aot as u8 + 1
+    }
+}
+impl TryFrom<u8> for AudioObjectType {
+    type Error = FieldValueError;
 
-#[derive(Debug, PartialEq)]
+    /// Converts an MPEG-4 Audio Object Type number, which must be one of the four that ADTS can
+    /// signal
+    fn try_from(value: u8) -> Result<AudioObjectType, FieldValueError> {
+        match value {
+            1 => Ok(AudioObjectType::AacMain),
+            2 => Ok(AudioObjectType::AacLC),
+            3 => Ok(AudioObjectType::AacSSR),
+            4 => Ok(AudioObjectType::AacLTP),
+            _ => Err(FieldValueError {
+                field: "audio_object_type",
+                value,
+            }),
+        }
+    }
+}
+
+/// An MPEG-2 AAC profile, as defined by ISO/IEC 13818-7
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Mpeg2Profile {
+    Main,
+    LowComplexity,
+    ScalableSamplingRate,
+    /// The value `3`, which MPEG-2 doesn't assign
+    Reserved,
+}
+
+/// The meaning of the `profile` field, which depends on the MPEG version given by the `ID` field
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Profile {
+    /// In an MPEG-2 stream, the field gives the AAC profile
+    Mpeg2Profile(Mpeg2Profile),
+    /// In an MPEG-4 stream, the field gives the Audio Object Type, less one
+    Mpeg4AudioObjectType(AudioObjectType),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProtectionIndicator {
     CrcPresent,
     CrcAbsent,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SamplingFrequency {
     /// 96kHz
     Freq96000 = 0x0,
@@ -112,34 +290,73 @@ pub enum SamplingFrequency {
     FreqReserved0xf = 0xf,
 }
 
-impl From<u8> for SamplingFrequency {
-    fn from(value: u8) -> SamplingFrequency {
-        match value {
-            0x0 => SamplingFrequency::Freq96000,
-            0x1 => SamplingFrequency::Freq88200,
-            0x2 => SamplingFrequency::Freq64000,
-            0x3 => SamplingFrequency::Freq48000,
-            0x4 => SamplingFrequency::Freq44100,
-            0x5 => SamplingFrequency::Freq32000,
-            0x6 => SamplingFrequency::Freq24000,
-            0x7 => SamplingFrequency::Freq22050,
-            0x8 => SamplingFrequency::Freq16000,
-            0x9 => SamplingFrequency::Freq12000,
-            0xa => SamplingFrequency::Freq11025,
-            0xb => SamplingFrequency::Freq8000,
-            0xc => SamplingFrequency::FreqReserved0xc,
-            0xd => SamplingFrequency::FreqReserved0xd,
-            0xe => SamplingFrequency::FreqReserved0xe,
-            0xf => SamplingFrequency::FreqReserved0xf,
-            _ => panic!(
-                "invalid value {:#x} when parsing SamplingFrequency, expected a 4 bit value",
-                value
-            ),
+impl TryFrom<u8> for SamplingFrequency {
+    type Error = FieldValueError;
+
+    /// Converts a 4-bit `sampling_frequency_index` value
+    fn try_from(value: u8) -> Result<SamplingFrequency, FieldValueError> {
+        if value > 0xf {
+            return Err(FieldValueError {
+                field: "sampling_frequency_index",
+                value,
+            });
         }
+        Ok(SamplingFrequency::from_bits(value))
+    }
+}
+
+impl TryFrom<u32> for SamplingFrequency {
+    type Error = SamplingFrequencyError;
+
+    /// Finds the `sampling_frequency_index` value for the given frequency in Hz, which must be
+    /// one of the twelve that ADTS can signal
+    ///
+    /// ```rust
+    /// use adts_reader::SamplingFrequency;
+    /// let freq = SamplingFrequency::try_from(44100u32).unwrap();
+    /// assert_eq!(freq, SamplingFrequency::Freq44100);
+    /// assert_eq!(freq.index(), 4);
+    /// assert!(SamplingFrequency::try_from(44000u32).is_err());
+    /// ```
+    fn try_from(freq: u32) -> Result<SamplingFrequency, SamplingFrequencyError> {
+        SamplingFrequency::ALL
+            .iter()
+            .find(|f| f.freq() == Some(freq))
+            .copied()
+            .ok_or(SamplingFrequencyError { freq })
     }
 }
 
 impl SamplingFrequency {
+    const ALL: [SamplingFrequency; 16] = [
+        SamplingFrequency::Freq96000,
+        SamplingFrequency::Freq88200,
+        SamplingFrequency::Freq64000,
+        SamplingFrequency::Freq48000,
+        SamplingFrequency::Freq44100,
+        SamplingFrequency::Freq32000,
+        SamplingFrequency::Freq24000,
+        SamplingFrequency::Freq22050,
+        SamplingFrequency::Freq16000,
+        SamplingFrequency::Freq12000,
+        SamplingFrequency::Freq11025,
+        SamplingFrequency::Freq8000,
+        SamplingFrequency::FreqReserved0xc,
+        SamplingFrequency::FreqReserved0xd,
+        SamplingFrequency::FreqReserved0xe,
+        SamplingFrequency::FreqReserved0xf,
+    ];
+
+    /// The value for the low 4 bits of `bits`, which every value of those bits has
+    pub(crate) fn from_bits(bits: u8) -> SamplingFrequency {
+        SamplingFrequency::ALL[usize::from(bits & 0xf)]
+    }
+
+    /// The 4-bit `sampling_frequency_index` value
+    pub fn index(&self) -> u8 {
+        *self as u8
+    }
+
     pub fn freq(&self) -> Option<u32> {
         match self {
             SamplingFrequency::Freq96000 => Some(96000),
@@ -162,7 +379,8 @@ impl SamplingFrequency {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChannelConfiguration {
     ObjectTypeSpecificConfig = 0x0,
     Mono = 0x1,
@@ -173,37 +391,181 @@ pub enum ChannelConfiguration {
     FiveOne = 0x6,
     SevenOne = 0x7,
 }
-impl From<u8> for ChannelConfiguration {
-    fn from(value: u8) -> ChannelConfiguration {
-        match value {
-            0x0 => ChannelConfiguration::ObjectTypeSpecificConfig,
-            0x1 => ChannelConfiguration::Mono,
-            0x2 => ChannelConfiguration::Stereo,
-            0x3 => ChannelConfiguration::Three,
-            0x4 => ChannelConfiguration::Four,
-            0x5 => ChannelConfiguration::Five,
-            0x6 => ChannelConfiguration::FiveOne,
-            0x7 => ChannelConfiguration::SevenOne,
-            _ => panic!(
-                "invalid value {:#x} when parsing ChannelConfiguration, expected a 3 bit value",
-                value
-            ),
+impl ChannelConfiguration {
+    const ALL: [ChannelConfiguration; 8] = [
+        ChannelConfiguration::ObjectTypeSpecificConfig,
+        ChannelConfiguration::Mono,
+        ChannelConfiguration::Stereo,
+        ChannelConfiguration::Three,
+        ChannelConfiguration::Four,
+        ChannelConfiguration::Five,
+        ChannelConfiguration::FiveOne,
+        ChannelConfiguration::SevenOne,
+    ];
+
+    /// The value for the low 3 bits of `bits`, which every value of those bits has
+    pub(crate) fn from_bits(bits: u8) -> ChannelConfiguration {
+        ChannelConfiguration::ALL[usize::from(bits & 0x7)]
+    }
+
+    /// The number of audio channels, or `None` for `ObjectTypeSpecificConfig`, where the channel
+    /// layout is given in the payload instead.  `SevenOne` counts as 8 channels.
+    pub fn channel_count(&self) -> Option<u8> {
+        match self {
+            ChannelConfiguration::ObjectTypeSpecificConfig => None,
+            ChannelConfiguration::SevenOne => Some(8),
+            other => Some(*other as u8),
         }
     }
 }
+impl From<ChannelConfiguration> for u8 {
+    /// The 3-bit `channel_configuration` value
+    fn from(config: ChannelConfiguration) -> u8 {
+        config as u8
+    }
+}
+impl TryFrom<u8> for ChannelConfiguration {
+    type Error = FieldValueError;
 
-#[derive(Debug, PartialEq)]
+    /// Converts a 3-bit `channel_configuration` value
+    fn try_from(value: u8) -> Result<ChannelConfiguration, FieldValueError> {
+        if value > 0x7 {
+            return Err(FieldValueError {
+                field: "channel_configuration",
+                value,
+            });
+        }
+        Ok(ChannelConfiguration::from_bits(value))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Originality {
     Original,
     Copy,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CopyrightIdentificationStart {
     Start,
     Other,
 }
 
+/// The values of the ADTS header fields which are expected to stay the same for every frame in a
+/// stream (the _fixed header_, less the sync-word, layer and protection fields).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdtsStreamConfig {
+    pub mpeg_version: MpegVersion,
+    pub audio_object_type: AudioObjectType,
+    pub sampling_frequency: SamplingFrequency,
+    /// either 1 or 0
+    pub private_bit: u8,
+    pub channel_configuration: ChannelConfiguration,
+    pub originality: Originality,
+    /// either 1 or 0
+    pub home: u8,
+}
+impl AdtsStreamConfig {
+    /// The first three bytes of the header of frames with this configuration and the given
+    /// protection, as returned by [`AdtsHeader::config_bytes()`](struct.AdtsHeader.html#method.config_bytes).
+    ///
+    /// Fails if `private_bit` is out of range.
+    ///
+    /// ```rust
+    /// # use adts_reader::*;
+    /// let config = AdtsStreamConfig {
+    ///     mpeg_version: MpegVersion::Mpeg4,
+    ///     audio_object_type: AudioObjectType::AacLC,
+    ///     sampling_frequency: SamplingFrequency::Freq48000,
+    ///     private_bit: 0,
+    ///     channel_configuration: ChannelConfiguration::Stereo,
+    ///     originality: Originality::Original,
+    ///     home: 0,
+    /// };
+    /// let bytes = config.config_bytes(ProtectionIndicator::CrcAbsent).unwrap();
+    /// assert_eq!(bytes, [0xff, 0xf1, 0x4c]);
+    /// ```
+    pub fn config_bytes(&self, protection: ProtectionIndicator) -> Result<[u8; 3], WriteError> {
+        let options = FrameOptions {
+            crc: protection == ProtectionIndicator::CrcPresent,
+            ..FrameOptions::default()
+        };
+        let header = write::pack_header(self, &options, 9)?;
+        Ok([header[0], header[1], header[2]])
+    }
+}
+
+/// An owned copy of every value held in an ADTS header, for logging, comparing or sending
+/// elsewhere once the buffer holding the frame has gone; created with
+/// [`AdtsHeader::info()`](struct.AdtsHeader.html#method.info).
+///
+/// ```rust
+/// # use adts_reader::*;
+/// let header = AdtsHeader::from_bytes(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc]).unwrap();
+/// let info = header.info();
+/// assert_eq!(info.sampling_frequency, SamplingFrequency::Freq48000);
+/// assert_eq!(info.frame_length, 8);
+/// assert_eq!(info.crc, None);
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdtsHeaderInfo {
+    pub mpeg_version: MpegVersion,
+    pub protection: ProtectionIndicator,
+    pub audio_object_type: AudioObjectType,
+    pub sampling_frequency: SamplingFrequency,
+    /// either 1 or 0
+    pub private_bit: u8,
+    pub channel_configuration: ChannelConfiguration,
+    pub originality: Originality,
+    /// either 1 or 0
+    pub home: u8,
+    /// either 1 or 0
+    pub copyright_identification_bit: u8,
+    pub copyright_identification_start: CopyrightIdentificationStart,
+    /// length of the whole frame, including the header
+    pub frame_length: u16,
+    pub buffer_fullness: u16,
+    /// between 1 and 4 inclusive, as for
+    /// [`AdtsHeader::number_of_raw_data_blocks_in_frame()`](struct.AdtsHeader.html#method.number_of_raw_data_blocks_in_frame)
+    pub number_of_raw_data_blocks: u8,
+    /// the `crc_check` value, if present
+    pub crc: Option<u16>,
+}
+impl AdtsHeaderInfo {
+    /// The values of the fixed header fields
+    pub fn stream_config(&self) -> AdtsStreamConfig {
+        AdtsStreamConfig {
+            mpeg_version: self.mpeg_version,
+            audio_object_type: self.audio_object_type,
+            sampling_frequency: self.sampling_frequency,
+            private_bit: self.private_bit,
+            channel_configuration: self.channel_configuration,
+            originality: self.originality,
+            home: self.home,
+        }
+    }
+
+    /// The per-frame values, as needed to write a frame with the same header
+    pub fn frame_options(&self) -> FrameOptions {
+        FrameOptions {
+            crc: self.protection == ProtectionIndicator::CrcPresent,
+            buffer_fullness: self.buffer_fullness,
+            number_of_raw_data_blocks: self.number_of_raw_data_blocks,
+            copyright_identification_bit: self.copyright_identification_bit,
+            copyright_identification_start: self.copyright_identification_start,
+        }
+    }
+}
+impl<'buf> From<&AdtsHeader<'buf>> for AdtsHeaderInfo {
+    fn from(header: &AdtsHeader<'buf>) -> Self {
+        AdtsHeaderInfo::decode(header.buf)
+    }
+}
+
 /// Extract information for a single ADTS frame from the start of the given byte buffer .
 pub struct AdtsHeader<'buf> {
     buf: &'buf [u8],
@@ -218,8 +580,7 @@ impl<'buf> AdtsHeader<'buf> {
     /// the whole of the payload that the header indicates should be present (however _if_ there is
     /// not enough data to hold the payload, then [`payload()`](#method.payload) will return
     /// `None`).
-    pub fn from_bytes(buf: &'buf [u8]) -> Result<AdtsHeader<'_>, AdtsHeaderError> {
-        assert!(!buf.is_empty());
+    pub fn from_bytes(buf: &'buf [u8]) -> Result<AdtsHeader<'buf>, AdtsHeaderError> {
         let header_len = 7;
         Self::check_len(header_len, buf.len())?;
         let header = AdtsHeader { buf };
@@ -232,7 +593,7 @@ impl<'buf> AdtsHeader<'buf> {
         }
         if header.frame_length() < header.header_length() {
             return Err(AdtsHeaderError::BadFrameLength {
-                actual: header.frame_length() as usize,
+                actual: header.frame_len(),
                 minimum: header.header_length() as usize,
             });
         }
@@ -257,11 +618,11 @@ impl<'buf> AdtsHeader<'buf> {
     }
 
     fn sync_word(&self) -> u16 {
-        u16::from(self.buf[0]) << 4 | u16::from(self.buf[1] >> 4)
+        fields::SYNCWORD.get(self.buf) as u16
     }
 
     pub fn mpeg_version(&self) -> MpegVersion {
-        if self.buf[1] & 0b0000_1000 != 0 {
+        if fields::ID.get(self.buf) == 1 {
             MpegVersion::Mpeg2
         } else {
             MpegVersion::Mpeg4
@@ -269,7 +630,7 @@ impl<'buf> AdtsHeader<'buf> {
     }
 
     pub fn protection(&self) -> ProtectionIndicator {
-        if self.buf[1] & 0b0000_0001 != 0 {
+        if fields::PROTECTION_ABSENT.get(self.buf) == 1 {
             ProtectionIndicator::CrcAbsent
         } else {
             ProtectionIndicator::CrcPresent
@@ -278,30 +639,59 @@ impl<'buf> AdtsHeader<'buf> {
 
     // Indicates what type of AAC data this stream contains
     pub fn audio_object_type(&self) -> AudioObjectType {
-        match self.buf[2] & 0b1100_0000 {
-            0b0000_0000 => AudioObjectType::AacMain,
-            0b0100_0000 => AudioObjectType::AacLC,
-            0b1000_0000 => AudioObjectType::AacSSR,
-            0b1100_0000 => AudioObjectType::AacLTP,
-            v => panic!("impossible value {:#b}", v),
+        const ALL: [AudioObjectType; 4] = [
+            AudioObjectType::AacMain,
+            AudioObjectType::AacLC,
+            AudioObjectType::AacSSR,
+            AudioObjectType::AacLTP,
+        ];
+        ALL[fields::PROFILE.get(self.buf) as usize & 0x3]
+    }
+
+    /// The `profile` field, interpreted according to the header's
+    /// [`mpeg_version()`](#method.mpeg_version).  Unlike
+    /// [`audio_object_type()`](#method.audio_object_type), this distinguishes an MPEG-2 profile
+    /// from the MPEG-4 object type of the same value.
+    ///
+    /// ```rust
+    /// use adts_reader::{AdtsHeader, AudioObjectType, Mpeg2Profile, Profile};
+    /// let mpeg4 = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let header = AdtsHeader::from_bytes(&mpeg4).unwrap();
+    /// assert_eq!(header.profile(), Profile::Mpeg4AudioObjectType(AudioObjectType::AacLC));
+    /// let mpeg2 = [0xff, 0xf9, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let header = AdtsHeader::from_bytes(&mpeg2).unwrap();
+    /// assert_eq!(header.profile(), Profile::Mpeg2Profile(Mpeg2Profile::LowComplexity));
+    /// ```
+    pub fn profile(&self) -> Profile {
+        match self.mpeg_version() {
+            MpegVersion::Mpeg2 => {
+                const ALL: [Mpeg2Profile; 4] = [
+                    Mpeg2Profile::Main,
+                    Mpeg2Profile::LowComplexity,
+                    Mpeg2Profile::ScalableSamplingRate,
+                    Mpeg2Profile::Reserved,
+                ];
+                Profile::Mpeg2Profile(ALL[fields::PROFILE.get(self.buf) as usize & 0x3])
+            }
+            MpegVersion::Mpeg4 => Profile::Mpeg4AudioObjectType(self.audio_object_type()),
         }
     }
 
     pub fn sampling_frequency(&self) -> SamplingFrequency {
-        SamplingFrequency::from(self.buf[2] >> 2 & 0b1111)
+        SamplingFrequency::from_bits(fields::SAMPLING_FREQUENCY_INDEX.get(self.buf) as u8)
     }
 
     /// either 1 or 0
     pub fn private_bit(&self) -> u8 {
-        self.buf[2] & 1
+        fields::PRIVATE_BIT.get(self.buf) as u8
     }
 
     pub fn channel_configuration(&self) -> ChannelConfiguration {
-        ChannelConfiguration::from(self.buf[2] << 2 & 0b0100 | self.buf[3] >> 6)
+        ChannelConfiguration::from_bits(fields::CHANNEL_CONFIGURATION.get(self.buf) as u8)
     }
 
     pub fn originality(&self) -> Originality {
-        if self.buf[3] & 0b0010_0000 != 0 {
+        if fields::ORIGINAL_COPY.get(self.buf) == 1 {
             Originality::Copy
         } else {
             Originality::Original
@@ -310,27 +700,88 @@ impl<'buf> AdtsHeader<'buf> {
 
     /// either 1 or 0
     pub fn home(&self) -> u8 {
-        self.buf[3] >> 4 & 1
+        fields::HOME.get(self.buf) as u8
     }
 
     /// either 1 or 0
     pub fn copyright_identification_bit(&self) -> u8 {
-        self.buf[3] >> 3 & 1
+        fields::COPYRIGHT_IDENTIFICATION_BIT.get(self.buf) as u8
     }
 
     pub fn copyright_identification_start(&self) -> CopyrightIdentificationStart {
-        if self.buf[3] & 0b0000_0100 != 0 {
+        if fields::COPYRIGHT_IDENTIFICATION_START.get(self.buf) == 1 {
             CopyrightIdentificationStart::Start
         } else {
             CopyrightIdentificationStart::Other
         }
     }
 
+    /// The first three bytes of the header, exactly as they appear in the frame.
+    ///
+    /// These pack the sync-word, `ID`, `layer`, `protection_absent`, `profile`,
+    /// `sampling_frequency_index`, `private_bit` and the first bit of `channel_configuration`.
+    /// Some downstream systems want these bytes as they are, rather than the decoded fields.
+    pub fn config_bytes(&self) -> [u8; 3] {
+        [self.buf[0], self.buf[1], self.buf[2]]
+    }
+
+    /// The fixed-header values of this frame, which would normally be identical for all frames in
+    /// the stream.
+    pub fn stream_config(&self) -> AdtsStreamConfig {
+        AdtsStreamConfig {
+            mpeg_version: self.mpeg_version(),
+            audio_object_type: self.audio_object_type(),
+            sampling_frequency: self.sampling_frequency(),
+            private_bit: self.private_bit(),
+            channel_configuration: self.channel_configuration(),
+            originality: self.originality(),
+            home: self.home(),
+        }
+    }
+
+    /// Copies all of this header's values, so that they can outlive the buffer
+    pub fn info(&self) -> AdtsHeaderInfo {
+        AdtsHeaderInfo::from(self)
+    }
+
+    /// The MPEG-4 `AudioSpecificConfig` describing this frame's audio, as needed for the `esds`
+    /// box when repackaging the stream as MP4.
+    ///
+    /// Since ADTS never signals SBR, the result describes plain AAC at the header's sampling
+    /// frequency; use
+    /// [`AudioSpecificConfig::from_adts_config()`](asc/struct.AudioSpecificConfig.html#method.from_adts_config)
+    /// directly for HE-AAC streams.  Fails only for a reserved `sampling_frequency_index`, but
+    /// a successful result may still be refused by
+    /// [`to_bytes()`](asc/struct.AudioSpecificConfig.html#method.to_bytes): in particular, a
+    /// `channel_configuration` of 0 gives `AscError::ProgramConfigRequired`, since the
+    /// `program_config_element` describing the channels isn't modelled.
+    ///
+    /// ```rust
+    /// # use adts_reader::*;
+    /// // AAC-LC, 48kHz, stereo
+    /// let header = AdtsHeader::from_bytes(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc]).unwrap();
+    /// let asc = header.audio_specific_config().unwrap();
+    /// assert_eq!(asc.to_bytes(), Ok(vec![0x11, 0x90]));
+    /// ```
+    pub fn audio_specific_config(&self) -> Result<asc::AudioSpecificConfig, asc::AscError> {
+        asc::AudioSpecificConfig::from_adts_config(&self.stream_config(), false)
+    }
+
+    /// The per-frame header values of this frame, in the form accepted by
+    /// [`write_frame()`](fn.write_frame.html).
+    pub fn frame_options(&self) -> FrameOptions {
+        FrameOptions {
+            crc: self.protection() == ProtectionIndicator::CrcPresent,
+            buffer_fullness: self.adts_buffer_fullness(),
+            number_of_raw_data_blocks: self.number_of_raw_data_blocks_in_frame(),
+            copyright_identification_bit: self.copyright_identification_bit(),
+            copyright_identification_start: self.copyright_identification_start(),
+        }
+    }
+
     /// length of this frame, including the length of the header.
     pub fn frame_length(&self) -> u16 {
-        u16::from(self.buf[3] & 0b11) << 11
-            | u16::from(self.buf[4]) << 3
-            | u16::from(self.buf[5]) >> 5
+        fields::FRAME_LENGTH.get(self.buf) as u16
     }
 
     /// Calculates the length of the frame payload from the `frame_length` header value, and the
@@ -345,23 +796,92 @@ impl<'buf> AdtsHeader<'buf> {
         }
     }
 
+    /// As for [`frame_length()`](#method.frame_length), as a `usize` ready for indexing
+    pub fn frame_len(&self) -> usize {
+        usize::from(self.frame_length())
+    }
+
+    /// The range of bytes holding the frame payload, relative to the start of the frame, or
+    /// `None` if the `frame_length` header value is too small to even include the headers.
+    ///
+    /// Combined with `get()`, this allows the payload to be sliced from a buffer without risk of
+    /// panicking, even where the buffer holds less than the whole frame:
+    ///
+    /// ```rust
+    /// # use adts_reader::AdtsHeader;
+    /// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let header = AdtsHeader::from_bytes(&buf).unwrap();
+    /// assert_eq!(header.payload_range(), Some(7..8));
+    /// let payload = header.payload_range().and_then(|range| buf.get(range));
+    /// assert_eq!(payload, Some(&[0x21][..]));
+    /// assert_eq!(header.payload_range().and_then(|range| buf[..7].get(range)), None);
+    /// ```
+    pub fn payload_range(&self) -> Option<Range<usize>> {
+        let start = usize::from(self.header_length());
+        let end = self.frame_len();
+        if end >= start {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+
+    /// `false` for a frame consisting only of its header, with a `frame_length` no greater than
+    /// the header length.
+    ///
+    /// Some encoders emit such frames, for priming or as padding.  They are valid, and
+    /// [`AdtsParser`](struct.AdtsParser.html) delivers them to the consumer like any other frame,
+    /// with an empty payload; [`ValidateOptions`](struct.ValidateOptions.html) can be set to
+    /// reject them.
+    pub fn has_payload(&self) -> bool {
+        matches!(self.payload_length(), Some(len) if len > 0)
+    }
+
     pub fn adts_buffer_fullness(&self) -> u16 {
-        u16::from(self.buf[5] & 0b00000011) << 6 | u16::from(self.buf[6]) >> 2
+        fields::ADTS_BUFFER_FULLNESS.get(self.buf) as u16
     }
 
     /// Gives the 16-bit cyclic redundancy check value stored in this frame header, or `None` if
     /// the header does not supply a CRC.
     ///
-    /// NB the implementation doesn't currently check that the CRC is correct
+    /// Use [`verify_crc()`](#method.verify_crc) to check that the CRC is correct.
     pub fn crc(&self) -> Option<u16> {
         match self.protection() {
             ProtectionIndicator::CrcAbsent => None,
-            ProtectionIndicator::CrcPresent => {
-                Some(u16::from(self.buf[7]) << 8 | u16::from(self.buf[8]))
-            }
+            ProtectionIndicator::CrcPresent => Some(fields::CRC_CHECK.get(self.buf) as u16),
         }
     }
 
+    /// Calculates the CRC of the header and payload, and compares it to the stored value.  In a
+    /// frame with several `raw_data_block`s, the header CRC (which then also covers the
+    /// `raw_data_block_position` table) and the CRC following each block are all checked.
+    ///
+    /// The whole frame must be available.
+    ///
+    /// ```rust
+    /// # use adts_reader::*;
+    /// use adts_reader::crc::CrcError;
+    /// # let config = AdtsHeader::from_bytes(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21])
+    /// #     .unwrap()
+    /// #     .stream_config();
+    /// let options = FrameOptions { crc: true, ..FrameOptions::default() };
+    /// let mut frame = write_frame(&config, &[0x21], &options).unwrap();
+    /// assert_eq!(AdtsHeader::from_bytes(&frame).unwrap().verify_crc(), Ok(()));
+    /// frame[9] ^= 0x01;
+    /// assert!(matches!(
+    ///     AdtsHeader::from_bytes(&frame).unwrap().verify_crc(),
+    ///     Err(CrcError::Mismatch { .. })
+    /// ));
+    /// ```
+    pub fn verify_crc(&self) -> Result<(), crc::CrcError> {
+        self.verify_crc_with(&crc::DEFAULT)
+    }
+
+    /// As for [`verify_crc()`](#method.verify_crc), using the given CRC implementation
+    pub fn verify_crc_with(&self, algorithm: &dyn crc::CrcAlgorithm) -> Result<(), crc::CrcError> {
+        crc::verify(self, algorithm)
+    }
+
     /// The number of data blocks in the frame, a value between 1 and 4 inclusive.
     ///
     /// (Note that in the serialised ADTS data stores the _number of blocks - 1_.  This method
@@ -369,12 +889,53 @@ impl<'buf> AdtsHeader<'buf> {
     ///
     /// Most streams store a single block per ADTS frame
     pub fn number_of_raw_data_blocks_in_frame(&self) -> u8 {
-        (self.buf[6] & 0b11) + 1
+        fields::NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.get(self.buf) as u8 + 1
+    }
+
+    /// The number of PCM samples coded in this frame, taking each `raw_data_block` to hold 1024
+    /// samples, as it does in almost all ADTS streams
+    pub fn samples_per_frame(&self) -> u64 {
+        self.samples_per_frame_of(FrameLength::Samples1024)
+    }
+
+    /// The number of PCM samples coded in this frame, given the length of its `raw_data_block`s
+    /// (which the header doesn't signal)
+    pub fn samples_per_frame_of(&self, frame_length: FrameLength) -> u64 {
+        u64::from(self.number_of_raw_data_blocks_in_frame()) * frame_length.samples()
+    }
+
+    /// The exact duration of this frame, taking each `raw_data_block` to hold 1024 samples, or
+    /// `None` if the sampling frequency is a reserved value.
+    ///
+    /// ```rust
+    /// # use adts_reader::AdtsHeader;
+    /// # use std::time::Duration;
+    /// let header = AdtsHeader::from_bytes(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc]).unwrap();
+    /// assert_eq!(header.samples_per_frame(), 1024);
+    /// let duration = header.frame_duration().unwrap();
+    /// assert_eq!(duration.to_duration(), Duration::from_nanos(21_333_333));
+    /// ```
+    pub fn frame_duration(&self) -> Option<MediaTime> {
+        self.frame_duration_of(FrameLength::Samples1024)
     }
 
-    /// The payload AAC data inside this ADTS frame
+    /// The exact duration of this frame, given the length of its `raw_data_block`s, or `None` if
+    /// the sampling frequency is a reserved value
+    pub fn frame_duration_of(&self, frame_length: FrameLength) -> Option<MediaTime> {
+        let rate = self.sampling_frequency().freq()?;
+        Some(MediaTime::new(
+            self.samples_per_frame_of(frame_length),
+            rate,
+        ))
+    }
+
+    /// The payload AAC data inside this ADTS frame.
+    ///
+    /// For a frame holding several `raw_data_block`s, this is everything following the fixed
+    /// header fields (and the first two bytes of a protected frame's block position table), so
+    /// use [`raw_data_blocks()`](#method.raw_data_blocks) to separate the blocks.
     pub fn payload(&self) -> Result<&'buf [u8], PayloadError> {
-        let len = self.frame_length() as usize;
+        let len = self.frame_len();
         if self.buf.len() < len {
             Err(PayloadError {
                 expected: len,
@@ -384,64 +945,215 @@ impl<'buf> AdtsHeader<'buf> {
             Ok(&self.buf[self.header_length() as usize..len])
         }
     }
+
+    /// Copies the whole frame into an [`OwnedAdtsFrame`](struct.OwnedAdtsFrame.html), which
+    /// can outlive the buffer this header was read from (to be queued for a decoder thread, for
+    /// example).  Fails if the buffer doesn't hold the whole frame.
+    ///
+    /// ```rust
+    /// # use adts_reader::AdtsHeader;
+    /// let buf = vec![0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let frame = AdtsHeader::from_bytes(&buf).unwrap().to_owned().unwrap();
+    /// drop(buf);
+    /// assert_eq!(frame.payload(), &[0x21]);
+    /// ```
+    pub fn to_owned(&self) -> Result<OwnedAdtsFrame, PayloadError> {
+        self.payload()?;
+        Ok(OwnedAdtsFrame::from_frame(
+            self.buf[..self.frame_len()].to_vec(),
+        ))
+    }
+
+    /// Each of the `raw_data_block`s in this frame, which must be complete within the buffer
+    /// this header was read from.  See the [`blocks`](blocks/index.html) module for how the
+    /// blocks of a frame holding several are located, and why that may fail.
+    ///
+    /// ```rust
+    /// # use adts_reader::AdtsHeader;
+    /// let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let header = AdtsHeader::from_bytes(&frame).unwrap();
+    /// for block in header.raw_data_blocks().unwrap() {
+    ///     assert_eq!(block.data, &[0x21]);
+    /// }
+    /// ```
+    pub fn raw_data_blocks(&self) -> Result<blocks::RawDataBlocks<'buf>, blocks::BlockLayoutError> {
+        blocks::RawDataBlocks::new(self.buf)
+    }
 }
 impl<'buf> fmt::Debug for AdtsHeader<'buf> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        f.debug_struct("AdtsHeader")
-            .field("mpeg_version", &self.mpeg_version())
-            .field("protection", &self.protection())
-            .field("audio_object_type", &self.audio_object_type())
-            .field("sampling_frequency", &self.sampling_frequency())
-            .field("private_bit", &self.private_bit())
-            .field("channel_configuration", &self.channel_configuration())
-            .field("originality", &self.originality())
-            .field("home", &self.home())
-            .field(
-                "copyright_identification_bit",
-                &self.copyright_identification_bit(),
-            )
-            .field(
-                "copyright_identification_start",
-                &self.copyright_identification_start(),
-            )
-            .field("frame_length", &self.frame_length())
-            .field("adts_buffer_fullness", &self.adts_buffer_fullness())
-            .field("crc", &self.crc())
-            .field(
-                "number_of_raw_data_blocks_in_frame",
-                &self.number_of_raw_data_blocks_in_frame(),
-            )
-            .finish()
+        decoded::fmt_info(&self.info(), f)
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Error indicating that the `copyright_identification_bit`s between one
+/// `copyright_identification_start` and the next didn't make a 72-bit identifier, passed to
+/// [`AdtsConsumer2::on_copyright_id()`](trait.AdtsConsumer2.html#method.on_copyright_id).
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CopyrightIdErr {
+    /// The next identifier started before 72 bits had arrived
     TooFewBits,
+    /// More than 72 bits arrived without the next identifier starting
     TooManyBits,
 }
 
-#[derive(Debug, PartialEq)]
+/// The 72-bit copyright identifier of ISO/IEC 13818-7, which is carried one bit per frame.
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct CopyrightIdentification {
+    /// The first 8 bits, identifying the registration authority which allocated the number
     pub copyright_identifier: u8,
+    /// The remaining 64 bits
     pub copyright_number: u64,
 }
 
+/// The options chosen with `AdtsParser`'s builder methods, other than its label, which is kept
+/// in its `ParserStats`
+#[derive(Default)]
+struct Settings {
+    limits: ParserLimits,
+    encryption: encryption::PayloadEncryption,
+    quirks: quirks::Quirks,
+    crc_policy: crc::CrcPolicy,
+    early_headers: bool,
+    raw_data_blocks: bool,
+    strict: bool,
+    id3_tags: bool,
+    streaming_payloads: bool,
+}
+
 #[derive(PartialEq)]
 enum AdtsState {
     Start,
     Incomplete,
+    /// searching for a sync-word following a bitstream error
     Error,
+    /// collecting an ID3v2 tag in `incomplete_frame`, when enabled with `with_id3_tags()`
+    Id3,
+    /// passing the rest of a frame's payload to the consumer as it arrives, when enabled with
+    /// `with_streaming_payloads()`
+    Payload,
+    /// discarding all data after too many errors, until `start()` is called
+    Failed,
+}
+
+/// `true` if the given data could be the start of an ADTS frame, judging by the 12-bit sync-word
+/// and the 2-bit layer field (always 0) as far as the data extends.
+fn maybe_sync(buf: &[u8]) -> bool {
+    buf[0] == 0xff && !matches!(buf.get(1), Some(b) if b & 0xf6 != 0xf0)
 }
 
+/// Problems with the bitstream reported by an [`AdtsParser`](struct.AdtsParser.html).
+///
+/// Further variants may be added in future releases.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum AdtsParseError {
     BadSyncWord,
     BadFrameLength,
+    /// A single call to `push()` exceeded one of the parser's [`ParserLimits`](struct.ParserLimits.html);
+    /// the rest of the data in that call was discarded
+    LimitExceeded,
+    /// A frame held fewer payload bytes than its header called for, although the parser had
+    /// checked the length; this points at a bug in the parser rather than the input, and the
+    /// frame is dropped
+    PayloadMismatch,
+    /// The `raw_data_block`s of a frame holding several could not be located (see
+    /// [`BlockLayoutError`](blocks/enum.BlockLayoutError.html)), so they were not delivered
+    /// individually; the frame is still passed whole to `on_frame()`
+    UnlocatedBlocks,
+    /// More bitstream errors were found without a good frame between them than
+    /// [`ParserLimits::max_consecutive_errors`](struct.ParserLimits.html#structfield.max_consecutive_errors)
+    /// allows, so the parser has given up, discarding all data until `start()` is called
+    TooManyErrors,
+}
+
+/// Defensive limits on the work [`AdtsParser::push()`](struct.AdtsParser.html#method.push) will
+/// do for a single buffer, as protection against the parser being stalled by adversarial input.
+///
+/// The defaults are far beyond what any legitimate input needs; they exist to bound the damage
+/// should a bug allow crafted input to keep the parser busy indefinitely.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserLimits {
+    /// Most frames passed to the consumer from a single `push()` call (default 2^26, which even
+    /// for the smallest possible frames is over 400MB of data)
+    pub max_frames_per_push: u64,
+    /// Most attempts to complete a frame held over from a previous `push()` call, within one call
+    /// (default 16, where completing a frame legitimately takes at most three)
+    pub max_incomplete_iterations: u32,
+    /// Largest `frame_length` accepted; a header giving a larger value is treated as a false
+    /// sync-word, and reported as `AdtsParseError::BadFrameLength` (default 8191, the largest
+    /// value the field can hold)
+    pub max_frame_length: usize,
+    /// Most bytes held between `push()` calls awaiting the rest of a frame or ID3v2 tag (default
+    /// 64KiB).  A longer frame not wholly within one `push()` is abandoned as for
+    /// `max_frames_per_push`, and a longer tag is discarded as junk.
+    pub max_buffered_bytes: usize,
+    /// Most bitstream errors found since the last good frame (counting each false sync-word
+    /// within a run of garbage, although only the first is reported), after which the parser
+    /// gives up on the stream: `AdtsParseError::TooManyErrors` is reported, and all data is
+    /// discarded until [`AdtsParser::start()`](struct.AdtsParser.html#method.start) is called
+    /// (default `None`, for no limit)
+    pub max_consecutive_errors: Option<u64>,
+}
+impl Default for ParserLimits {
+    fn default() -> Self {
+        ParserLimits {
+            max_frames_per_push: 1 << 26,
+            max_incomplete_iterations: 16,
+            max_frame_length: 8191,
+            max_buffered_bytes: id3::MAX_TAG_LEN,
+            max_consecutive_errors: None,
+        }
+    }
+}
+
+/// What an [`AdtsParser`](struct.AdtsParser.html) is doing between calls to `push()`, as given
+/// by [`AdtsParser::state()`](struct.AdtsParser.html#method.state).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserState {
+    /// Expecting the next data pushed to start a frame
+    Ready,
+    /// Holding the start of a frame (or of an ID3v2 tag, when
+    /// [enabled](struct.AdtsParser.html#method.with_id3_tags)), awaiting the rest of it
+    Incomplete,
+    /// Discarding data after a bitstream error, until the next sync-word is found
+    Error,
+    /// Discarding all data after exceeding
+    /// [`ParserLimits::max_consecutive_errors`](struct.ParserLimits.html#structfield.max_consecutive_errors),
+    /// until [`AdtsParser::start()`](struct.AdtsParser.html#method.start) is called
+    Failed,
+}
+
+/// What happened during a call to
+/// [`AdtsParser::push_checked()`](struct.AdtsParser.html#method.push_checked).
+///
+/// Further fields may be added in future releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PushSummary {
+    /// The number of frames found, including any dropped for a CRC mismatch or reported as
+    /// unsupported
+    pub frames: usize,
+    /// The number of bytes dealt with, either as part of a frame, discarded as junk, or skipped
+    /// as an ID3v2 tag.  This
+    /// includes any bytes held over from earlier calls, and excludes bytes now held awaiting the
+    /// rest of a frame, so may be more or less than the length of the data pushed.
+    pub bytes_consumed: usize,
+    /// The number of bytes held awaiting the rest of a frame, as given by
+    /// [`AdtsParser::pending_bytes()`](struct.AdtsParser.html#method.pending_bytes)
+    pub pending: usize,
+    /// The state the parser was left in
+    pub state: ParserState,
 }
 
 /// Trait to be implemented by types that wish to consume the ADTS data produced by [`AdtsParser`](struct.AdtsParser.html).
 ///
+/// Only some of the header fields are passed to these callbacks, so per-frame values such as
+/// `frame_length`, the CRC, or the copyright identification bits can't be seen here.  New code
+/// should implement [`AdtsConsumer2`](trait.AdtsConsumer2.html) instead, which is given each
+/// frame's whole [`AdtsHeader`](struct.AdtsHeader.html) along with its payload.
+///
 /// # Example
 ///
 /// ```rust
@@ -471,6 +1183,7 @@ pub trait AdtsConsumer {
     /// however appear in every frame (so that the bitstream format can support seeking, not that
     /// this implementation helps there) and so it would be possible for a malformed bitstream to
     /// signal a configuration change part way through.
+    #[allow(clippy::too_many_arguments)]
     fn new_config(
         &mut self,
         mpeg_version: MpegVersion,
@@ -490,77 +1203,958 @@ pub trait AdtsConsumer {
     fn error(&mut self, err: AdtsParseError);
 }
 
-/// Find ADTS frames within provided buffers of data, announcing audio configuration as it is
-/// discovered (normally just once at the start, but possibly changing during the stream if the
-/// stream is malformed).
+/// Per-frame information passed to [`AdtsConsumer2::on_frame()`](trait.AdtsConsumer2.html#tymethod.on_frame).
 ///
-/// Does not currently try to handle re-synchronise with the ADTS bitstream on encountering bad
-/// data.
-pub struct AdtsParser<C>
-where
-    C: AdtsConsumer,
-{
-    pub consumer: C,
-    current_config: [u8; 3],
-    state: AdtsState,
-    incomplete_frame: Vec<u8>,
-    desired_data_len: Option<usize>,
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+pub struct FrameContext<'buf> {
+    /// The header of the frame, giving access to all of its fields
+    pub header: AdtsHeader<'buf>,
+    /// The frame payload, following the header (and CRC, if present)
+    pub payload: &'buf [u8],
+    /// The number of frames preceding this one, counting from the first frame pushed into the
+    /// parser
+    pub index: u64,
+    /// The position of the first byte of this frame within all the data pushed into the parser
+    pub offset: u64,
+    /// The media time at which this frame starts; the total duration of the frames preceding it,
+    /// with `raw_data_block`s of the length given to
+    /// [`AdtsParser::with_frame_length()`](struct.AdtsParser.html#method.with_frame_length)
+    pub time: Duration,
+    /// The presentation timestamp of this frame in 90kHz ticks, when the stream is being
+    /// demultiplexed from MPEG-TS PES packets declared with
+    /// [`AdtsParser::begin_pes_packet()`](struct.AdtsParser.html#method.begin_pes_packet), or
+    /// follows an HLS timestamp tag found by
+    /// [`AdtsParser::with_id3_tags()`](struct.AdtsParser.html#method.with_id3_tags)
+    pub pts: Option<u64>,
+    /// `true` if data was discarded immediately before this frame, because of an error, so that
+    /// there is probably a gap in the audio here
+    pub resynced: bool,
+    /// How the payload is encrypted, as given to
+    /// [`AdtsParser::set_encryption()`](struct.AdtsParser.html#method.set_encryption)
+    pub encryption: encryption::PayloadEncryption,
+    /// The workarounds from [`AdtsParser::with_quirks()`](struct.AdtsParser.html#method.with_quirks)
+    /// which corrected this frame's header; `header` gives the corrected values
+    pub quirks: quirks::Quirks,
+    /// The label given to [`AdtsParser::with_label()`](struct.AdtsParser.html#method.with_label),
+    /// if any
+    pub label: Option<&'buf str>,
 }
-impl<C> AdtsParser<C>
-where
-    C: AdtsConsumer,
-{
-    pub fn new(consumer: C) -> AdtsParser<C> {
-        AdtsParser {
-            consumer,
-            current_config: [0; 3],
-            state: AdtsState::Start,
-            incomplete_frame: vec![],
-            desired_data_len: None,
+
+impl FrameContext<'_> {
+    /// Copies the frame into an [`OwnedAdtsFrame`](struct.OwnedAdtsFrame.html), which can
+    /// outlive the data pushed into the parser.  The copy has any corrections made by the
+    /// parser's quirks, as `header` does.
+    pub fn to_owned(&self) -> OwnedAdtsFrame {
+        let len = usize::from(self.header.header_length()) + self.payload.len();
+        OwnedAdtsFrame::from_frame(self.header.buf[..len].to_vec())
+    }
+}
+
+/// The header of a frame whose payload may not have arrived yet, passed to
+/// [`AdtsConsumer2::on_header()`](trait.AdtsConsumer2.html#method.on_header).
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+pub struct HeaderContext<'buf> {
+    /// The header of the frame, as found in the bitstream (without any corrections from
+    /// [`AdtsParser::with_quirks()`](struct.AdtsParser.html#method.with_quirks)).  The rest of the
+    /// frame may not be available, so `payload()` is likely to fail.
+    pub header: AdtsHeader<'buf>,
+    /// The length of the whole frame, header included, after any corrections for quirks
+    pub len: usize,
+    /// As for [`FrameContext::index`](struct.FrameContext.html#structfield.index)
+    pub index: u64,
+    /// As for [`FrameContext::offset`](struct.FrameContext.html#structfield.offset)
+    pub offset: u64,
+    /// As for [`FrameContext::time`](struct.FrameContext.html#structfield.time)
+    pub time: Duration,
+    /// As for [`FrameContext::label`](struct.FrameContext.html#structfield.label)
+    pub label: Option<&'buf str>,
+}
+
+/// Where in the stream an error was found, passed to
+/// [`AdtsConsumer2::on_error_at()`](trait.AdtsConsumer2.html#method.on_error_at).
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+pub struct ErrorContext<'buf> {
+    /// The number of frames found before the error
+    pub index: u64,
+    /// The position within all the data pushed into the parser at which the error was found;
+    /// for errors in the bitstream, the first byte which could not be parsed, and for errors
+    /// delivering a frame, the first byte of that frame
+    pub offset: u64,
+    /// The media time reached by the frames before the error, as for
+    /// [`FrameContext::time`](struct.FrameContext.html#structfield.time)
+    pub time: Duration,
+    /// As for [`FrameContext::label`](struct.FrameContext.html#structfield.label)
+    pub label: Option<&'buf str>,
+}
+
+/// An ID3v2 tag found between frames, passed to
+/// [`AdtsConsumer2::on_id3_tag()`](trait.AdtsConsumer2.html#method.on_id3_tag) when enabled with
+/// [`AdtsParser::with_id3_tags()`](struct.AdtsParser.html#method.with_id3_tags).
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+pub struct Id3Tag<'buf> {
+    /// The whole tag, including its header
+    pub tag: &'buf [u8],
+    /// The MPEG-TS timestamp (in 90kHz ticks, of 33 bits) given by the tag's
+    /// `com.apple.streaming.transportStreamTimestamp` `PRIV` frame, with which HLS packed audio
+    /// segments give the presentation time of their first frame
+    pub timestamp: Option<u64>,
+    /// The number of frames preceding the tag
+    pub index: u64,
+    /// The position of the first byte of the tag within all the data pushed into the parser
+    pub offset: u64,
+    /// As for [`FrameContext::label`](struct.FrameContext.html#structfield.label)
+    pub label: Option<&'buf str>,
+}
+
+/// Why a frame was passed to
+/// [`AdtsConsumer2::on_raw_unsupported()`](trait.AdtsConsumer2.html#method.on_raw_unsupported).
+///
+/// Further reasons may be added in future releases.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Unsupported {
+    /// The 2-bit `layer` field, which is always `0` in ADTS, holds the given value
+    Layer(u8),
+    /// The `sampling_frequency_index` holds one of the reserved values
+    ReservedSamplingFrequency(SamplingFrequency),
+}
+
+/// A frame which the crate can't interpret, passed to
+/// [`AdtsConsumer2::on_raw_unsupported()`](trait.AdtsConsumer2.html#method.on_raw_unsupported).
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+pub struct RawUnsupported<'buf> {
+    /// The bytes of the whole frame, header included, as found in the bitstream
+    pub frame: &'buf [u8],
+    pub reason: Unsupported,
+    /// As for [`FrameContext::index`](struct.FrameContext.html#structfield.index)
+    pub index: u64,
+    /// As for [`FrameContext::offset`](struct.FrameContext.html#structfield.offset)
+    pub offset: u64,
+    /// As for [`FrameContext::label`](struct.FrameContext.html#structfield.label)
+    pub label: Option<&'buf str>,
+}
+
+/// A field of the fixed header, as may differ between two stream configurations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfigField {
+    MpegVersion,
+    Protection,
+    AudioObjectType,
+    SamplingFrequency,
+    PrivateBit,
+    ChannelConfiguration,
+    Originality,
+    Home,
+}
+impl AdtsStreamConfig {
+    /// The fields whose values differ between this configuration and the given one, in header
+    /// order.  `ConfigField::Protection` is never included, since it isn't part of the stream
+    /// configuration.
+    pub fn changed_fields(&self, other: &AdtsStreamConfig) -> Vec<ConfigField> {
+        let mut result = vec![];
+        if self.mpeg_version != other.mpeg_version {
+            result.push(ConfigField::MpegVersion);
+        }
+        if self.audio_object_type != other.audio_object_type {
+            result.push(ConfigField::AudioObjectType);
+        }
+        if self.sampling_frequency != other.sampling_frequency {
+            result.push(ConfigField::SamplingFrequency);
+        }
+        if self.private_bit != other.private_bit {
+            result.push(ConfigField::PrivateBit);
         }
+        if self.channel_configuration != other.channel_configuration {
+            result.push(ConfigField::ChannelConfiguration);
+        }
+        if self.originality != other.originality {
+            result.push(ConfigField::Originality);
+        }
+        if self.home != other.home {
+            result.push(ConfigField::Home);
+        }
+        result
     }
+}
 
-    fn is_new_config(&self, header_data: &[u8]) -> bool {
-        self.current_config != header_data[0..3]
+/// A new configuration found within the ADTS bitstream, passed to
+/// [`AdtsConsumer2::on_config_change()`](trait.AdtsConsumer2.html#method.on_config_change).
+///
+/// The parser compares every field of the fixed header except `private_bit`, which is left for
+/// private use and may vary from frame to frame without the audio changing, so that a change of
+/// just the `private_bit` isn't reported (although it is listed in `changed` when it accompanies
+/// some other change).
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// The configuration previously reported, or `None` for the first frame of the stream
+    pub previous: Option<(AdtsStreamConfig, ProtectionIndicator)>,
+    pub config: AdtsStreamConfig,
+    pub protection: ProtectionIndicator,
+    /// The fields whose values differ from `previous`, in header order, which is empty for the
+    /// first frame
+    pub changed: Vec<ConfigField>,
+    /// As for [`FrameContext::index`](struct.FrameContext.html#structfield.index), of the first
+    /// frame with the new configuration
+    pub index: u64,
+    /// As for [`FrameContext::offset`](struct.FrameContext.html#structfield.offset), of the
+    /// first frame with the new configuration
+    pub offset: u64,
+}
+impl ConfigChange {
+    /// `true` if the given field differs from the previous configuration
+    pub fn is_changed(&self, field: ConfigField) -> bool {
+        self.changed.contains(&field)
     }
+}
 
-    fn remember(&mut self, remaining_data: &[u8], desired_data_len: usize) {
-        self.state = AdtsState::Incomplete;
-        self.incomplete_frame.clear();
-        self.incomplete_frame.extend_from_slice(remaining_data);
-        self.desired_data_len = Some(desired_data_len);
+/// The start of a new, unrelated stream concatenated onto the one before it (as in a recording of
+/// back-to-back captures), passed to
+/// [`AdtsConsumer2::on_stream_boundary()`](trait.AdtsConsumer2.html#method.on_stream_boundary).
+///
+/// ADTS carries no timestamps, so the parser takes a change of configuration to mark a new stream
+/// only when it coincides with a discontinuity: data discarded ahead of the new stream's first
+/// frame (commonly the ID3v2 tag with which HLS packed audio and many recorders start a file), or
+/// `adts_buffer_fullness` switching between variable and constant rate signalling.  Any other
+/// configuration change is reported by `on_config()` alone.
+///
+/// Further fields may be added in future releases without breaking existing consumers.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamBoundary {
+    /// The position within all the data pushed into the parser at which the new stream starts:
+    /// the start of the data discarded ahead of its first frame if there was any, or otherwise
+    /// the first frame itself.  A recorder writing one file per stream should split here.
+    pub offset: u64,
+    /// Number of bytes discarded between `offset` and the first frame of the new stream,
+    /// including any ID3v2 tags skipped by
+    /// [`AdtsParser::with_id3_tags()`](struct.AdtsParser.html#method.with_id3_tags)
+    pub discarded: u64,
+    /// `true` if the discarded data started with an ID3v2 tag
+    pub id3: bool,
+    /// As for [`FrameContext::index`](struct.FrameContext.html#structfield.index), of the new
+    /// stream's first frame
+    pub index: u64,
+    /// As for [`FrameContext::time`](struct.FrameContext.html#structfield.time), of the new
+    /// stream's first frame
+    pub time: Duration,
+    /// The configuration of the stream which has ended
+    pub previous: AdtsStreamConfig,
+    /// The configuration of the new stream
+    pub config: AdtsStreamConfig,
+    /// `true` if `adts_buffer_fullness` switched between signalling a variable rate stream
+    /// (`0x7FF`) and a constant rate one
+    pub fullness_changed: bool,
+    /// The label given to [`AdtsParser::with_label()`](struct.AdtsParser.html#method.with_label),
+    /// if any
+    pub label: Option<String>,
+}
+
+/// A successor to [`AdtsConsumer`](trait.AdtsConsumer.html), receiving the header values as a
+/// single [`AdtsStreamConfig`](struct.AdtsStreamConfig.html), and each frame along with a
+/// [`FrameContext`](struct.FrameContext.html).
+///
+/// New information can be added to `FrameContext`, and new methods (with default
+/// implementations) to this trait, without breaking implementations of it.  Every
+/// `AdtsConsumer` is also an `AdtsConsumer2`, so `AdtsParser` accepts either.
+///
+/// ```rust
+/// use adts_reader::*;
+///
+/// struct MyConsumer;
+/// impl AdtsConsumer2 for MyConsumer {
+///     fn on_frame(&mut self, frame: &FrameContext<'_>) {
+///         println!("frame {} at offset {}, {} bytes", frame.index, frame.offset, frame.payload.len());
+///     }
+/// }
+///
+/// let mut parser = AdtsParser::new(MyConsumer);
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// ```
+pub trait AdtsConsumer2 {
+    /// Called when a new configuration is found within the ADTS bitstream, as for
+    /// `AdtsConsumer::new_config()`.  Unless it fails its CRC check, the frame carrying the new
+    /// configuration is passed to `on_frame()` straight afterwards, with its whole header.
+    fn on_config(&mut self, config: AdtsStreamConfig, protection: ProtectionIndicator) {
+        let _ = (config, protection);
     }
 
-    /// Initialize or re-initialize parser state.  Call this function before processing a group of
-    /// ADTS frames to ensure that any error state due to processing an earlier group of ADTS
-    /// frames is cleared.
-    pub fn start(&mut self) {
-        if self.state == AdtsState::Incomplete {
-            self.incomplete_frame.clear();
-            self.desired_data_len = None;
-            eprintln!("ADTS: incomplete data buffer dropped by call to start()");
-        }
-        self.state = AdtsState::Start;
+    /// Called in place of `on_config()`, with the previous configuration and the fields which
+    /// differ from it, as a consumer following adaptive bitrate switches may need.  The default
+    /// implementation just calls `on_config()`, so consumers only need to implement one of the
+    /// two.
+    fn on_config_change(&mut self, change: &ConfigChange) {
+        self.on_config(change.config, change.protection);
     }
 
-    /// Extracts information about each ADTS frame in the given buffer, which is passed to the
-    /// `AdtsConsumer` implementation supplied at construction time.
+    /// Called with each complete frame, including frames whose payload is empty (see
+    /// [`AdtsHeader::has_payload()`](struct.AdtsHeader.html#method.has_payload))
+    fn on_frame(&mut self, frame: &FrameContext<'_>);
+
+    /// Called if `AdtsParser` encounters an error in the ADTS bitstream
+    fn on_error(&mut self, err: AdtsParseError) {
+        let _ = err;
+    }
+
+    /// Called in place of `on_error()`, with details of where in the stream the error was found.
+    /// The default implementation just calls `on_error()`, so consumers only need to implement
+    /// one of the two.
+    fn on_error_at(&mut self, err: AdtsParseError, context: &ErrorContext<'_>) {
+        let _ = context;
+        self.on_error(err);
+    }
+
+    /// Called before `on_frame()` with each frame whose CRC doesn't match, when the parser's
+    /// [`CrcPolicy`](crc/enum.CrcPolicy.html) is `Flag` or `Drop` (under `Drop`, `on_frame()` is
+    /// then not called for the frame)
+    fn on_crc_error(&mut self, frame: &FrameContext<'_>, err: crc::CrcError) {
+        let _ = (frame, err);
+    }
+
+    /// Called with the header of each frame as soon as the header bytes have been pushed, before
+    /// the rest of the frame arrives, when enabled with
+    /// [`AdtsParser::with_early_headers()`](struct.AdtsParser.html#method.with_early_headers).
     ///
-    /// If the given buffer ends part-way through an ADTS frame, the remaining unconsumed data
-    /// will be buffered inside this AdtsParser instance, and the rest of the ADTS frame may be
-    /// passed in another buffer in the next call to this method.
-    pub fn push(&mut self, adts_buf: &[u8]) {
-        let mut buf = adts_buf;
-        match self.state {
-            AdtsState::Error => return, // TODO: resync to recover from bitstream errors
-            AdtsState::Incomplete => {
-                // on last call to push(), the end of the adts_buf held the start of an ADTS
-                // frame, and we copied that data into incomplete_buffer, so now lets try to add
-                // enough initial bytes from the adts_buf given to this call to get a complete
-                // frame
-                loop {
-                    let bytes_needed_to_complete_frame =
-                        self.desired_data_len.unwrap() - self.incomplete_frame.len();
+    /// This precedes any `on_config()` call for the same frame.  Announcing a header doesn't
+    /// guarantee that `on_frame()` follows: the frame is still dropped if its CRC doesn't match
+    /// under `CrcPolicy::Drop`, or if the partial frame is abandoned by a call to
+    /// [`AdtsParser::start()`](struct.AdtsParser.html#method.start), a
+    /// [limit](struct.ParserLimits.html) being exceeded, or the data proving not to be a frame
+    /// after all.
+    fn on_header(&mut self, header: &HeaderContext<'_>) {
+        let _ = header;
+    }
+
+    /// Called after `on_frame()` with each of the frame's `raw_data_block`s in turn, when enabled
+    /// with [`AdtsParser::with_raw_data_blocks()`](struct.AdtsParser.html#method.with_raw_data_blocks).
+    /// Each block is a separate access unit for a decoder.
+    fn on_raw_data_block(&mut self, frame: &FrameContext<'_>, block: &blocks::RawDataBlock<'_>) {
+        let _ = (frame, block);
+    }
+
+    /// Called in place of `on_frame()` for frames which the crate can't interpret, when enabled
+    /// with [`AdtsParser::with_strict()`](struct.AdtsParser.html#method.with_strict)
+    fn on_raw_unsupported(&mut self, frame: &RawUnsupported<'_>) {
+        let _ = frame;
+    }
+
+    /// Called when the next frame looks to start a new stream, concatenated onto the previous
+    /// one, rather than just changing configuration part way through (see
+    /// [`StreamBoundary`](struct.StreamBoundary.html) for how the two are told apart).  The
+    /// usual `on_config()` call for the new stream's configuration follows.
+    fn on_stream_boundary(&mut self, boundary: &StreamBoundary) {
+        let _ = boundary;
+    }
+
+    /// Called with each ID3v2 tag found between frames, when enabled with
+    /// [`AdtsParser::with_id3_tags()`](struct.AdtsParser.html#method.with_id3_tags)
+    fn on_id3_tag(&mut self, tag: &Id3Tag<'_>) {
+        let _ = tag;
+    }
+
+    /// Called in place of `on_frame()` with the header of each frame, when enabled with
+    /// [`AdtsParser::with_streaming_payloads()`](struct.AdtsParser.html#method.with_streaming_payloads).
+    /// The frame's payload follows in calls to `on_payload_chunk()`, and then `on_payload_end()`.
+    fn on_payload_start(&mut self, header: &HeaderContext<'_>) {
+        let _ = header;
+    }
+
+    /// Called with the next part of the payload of the frame given to `on_payload_start()`, as
+    /// soon as it has been pushed.  A frame whose payload is empty has no chunks.
+    fn on_payload_chunk(&mut self, chunk: &[u8]) {
+        let _ = chunk;
+    }
+
+    /// Called once the whole payload of the frame given to `on_payload_start()` has been passed
+    /// to `on_payload_chunk()`, with `complete` set, or with it unset if the rest of the frame
+    /// was abandoned by a call to [`AdtsParser::start()`](struct.AdtsParser.html#method.start)
+    fn on_payload_end(&mut self, complete: bool) {
+        let _ = complete;
+    }
+
+    /// Called after `on_frame()` (or `on_payload_start()`) for the frame carrying the last bit of
+    /// a copyright identifier, once all 72 of its `copyright_identification_bit`s have arrived,
+    /// or with an error when the bits between one `copyright_identification_start` and the next
+    /// aren't an identifier's worth.  Bits are only collected from the first frame signalling
+    /// `copyright_identification_start`, so streams which don't use the mechanism produce no
+    /// calls.  An identifier is abandoned if frames may have been lost, following an error.
+    fn on_copyright_id(&mut self, id: Result<CopyrightIdentification, CopyrightIdErr>) {
+        let _ = id;
+    }
+}
+
+impl<C: AdtsConsumer> AdtsConsumer2 for C {
+    fn on_config(&mut self, config: AdtsStreamConfig, protection: ProtectionIndicator) {
+        self.new_config(
+            config.mpeg_version,
+            protection,
+            config.audio_object_type,
+            config.sampling_frequency,
+            config.private_bit,
+            config.channel_configuration,
+            config.originality,
+            config.home,
+        );
+    }
+
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        self.payload(
+            frame.header.adts_buffer_fullness(),
+            frame.header.number_of_raw_data_blocks_in_frame(),
+            frame.payload,
+        );
+    }
+
+    fn on_error(&mut self, err: AdtsParseError) {
+        self.error(err);
+    }
+}
+
+/// Allows `AdtsParser<Box<dyn AdtsConsumer>>`, where the consumer is chosen at runtime
+impl<C: AdtsConsumer + ?Sized> AdtsConsumer for Box<C> {
+    fn new_config(
+        &mut self,
+        mpeg_version: MpegVersion,
+        protection: ProtectionIndicator,
+        aot: AudioObjectType,
+        freq: SamplingFrequency,
+        private_bit: u8,
+        channels: ChannelConfiguration,
+        originality: Originality,
+        home: u8,
+    ) {
+        (**self).new_config(
+            mpeg_version,
+            protection,
+            aot,
+            freq,
+            private_bit,
+            channels,
+            originality,
+            home,
+        );
+    }
+
+    fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) {
+        (**self).payload(buffer_fullness, number_of_blocks, buf);
+    }
+
+    fn error(&mut self, err: AdtsParseError) {
+        (**self).error(err);
+    }
+}
+
+/// Forwards every method to the boxed consumer, so that overridden default methods are still
+/// called
+macro_rules! forward_consumer2 {
+    ($ty:ty) => {
+        impl<'a> AdtsConsumer2 for $ty {
+            fn on_config(&mut self, config: AdtsStreamConfig, protection: ProtectionIndicator) {
+                (**self).on_config(config, protection);
+            }
+            fn on_config_change(&mut self, change: &ConfigChange) {
+                (**self).on_config_change(change);
+            }
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                (**self).on_frame(frame);
+            }
+            fn on_error(&mut self, err: AdtsParseError) {
+                (**self).on_error(err);
+            }
+            fn on_error_at(&mut self, err: AdtsParseError, context: &ErrorContext<'_>) {
+                (**self).on_error_at(err, context);
+            }
+            fn on_crc_error(&mut self, frame: &FrameContext<'_>, err: crc::CrcError) {
+                (**self).on_crc_error(frame, err);
+            }
+            fn on_header(&mut self, header: &HeaderContext<'_>) {
+                (**self).on_header(header);
+            }
+            fn on_raw_data_block(
+                &mut self,
+                frame: &FrameContext<'_>,
+                block: &blocks::RawDataBlock<'_>,
+            ) {
+                (**self).on_raw_data_block(frame, block);
+            }
+            fn on_raw_unsupported(&mut self, frame: &RawUnsupported<'_>) {
+                (**self).on_raw_unsupported(frame);
+            }
+            fn on_stream_boundary(&mut self, boundary: &StreamBoundary) {
+                (**self).on_stream_boundary(boundary);
+            }
+            fn on_id3_tag(&mut self, tag: &Id3Tag<'_>) {
+                (**self).on_id3_tag(tag);
+            }
+            fn on_payload_start(&mut self, header: &HeaderContext<'_>) {
+                (**self).on_payload_start(header);
+            }
+            fn on_payload_chunk(&mut self, chunk: &[u8]) {
+                (**self).on_payload_chunk(chunk);
+            }
+            fn on_payload_end(&mut self, complete: bool) {
+                (**self).on_payload_end(complete);
+            }
+            fn on_copyright_id(&mut self, id: Result<CopyrightIdentification, CopyrightIdErr>) {
+                (**self).on_copyright_id(id);
+            }
+        }
+    };
+}
+// `AdtsParser<Box<dyn AdtsConsumer2>>`, where the consumer is chosen at runtime
+forward_consumer2!(Box<dyn AdtsConsumer2 + 'a>);
+forward_consumer2!(Box<dyn AdtsConsumer2 + Send + 'a>);
+
+/// The bits of the first four header bytes which `AdtsParser` compares to notice a change of
+/// configuration: the fixed header, except for `private_bit`
+const CONFIG_MASK: [u8; 4] = [0xff, 0xff, 0xfd, 0xf0];
+
+/// Find ADTS frames within provided buffers of data, announcing audio configuration as it is
+/// discovered (normally just once at the start, but possibly changing during the stream if the
+/// stream is malformed).
+///
+/// On encountering data which is not a valid ADTS frame, the parser reports the error to the
+/// consumer and then discards input until it finds the next sync-word, from which it resumes.  A
+/// single error is reported for each run of discarded bytes, however long.  Counters describing
+/// this error recovery are available from [`stats()`](#method.stats).
+pub struct AdtsParser<C, K = clock::SystemClock>
+where
+    C: AdtsConsumer2,
+    K: clock::Clock,
+{
+    pub consumer: C,
+    /// the first four bytes of the header of the frame whose configuration was last reported
+    current_config: [u8; 4],
+    state: AdtsState,
+    incomplete_frame: Vec<u8>,
+    desired_data_len: Option<usize>,
+    recovery: stats::Recovery,
+    clock: K,
+    settings: Settings,
+    /// holds a copy of frames whose header is being corrected for a quirk
+    corrected_frame: Vec<u8>,
+    /// whether `on_header()` has been called for the frame in `incomplete_frame`
+    header_announced: bool,
+    /// in the `Payload` state, the length of the frame being streamed, and how many of its bytes
+    /// are yet to arrive
+    payload_len: usize,
+    payload_remaining: usize,
+    /// bitstream errors since the last good frame, and the number of frames found when the
+    /// count started
+    consecutive_errors: u64,
+    error_frames: u64,
+    pes: pes::PesTiming,
+}
+impl<C> AdtsParser<C>
+where
+    C: AdtsConsumer2,
+{
+    pub fn new(consumer: C) -> AdtsParser<C> {
+        AdtsParser::with_clock(consumer, clock::SystemClock)
+    }
+}
+impl<C, K> AdtsParser<C, K>
+where
+    C: AdtsConsumer2,
+    K: clock::Clock,
+{
+    /// Creates a parser which timestamps errors in its [`stats()`](#method.stats) using the given
+    /// clock
+    pub fn with_clock(consumer: C, clock: K) -> AdtsParser<C, K> {
+        AdtsParser {
+            consumer,
+            current_config: [0; 4],
+            state: AdtsState::Start,
+            incomplete_frame: vec![],
+            desired_data_len: None,
+            recovery: stats::Recovery::default(),
+            clock,
+            settings: Settings::default(),
+            corrected_frame: vec![],
+            header_announced: false,
+            payload_len: 0,
+            payload_remaining: 0,
+            consecutive_errors: 0,
+            error_frames: 0,
+            pes: pes::PesTiming::default(),
+        }
+    }
+
+    /// Replaces the default [`ParserLimits`](struct.ParserLimits.html)
+    pub fn with_limits(mut self, limits: ParserLimits) -> Self {
+        self.settings.limits = limits;
+        self
+    }
+
+    /// Applies workarounds for the given known encoder bugs to every frame parsed (by default,
+    /// none are applied)
+    pub fn with_quirks(mut self, quirks: quirks::Quirks) -> Self {
+        self.settings.quirks = quirks;
+        self
+    }
+
+    /// Sets how frames whose CRC doesn't match are handled (by default, CRCs are not checked)
+    pub fn with_crc_policy(mut self, policy: crc::CrcPolicy) -> Self {
+        self.settings.crc_policy = policy;
+        self
+    }
+
+    /// When `true`, the consumer's [`on_header()`](trait.AdtsConsumer2.html#method.on_header) is
+    /// called for each frame as soon as its header has been pushed, rather than the consumer
+    /// hearing nothing until the whole frame is available (by default, `false`).  This lets
+    /// low-latency relays allocate space for a frame and forward its header while the payload is
+    /// still arriving.
+    pub fn with_early_headers(mut self, early_headers: bool) -> Self {
+        self.settings.early_headers = early_headers;
+        self
+    }
+
+    /// When `true`, each `raw_data_block` of every frame is passed to the consumer's
+    /// [`on_raw_data_block()`](trait.AdtsConsumer2.html#method.on_raw_data_block) after the
+    /// frame itself (by default, `false`).  Frames holding several blocks without a CRC can only
+    /// be split with the `elements` feature, and not always then; those that can't are reported
+    /// as `AdtsParseError::UnlocatedBlocks`.
+    pub fn with_raw_data_blocks(mut self, raw_data_blocks: bool) -> Self {
+        self.settings.raw_data_blocks = raw_data_blocks;
+        self
+    }
+
+    /// When `true`, ID3v2 tags found between frames are passed to the consumer's
+    /// [`on_id3_tag()`](trait.AdtsConsumer2.html#method.on_id3_tag) and skipped, rather than
+    /// being reported as an error and discarded as garbage (by default, `false`).
+    ///
+    /// HLS packed audio segments each start with a tag giving the MPEG-TS timestamp of their
+    /// first frame, which is also given to that frame in
+    /// [`FrameContext::pts`](struct.FrameContext.html#structfield.pts), with the frames
+    /// following counting on from it, so that segments can be placed on the timeline of the
+    /// rest of the presentation.  A tag between frames whose configuration differs still marks
+    /// a [`StreamBoundary`](struct.StreamBoundary.html).
+    ///
+    /// ```rust
+    /// # use adts_reader::*;
+    /// struct Pts(Vec<Option<u64>>);
+    /// impl AdtsConsumer2 for Pts {
+    ///     fn on_frame(&mut self, frame: &FrameContext<'_>) {
+    ///         self.0.push(frame.pts);
+    ///     }
+    /// }
+    /// let mut segment = b"ID3\x04\x00\x00\x00\x00\x00\x3f".to_vec();
+    /// segment.extend_from_slice(b"PRIV\x00\x00\x00\x35\x00\x00");
+    /// segment.extend_from_slice(b"com.apple.streaming.transportStreamTimestamp\x00");
+    /// segment.extend_from_slice(&9000u64.to_be_bytes());
+    /// segment.extend_from_slice(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+    /// let mut parser = AdtsParser::new(Pts(vec![])).with_id3_tags(true);
+    /// parser.push(&segment);
+    /// assert_eq!(parser.consumer.0, [Some(9000)]);
+    /// assert_eq!(parser.stats().errors, 0);
+    /// ```
+    pub fn with_id3_tags(mut self, id3_tags: bool) -> Self {
+        self.settings.id3_tags = id3_tags;
+        self
+    }
+
+    /// When `true`, frames are passed to the consumer in pieces as their data is pushed, through
+    /// [`on_payload_start()`](trait.AdtsConsumer2.html#method.on_payload_start),
+    /// [`on_payload_chunk()`](trait.AdtsConsumer2.html#method.on_payload_chunk) and
+    /// [`on_payload_end()`](trait.AdtsConsumer2.html#method.on_payload_end), rather than whole
+    /// to `on_frame()` (by default, `false`).  The parser then holds nothing more than an
+    /// incomplete header between calls to `push()`, so a large frame split across many small
+    /// reads is never copied, which suits latency-sensitive relays.
+    ///
+    /// Since no frame is ever available whole, `on_frame()`, `on_crc_error()`,
+    /// `on_raw_data_block()` and `on_raw_unsupported()` are not called in this mode, and CRCs
+    /// are not checked.
+    pub fn with_streaming_payloads(mut self, streaming_payloads: bool) -> Self {
+        self.settings.streaming_payloads = streaming_payloads;
+        self
+    }
+
+    /// When `true`, frames using header values which this crate doesn't model (a non-zero
+    /// `layer`, or a reserved `sampling_frequency_index`, as a future extension of the format
+    /// might use) are passed whole to the consumer's
+    /// [`on_raw_unsupported()`](trait.AdtsConsumer2.html#method.on_raw_unsupported), rather
+    /// than to `on_config()` and `on_frame()` with values the consumer can make nothing of (by
+    /// default, `false`).  Recording and passthrough applications can then keep such frames
+    /// without having to interpret them.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.settings.strict = strict;
+        self
+    }
+
+    /// Identifies the stream this parser handles (a channel name, or a caller's stream ID, say),
+    /// for services parsing many streams at once.  The label is attached to every
+    /// [`FrameContext`](struct.FrameContext.html), [`HeaderContext`](struct.HeaderContext.html)
+    /// and [`StreamBoundary`](struct.StreamBoundary.html) passed to the consumer, to the
+    /// [`stats()`](#method.stats), and to the parser's own diagnostic output, so that none of
+    /// them need be wrapped to say which stream they describe.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.recovery.stats.label = Some(label.into());
+        self
+    }
+
+    /// The label given to [`with_label()`](#method.with_label), if any
+    pub fn label(&self) -> Option<&str> {
+        self.recovery.stats.label.as_deref()
+    }
+
+    /// Sets the number of samples per `raw_data_block`, used to calculate the media time of each
+    /// frame and error (default `FrameLength::Samples1024`)
+    pub fn with_frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.recovery.frame_length = frame_length;
+        self
+    }
+
+    /// Error recovery statistics for the data pushed into this parser so far
+    pub fn stats(&self) -> &ParserStats {
+        &self.recovery.stats
+    }
+
+    /// The number of bytes pushed into the parser so far, which is the offset at which the data
+    /// from the next `push()` starts, as given in
+    /// [`FrameContext::offset`](struct.FrameContext.html#structfield.offset) and
+    /// [`ErrorContext::offset`](struct.ErrorContext.html#structfield.offset)
+    pub fn offset(&self) -> u64 {
+        let streamed = if self.state == AdtsState::Payload {
+            self.payload_len - self.payload_remaining
+        } else {
+            0
+        };
+        self.recovery.position() + (self.pending_bytes() + streamed) as u64
+    }
+
+    /// The number of frames found in the data pushed so far
+    pub fn frames(&self) -> u64 {
+        self.recovery.frames()
+    }
+
+    /// Number of bytes of an incomplete frame held over from earlier calls to `push()`, awaiting
+    /// the rest of the frame.  The parser holds no more than one frame's worth of data between
+    /// calls, however the input is divided (or, with
+    /// [`with_id3_tags()`](#method.with_id3_tags), one ID3v2 tag of up to
+    /// [`ParserLimits::max_buffered_bytes`](struct.ParserLimits.html#structfield.max_buffered_bytes)).
+    pub fn pending_bytes(&self) -> usize {
+        // the buffer keeps the last frame completed from it, until the next is remembered
+        if matches!(self.state, AdtsState::Incomplete | AdtsState::Id3) {
+            self.incomplete_frame.len()
+        } else {
+            0
+        }
+    }
+
+    /// Declares how the payloads of the frames pushed from now on are encrypted, since the ADTS
+    /// headers give no indication.  The value is passed on to the consumer in each
+    /// [`FrameContext`](struct.FrameContext.html), so that payload-dependent processing can be
+    /// skipped.  May be changed between calls to `push()`, for example at an HLS segment
+    /// boundary where the key changes.
+    pub fn set_encryption(&mut self, encryption: encryption::PayloadEncryption) {
+        self.settings.encryption = encryption;
+    }
+
+    /// The [`config_bytes()`](struct.AdtsHeader.html#method.config_bytes) of the most recent
+    /// configuration reported to the consumer, or `None` before the first frame
+    pub fn config_bytes(&self) -> Option<[u8; 3]> {
+        // an all-zero value can't hold a sync-word, so only appears before the first frame
+        let [a, b, c, _] = self.current_config;
+        Some([a, b, c]).filter(|c| c != &[0; 3])
+    }
+
+    fn is_new_config(&self, header_data: &[u8]) -> bool {
+        self.current_config
+            .iter()
+            .zip(&header_data[..4])
+            .zip(CONFIG_MASK)
+            .any(|((a, b), mask)| (a ^ b) & mask != 0)
+    }
+
+    fn remember(&mut self, remaining_data: &[u8], desired_data_len: usize) {
+        self.state = AdtsState::Incomplete;
+        self.incomplete_frame.clear();
+        self.incomplete_frame.extend_from_slice(remaining_data);
+        self.desired_data_len = Some(desired_data_len);
+        self.header_announced = false;
+    }
+
+    /// Initialize or re-initialize parser state.  Call this function before processing a group of
+    /// ADTS frames to ensure that any error state due to processing an earlier group of ADTS
+    /// frames is cleared.
+    pub fn start(&mut self) {
+        if self.state == AdtsState::Payload {
+            self.consumer.on_payload_end(false);
+        }
+        if matches!(self.state, AdtsState::Incomplete | AdtsState::Id3) {
+            self.incomplete_frame.clear();
+            self.desired_data_len = None;
+            match self.recovery.stats.label {
+                Some(ref label) => eprintln!(
+                    "ADTS ({}): incomplete data buffer dropped by call to start()",
+                    label
+                ),
+                None => eprintln!("ADTS: incomplete data buffer dropped by call to start()"),
+            }
+        }
+        self.recovery.reset();
+        self.consecutive_errors = 0;
+        self.state = AdtsState::Start;
+    }
+
+    /// Declares that the data pushed from now on comes from a new MPEG-TS PES packet, having the
+    /// given presentation timestamp (in 90kHz ticks), if its header had one.
+    ///
+    /// The PTS of a PES packet applies to the first frame which starts within it, which is
+    /// given that value in [`FrameContext::pts`](struct.FrameContext.html#structfield.pts);
+    /// frames following are given timestamps counting on from it, by frame duration (wrapping
+    /// at 33 bits, as PTS values do).  Frames before the first PES packet with a PTS have none.
+    ///
+    /// With the `mpeg2ts` feature,
+    /// [`mpeg2ts::AdtsElementaryStreamConsumer`](mpeg2ts/struct.AdtsElementaryStreamConsumer.html)
+    /// calls this for each PES packet demultiplexed by `mpeg2ts-reader`.
+    ///
+    /// ```rust
+    /// # use adts_reader::{AdtsConsumer2, AdtsParser, FrameContext};
+    /// struct Pts(Vec<Option<u64>>);
+    /// impl AdtsConsumer2 for Pts {
+    ///     fn on_frame(&mut self, frame: &FrameContext<'_>) {
+    ///         self.0.push(frame.pts);
+    ///     }
+    /// }
+    /// let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let mut parser = AdtsParser::new(Pts(vec![]));
+    /// parser.begin_pes_packet(Some(9000));
+    /// parser.push(&frame);
+    /// parser.push(&frame);
+    /// // the second frame starts 1024 samples at 48kHz later
+    /// assert_eq!(parser.consumer.0, [Some(9000), Some(10920)]);
+    /// ```
+    pub fn begin_pes_packet(&mut self, pts: Option<u64>) {
+        self.pes.begin_packet(self.offset(), pts);
+    }
+
+    /// Whether the parser is between frames, part way through one, or discarding data after an
+    /// error
+    pub fn state(&self) -> ParserState {
+        match self.state {
+            AdtsState::Start => ParserState::Ready,
+            AdtsState::Incomplete | AdtsState::Id3 | AdtsState::Payload => ParserState::Incomplete,
+            AdtsState::Error => ParserState::Error,
+            AdtsState::Failed => ParserState::Failed,
+        }
+    }
+
+    /// As for [`push()`](#method.push), returning a summary of what was done with the data, for
+    /// callers applying backpressure or monitoring the stream's health.
+    ///
+    /// ```rust
+    /// # use adts_reader::{AdtsConsumer2, AdtsParser, FrameContext, ParserState};
+    /// # struct Frames;
+    /// # impl AdtsConsumer2 for Frames {
+    /// #     fn on_frame(&mut self, _: &FrameContext<'_>) {}
+    /// # }
+    /// let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+    /// let mut parser = AdtsParser::new(Frames);
+    /// let summary = parser.push_checked(&[&frame[..], &frame[..3]].concat());
+    /// assert_eq!(summary.frames, 1);
+    /// assert_eq!(summary.bytes_consumed, 8);
+    /// assert_eq!(summary.pending, 3);
+    /// assert_eq!(summary.state, ParserState::Incomplete);
+    /// assert_eq!(parser.push_checked(&[0; 4]).state, ParserState::Error);
+    /// ```
+    pub fn push_checked(&mut self, adts_buf: &[u8]) -> PushSummary {
+        let frames = self.recovery.frames();
+        let position = self.recovery.position();
+        self.push(adts_buf);
+        PushSummary {
+            frames: (self.recovery.frames() - frames) as usize,
+            bytes_consumed: (self.recovery.position() - position) as usize,
+            pending: self.pending_bytes(),
+            state: self.state(),
+        }
+    }
+
+    /// Extracts information about each ADTS frame in the given buffer, which is passed to the
+    /// `AdtsConsumer` implementation supplied at construction time.
+    ///
+    /// If the given buffer ends part-way through an ADTS frame, the remaining unconsumed data
+    /// will be buffered inside this AdtsParser instance, and the rest of the ADTS frame may be
+    /// passed in another buffer in the next call to this method.
+    pub fn push(&mut self, adts_buf: &[u8]) {
+        let mut buf = adts_buf;
+        let mut frames = 0;
+        let mut incomplete_iterations = 0;
+        loop {
+            match self.state {
+                AdtsState::Start => break,
+                AdtsState::Error => match self.skip_garbage(buf) {
+                    Some(pos) => buf = &buf[pos..],
+                    None => return,
+                },
+                AdtsState::Failed => {
+                    self.recovery.garbage(buf.len());
+                    return;
+                }
+                AdtsState::Payload => {
+                    let n = self.payload_remaining.min(buf.len());
+                    if n > 0 {
+                        self.consumer.on_payload_chunk(&buf[..n]);
+                    }
+                    buf = &buf[n..];
+                    self.payload_remaining -= n;
+                    if self.payload_remaining > 0 {
+                        return;
+                    }
+                    self.consumer.on_payload_end(true);
+                    self.recovery.frame(self.payload_len);
+                    frames += 1;
+                    self.state = AdtsState::Start;
+                }
+                AdtsState::Id3 => {
+                    let needed = self.desired_data_len.unwrap() - self.incomplete_frame.len();
+                    if buf.len() < needed {
+                        self.incomplete_frame.extend_from_slice(buf);
+                        return;
+                    }
+                    self.incomplete_frame.extend_from_slice(&buf[..needed]);
+                    buf = &buf[needed..];
+                    match id3::buffered_len(
+                        &self.incomplete_frame,
+                        self.settings.limits.max_buffered_bytes,
+                    ) {
+                        Some(len) if len > self.incomplete_frame.len() => {
+                            self.desired_data_len = Some(len);
+                        }
+                        Some(_) => {
+                            let tag = std::mem::take(&mut self.incomplete_frame);
+                            self.desired_data_len = None;
+                            self.state = AdtsState::Start;
+                            self.id3_tag(&tag);
+                        }
+                        None => self.false_start(AdtsParseError::BadSyncWord),
+                    }
+                }
+                AdtsState::Incomplete => {
+                    incomplete_iterations += 1;
+                    if incomplete_iterations > self.settings.limits.max_incomplete_iterations {
+                        let pending = std::mem::take(&mut self.incomplete_frame);
+                        self.desired_data_len = None;
+                        self.limit_exceeded(pending.len() + buf.len());
+                        return;
+                    }
+                    // on last call to push(), the end of the adts_buf held the start of an ADTS
+                    // frame, and we copied that data into incomplete_buffer, so now lets try to
+                    // add enough initial bytes from the adts_buf given to this call to get a
+                    // complete frame
+                    let bytes_needed_to_complete_frame =
+                        self.desired_data_len.unwrap() - self.incomplete_frame.len();
                     if buf.len() < bytes_needed_to_complete_frame {
                         self.incomplete_frame.extend_from_slice(buf);
                         return;
@@ -568,127 +2162,591 @@ where
                     self.incomplete_frame
                         .extend_from_slice(&buf[..bytes_needed_to_complete_frame]);
                     buf = &buf[bytes_needed_to_complete_frame..];
-                    let mut still_more = false; // TODO: this is horrible
+                    if self.recovery.resyncing() && !maybe_sync(&self.incomplete_frame) {
+                        // skip_garbage() kept a 0xff at the end of the data for a possible
+                        // sync-word, but the byte following shows that it wasn't one, so carry on
+                        // skipping garbage just as if the data had been pushed all at once
+                        let pending = std::mem::take(&mut self.incomplete_frame);
+                        self.desired_data_len = None;
+                        self.recovery.garbage(1);
+                        self.state = AdtsState::Error;
+                        self.push(&pending[1..]);
+                        continue;
+                    }
                     match AdtsHeader::from_bytes(&self.incomplete_frame[..]) {
                         Ok(header) => {
-                            if (header.frame_length() as usize) > self.incomplete_frame.len() {
-                                self.desired_data_len = Some(header.frame_length() as usize);
-                                still_more = true;
+                            let frame_length = self.settings.quirks.frame_len(&header);
+                            if frame_length > self.settings.limits.max_frame_length {
+                                self.false_start(AdtsParseError::BadFrameLength);
+                                continue;
+                            }
+                            if self.settings.early_headers && !self.header_announced {
+                                Self::announce_header(
+                                    &mut self.consumer,
+                                    &self.recovery,
+                                    &header,
+                                    frame_length,
+                                );
+                                self.header_announced = true;
+                            }
+                            if self.settings.streaming_payloads {
+                                // only the header need be collected before streaming the rest
+                                let header_len =
+                                    usize::from(header.header_length()).min(frame_length);
+                                if header_len > self.incomplete_frame.len() {
+                                    self.desired_data_len = Some(header_len);
+                                    continue;
+                                }
+                                let pending = std::mem::take(&mut self.incomplete_frame);
+                                if self.stream_frame(&pending, frame_length) {
+                                    frames += 1;
+                                }
+                                self.incomplete_frame = pending;
+                                continue;
+                            }
+                            if frame_length > self.incomplete_frame.len() {
+                                if frame_length > self.settings.limits.max_buffered_bytes {
+                                    let pending = std::mem::take(&mut self.incomplete_frame);
+                                    self.desired_data_len = None;
+                                    self.limit_exceeded(pending.len() + buf.len());
+                                    return;
+                                }
+                                self.desired_data_len = Some(frame_length);
+                                continue;
+                            }
+                            if let Some(reason) = self.unsupported(&header) {
+                                Self::push_unsupported(
+                                    &mut self.consumer,
+                                    &self.recovery,
+                                    self.recovery.stats.label.as_deref(),
+                                    &header,
+                                    frame_length,
+                                    reason,
+                                );
                             } else {
                                 if self.is_new_config(&self.incomplete_frame[..]) {
                                     Self::push_config(
                                         &mut self.current_config,
                                         &mut self.consumer,
+                                        &mut self.recovery,
                                         &header,
                                         &self.incomplete_frame[..],
                                     );
                                 }
-                                Self::push_payload(&mut self.consumer, header);
-                                self.state = AdtsState::Start;
+                                Self::push_payload(
+                                    &mut self.consumer,
+                                    &self.settings,
+                                    &mut self.recovery,
+                                    &mut self.corrected_frame,
+                                    &mut self.pes,
+                                    header,
+                                );
                             }
+                            self.recovery.frame(frame_length);
+                            frames += 1;
+                            self.state = AdtsState::Start;
+                        }
+                        Err(AdtsHeaderError::NotEnoughData { expected, .. }) => {
+                            self.desired_data_len = Some(expected);
                         }
                         Err(e) => {
-                            self.state = AdtsState::Error;
-                            match e {
-                                AdtsHeaderError::BadSyncWord { .. } => {
-                                    self.consumer.error(AdtsParseError::BadSyncWord);
-                                    return;
-                                }
-                                AdtsHeaderError::BadFrameLength { .. } => {
-                                    self.consumer.error(AdtsParseError::BadFrameLength);
-                                    return;
-                                }
-                                AdtsHeaderError::NotEnoughData { expected, .. } => {
-                                    self.desired_data_len = Some(expected);
-                                    still_more = true;
+                            match id3::buffered_len(
+                                &self.incomplete_frame,
+                                self.settings.limits.max_buffered_bytes,
+                            )
+                            .filter(|_| self.settings.id3_tags)
+                            {
+                                // the buffered data turned out to start an ID3 tag, whose
+                                // length is known once its header is complete
+                                Some(len) => {
+                                    self.state = AdtsState::Id3;
+                                    self.desired_data_len =
+                                        Some(len.max(self.incomplete_frame.len()));
                                 }
+                                None => self.false_start(Self::parse_error(&e)),
                             }
                         }
                     }
-                    if !still_more {
-                        break;
-                    }
                 }
             }
-            AdtsState::Start => (),
-        };
+        }
         let mut pos = 0;
         while pos < buf.len() {
             let remaining_data = &buf[pos..];
+            if frames >= self.settings.limits.max_frames_per_push {
+                self.limit_exceeded(remaining_data.len());
+                return;
+            }
             let h = match AdtsHeader::from_bytes(remaining_data) {
-                Ok(header) => header,
+                Ok(header)
+                    if self.settings.quirks.frame_len(&header)
+                        > self.settings.limits.max_frame_length =>
+                {
+                    Err(AdtsParseError::BadFrameLength)
+                }
+                Ok(header) => Ok(header),
+                Err(AdtsHeaderError::NotEnoughData { expected, .. }) => {
+                    self.remember(remaining_data, expected);
+                    return;
+                }
                 Err(e) => {
-                    self.state = AdtsState::Error;
-                    match e {
-                        AdtsHeaderError::BadSyncWord { .. } => {
-                            self.consumer.error(AdtsParseError::BadSyncWord)
-                        }
-                        AdtsHeaderError::BadFrameLength { .. } => {
-                            self.consumer.error(AdtsParseError::BadFrameLength);
+                    if let Some(len) =
+                        id3::buffered_len(remaining_data, self.settings.limits.max_buffered_bytes)
+                            .filter(|_| self.settings.id3_tags)
+                    {
+                        if len > remaining_data.len() {
+                            self.remember(remaining_data, len);
+                            self.state = AdtsState::Id3;
                             return;
                         }
-                        AdtsHeaderError::NotEnoughData { expected, .. } => {
-                            self.remember(remaining_data, expected);
-                            return;
+                        self.id3_tag(&remaining_data[..len]);
+                        pos += len;
+                        continue;
+                    }
+                    Err(Self::parse_error(&e))
+                }
+            };
+            let h = match h {
+                Ok(header) => header,
+                Err(err) => {
+                    self.bitstream_error(err);
+                    if id3::starts_tag(remaining_data) {
+                        self.recovery.id3();
+                    }
+                    self.recovery.garbage(1);
+                    match self.skip_garbage(&remaining_data[1..]) {
+                        Some(skipped) => {
+                            pos += 1 + skipped;
+                            continue;
                         }
+                        None => return,
                     }
-                    return;
                 }
             };
-            let new_pos = pos + h.frame_length() as usize;
+            let frame_length = self.settings.quirks.frame_len(&h);
+            let new_pos = pos + frame_length;
+            if self.settings.early_headers {
+                Self::announce_header(&mut self.consumer, &self.recovery, &h, frame_length);
+            }
+            if self.settings.streaming_payloads {
+                let header_len = usize::from(h.header_length()).min(frame_length);
+                if header_len > remaining_data.len() {
+                    self.remember(remaining_data, header_len);
+                    self.header_announced = self.settings.early_headers;
+                    return;
+                }
+                let end = new_pos.min(buf.len());
+                if self.stream_frame(&buf[pos..end], frame_length) {
+                    frames += 1;
+                }
+                pos = end;
+                continue;
+            }
             if new_pos > buf.len() {
-                self.remember(remaining_data, h.frame_length() as usize);
+                if frame_length > self.settings.limits.max_buffered_bytes {
+                    self.limit_exceeded(remaining_data.len());
+                    return;
+                }
+                self.remember(remaining_data, frame_length);
+                self.header_announced = self.settings.early_headers;
                 return;
             }
-            if self.is_new_config(remaining_data) {
-                Self::push_config(
-                    &mut self.current_config,
+            if let Some(reason) = self.unsupported(&h) {
+                Self::push_unsupported(
                     &mut self.consumer,
+                    &self.recovery,
+                    self.recovery.stats.label.as_deref(),
                     &h,
-                    remaining_data,
+                    frame_length,
+                    reason,
+                );
+            } else {
+                if self.is_new_config(remaining_data) {
+                    Self::push_config(
+                        &mut self.current_config,
+                        &mut self.consumer,
+                        &mut self.recovery,
+                        &h,
+                        remaining_data,
+                    );
+                }
+                Self::push_payload(
+                    &mut self.consumer,
+                    &self.settings,
+                    &mut self.recovery,
+                    &mut self.corrected_frame,
+                    &mut self.pes,
+                    h,
                 );
             }
-            Self::push_payload(&mut self.consumer, h);
-            self.state = AdtsState::Start;
+            self.recovery.frame(new_pos - pos);
+            frames += 1;
             pos = new_pos;
         }
     }
 
+    /// Pushes all the data remaining in the given `bytes::Buf`, as for [`push()`](#method.push),
+    /// advancing it to the end.
+    ///
+    /// Each of the buffer's chunks is parsed in place, so a `Buf` gathering data from several
+    /// separate allocations (a chain of `Bytes` from a ring of socket buffers, say) needn't be
+    /// copied into one contiguous buffer first.  As with any division of the input, only a frame
+    /// spanning the boundary between chunks is copied.
+    ///
+    /// Available when the `bytes` feature is enabled.
+    ///
+    /// ```rust
+    /// use adts_reader::{AdtsConsumer2, AdtsParser, FrameContext};
+    /// use bytes::{Buf, Bytes};
+    /// # struct MyConsumer;
+    /// # impl AdtsConsumer2 for MyConsumer {
+    /// #     fn on_frame(&mut self, _: &FrameContext<'_>) {}
+    /// # }
+    /// let mut input = Bytes::from_static(&[0xff, 0xf1, 0x4c, 0x80])
+    ///     .chain(Bytes::from_static(&[0x01, 0x1f, 0xfc, 0x21]));
+    /// let mut parser = AdtsParser::new(MyConsumer);
+    /// parser.push_buf(&mut input);
+    /// assert!(!input.has_remaining());
+    /// ```
+    #[cfg(feature = "bytes")]
+    pub fn push_buf<B: bytes::Buf>(&mut self, buf: &mut B) {
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let len = chunk.len();
+            self.push(chunk);
+            buf.advance(len);
+        }
+    }
+
+    /// Enters the error state, reporting the error to the consumer unless it is part of a run of
+    /// garbage which has already been reported.
+    fn bitstream_error(&mut self, err: AdtsParseError) {
+        self.state = AdtsState::Error;
+        if self.recovery.error(self.clock.now()) {
+            Self::report_error(&mut self.consumer, &self.recovery, err);
+        }
+        self.count_error();
+    }
+
+    /// The error reported for data which `AdtsHeader::from_bytes()` rejected
+    fn parse_error(err: &AdtsHeaderError) -> AdtsParseError {
+        match err {
+            AdtsHeaderError::BadFrameLength { .. } => AdtsParseError::BadFrameLength,
+            _ => AdtsParseError::BadSyncWord,
+        }
+    }
+
+    /// Counts an error towards `ParserLimits::max_consecutive_errors`, giving up on the stream
+    /// if there have now been too many since the last good frame
+    fn count_error(&mut self) {
+        if self.recovery.frames() != self.error_frames {
+            self.error_frames = self.recovery.frames();
+            self.consecutive_errors = 0;
+        }
+        self.consecutive_errors += 1;
+        if let Some(max) = self.settings.limits.max_consecutive_errors {
+            if self.consecutive_errors > max {
+                self.state = AdtsState::Failed;
+                Self::report_error(
+                    &mut self.consumer,
+                    &self.recovery,
+                    AdtsParseError::TooManyErrors,
+                );
+            }
+        }
+    }
+
+    /// Starts passing a frame of the given length to the consumer in pieces, beginning with the
+    /// given data (its header, and as much of its payload as has arrived).  Returns `true` if the
+    /// data held the whole frame, or otherwise leaves the parser awaiting the rest.
+    fn stream_frame(&mut self, data: &[u8], frame_length: usize) -> bool {
+        let h = AdtsHeader { buf: data };
+        if self.is_new_config(data) {
+            Self::push_config(
+                &mut self.current_config,
+                &mut self.consumer,
+                &mut self.recovery,
+                &h,
+                data,
+            );
+        }
+        self.consumer.on_payload_start(&HeaderContext {
+            header: AdtsHeader { buf: data },
+            len: frame_length,
+            index: self.recovery.frames(),
+            offset: self.recovery.position(),
+            time: self.recovery.elapsed().to_duration(),
+            label: self.recovery.stats.label.as_deref(),
+        });
+        if let Some(id) = self.recovery.copyright.frame(&h) {
+            self.consumer.on_copyright_id(id);
+        }
+        self.recovery.elapse(&h);
+        self.recovery.last_frame(&h);
+        let header_len = usize::from(h.header_length()).min(frame_length);
+        if data.len() > header_len {
+            self.consumer.on_payload_chunk(&data[header_len..]);
+        }
+        if data.len() < frame_length {
+            self.state = AdtsState::Payload;
+            self.payload_len = frame_length;
+            self.payload_remaining = frame_length - data.len();
+            return false;
+        }
+        self.consumer.on_payload_end(true);
+        self.recovery.frame(frame_length);
+        self.state = AdtsState::Start;
+        true
+    }
+
+    /// Handles data buffered in `incomplete_frame` which turned out not to start a frame after
+    /// all, by looking for a sync-word following the false one, before carrying on with the rest
+    /// of the data being pushed
+    fn false_start(&mut self, err: AdtsParseError) {
+        self.bitstream_error(err);
+        if id3::starts_tag(&self.incomplete_frame) {
+            self.recovery.id3();
+        }
+        let pending = std::mem::take(&mut self.incomplete_frame);
+        self.desired_data_len = None;
+        self.recovery.garbage(1);
+        self.push(&pending[1..]);
+    }
+
+    /// Passes a complete ID3v2 tag to the consumer and skips it, taking any HLS timestamp it
+    /// holds to be that of the next frame
+    fn id3_tag(&mut self, tag: &[u8]) {
+        let timestamp = id3::transport_stream_timestamp(tag);
+        self.consumer.on_id3_tag(&Id3Tag {
+            tag,
+            timestamp,
+            index: self.recovery.frames(),
+            offset: self.recovery.position(),
+            label: self.recovery.stats.label.as_deref(),
+        });
+        self.recovery.id3_tag(tag.len());
+        if timestamp.is_some() {
+            self.pes.begin_packet(self.recovery.position(), timestamp);
+        }
+    }
+
+    /// Abandons the current `push()`, discarding its remaining `len` bytes.  The next `push()`
+    /// resumes from the next sync-word found.
+    fn limit_exceeded(&mut self, len: usize) {
+        self.state = AdtsState::Error;
+        if self.recovery.error(self.clock.now()) {
+            Self::report_error(
+                &mut self.consumer,
+                &self.recovery,
+                AdtsParseError::LimitExceeded,
+            );
+        }
+        self.recovery.garbage(len);
+        self.count_error();
+    }
+
+    /// Discards data up to the next possible sync-word, returning its position within `buf` if
+    /// one was found (in which case normal parsing may resume from there).
+    fn skip_garbage(&mut self, buf: &[u8]) -> Option<usize> {
+        if self.state == AdtsState::Failed {
+            self.recovery.garbage(buf.len());
+            return None;
+        }
+        let found = sync::find_possible_sync(buf);
+        self.recovery.garbage(found.unwrap_or(buf.len()));
+        if found.is_some() {
+            self.state = AdtsState::Start;
+        }
+        found
+    }
+
     fn push_config(
-        current_config: &mut [u8; 3],
+        current_config: &mut [u8; 4],
         consumer: &mut C,
+        recovery: &mut stats::Recovery,
         h: &AdtsHeader<'_>,
         frame_buffer: &[u8],
     ) {
-        current_config.copy_from_slice(&frame_buffer[0..3]);
-        consumer.new_config(
-            h.mpeg_version(),
-            h.protection(),
-            h.audio_object_type(),
-            h.sampling_frequency(),
-            h.private_bit(),
-            h.channel_configuration(),
-            h.originality(),
-            h.home(),
+        if let Some(boundary) = recovery.stream_boundary(h) {
+            consumer.on_stream_boundary(&boundary);
+        }
+        let previous = Some(*current_config).filter(|c| c != &[0; 4]).map(|c| {
+            let p = AdtsHeader { buf: &c };
+            (p.stream_config(), p.protection())
+        });
+        current_config.copy_from_slice(&frame_buffer[0..4]);
+        let config = h.stream_config();
+        let protection = h.protection();
+        let changed = match previous {
+            Some((previous_config, previous_protection)) => {
+                let mut changed = previous_config.changed_fields(&config);
+                if previous_protection != protection {
+                    // protection_absent comes between the MPEG version and the profile
+                    let i = changed
+                        .iter()
+                        .take_while(|&&f| f == ConfigField::MpegVersion)
+                        .count();
+                    changed.insert(i, ConfigField::Protection);
+                }
+                changed
+            }
+            None => vec![],
+        };
+        consumer.on_config_change(&ConfigChange {
+            previous,
+            config,
+            protection,
+            changed,
+            index: recovery.frames(),
+            offset: recovery.position(),
+        });
+    }
+
+    /// In strict mode, the reason the crate can't interpret the frame with the given header
+    fn unsupported(&self, h: &AdtsHeader<'_>) -> Option<Unsupported> {
+        if !self.settings.strict {
+            return None;
+        }
+        let layer = fields::LAYER.get(h.buf) as u8;
+        let freq = h.sampling_frequency();
+        if layer != 0 {
+            Some(Unsupported::Layer(layer))
+        } else if freq.freq().is_none() {
+            Some(Unsupported::ReservedSamplingFrequency(freq))
+        } else {
+            None
+        }
+    }
+
+    fn push_unsupported(
+        consumer: &mut C,
+        recovery: &stats::Recovery,
+        label: Option<&str>,
+        h: &AdtsHeader<'_>,
+        len: usize,
+        reason: Unsupported,
+    ) {
+        consumer.on_raw_unsupported(&RawUnsupported {
+            frame: &h.buf[..len],
+            reason,
+            index: recovery.frames(),
+            offset: recovery.position(),
+            label,
+        });
+    }
+
+    fn announce_header(
+        consumer: &mut C,
+        recovery: &stats::Recovery,
+        h: &AdtsHeader<'_>,
+        len: usize,
+    ) {
+        consumer.on_header(&HeaderContext {
+            header: AdtsHeader { buf: h.buf },
+            len,
+            index: recovery.frames(),
+            offset: recovery.position(),
+            time: recovery.elapsed().to_duration(),
+            label: recovery.stats.label.as_deref(),
+        });
+    }
+
+    fn report_error(consumer: &mut C, recovery: &stats::Recovery, err: AdtsParseError) {
+        consumer.on_error_at(
+            err,
+            &ErrorContext {
+                index: recovery.frames(),
+                offset: recovery.position(),
+                time: recovery.elapsed().to_duration(),
+                label: recovery.stats.label.as_deref(),
+            },
         );
     }
 
-    fn push_payload(consumer: &mut C, h: AdtsHeader<'_>) {
+    fn push_payload(
+        consumer: &mut C,
+        settings: &Settings,
+        recovery: &mut stats::Recovery,
+        corrected_frame: &mut Vec<u8>,
+        pes: &mut pes::PesTiming,
+        h: AdtsHeader<'_>,
+    ) {
+        let quirks = settings.quirks;
+        let encryption = settings.encryption;
+        let applied = quirks.applicable(&h);
+        // the CRC covers the header as written, before any correction
+        let mut sent = [0; 7];
+        sent.copy_from_slice(&h.buf[..7]);
+        let h = if applied.is_empty() {
+            h
+        } else {
+            recovery.stats.quirk_corrected_frames += 1;
+            corrected_frame.clear();
+            corrected_frame.extend_from_slice(&h.buf[..quirks.frame_len(&h)]);
+            applied.correct(corrected_frame);
+            AdtsHeader::from_bytes(corrected_frame).expect("corrected header remains valid")
+        };
         match h.payload() {
             Ok(payload) => {
-                consumer.payload(
-                    h.adts_buffer_fullness(),
-                    h.number_of_raw_data_blocks_in_frame(),
+                if payload.is_empty() {
+                    recovery.stats.empty_frames += 1;
+                }
+                let time = recovery.elapsed().to_duration();
+                let pts = pes.frame(recovery.position(), &h, recovery.frame_length);
+                recovery.elapse(&h);
+                recovery.last_frame(&h);
+                let frame = FrameContext {
+                    header: h,
                     payload,
-                );
+                    index: recovery.frames(),
+                    offset: recovery.position(),
+                    time,
+                    pts,
+                    resynced: recovery.resyncing(),
+                    encryption,
+                    quirks: applied,
+                    label: recovery.stats.label.as_deref(),
+                };
+                if settings.crc_policy != crc::CrcPolicy::Ignore && !encryption.is_encrypted() {
+                    match crc::verify_as_sent(&sent, &frame.header, &crc::DEFAULT) {
+                        Ok(()) | Err(crc::CrcError::Absent) => (),
+                        Err(err) => {
+                            recovery.stats.crc_mismatches += 1;
+                            consumer.on_crc_error(&frame, err);
+                            if settings.crc_policy == crc::CrcPolicy::Drop {
+                                recovery.copyright.reset();
+                                return;
+                            }
+                        }
+                    }
+                }
+                consumer.on_frame(&frame);
+                if let Some(id) = recovery.copyright.frame(&frame.header) {
+                    consumer.on_copyright_id(id);
+                }
+                if settings.raw_data_blocks {
+                    match frame.header.raw_data_blocks() {
+                        Ok(blocks) => {
+                            for block in blocks {
+                                consumer.on_raw_data_block(&frame, &block);
+                            }
+                        }
+                        Err(_) => consumer.on_error_at(
+                            AdtsParseError::UnlocatedBlocks,
+                            &ErrorContext {
+                                index: frame.index,
+                                offset: frame.offset,
+                                time: frame.time,
+                                label: frame.label,
+                            },
+                        ),
+                    }
+                }
             }
-            Err(PayloadError { expected, actual }) => {
+            Err(PayloadError { .. }) => {
                 // since we checked we had enough data for the whole frame above, this must be
-                // a bug,
-                panic!(
-                    "Unexpected payload size mismatch: expected {}, actual size {}",
-                    expected, actual
-                );
+                // a bug, but one better reported than allowed to take down the caller
+                Self::report_error(consumer, recovery, AdtsParseError::PayloadMismatch);
             }
         }
     }
@@ -700,7 +2758,7 @@ mod tests {
     use bitstream_io::{BigEndian, BitWrite, BitWriter, BE};
     use std::io;
 
-    fn make_test_data<F>(builder: F) -> Vec<u8>
+    pub(crate) fn make_test_data<F>(builder: F) -> Vec<u8>
     where
         F: Fn(BitWriter<&mut Vec<u8>, BE>) -> Result<(), io::Error>,
     {
@@ -709,7 +2767,7 @@ mod tests {
         data
     }
 
-    fn write_frame(w: &mut BitWriter<&mut Vec<u8>, BE>) -> Result<(), io::Error> {
+    pub(crate) fn write_frame(w: &mut BitWriter<&mut Vec<u8>, BE>) -> Result<(), io::Error> {
         w.write(12, 0xfff)?; // sync_word
         w.write(1, 0)?; // mpeg_version
         w.write(2, 0)?; // layer
@@ -735,8 +2793,13 @@ mod tests {
         assert_eq!(header.mpeg_version(), MpegVersion::Mpeg4);
         assert_eq!(header.protection(), ProtectionIndicator::CrcAbsent);
         assert_eq!(header.audio_object_type(), AudioObjectType::AacMain);
+        assert_eq!(
+            header.profile(),
+            Profile::Mpeg4AudioObjectType(AudioObjectType::AacMain)
+        );
         assert_eq!(header.sampling_frequency(), SamplingFrequency::Freq48000);
         assert_eq!(header.sampling_frequency().freq(), Some(48000));
+        assert_eq!(header.private_bit(), 1);
         assert_eq!(header.channel_configuration(), ChannelConfiguration::Stereo);
         assert_eq!(header.originality(), Originality::Copy);
         assert_eq!(header.home(), 0);
@@ -799,20 +2862,1241 @@ mod tests {
 
     #[test]
     fn parser() {
-        let header_data = make_test_data(|mut w| {
-            write_frame(&mut w)?;
-            write_frame(&mut w)
-        });
+        let header_data = testing::StreamBuilder::new()
+            .payload_len(1)
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
         for split in 0..header_data.len() {
             let mut parser = AdtsParser::new(MockConsumer::new());
             let (head, tail) = header_data.split_at(split);
             parser.push(head);
+            assert_eq!(parser.pending_bytes(), split % 8, "split at {}", split);
             parser.push(tail);
+            assert_eq!(parser.pending_bytes(), 0, "split at {}", split);
             assert_eq!(2, parser.consumer.payload_seq);
             assert_eq!(Some(1), parser.consumer.payload_size);
         }
     }
 
+    #[derive(Default)]
+    struct CountingConsumer {
+        payloads: Vec<Vec<u8>>,
+        errors: Vec<AdtsParseError>,
+    }
+    impl AdtsConsumer for CountingConsumer {
+        fn new_config(
+            &mut self,
+            _: MpegVersion,
+            _: ProtectionIndicator,
+            _: AudioObjectType,
+            _: SamplingFrequency,
+            _: u8,
+            _: ChannelConfiguration,
+            _: Originality,
+            _: u8,
+        ) {
+        }
+        fn payload(&mut self, _: u16, _: u8, buf: &[u8]) {
+            self.payloads.push(buf.to_vec());
+        }
+        fn error(&mut self, err: AdtsParseError) {
+            self.errors.push(err);
+        }
+    }
+
+    #[test]
+    fn resync() {
+        let data = testing::StreamBuilder::new()
+            .frame(&[1])
+            // garbage including a false sync-word
+            .bytes(&[0, 0xff, 0xf1, 0, 0, 0, 0, 0, 0])
+            .frame(&[2, 2])
+            .frame(&[3])
+            .junk(4)
+            .frame(&[4])
+            .build();
+        for split in 0..data.len() {
+            let mut parser = AdtsParser::new(CountingConsumer::default());
+            let (head, tail) = data.split_at(split);
+            parser.push(head);
+            parser.push(tail);
+            assert_eq!(
+                parser.consumer.payloads,
+                vec![vec![1], vec![2, 2], vec![3], vec![4]],
+                "split at {}",
+                split
+            );
+            assert_eq!(parser.consumer.errors.len(), 2, "split at {}", split);
+            let stats = parser.stats();
+            assert_eq!(stats.errors, 2);
+            assert_eq!(stats.resync_events, 2);
+            assert_eq!(stats.garbage_bytes, 9 + 4);
+            assert_eq!(stats.longest_garbage_run, 9);
+            assert_eq!(stats.bytes_since_last_error, Some(4 + 8));
+            assert!(stats.time_since_last_error().is_some());
+        }
+
+        // garbage ending in 0xff, followed by bytes which are a header in all but the layer
+        // field, and so must be skipped however the input is divided
+        let data = testing::StreamBuilder::new()
+            .junk(7)
+            .bytes(&[0xff, 0xfa, 0x1d, 0xed, 0x78, 0x84, 0x65])
+            .frame(&[1])
+            .frame(&[2, 2])
+            .build();
+        for split in 0..data.len() {
+            let mut parser = AdtsParser::new(CountingConsumer::default());
+            let (head, tail) = data.split_at(split);
+            parser.push(head);
+            parser.push(tail);
+            assert_eq!(
+                parser.consumer.payloads,
+                vec![vec![1], vec![2, 2]],
+                "split at {}",
+                split
+            );
+            assert_eq!(parser.consumer.errors.len(), 1, "split at {}", split);
+            assert_eq!(parser.stats().garbage_bytes, 14, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        // 8 byte frames, each holding its index
+        let stream = testing::StreamBuilder::new()
+            .payload_len(1)
+            .frames(7, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let (data, rest) = stream.split_at(5 * 8);
+        let limits = ParserLimits {
+            max_frames_per_push: 3,
+            max_incomplete_iterations: 1,
+            ..ParserLimits::default()
+        };
+        let mut parser = AdtsParser::new(CountingConsumer::default()).with_limits(limits);
+        parser.push(data);
+        assert_eq!(parser.consumer.payloads, vec![vec![0], vec![1], vec![2]]);
+        assert_eq!(parser.consumer.errors, vec![AdtsParseError::LimitExceeded]);
+        assert_eq!(parser.stats().garbage_bytes, 16);
+        // the next push resumes at the next frame
+        parser.push(&rest[..8]);
+        assert_eq!(parser.consumer.payloads.len(), 4);
+        assert_eq!(parser.stats().resync_events, 1);
+
+        // completing a frame split within its header takes two iterations
+        let next = &rest[8..];
+        parser.push(&next[..3]);
+        parser.push(&next[3..]);
+        assert_eq!(parser.consumer.payloads.len(), 4);
+        assert_eq!(parser.consumer.errors.len(), 2);
+        // ..while a frame split after the header needs only one
+        parser.push(&next[..7]);
+        parser.push(&next[7..]);
+        assert_eq!(parser.consumer.payloads.len(), 5);
+    }
+
+    #[test]
+    fn resource_limits() {
+        // a 107 byte frame, followed by two of 8 bytes
+        let big = testing::StreamBuilder::new()
+            .payload_len(100)
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let small = testing::StreamBuilder::new()
+            .payload_len(1)
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let data = [&big[..], &small[..]].concat();
+
+        // the large frame is rejected wherever it falls
+        let limits = ParserLimits {
+            max_frame_length: 50,
+            ..ParserLimits::default()
+        };
+        for split in [3, 20, data.len()] {
+            let mut parser =
+                AdtsParser::new(CountingConsumer::default()).with_limits(limits.clone());
+            parser.push(&data[..split]);
+            parser.push(&data[split..]);
+            assert_eq!(parser.consumer.payloads, [[0], [1]], "split at {}", split);
+            assert_eq!(parser.consumer.errors, [AdtsParseError::BadFrameLength]);
+        }
+
+        // the large frame can't be held, but is accepted when pushed whole
+        let limits = ParserLimits {
+            max_buffered_bytes: 50,
+            ..ParserLimits::default()
+        };
+        let mut parser = AdtsParser::new(CountingConsumer::default()).with_limits(limits);
+        parser.push(&data);
+        parser.push(&data[..20]);
+        assert_eq!(parser.pending_bytes(), 0);
+        parser.push(&data[20..]);
+        assert_eq!(parser.consumer.payloads.len(), 3 + 2);
+        assert_eq!(parser.consumer.errors, [AdtsParseError::LimitExceeded]);
+
+        // giving up on a stream of false sync-words, until restarted
+        let limits = ParserLimits {
+            max_consecutive_errors: Some(3),
+            ..ParserLimits::default()
+        };
+        let junk = [0xff, 0xf1, 0x4c, 0x80, 0x00, 0x00, 0x00, 0x00].repeat(10);
+        let mut parser = AdtsParser::new(CountingConsumer::default()).with_limits(limits);
+        parser.push(&small);
+        parser.push(&junk);
+        assert_eq!(
+            parser.consumer.errors,
+            [
+                AdtsParseError::BadFrameLength,
+                AdtsParseError::TooManyErrors
+            ]
+        );
+        assert_eq!(parser.state(), ParserState::Failed);
+        parser.push(&small);
+        assert_eq!(parser.consumer.payloads.len(), 2);
+        assert_eq!(
+            parser.stats().garbage_bytes,
+            (junk.len() + small.len()) as u64
+        );
+        parser.start();
+        parser.push(&small);
+        assert_eq!(parser.consumer.payloads.len(), 4);
+    }
+
+    #[test]
+    fn garbage_until_end() {
+        let clock = clock::ManualClock::new();
+        let mut parser = AdtsParser::with_clock(CountingConsumer::default(), clock.clone());
+        assert_eq!(parser.stats(), &ParserStats::default());
+        for _ in 0..10 {
+            parser.push(&[0xaa; 1000]);
+            clock.advance(std::time::Duration::from_secs(1));
+        }
+        assert!(parser.consumer.payloads.is_empty());
+        assert_eq!(parser.consumer.errors, vec![AdtsParseError::BadSyncWord]);
+        let stats = parser.stats();
+        assert_eq!(stats.resync_events, 0);
+        assert_eq!(stats.garbage_bytes, 10_000);
+        assert_eq!(stats.longest_garbage_run, 10_000);
+        assert_eq!(stats.bytes_since_last_error, Some(10_000));
+        assert_eq!(
+            stats.time_since_last_error_at(clock::Clock::now(&clock)),
+            Some(std::time::Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn frame_context() {
+        #[derive(Default)]
+        struct ContextConsumer {
+            configs: Vec<AdtsStreamConfig>,
+            frames: Vec<(u64, u64, bool, u16)>,
+            encryption: Vec<encryption::PayloadEncryption>,
+            times: Vec<Duration>,
+        }
+        impl AdtsConsumer2 for ContextConsumer {
+            fn on_config(&mut self, config: AdtsStreamConfig, _: ProtectionIndicator) {
+                self.configs.push(config);
+            }
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.frames.push((
+                    frame.index,
+                    frame.offset,
+                    frame.resynced,
+                    frame.header.frame_length(),
+                ));
+                self.encryption.push(frame.encryption);
+                self.times.push(frame.time);
+            }
+        }
+        let config = testing::StreamBuilder::DEFAULT_CONFIG;
+        let data = testing::StreamBuilder::new()
+            .frame(&[1])
+            .bytes(&[0; 5])
+            .frame(&[1; 2])
+            .frame(&[1; 3])
+            .build();
+        let mut parser =
+            AdtsParser::new(ContextConsumer::default()).with_frame_length(FrameLength::Samples960);
+        assert_eq!(parser.config_bytes(), None);
+        // split part way through the second frame, to exercise the incomplete frame path
+        parser.push(&data[..16]);
+        parser.set_encryption(encryption::PayloadEncryption::SampleAes);
+        parser.push(&data[16..]);
+        assert_eq!(parser.consumer.configs, vec![config]);
+        assert_eq!(
+            parser.config_bytes(),
+            Some(config.config_bytes(ProtectionIndicator::CrcAbsent).unwrap())
+        );
+        assert_eq!(
+            AdtsHeader::from_bytes(&data).unwrap().config_bytes(),
+            data[..3]
+        );
+        assert_eq!(
+            parser.consumer.frames,
+            vec![(0, 0, false, 8), (1, 13, true, 9), (2, 22, false, 10)]
+        );
+        assert_eq!(
+            parser.consumer.times,
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(20),
+                Duration::from_millis(40)
+            ]
+        );
+        // the garbage followed the first frame
+        assert_eq!(parser.stats().last_error_offset, Some(8));
+        assert_eq!(
+            parser.stats().last_error_media_time,
+            Some(Duration::from_millis(20))
+        );
+        // the setting applies to frames completed by later pushes
+        use encryption::PayloadEncryption::*;
+        assert_eq!(
+            parser.consumer.encryption,
+            vec![Clear, SampleAes, SampleAes]
+        );
+    }
+
+    #[test]
+    fn crc_policy() {
+        #[derive(Default)]
+        struct CrcConsumer {
+            frames: Vec<u64>,
+            crc_errors: Vec<u64>,
+        }
+        impl AdtsConsumer2 for CrcConsumer {
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.frames.push(frame.index);
+            }
+            fn on_crc_error(&mut self, frame: &FrameContext<'_>, err: crc::CrcError) {
+                assert!(matches!(err, crc::CrcError::Mismatch { .. }));
+                self.crc_errors.push(frame.index);
+            }
+        }
+        let mut data = testing::StreamBuilder::new()
+            .crc(true)
+            .frame(&[1])
+            .frame(&[2])
+            .crc(false)
+            .frame(&[3])
+            .build();
+        // the second frame's payload
+        data[10 + 9] ^= 1;
+        let parse = |policy| {
+            let mut parser = AdtsParser::new(CrcConsumer::default()).with_crc_policy(policy);
+            parser.push(&data);
+            assert_eq!(parser.stats().errors, 0);
+            let mismatches = parser.stats().crc_mismatches;
+            (
+                parser.consumer.frames,
+                parser.consumer.crc_errors,
+                mismatches,
+            )
+        };
+        assert_eq!(parse(crc::CrcPolicy::Ignore), (vec![0, 1, 2], vec![], 0));
+        assert_eq!(parse(crc::CrcPolicy::Flag), (vec![0, 1, 2], vec![1], 1));
+        assert_eq!(parse(crc::CrcPolicy::Drop), (vec![0, 2], vec![1], 1));
+    }
+
+    #[test]
+    fn empty_payloads() {
+        #[derive(Default)]
+        struct Payloads(Vec<(bool, Vec<u8>)>);
+        impl AdtsConsumer2 for Payloads {
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.0
+                    .push((frame.header.has_payload(), frame.payload.to_vec()));
+            }
+        }
+        let data = testing::StreamBuilder::new()
+            .payload_len(0)
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .frame(&[3])
+            .build();
+        assert_eq!(data.len(), 7 + 7 + 9 + 10);
+        for split in 0..data.len() {
+            let mut parser = AdtsParser::new(Payloads::default());
+            parser.push(&data[..split]);
+            parser.push(&data[split..]);
+            assert_eq!(
+                parser.consumer.0,
+                vec![
+                    (false, vec![]),
+                    (false, vec![]),
+                    (false, vec![]),
+                    (true, vec![3])
+                ],
+                "split at {}",
+                split
+            );
+            assert_eq!(parser.stats().empty_frames, 3);
+            assert_eq!(parser.stats().errors, 0);
+        }
+    }
+
+    #[test]
+    fn header_info() {
+        let options = FrameOptions {
+            crc: true,
+            buffer_fullness: 0x123,
+            copyright_identification_bit: 1,
+            copyright_identification_start: CopyrightIdentificationStart::Start,
+            ..FrameOptions::default()
+        };
+        let frame = crate::write_frame(
+            &testing::StreamBuilder::DEFAULT_CONFIG,
+            &[1, 2, 3],
+            &options,
+        )
+        .unwrap();
+        let info = AdtsHeader::from_bytes(&frame).unwrap().info();
+        assert_eq!(info.frame_length, 12);
+        assert!(info.crc.is_some());
+        assert_eq!(info.frame_options(), options);
+        // the copied values are enough to write the same frame again
+        let again = crate::write_frame(&info.stream_config(), &[1, 2, 3], &info.frame_options());
+        assert_eq!(again.unwrap(), frame);
+
+        let plain = crate::write_frame(
+            &testing::StreamBuilder::DEFAULT_CONFIG,
+            &[1, 2, 3],
+            &FrameOptions::default(),
+        )
+        .unwrap();
+        let infos: std::collections::HashSet<_> = [&frame, &plain, &frame]
+            .iter()
+            .map(|f| AdtsHeader::from_bytes(f).unwrap().info())
+            .collect();
+        assert_eq!(infos.len(), 2);
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&info).unwrap();
+            assert_eq!(serde_json::from_str::<AdtsHeaderInfo>(&json).unwrap(), info);
+        }
+    }
+
+    #[test]
+    fn early_headers() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Header { index: u64, offset: u64, len: usize },
+            Config,
+            Frame(u64),
+        }
+        #[derive(Default)]
+        struct Events(Vec<Event>);
+        impl AdtsConsumer2 for Events {
+            fn on_config(&mut self, _: AdtsStreamConfig, _: ProtectionIndicator) {
+                self.0.push(Event::Config);
+            }
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.0.push(Event::Frame(frame.index));
+            }
+            fn on_header(&mut self, header: &HeaderContext<'_>) {
+                assert_eq!(header.header.frame_len(), header.len);
+                self.0.push(Event::Header {
+                    index: header.index,
+                    offset: header.offset,
+                    len: header.len,
+                });
+            }
+        }
+        let builder = testing::StreamBuilder::new()
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG);
+        let offsets = builder.offsets().to_vec();
+        let data = builder.build();
+        let header = |index: usize| Event::Header {
+            index: index as u64,
+            offset: offsets[index] as u64,
+            len: offsets.get(index + 1).copied().unwrap_or(data.len()) - offsets[index],
+        };
+        let expected = vec![
+            header(0),
+            Event::Config,
+            Event::Frame(0),
+            header(1),
+            Event::Config,
+            Event::Frame(1),
+            header(2),
+            Event::Frame(2),
+        ];
+
+        // each header is announced once, however the data is divided
+        let mut parser = AdtsParser::new(Events::default()).with_early_headers(true);
+        parser.push(&data);
+        assert_eq!(parser.consumer.0, expected);
+        let mut parser = AdtsParser::new(Events::default()).with_early_headers(true);
+        for b in data.chunks(1) {
+            parser.push(b);
+        }
+        assert_eq!(parser.consumer.0, expected);
+
+        // the header is announced as soon as it is complete, ahead of the payload
+        let mut parser = AdtsParser::new(Events::default()).with_early_headers(true);
+        parser.push(&data[..6]);
+        assert!(parser.consumer.0.is_empty());
+        parser.push(&data[6..7]);
+        assert_eq!(parser.consumer.0, vec![header(0)]);
+        parser.push(&data[7..offsets[1] + 9]);
+        assert_eq!(parser.consumer.0, expected[..4]);
+
+        let mut parser = AdtsParser::new(Events::default());
+        parser.push(&data);
+        assert!(!parser
+            .consumer
+            .0
+            .iter()
+            .any(|e| matches!(e, Event::Header { .. })));
+    }
+
+    #[test]
+    fn no_panics() {
+        assert_eq!(
+            AdtsHeader::from_bytes(&[]).err(),
+            Some(AdtsHeaderError::NotEnoughData {
+                expected: 7,
+                actual: 0
+            })
+        );
+        for v in 0..=255u8 {
+            assert_eq!(
+                SamplingFrequency::try_from(v).ok(),
+                Some(SamplingFrequency::from_bits(v)).filter(|_| v <= 0xf)
+            );
+            assert_eq!(
+                ChannelConfiguration::try_from(v).ok(),
+                Some(ChannelConfiguration::from_bits(v)).filter(|_| v <= 0x7)
+            );
+        }
+        assert_eq!(
+            SamplingFrequency::try_from(0x3u8),
+            Ok(SamplingFrequency::Freq48000)
+        );
+        for freq in SamplingFrequency::ALL {
+            assert_eq!(SamplingFrequency::try_from(freq.index()), Ok(freq));
+            if let Some(hz) = freq.freq() {
+                assert_eq!(SamplingFrequency::try_from(hz), Ok(freq));
+            }
+        }
+        assert_eq!(
+            SamplingFrequency::try_from(7350u32),
+            Err(SamplingFrequencyError { freq: 7350 })
+        );
+        for config in ChannelConfiguration::ALL {
+            assert_eq!(ChannelConfiguration::try_from(u8::from(config)), Ok(config));
+        }
+        assert_eq!(ChannelConfiguration::Mono.channel_count(), Some(1));
+        assert_eq!(ChannelConfiguration::FiveOne.channel_count(), Some(6));
+        assert_eq!(ChannelConfiguration::SevenOne.channel_count(), Some(8));
+        assert_eq!(
+            ChannelConfiguration::ObjectTypeSpecificConfig.channel_count(),
+            None
+        );
+        for aot in 1..=4 {
+            assert_eq!(u8::from(AudioObjectType::try_from(aot).unwrap()), aot);
+        }
+        assert_eq!(u8::from(AudioObjectType::AacLC), 2);
+        assert!(AudioObjectType::try_from(0).is_err());
+        assert!(AudioObjectType::try_from(5).is_err());
+        assert_eq!(
+            ChannelConfiguration::try_from(8),
+            Err(FieldValueError {
+                field: "channel_configuration",
+                value: 8
+            })
+        );
+    }
+
+    #[test]
+    fn push_summary() {
+        struct Ignore;
+        impl AdtsConsumer2 for Ignore {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+        }
+        let data = testing::StreamBuilder::new()
+            .frames(3, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut parser = AdtsParser::new(Ignore);
+        assert_eq!(parser.state(), ParserState::Ready);
+        let summary = parser.push_checked(&data[..15]);
+        assert_eq!((summary.frames, summary.bytes_consumed), (1, 11));
+        assert_eq!(summary.pending, 4);
+        // completing the held frame consumes more than was pushed
+        let summary = parser.push_checked(&data[15..33]);
+        assert_eq!((summary.frames, summary.bytes_consumed), (2, 22));
+        assert_eq!(summary.state, ParserState::Ready);
+        let summary = parser.push_checked(&[0x55; 10]);
+        assert_eq!((summary.frames, summary.bytes_consumed), (0, 10));
+        assert_eq!(summary.state, ParserState::Error);
+    }
+
+    #[test]
+    fn stream_boundaries() {
+        #[derive(Default)]
+        struct Boundaries {
+            boundaries: Vec<StreamBoundary>,
+            configs: usize,
+        }
+        impl AdtsConsumer2 for Boundaries {
+            fn on_config(&mut self, _: AdtsStreamConfig, _: ProtectionIndicator) {
+                self.configs += 1;
+            }
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+            fn on_stream_boundary(&mut self, boundary: &StreamBoundary) {
+                self.boundaries.push(boundary.clone());
+            }
+        }
+        let other = AdtsStreamConfig {
+            sampling_frequency: SamplingFrequency::Freq44100,
+            ..testing::StreamBuilder::DEFAULT_CONFIG
+        };
+        let tag = b"ID3\x04\x00\x00\x00\x00\x00\x02\x00\x00";
+        let builder = testing::StreamBuilder::new()
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .bytes(tag)
+            .frames(2, other)
+            // a plain configuration change, with no discontinuity
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .options(FrameOptions {
+                buffer_fullness: 0x100,
+                ..FrameOptions::default()
+            })
+            .frames(1, other);
+        let offsets = builder.offsets().to_vec();
+        let data = builder.build();
+        let time_of = |index: usize| {
+            offsets[..index]
+                .iter()
+                .map(|&o| {
+                    AdtsHeader::from_bytes(&data[o..])
+                        .unwrap()
+                        .frame_duration()
+                        .unwrap()
+                })
+                .fold(MediaTime::ZERO, |a, b| a + b)
+                .to_duration()
+        };
+        let expected = vec![
+            StreamBoundary {
+                offset: offsets[2] as u64 - tag.len() as u64,
+                discarded: tag.len() as u64,
+                id3: true,
+                index: 2,
+                time: time_of(2),
+                previous: testing::StreamBuilder::DEFAULT_CONFIG,
+                config: other,
+                fullness_changed: false,
+                label: None,
+            },
+            StreamBoundary {
+                offset: offsets[5] as u64,
+                discarded: 0,
+                id3: false,
+                index: 5,
+                time: time_of(5),
+                previous: testing::StreamBuilder::DEFAULT_CONFIG,
+                config: other,
+                fullness_changed: true,
+                label: None,
+            },
+        ];
+        for chunk in [data.len(), 1] {
+            let mut parser = AdtsParser::new(Boundaries::default());
+            for b in data.chunks(chunk) {
+                parser.push(b);
+            }
+            assert_eq!(parser.consumer.boundaries, expected);
+            assert_eq!(parser.consumer.configs, 4);
+            assert_eq!(parser.stats().stream_boundaries, 2);
+        }
+
+        // junk which isn't an ID3 tag is still a discontinuity
+        let data = testing::StreamBuilder::new()
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .junk(3)
+            .frames(1, other)
+            .build();
+        let mut parser = AdtsParser::new(Boundaries::default());
+        parser.push(&data);
+        assert_eq!(parser.consumer.boundaries.len(), 1);
+        assert!(!parser.consumer.boundaries[0].id3);
+        assert_eq!(parser.consumer.boundaries[0].discarded, 3);
+    }
+
+    #[test]
+    fn raw_data_blocks() {
+        #[derive(Default)]
+        struct Blocks {
+            blocks: Vec<(u64, Vec<u8>, Option<u16>)>,
+            errors: Vec<AdtsParseError>,
+        }
+        impl AdtsConsumer2 for Blocks {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+            fn on_raw_data_block(
+                &mut self,
+                frame: &FrameContext<'_>,
+                block: &blocks::RawDataBlock<'_>,
+            ) {
+                self.blocks
+                    .push((frame.index, block.data.to_vec(), block.crc));
+            }
+            fn on_error(&mut self, err: AdtsParseError) {
+                self.errors.push(err);
+            }
+        }
+        let config = testing::StreamBuilder::DEFAULT_CONFIG;
+        let two_blocks = FrameOptions {
+            number_of_raw_data_blocks: 2,
+            ..FrameOptions::default()
+        };
+        // a protected frame of two blocks: the position table, header CRC, then each block
+        // followed by its CRC (written unprotected, then converted)
+        let mut protected = crate::write_frame(
+            &config,
+            &[0, 4, 0xaa, 0xbb, 1, 2, 0, 1, 3, 0, 2],
+            &two_blocks,
+        )
+        .unwrap();
+        fields::PROTECTION_ABSENT.set(&mut protected, 0);
+        let mut data = crate::write_frame(&config, &[9, 9], &FrameOptions::default()).unwrap();
+        data.extend(protected);
+        // no position table, and a CPE in the first block whose length can't be found
+        data.extend(crate::write_frame(&config, &[0x21, 0x00, 0x21, 0x00], &two_blocks).unwrap());
+
+        let mut parser = AdtsParser::new(Blocks::default()).with_raw_data_blocks(true);
+        parser.push(&data);
+        assert_eq!(
+            parser.consumer.blocks,
+            vec![
+                (0, vec![9, 9], None),
+                (1, vec![1, 2], Some(1)),
+                (1, vec![3], Some(2)),
+            ]
+        );
+        assert_eq!(
+            parser.consumer.errors,
+            vec![AdtsParseError::UnlocatedBlocks]
+        );
+
+        let mut parser = AdtsParser::new(Blocks::default());
+        parser.push(&data);
+        assert!(parser.consumer.blocks.is_empty());
+        assert!(parser.consumer.errors.is_empty());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn push_buf() {
+        use bytes::{Buf, Bytes};
+        #[derive(Default)]
+        struct Frames(Vec<(u64, Vec<u8>)>);
+        impl AdtsConsumer2 for Frames {
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.0.push((frame.offset, frame.payload.to_vec()));
+            }
+        }
+        let data = testing::StreamBuilder::new()
+            .frames(3, testing::StreamBuilder::DEFAULT_CONFIG)
+            .junk(2)
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut expected = AdtsParser::new(Frames::default());
+        expected.push(&data);
+
+        // a chain of small chunks, each a separate allocation
+        let mut input: Box<dyn Buf> = Box::new(Bytes::new());
+        for chunk in data.chunks(3) {
+            input = Box::new(input.chain(Bytes::copy_from_slice(chunk)));
+        }
+        let mut parser = AdtsParser::new(Frames::default());
+        parser.push_buf(&mut input);
+        assert!(!input.has_remaining());
+        assert_eq!(parser.consumer.0, expected.consumer.0);
+        assert_eq!(parser.stats().garbage_bytes, 2);
+    }
+
+    #[test]
+    fn strict() {
+        #[derive(Default)]
+        struct Strict {
+            frames: Vec<u64>,
+            configs: usize,
+            unsupported: Vec<(u64, u64, Vec<u8>, Unsupported)>,
+        }
+        impl AdtsConsumer2 for Strict {
+            fn on_config(&mut self, _: AdtsStreamConfig, _: ProtectionIndicator) {
+                self.configs += 1;
+            }
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.frames.push(frame.index);
+            }
+            fn on_raw_unsupported(&mut self, frame: &RawUnsupported<'_>) {
+                self.unsupported.push((
+                    frame.index,
+                    frame.offset,
+                    frame.frame.to_vec(),
+                    frame.reason,
+                ));
+            }
+        }
+        let builder = testing::StreamBuilder::new()
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .frames(
+                1,
+                AdtsStreamConfig {
+                    sampling_frequency: SamplingFrequency::FreqReserved0xd,
+                    ..testing::StreamBuilder::DEFAULT_CONFIG
+                },
+            )
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG);
+        let offsets = builder.offsets().to_vec();
+        let mut data = builder.build();
+        fields::LAYER.set(&mut data[offsets[2]..], 2);
+        let frame = |i: usize| data[offsets[i]..offsets[i + 1]].to_vec();
+
+        let mut parser = AdtsParser::new(Strict::default()).with_strict(true);
+        for b in data.chunks(4) {
+            parser.push(b);
+        }
+        assert_eq!(parser.consumer.frames, vec![0, 3]);
+        assert_eq!(parser.consumer.configs, 1);
+        assert_eq!(
+            parser.consumer.unsupported,
+            vec![
+                (
+                    1,
+                    offsets[1] as u64,
+                    frame(1),
+                    Unsupported::ReservedSamplingFrequency(SamplingFrequency::FreqReserved0xd)
+                ),
+                (2, offsets[2] as u64, frame(2), Unsupported::Layer(2)),
+            ]
+        );
+
+        let mut parser = AdtsParser::new(Strict::default());
+        parser.push(&data);
+        assert_eq!(parser.consumer.frames, vec![0, 1, 2, 3]);
+        assert!(parser.consumer.unsupported.is_empty());
+    }
+
+    #[test]
+    fn labels() {
+        #[derive(Default)]
+        struct Labels(Vec<String>);
+        impl AdtsConsumer2 for Labels {
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.0.push(format!("frame {:?}", frame.label));
+            }
+            fn on_header(&mut self, header: &HeaderContext<'_>) {
+                self.0.push(format!("header {:?}", header.label));
+            }
+            fn on_stream_boundary(&mut self, boundary: &StreamBoundary) {
+                self.0.push(format!("boundary {:?}", boundary.label));
+            }
+        }
+        let data = testing::StreamBuilder::new()
+            .frames(1, testing::StreamBuilder::DEFAULT_CONFIG)
+            .junk(2)
+            .frames(
+                1,
+                AdtsStreamConfig {
+                    sampling_frequency: SamplingFrequency::Freq44100,
+                    ..testing::StreamBuilder::DEFAULT_CONFIG
+                },
+            )
+            .build();
+        let mut parser = AdtsParser::new(Labels::default())
+            .with_label("cam1")
+            .with_early_headers(true);
+        parser.push(&data);
+        assert_eq!(parser.label(), Some("cam1"));
+        assert_eq!(parser.stats().label.as_deref(), Some("cam1"));
+        assert_eq!(
+            parser.consumer.0,
+            [
+                "header Some(\"cam1\")",
+                "frame Some(\"cam1\")",
+                "header Some(\"cam1\")",
+                "boundary Some(\"cam1\")",
+                "frame Some(\"cam1\")",
+            ]
+        );
+
+        let parser = AdtsParser::new(Labels::default());
+        assert_eq!(parser.label(), None);
+        assert_eq!(parser.stats().label, None);
+    }
+
+    #[test]
+    fn streaming_payloads() {
+        #[derive(Default)]
+        struct Pieces {
+            /// the offset of each frame, and its payload as reassembled from the chunks
+            frames: Vec<(u64, Vec<u8>)>,
+            chunks: usize,
+            abandoned: usize,
+            configs: usize,
+        }
+        impl AdtsConsumer2 for Pieces {
+            fn on_config(&mut self, _: AdtsStreamConfig, _: ProtectionIndicator) {
+                self.configs += 1;
+            }
+            fn on_frame(&mut self, _: &FrameContext<'_>) {
+                panic!("frames are streamed");
+            }
+            fn on_payload_start(&mut self, header: &HeaderContext<'_>) {
+                self.frames.push((header.offset, vec![]));
+            }
+            fn on_payload_chunk(&mut self, chunk: &[u8]) {
+                self.chunks += 1;
+                self.frames.last_mut().unwrap().1.extend_from_slice(chunk);
+            }
+            fn on_payload_end(&mut self, complete: bool) {
+                if !complete {
+                    self.abandoned += 1;
+                }
+            }
+        }
+        let builder = testing::StreamBuilder::new()
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .payload_len(200)
+            .frames(3, testing::StreamBuilder::DEFAULT_CONFIG)
+            .frame(&[]);
+        let offsets = builder.offsets().to_vec();
+        let data = builder.build();
+        let expected: Vec<_> = offsets
+            .iter()
+            .map(|&offset| {
+                let header = AdtsHeader::from_bytes(&data[offset..]).unwrap();
+                (offset as u64, header.payload().unwrap().to_vec())
+            })
+            .collect();
+        for chunk in [1, 5, 8, 100, data.len()] {
+            let mut parser = AdtsParser::new(Pieces::default()).with_streaming_payloads(true);
+            for buf in data.chunks(chunk) {
+                parser.push(buf);
+                // nothing more than a header is ever held
+                assert!(parser.pending_bytes() < 9);
+            }
+            assert_eq!(parser.consumer.frames, expected, "chunks of {}", chunk);
+            assert_eq!(parser.consumer.configs, 1);
+            assert_eq!(parser.consumer.abandoned, 0);
+            assert_eq!(parser.frames(), 4);
+            assert_eq!(parser.offset(), data.len() as u64);
+            assert_eq!(parser.state(), ParserState::Ready);
+        }
+
+        let mut parser = AdtsParser::new(Pieces::default()).with_streaming_payloads(true);
+        parser.push(&data[..100]);
+        assert_eq!(parser.consumer.chunks, 1);
+        assert_eq!(parser.offset(), 100);
+        assert_eq!(parser.state(), ParserState::Incomplete);
+        parser.start();
+        assert_eq!(parser.consumer.abandoned, 1);
+        assert_eq!(parser.state(), ParserState::Ready);
+    }
+
+    #[test]
+    fn error_context() {
+        #[derive(Default)]
+        struct Errors(Vec<(AdtsParseError, u64, u64, Duration)>);
+        impl AdtsConsumer2 for Errors {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+            fn on_error_at(&mut self, err: AdtsParseError, context: &ErrorContext<'_>) {
+                assert_eq!(context.label, Some("cam1"));
+                self.0
+                    .push((err, context.index, context.offset, context.time));
+            }
+        }
+        // 11 byte frames of 1024 samples at 48kHz
+        let frames = testing::StreamBuilder::new()
+            .frames(2, testing::StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut data = frames.clone();
+        data.extend_from_slice(&[0; 10]);
+        data.extend_from_slice(&frames);
+        let mut parser = AdtsParser::new(Errors::default()).with_label("cam1");
+        parser.push(&data[..30]);
+        assert_eq!(parser.frames(), 2);
+        assert_eq!(parser.offset(), 30);
+        parser.push(&data[30..]);
+        assert_eq!(parser.frames(), 4);
+        assert_eq!(parser.offset(), data.len() as u64);
+        assert_eq!(
+            parser.consumer.0,
+            [(
+                AdtsParseError::BadSyncWord,
+                2,
+                22,
+                Duration::from_nanos(2048 * 1_000_000_000 / 48000)
+            )]
+        );
+    }
+
+    #[test]
+    fn config_changes() {
+        #[derive(Default)]
+        struct Changes(Vec<(Vec<ConfigField>, u64)>);
+        impl AdtsConsumer2 for Changes {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+            fn on_config_change(&mut self, change: &ConfigChange) {
+                self.0.push((change.changed.clone(), change.index));
+            }
+        }
+        let stereo = testing::StreamBuilder::DEFAULT_CONFIG;
+        let mono = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Mono,
+            ..stereo
+        };
+        let private = AdtsStreamConfig {
+            private_bit: 1,
+            ..mono
+        };
+        let copy = AdtsStreamConfig {
+            originality: Originality::Copy,
+            home: 1,
+            ..private
+        };
+        let data = testing::StreamBuilder::new()
+            .frames(2, stereo)
+            // only the low bits of channel_configuration change, beyond the third header byte
+            .frames(2, mono)
+            // not reported, but listed alongside the next change
+            .frames(2, private)
+            .frames(2, copy)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .frames(1, mono)
+            .build();
+        let mut parser = AdtsParser::new(Changes::default());
+        parser.push(&data);
+        assert_eq!(
+            parser.consumer.0,
+            [
+                (vec![], 0),
+                (vec![ConfigField::ChannelConfiguration], 2),
+                (
+                    vec![
+                        ConfigField::PrivateBit,
+                        ConfigField::Originality,
+                        ConfigField::Home
+                    ],
+                    6
+                ),
+                (
+                    vec![
+                        ConfigField::Protection,
+                        ConfigField::PrivateBit,
+                        ConfigField::Originality,
+                        ConfigField::Home
+                    ],
+                    8
+                ),
+            ]
+        );
+        assert!(stereo.changed_fields(&stereo).is_empty());
+    }
+
+    #[test]
+    fn copyright_ids() {
+        #[derive(Default)]
+        struct Ids(Vec<Result<CopyrightIdentification, CopyrightIdErr>>);
+        impl AdtsConsumer2 for Ids {
+            fn on_frame(&mut self, _: &FrameContext<'_>) {}
+            fn on_copyright_id(&mut self, id: Result<CopyrightIdentification, CopyrightIdErr>) {
+                self.0.push(id);
+            }
+        }
+        let data = copyright::tests::stream(0xff_0000_0000_0000_0001, 72, 0);
+        let mut parser = AdtsParser::new(Ids::default());
+        for chunk in data.chunks(5) {
+            parser.push(chunk);
+        }
+        assert_eq!(
+            parser.consumer.0,
+            [Ok(CopyrightIdentification {
+                copyright_identifier: 0xff,
+                copyright_number: 1,
+            })]
+        );
+        let mut parser = AdtsParser::new(Ids::default()).with_streaming_payloads(true);
+        parser.push(&data);
+        assert_eq!(parser.consumer.0.len(), 1);
+
+        // frames may have been lost at the error, so the identifier is abandoned
+        let mut damaged = data[..500].to_vec();
+        damaged.extend_from_slice(&[0; 10]);
+        damaged.extend_from_slice(&data[500..]);
+        let mut parser = AdtsParser::new(Ids::default());
+        parser.push(&damaged);
+        assert!(parser.consumer.0.is_empty());
+    }
+
+    #[test]
+    fn quirks() {
+        use quirks::{Quirk, Quirks};
+        #[derive(Default)]
+        struct QuirkConsumer {
+            frames: Vec<(Vec<u8>, u16, Quirks)>,
+            errors: usize,
+        }
+        impl AdtsConsumer2 for QuirkConsumer {
+            fn on_frame(&mut self, frame: &FrameContext<'_>) {
+                self.frames.push((
+                    frame.payload.to_vec(),
+                    frame.header.adts_buffer_fullness(),
+                    frame.quirks,
+                ));
+            }
+            fn on_error(&mut self, _: AdtsParseError) {
+                self.errors += 1;
+            }
+        }
+        // the first two frames as written by a broken encoder, with a CRC that frame_length
+        // doesn't count, and a fullness of 0x123 with its bytes swapped
+        let builder = testing::StreamBuilder::new()
+            .options(FrameOptions {
+                crc: true,
+                buffer_fullness: 0x23 << 3 | 0x1,
+                ..FrameOptions::default()
+            })
+            .frame(&[1, 1, 1])
+            .frame(&[2, 2])
+            .options(FrameOptions::default())
+            .frame(&[3, 3]);
+        let offsets = builder.offsets().to_vec();
+        let mut data = builder.build();
+        for window in offsets[..3].windows(2) {
+            let frame = &mut data[window[0]..window[1]];
+            let frame_length = fields::FRAME_LENGTH.get(frame);
+            fields::FRAME_LENGTH.set(frame, frame_length - 2);
+            // the CRC covers the header as written
+            let crc = crc::frame_crc(&frame[..7], &frame[9..]);
+            fields::CRC_CHECK.set(frame, crc.into());
+        }
+
+        let quirks = Quirks::NONE
+            .with(Quirk::FrameLengthExcludesCrc)
+            .with(Quirk::BufferFullnessByteSwapped);
+        let mut parser = AdtsParser::new(QuirkConsumer::default()).with_quirks(quirks);
+        // split part way through the second frame, to exercise the incomplete frame path
+        parser.push(&data[..15]);
+        parser.push(&data[15..]);
+        assert_eq!(parser.consumer.errors, 0);
+        assert_eq!(
+            parser.consumer.frames,
+            vec![
+                (vec![1, 1, 1], 0x123, quirks),
+                (vec![2, 2], 0x123, quirks),
+                (vec![3, 3], 0x7ff, Quirks::NONE),
+            ]
+        );
+        assert_eq!(parser.stats().quirk_corrected_frames, 2);
+
+        // the CRCs of corrected frames are checked against the header as written
+        let mut parser = AdtsParser::new(QuirkConsumer::default())
+            .with_quirks(quirks)
+            .with_crc_policy(crc::CrcPolicy::Drop);
+        parser.push(&data);
+        assert_eq!(parser.consumer.frames.len(), 3);
+        assert_eq!(parser.stats().crc_mismatches, 0);
+        let mut damaged = data.clone();
+        damaged[9] ^= 1;
+        let mut parser = AdtsParser::new(QuirkConsumer::default())
+            .with_quirks(quirks)
+            .with_crc_policy(crc::CrcPolicy::Drop);
+        parser.push(&damaged);
+        assert_eq!(parser.consumer.frames.len(), 2);
+        assert_eq!(parser.stats().crc_mismatches, 1);
+
+        // without the workarounds, sync is lost
+        let mut parser = AdtsParser::new(QuirkConsumer::default());
+        parser.push(&data);
+        assert!(parser.consumer.errors > 0);
+    }
+
+    /// Field values read from a header with bitstream-io, in the order ISO/IEC 14496-3 lists
+    /// them, as a reference that shares no code with the crate's own field extraction
+    fn reference_fields(buf: &[u8; 9]) -> Vec<u32> {
+        let mut r = bitstream_io::BitReader::endian(&buf[..], BigEndian);
+        [12, 1, 2, 1, 2, 4, 1, 3, 1, 1, 1, 1, 13, 11, 2, 16]
+            .iter()
+            .map(|&bits| bitstream_io::BitRead::read::<u32>(&mut r, bits).unwrap())
+            .collect()
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn accessors_match_reference(buf: [u8; 9]) {
+            let expected = reference_fields(&buf);
+            let fields: Vec<u32> = fields::HEADER_FIELDS
+                .iter()
+                .chain(Some(&fields::CRC_CHECK))
+                .map(|f| f.get(&buf))
+                .collect();
+            proptest::prop_assert_eq!(&fields, &expected);
+
+            let h = AdtsHeader { buf: &buf };
+            proptest::prop_assert_eq!(u32::from(h.sync_word()), expected[0]);
+            proptest::prop_assert_eq!(h.mpeg_version() == MpegVersion::Mpeg2, expected[1] == 1);
+            proptest::prop_assert_eq!(
+                h.protection() == ProtectionIndicator::CrcAbsent,
+                expected[3] == 1
+            );
+            proptest::prop_assert_eq!(h.audio_object_type() as u32, expected[4]);
+            proptest::prop_assert_eq!(h.sampling_frequency() as u32, expected[5]);
+            proptest::prop_assert_eq!(u32::from(h.private_bit()), expected[6]);
+            proptest::prop_assert_eq!(h.channel_configuration() as u32, expected[7]);
+            proptest::prop_assert_eq!(h.originality() == Originality::Copy, expected[8] == 1);
+            proptest::prop_assert_eq!(u32::from(h.home()), expected[9]);
+            proptest::prop_assert_eq!(u32::from(h.copyright_identification_bit()), expected[10]);
+            proptest::prop_assert_eq!(
+                h.copyright_identification_start() == CopyrightIdentificationStart::Start,
+                expected[11] == 1
+            );
+            proptest::prop_assert_eq!(u32::from(h.frame_length()), expected[12]);
+            proptest::prop_assert_eq!(u32::from(h.adts_buffer_fullness()), expected[13]);
+            proptest::prop_assert_eq!(
+                u32::from(h.number_of_raw_data_blocks_in_frame()),
+                expected[14] + 1
+            );
+            proptest::prop_assert_eq!(
+                h.crc().map(u32::from),
+                if expected[3] == 0 { Some(expected[15]) } else { None }
+            );
+        }
+
+        #[test]
+        fn set_matches_reference(background: [u8; 9], values: [u32; 16]) {
+            let all_fields = fields::HEADER_FIELDS.iter().chain(Some(&fields::CRC_CHECK));
+            let mut buf = background;
+            let mut expected = vec![];
+            let mut w = BitWriter::endian(&mut expected, BigEndian);
+            for (field, value) in all_fields.zip(values) {
+                let value = value & field.max();
+                field.set(&mut buf, value);
+                w.write(field.width as u32, value).unwrap();
+            }
+            proptest::prop_assert_eq!(&buf[..], &expected[..]);
+        }
+    }
+
     #[test]
     fn too_short() {
         let header_data = make_test_data(|mut w| write_frame(&mut w));
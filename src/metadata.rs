@@ -0,0 +1,205 @@
+//! Plain-old-data copies of header and stream information.
+//!
+//! The types here own all their data and use only fixed-size integer and `bool` fields, holding
+//! header fields as the raw values found in the bitstream, so that they can be mapped 1:1 onto
+//! message types in serialisation schemas (protobuf, flatbuffers, etc) used by telemetry
+//! pipelines.
+
+use crate::{
+    fields, AdtsHeader, CopyrightIdentificationStart, MpegVersion, Originality, ProtectionIndicator,
+};
+
+/// The header values of a single ADTS frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameMetadata {
+    /// The raw `ID` bit; `0` for MPEG-4, `1` for MPEG-2
+    pub mpeg_version: u32,
+    pub crc_present: bool,
+    /// The raw 2-bit `profile_ObjectType` field value
+    pub profile: u32,
+    pub sampling_frequency_index: u32,
+    /// Sampling frequency in Hz, or `0` if `sampling_frequency_index` holds a reserved value
+    pub sampling_frequency_hz: u32,
+    pub private_bit: bool,
+    pub channel_configuration: u32,
+    /// The `original_copy` bit; `true` when the stream is marked as a copy
+    pub copy: bool,
+    pub home: bool,
+    pub copyright_identification_bit: bool,
+    pub copyright_identification_start: bool,
+    /// Length of the whole frame in bytes, including the header
+    pub frame_length: u32,
+    pub header_length: u32,
+    pub buffer_fullness: u32,
+    pub number_of_raw_data_blocks: u32,
+    /// The stored `crc_check` value, or `0` if `crc_present` is `false`
+    pub crc: u32,
+}
+impl<'buf> From<&AdtsHeader<'buf>> for FrameMetadata {
+    fn from(header: &AdtsHeader<'buf>) -> Self {
+        FrameMetadata {
+            mpeg_version: match header.mpeg_version() {
+                MpegVersion::Mpeg2 => 1,
+                MpegVersion::Mpeg4 => 0,
+            },
+            crc_present: header.protection() == ProtectionIndicator::CrcPresent,
+            profile: fields::PROFILE.get(header.buf),
+            sampling_frequency_index: header.sampling_frequency() as u32,
+            sampling_frequency_hz: header.sampling_frequency().freq().unwrap_or(0),
+            private_bit: header.private_bit() != 0,
+            channel_configuration: header.channel_configuration() as u32,
+            copy: header.originality() == Originality::Copy,
+            home: header.home() != 0,
+            copyright_identification_bit: header.copyright_identification_bit() != 0,
+            copyright_identification_start: header.copyright_identification_start()
+                == CopyrightIdentificationStart::Start,
+            frame_length: header.frame_length().into(),
+            header_length: header.header_length().into(),
+            buffer_fullness: header.adts_buffer_fullness().into(),
+            number_of_raw_data_blocks: header.number_of_raw_data_blocks_in_frame().into(),
+            crc: header.crc().unwrap_or(0).into(),
+        }
+    }
+}
+
+/// Summary information about a stream, accumulated from the
+/// [`FrameMetadata`](struct.FrameMetadata.html) of each frame.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let header = AdtsHeader::from_bytes(&buf).unwrap();
+/// let mut stream = StreamMetadata::default();
+/// stream.record_frame(&FrameMetadata::from(&header));
+/// assert_eq!(stream.frame_count, 1);
+/// assert_eq!(stream.sampling_frequency_hz, 48000);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamMetadata {
+    /// As for `FrameMetadata::mpeg_version`, taken from the most recent frame
+    pub mpeg_version: u32,
+    /// As for `FrameMetadata::profile`, taken from the most recent frame
+    pub profile: u32,
+    /// As for `FrameMetadata::sampling_frequency_index`, taken from the most recent frame
+    pub sampling_frequency_index: u32,
+    /// As for `FrameMetadata::sampling_frequency_hz`, taken from the most recent frame
+    pub sampling_frequency_hz: u32,
+    /// As for `FrameMetadata::channel_configuration`, taken from the most recent frame
+    pub channel_configuration: u32,
+    pub frame_count: u64,
+    /// Total bytes of all frames, including headers
+    pub byte_count: u64,
+    pub payload_byte_count: u64,
+    /// Number of times the configuration was seen to differ from that of the previous frame
+    pub config_change_count: u64,
+    pub error_count: u64,
+    /// Smallest `frame_length` seen, or `0` if no frames have been recorded
+    pub min_frame_length: u32,
+    pub max_frame_length: u32,
+}
+impl StreamMetadata {
+    /// Update the summary to account for another frame in the stream
+    pub fn record_frame(&mut self, frame: &FrameMetadata) {
+        let config = (
+            frame.mpeg_version,
+            frame.profile,
+            frame.sampling_frequency_index,
+            frame.channel_configuration,
+        );
+        let current = (
+            self.mpeg_version,
+            self.profile,
+            self.sampling_frequency_index,
+            self.channel_configuration,
+        );
+        if self.frame_count > 0 && config != current {
+            self.config_change_count += 1;
+        }
+        self.mpeg_version = frame.mpeg_version;
+        self.profile = frame.profile;
+        self.sampling_frequency_index = frame.sampling_frequency_index;
+        self.sampling_frequency_hz = frame.sampling_frequency_hz;
+        self.channel_configuration = frame.channel_configuration;
+        self.min_frame_length = if self.frame_count == 0 {
+            frame.frame_length
+        } else {
+            self.min_frame_length.min(frame.frame_length)
+        };
+        self.max_frame_length = self.max_frame_length.max(frame.frame_length);
+        self.frame_count += 1;
+        self.byte_count += u64::from(frame.frame_length);
+        self.payload_byte_count +=
+            u64::from(frame.frame_length.saturating_sub(frame.header_length));
+    }
+
+    /// Update the summary to account for an error in the stream
+    pub fn record_error(&mut self) {
+        self.error_count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn stream() {
+        let mono = AdtsStreamConfig {
+            mpeg_version: MpegVersion::Mpeg2,
+            sampling_frequency: SamplingFrequency::Freq44100,
+            channel_configuration: ChannelConfiguration::Mono,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let stereo = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Stereo,
+            ..mono
+        };
+        let data = StreamBuilder::new()
+            .config(mono)
+            .frame(&[0; 10])
+            .frame(&[0; 5])
+            .config(stereo)
+            .frame(&[0; 20])
+            .build();
+        let mut stream = StreamMetadata::default();
+        for header in AdtsFrameIter::new(&data) {
+            let frame = FrameMetadata::from(&header.unwrap());
+            assert_eq!(frame.mpeg_version, 1);
+            assert_eq!(frame.profile, 1);
+            stream.record_frame(&frame);
+        }
+        assert_eq!(stream.frame_count, 3);
+        assert_eq!(stream.byte_count, 35 + 3 * 7);
+        assert_eq!(stream.payload_byte_count, 35);
+        assert_eq!(stream.min_frame_length, 12);
+        assert_eq!(stream.max_frame_length, 27);
+        assert_eq!(stream.config_change_count, 1);
+        assert_eq!(stream.channel_configuration, 2);
+        assert_eq!(stream.sampling_frequency_hz, 44100);
+    }
+
+    #[test]
+    fn long_running() {
+        // the counts after about 200 years of continuous 96kHz audio in 8191-byte frames
+        let frames = 96000 / 1024 * 60 * 60 * 24 * 365 * 200;
+        let mut stream = StreamMetadata {
+            frame_count: frames,
+            byte_count: frames * 8191,
+            payload_byte_count: frames * (8191 - 7),
+            ..StreamMetadata::default()
+        };
+        let frame = FrameMetadata {
+            frame_length: 8191,
+            header_length: 7,
+            ..FrameMetadata::default()
+        };
+        stream.record_frame(&frame);
+        assert_eq!(stream.frame_count, frames + 1);
+        assert_eq!(stream.byte_count, (frames + 1) * 8191);
+        assert_eq!(stream.payload_byte_count, (frames + 1) * 8184);
+    }
+}
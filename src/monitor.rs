@@ -0,0 +1,639 @@
+//! Health monitoring for live ADTS ingest.
+
+use crate::clock::{Clock, SystemClock};
+use crate::{
+    AdtsConsumer, AdtsParseError, AudioObjectType, ChannelConfiguration, FrameLength, MediaTime,
+    MpegVersion, Originality, PayloadProfile, ProtectionIndicator, SamplingFrequency,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Tolerances for [`CadenceMonitor`](struct.CadenceMonitor.html).
+#[derive(Debug, Clone)]
+pub struct CadenceConfig {
+    /// How far arrival of media may fall behind real time before `CadenceEvent::Stalled` is
+    /// reported (default 500ms)
+    pub stall_tolerance: Duration,
+    /// How far arrival of media may run ahead of real time before `CadenceEvent::Burst` is
+    /// reported (default 500ms)
+    pub burst_tolerance: Duration,
+}
+impl Default for CadenceConfig {
+    fn default() -> Self {
+        CadenceConfig {
+            stall_tolerance: Duration::from_millis(500),
+            burst_tolerance: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Deviations from the expected real-time arrival of media, reported by
+/// [`CadenceMonitor::poll()`](struct.CadenceMonitor.html#method.poll).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CadenceEvent {
+    /// Less media than expected has arrived given the time elapsed; input is this far behind
+    Stalled { behind: Duration },
+    /// More media than expected has arrived given the time elapsed; input is this far ahead
+    Burst { ahead: Duration },
+}
+
+/// Statistics describing the variation in the arrival times of frames, relative to the spacing
+/// of their media timestamps, as produced by [`JitterTracker`](struct.JitterTracker.html).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct JitterStats {
+    /// Number of frames whose arrival has been recorded
+    pub frames: u64,
+    /// Smoothed interarrival jitter, estimated as described in RFC 3550 (section 6.4.1)
+    pub jitter: Duration,
+    /// The largest difference seen between the spacing of two consecutive frames' arrival times
+    /// and the spacing of their media timestamps
+    pub max_deviation: Duration,
+}
+
+/// Computes [`JitterStats`](struct.JitterStats.html) from the arrival time and media timestamp of
+/// each frame in turn.
+///
+/// [`CadenceMonitor`](struct.CadenceMonitor.html) maintains one of these itself, using the time
+/// at which each frame is parsed as its arrival time.  Where the true arrival time is known more
+/// precisely (for example a socket receive timestamp passed through a
+/// [`TaggedParser`](../tagged/struct.TaggedParser.html)), feed it to a tracker directly.
+///
+/// ```rust
+/// use adts_reader::monitor::JitterTracker;
+/// use adts_reader::MediaTime;
+/// use std::time::{Duration, Instant};
+/// let mut tracker = JitterTracker::default();
+/// let start = Instant::now();
+/// tracker.record(start, MediaTime::from_blocks(0, 8000));
+/// // a frame lasts 128ms at 8kHz, but the next arrives 130ms later
+/// tracker.record(start + Duration::from_millis(130), MediaTime::from_blocks(1, 8000));
+/// assert_eq!(tracker.stats().max_deviation, Duration::from_millis(2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JitterTracker {
+    stats: JitterStats,
+    previous: Option<(Instant, MediaTime)>,
+    /// the RFC 3550 estimate in nanoseconds, held with extra precision so that small deviations
+    /// aren't lost when divided by 16
+    jitter: f64,
+}
+impl JitterTracker {
+    /// Records the arrival of a frame whose first sample has the given media time
+    pub fn record(&mut self, arrival: Instant, media: MediaTime) {
+        self.stats.frames += 1;
+        if let Some((prev_arrival, prev_media)) = self.previous {
+            let transit = signed_nanos(arrival, prev_arrival);
+            let spacing = signed_nanos(media.to_duration(), prev_media.to_duration());
+            let deviation = (transit - spacing).unsigned_abs();
+            self.jitter += (deviation as f64 - self.jitter) / 16.0;
+            self.stats.jitter = Duration::from_nanos(self.jitter as u64);
+            let deviation = Duration::from_nanos(deviation.min(u64::MAX.into()) as u64);
+            self.stats.max_deviation = self.stats.max_deviation.max(deviation);
+        }
+        self.previous = Some((arrival, media));
+    }
+
+    pub fn stats(&self) -> &JitterStats {
+        &self.stats
+    }
+}
+
+/// `a - b` in nanoseconds, for either `Instant`s or `Duration`s
+fn signed_nanos<T: PartialOrd + std::ops::Sub<Output = Duration>>(a: T, b: T) -> i128 {
+    if a >= b {
+        (a - b).as_nanos() as i128
+    } else {
+        -((b - a).as_nanos() as i128)
+    }
+}
+
+/// Wraps an `AdtsConsumer`, tracking the duration of the media delivered to it so that the rate
+/// at which data is pushed into the `AdtsParser` can be compared to real time.
+///
+/// The expected cadence is derived from the sampling frequency signalled in the stream.  Call
+/// [`poll()`](#method.poll) after each `push()` (and, to notice input stopping altogether,
+/// periodically between pushes), or [`poll_now()`](#method.poll_now) to read the time from the
+/// monitor's [`Clock`](../clock/trait.Clock.html).  Once an event is reported, the monitor adopts
+/// the new timing as its reference, so that a single stall isn't reported again for every
+/// subsequent frame.
+///
+/// The monitor also measures the jitter in frame arrival times, available from
+/// [`jitter()`](#method.jitter).
+///
+/// ```rust
+/// use adts_reader::AdtsParser;
+/// use adts_reader::monitor::{CadenceConfig, CadenceMonitor};
+/// # use adts_reader::*;
+/// # struct MyConsumer;
+/// # impl AdtsConsumer for MyConsumer {
+/// #     fn new_config(&mut self, mpeg_version: MpegVersion, protection: ProtectionIndicator, aot: AudioObjectType, freq: SamplingFrequency, private_bit: u8, channels: ChannelConfiguration, originality: Originality, home: u8) { }
+/// #     fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) { }
+/// #     fn error(&mut self, err: AdtsParseError) { }
+/// # }
+/// # let buf = [];
+/// let mut parser = AdtsParser::new(CadenceMonitor::new(MyConsumer, CadenceConfig::default()));
+/// parser.push(&buf);
+/// if let Some(event) = parser.consumer.poll_now() {
+///     println!("input cadence problem: {:?}", event);
+/// }
+/// ```
+pub struct CadenceMonitor<C: AdtsConsumer, K: Clock = SystemClock> {
+    pub consumer: C,
+    config: CadenceConfig,
+    clock: K,
+    sample_rate: Option<u32>,
+    /// media delivered so far
+    media: MediaTime,
+    /// the instant at which the start of the media would have arrived, were it arriving at
+    /// exactly real time
+    reference: Option<Instant>,
+    jitter: JitterTracker,
+    frame_length: FrameLength,
+}
+impl<C: AdtsConsumer> CadenceMonitor<C> {
+    pub fn new(consumer: C, config: CadenceConfig) -> CadenceMonitor<C> {
+        CadenceMonitor::with_clock(consumer, config, SystemClock)
+    }
+}
+impl<C: AdtsConsumer, K: Clock> CadenceMonitor<C, K> {
+    /// Creates a monitor whose [`poll_now()`](#method.poll_now) reads the time from the given
+    /// clock
+    pub fn with_clock(consumer: C, config: CadenceConfig, clock: K) -> CadenceMonitor<C, K> {
+        CadenceMonitor {
+            consumer,
+            config,
+            clock,
+            sample_rate: None,
+            media: MediaTime::ZERO,
+            reference: None,
+            jitter: JitterTracker::default(),
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to calculate the expected cadence
+    /// (by default, 1024)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Total duration of the media delivered so far
+    pub fn media_duration(&self) -> Duration {
+        self.media.to_duration()
+    }
+
+    /// Total duration of the media delivered so far, exactly
+    pub fn media_time(&self) -> MediaTime {
+        self.media
+    }
+
+    /// Arrival jitter of the frames delivered so far, taking the time each frame was parsed as its
+    /// arrival time
+    pub fn jitter(&self) -> &JitterStats {
+        self.jitter.stats()
+    }
+
+    /// Compares the media delivered so far against the real time elapsed, given the current time.
+    ///
+    /// The first call after media starts arriving establishes the reference timing.
+    pub fn poll(&mut self, now: Instant) -> Option<CadenceEvent> {
+        let media = self.media_duration();
+        if media.is_zero() {
+            return None;
+        }
+        let reference = *self
+            .reference
+            .get_or_insert_with(|| now.checked_sub(media).unwrap_or(now));
+        // the time at which the media received so far would have completed arriving in real time
+        let due = reference + media;
+        let event = if now > due + self.config.stall_tolerance {
+            CadenceEvent::Stalled { behind: now - due }
+        } else if due > now + self.config.burst_tolerance {
+            CadenceEvent::Burst { ahead: due - now }
+        } else {
+            return None;
+        };
+        self.reference = now.checked_sub(media);
+        Some(event)
+    }
+
+    /// As for [`poll()`](#method.poll), taking the current time from the monitor's clock
+    pub fn poll_now(&mut self) -> Option<CadenceEvent> {
+        let now = self.clock.now();
+        self.poll(now)
+    }
+}
+impl<C: AdtsConsumer, K: Clock> AdtsConsumer for CadenceMonitor<C, K> {
+    fn new_config(
+        &mut self,
+        mpeg_version: MpegVersion,
+        protection: ProtectionIndicator,
+        aot: AudioObjectType,
+        freq: SamplingFrequency,
+        private_bit: u8,
+        channels: ChannelConfiguration,
+        originality: Originality,
+        home: u8,
+    ) {
+        self.sample_rate = freq.freq();
+        self.consumer.new_config(
+            mpeg_version,
+            protection,
+            aot,
+            freq,
+            private_bit,
+            channels,
+            originality,
+            home,
+        );
+    }
+
+    fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) {
+        if let Some(rate) = self.sample_rate {
+            self.jitter.record(self.clock.now(), self.media);
+            self.media = self.media
+                + MediaTime::from_blocks_of(number_of_blocks.into(), self.frame_length, rate);
+        }
+        self.consumer
+            .payload(buffer_fullness, number_of_blocks, buf);
+    }
+
+    fn error(&mut self, err: AdtsParseError) {
+        self.consumer.error(err);
+    }
+}
+
+/// Settings for [`SilenceMonitor`](struct.SilenceMonitor.html).
+#[derive(Debug, Clone)]
+pub struct SilenceConfig {
+    /// How long a run of likely-silent frames must last before it is reported (default 2s)
+    pub min_duration: Duration,
+}
+impl Default for SilenceConfig {
+    fn default() -> Self {
+        SilenceConfig {
+            min_duration: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A contiguous run of frames judged
+/// [`likely_silence()`](../struct.PayloadProfile.html#method.likely_silence).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SilenceRun {
+    /// Media time of the start of the first silent frame, relative to the start of the stream
+    pub start: MediaTime,
+    /// Total duration of the silent frames
+    pub duration: MediaTime,
+    /// Number of silent frames
+    pub frames: u64,
+}
+impl SilenceRun {
+    /// Media time of the end of the last silent frame
+    pub fn end(&self) -> MediaTime {
+        self.start + self.duration
+    }
+}
+
+/// Dead air detected by [`SilenceMonitor`](struct.SilenceMonitor.html).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SilenceEvent {
+    /// A run of silent frames beginning at the given media time has lasted at least
+    /// `SilenceConfig::min_duration`
+    Started { start: MediaTime },
+    /// A run previously reported as `Started` ended, with a non-silent frame
+    Ended(SilenceRun),
+}
+
+/// Wraps an `AdtsConsumer`, reporting runs of frames whose payloads are likely to encode silence,
+/// judged by [`PayloadProfile`](../struct.PayloadProfile.html) without decoding the audio.
+///
+/// Runs shorter than `SilenceConfig::min_duration` (such as the pauses in speech) are ignored.
+/// Call [`poll()`](#method.poll) after each `push()` to collect the resulting events.  Timestamps
+/// are the media time of the frames concerned, counted from the first frame delivered.
+///
+/// ```rust
+/// use adts_reader::AdtsParser;
+/// use adts_reader::monitor::{SilenceConfig, SilenceEvent, SilenceMonitor};
+/// # use adts_reader::*;
+/// # struct MyConsumer;
+/// # impl AdtsConsumer for MyConsumer {
+/// #     fn new_config(&mut self, mpeg_version: MpegVersion, protection: ProtectionIndicator, aot: AudioObjectType, freq: SamplingFrequency, private_bit: u8, channels: ChannelConfiguration, originality: Originality, home: u8) { }
+/// #     fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) { }
+/// #     fn error(&mut self, err: AdtsParseError) { }
+/// # }
+/// # let buf = [];
+/// let mut parser = AdtsParser::new(SilenceMonitor::new(MyConsumer, SilenceConfig::default()));
+/// parser.push(&buf);
+/// while let Some(event) = parser.consumer.poll() {
+///     match event {
+///         SilenceEvent::Started { start } => println!("dead air from {:?}", start.to_duration()),
+///         SilenceEvent::Ended(run) => println!("audio resumed after {:?}", run.duration.to_duration()),
+///     }
+/// }
+/// ```
+pub struct SilenceMonitor<C: AdtsConsumer> {
+    pub consumer: C,
+    config: SilenceConfig,
+    sample_rate: Option<u32>,
+    /// media delivered so far
+    media: MediaTime,
+    run: Option<SilenceRun>,
+    /// true once the current run has been reported as started
+    reported: bool,
+    events: VecDeque<SilenceEvent>,
+    frame_length: FrameLength,
+}
+impl<C: AdtsConsumer> SilenceMonitor<C> {
+    pub fn new(consumer: C, config: SilenceConfig) -> SilenceMonitor<C> {
+        SilenceMonitor {
+            consumer,
+            config,
+            sample_rate: None,
+            media: MediaTime::ZERO,
+            run: None,
+            reported: false,
+            events: VecDeque::new(),
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to calculate the timing of silent
+    /// runs (by default, 1024)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Returns the next event not yet collected, if any
+    pub fn poll(&mut self) -> Option<SilenceEvent> {
+        self.events.pop_front()
+    }
+
+    /// The run of silent frames ending with the most recent frame, if it was silent, whether or
+    /// not the run is yet long enough to be reported
+    pub fn current_run(&self) -> Option<&SilenceRun> {
+        self.run.as_ref()
+    }
+
+    /// Ends any run of silence in progress at the end of the input, returning it if it had been
+    /// reported as started
+    pub fn finish(&mut self) -> Option<SilenceRun> {
+        let run = self.run.take();
+        if self.reported {
+            self.reported = false;
+            run
+        } else {
+            None
+        }
+    }
+}
+impl<C: AdtsConsumer> AdtsConsumer for SilenceMonitor<C> {
+    fn new_config(
+        &mut self,
+        mpeg_version: MpegVersion,
+        protection: ProtectionIndicator,
+        aot: AudioObjectType,
+        freq: SamplingFrequency,
+        private_bit: u8,
+        channels: ChannelConfiguration,
+        originality: Originality,
+        home: u8,
+    ) {
+        self.sample_rate = freq.freq();
+        self.consumer.new_config(
+            mpeg_version,
+            protection,
+            aot,
+            freq,
+            private_bit,
+            channels,
+            originality,
+            home,
+        );
+    }
+
+    fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) {
+        if let Some(rate) = self.sample_rate {
+            let duration =
+                MediaTime::from_blocks_of(number_of_blocks.into(), self.frame_length, rate);
+            if PayloadProfile::of(buf).likely_silence() {
+                let media = self.media;
+                let run = self.run.get_or_insert(SilenceRun {
+                    start: media,
+                    duration: MediaTime::ZERO,
+                    frames: 0,
+                });
+                run.duration = run.duration + duration;
+                run.frames += 1;
+                if !self.reported && run.duration.to_duration() >= self.config.min_duration {
+                    self.reported = true;
+                    self.events
+                        .push_back(SilenceEvent::Started { start: run.start });
+                }
+            } else if let Some(run) = self.finish() {
+                self.events.push_back(SilenceEvent::Ended(run));
+            }
+            self.media = self.media + duration;
+        }
+        self.consumer
+            .payload(buffer_fullness, number_of_blocks, buf);
+    }
+
+    fn error(&mut self, err: AdtsParseError) {
+        self.consumer.error(err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    const CONFIG: AdtsStreamConfig = AdtsStreamConfig {
+        sampling_frequency: SamplingFrequency::Freq8000,
+        channel_configuration: ChannelConfiguration::Mono,
+        ..StreamBuilder::DEFAULT_CONFIG
+    };
+
+    struct NullConsumer;
+    impl AdtsConsumer for NullConsumer {
+        fn new_config(
+            &mut self,
+            _: MpegVersion,
+            _: ProtectionIndicator,
+            _: AudioObjectType,
+            _: SamplingFrequency,
+            _: u8,
+            _: ChannelConfiguration,
+            _: Originality,
+            _: u8,
+        ) {
+        }
+        fn payload(&mut self, _: u16, _: u8, _: &[u8]) {}
+        fn error(&mut self, _: AdtsParseError) {}
+    }
+
+    #[test]
+    fn stall_and_burst() {
+        // each frame is 1024 / 8000 = 128ms
+        let frame = StreamBuilder::new().config(CONFIG).frame(&[0]).build();
+        let frame_duration = Duration::from_millis(128);
+        let clock = ManualClock::new();
+        let monitor = CadenceMonitor::with_clock(NullConsumer, CadenceConfig::default(), &clock);
+        let mut parser = AdtsParser::new(monitor);
+        // frames arriving in real time
+        for _ in 0..10 {
+            parser.push(&frame);
+            assert_eq!(parser.consumer.poll_now(), None);
+            clock.advance(frame_duration);
+        }
+        assert_eq!(parser.consumer.media_duration(), frame_duration * 10);
+        // nothing arrives for a while
+        clock.advance(Duration::from_millis(600));
+        assert_eq!(
+            parser.consumer.poll_now(),
+            Some(CadenceEvent::Stalled {
+                behind: Duration::from_millis(600 + 128)
+            })
+        );
+        // ..then input resumes in real time, from the new reference point
+        parser.push(&frame);
+        clock.advance(frame_duration);
+        assert_eq!(parser.consumer.poll_now(), None);
+        // a burst of frames at once
+        for _ in 0..5 {
+            parser.push(&frame);
+        }
+        assert_eq!(
+            parser.consumer.poll(clock.now()),
+            Some(CadenceEvent::Burst {
+                ahead: frame_duration * 5
+            })
+        );
+    }
+
+    #[test]
+    fn jitter() {
+        let clock = ManualClock::new();
+        let mut monitor =
+            CadenceMonitor::with_clock(NullConsumer, CadenceConfig::default(), &clock);
+        monitor.sample_rate = Some(8000);
+        // frames of 128ms arriving alternately 10ms early and 10ms late
+        for i in 0..100 {
+            monitor.payload(0x7ff, 1, &[]);
+            clock.advance(Duration::from_millis(if i % 2 == 0 { 108 } else { 148 }));
+        }
+        let stats = monitor.jitter();
+        assert_eq!(stats.frames, 100);
+        assert_eq!(stats.max_deviation, Duration::from_millis(20));
+        // the smoothed estimate converges on the typical deviation
+        assert!(
+            stats.jitter > Duration::from_millis(19) && stats.jitter <= Duration::from_millis(20)
+        );
+
+        // perfectly regular arrival
+        let mut tracker = JitterTracker::default();
+        let start = clock.now();
+        for i in 0..10 {
+            tracker.record(
+                start + Duration::from_millis(128) * i,
+                MediaTime::from_blocks(i.into(), 8000),
+            );
+        }
+        assert_eq!(tracker.stats().jitter, Duration::ZERO);
+        assert_eq!(tracker.stats().max_deviation, Duration::ZERO);
+    }
+
+    #[test]
+    fn silence_runs() {
+        // each frame is 1024 / 8000 = 128ms
+        let noise: Vec<u8> = (0..=255).collect();
+        let audio = StreamBuilder::new().config(CONFIG).frame(&noise).build();
+        let silence = StreamBuilder::new()
+            .config(CONFIG)
+            .frame(&[0x21, 0, 0])
+            .build();
+        let monitor = SilenceMonitor::new(
+            NullConsumer,
+            SilenceConfig {
+                min_duration: Duration::from_millis(500),
+            },
+        );
+        let mut parser = AdtsParser::new(monitor);
+        parser.push(&audio);
+        // a short pause is ignored
+        for _ in 0..3 {
+            parser.push(&silence);
+        }
+        assert_eq!(parser.consumer.current_run().unwrap().frames, 3);
+        parser.push(&audio);
+        assert_eq!(parser.consumer.current_run(), None);
+        assert_eq!(parser.consumer.poll(), None);
+        // dead air, reported once it has lasted 4 frames
+        for _ in 0..3 {
+            parser.push(&silence);
+        }
+        assert_eq!(parser.consumer.poll(), None);
+        parser.push(&silence);
+        let start = MediaTime::from_blocks(5, 8000);
+        assert_eq!(
+            parser.consumer.poll(),
+            Some(SilenceEvent::Started { start })
+        );
+        for _ in 0..6 {
+            parser.push(&silence);
+        }
+        parser.push(&audio);
+        let run = SilenceRun {
+            start,
+            duration: MediaTime::from_blocks(10, 8000),
+            frames: 10,
+        };
+        assert_eq!(parser.consumer.poll(), Some(SilenceEvent::Ended(run)));
+        assert_eq!(run.end().to_duration(), Duration::from_millis(128 * 15));
+        assert_eq!(parser.consumer.poll(), None);
+        // silence continuing to the end of the input
+        for _ in 0..4 {
+            parser.push(&silence);
+        }
+        assert!(matches!(
+            parser.consumer.poll(),
+            Some(SilenceEvent::Started { .. })
+        ));
+        assert_eq!(parser.consumer.finish().unwrap().frames, 4);
+        assert_eq!(parser.consumer.finish(), None);
+    }
+
+    #[test]
+    fn years_of_media() {
+        let mut monitor = CadenceMonitor::new(NullConsumer, CadenceConfig::default());
+        let years = 100;
+        let seconds = 60 * 60 * 24 * 365 * years;
+        monitor.sample_rate = Some(96000);
+        monitor.media = MediaTime::new(96000 * seconds - 1024, 96000);
+        monitor.payload(0x7ff, 1, &[]);
+        assert_eq!(monitor.media_duration(), Duration::from_secs(seconds));
+        // one more frame, at a different rate
+        monitor.new_config(
+            MpegVersion::Mpeg4,
+            ProtectionIndicator::CrcAbsent,
+            AudioObjectType::AacLC,
+            SamplingFrequency::Freq8000,
+            0,
+            ChannelConfiguration::Mono,
+            Originality::Original,
+            0,
+        );
+        monitor.payload(0x7ff, 1, &[]);
+        assert_eq!(
+            monitor.media_duration(),
+            Duration::from_secs(seconds) + Duration::from_millis(128)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! An [mpeg2ts-reader](https://github.com/dholroyd/mpeg2ts-reader) `ElementaryStreamConsumer`
+//! feeding the PES packets of an ADTS stream demultiplexed from MPEG transport streams to an
+//! `AdtsParser`.
+//!
+//! Available when the `mpeg2ts` feature is enabled.
+//!
+//! [`AdtsElementaryStreamConsumer`](struct.AdtsElementaryStreamConsumer.html) passes the PTS of
+//! each PES packet to
+//! [`AdtsParser::begin_pes_packet()`](../struct.AdtsParser.html#method.begin_pes_packet), so
+//! that the parser's consumer finds a timestamp for each frame in
+//! [`FrameContext::pts`](../struct.FrameContext.html#structfield.pts).  To demultiplex a stream,
+//! wrap the adapter in an `mpeg2ts_reader::pes::PesPacketFilter`, and return that filter for the
+//! ADTS stream's PID from the demultiplexer's filter switch.
+
+use crate::clock::{Clock, SystemClock};
+use crate::{AdtsConsumer2, AdtsParser};
+use mpeg2ts_reader::pes::{ElementaryStreamConsumer, PesContents, PesHeader, PtsDts};
+
+/// Implements `ElementaryStreamConsumer`, pushing the payload of each PES packet into the given
+/// `AdtsParser`.
+///
+/// Data lost from the transport stream, as signalled by `continuity_error()` or a PES header
+/// which can't be parsed, causes the parser to resynchronise, as after
+/// [`AdtsParser::start()`](../struct.AdtsParser.html#method.start).
+///
+/// ```rust
+/// use adts_reader::mpeg2ts::AdtsElementaryStreamConsumer;
+/// use adts_reader::{AdtsConsumer2, AdtsParser, FrameContext};
+/// use mpeg2ts_reader::pes::{ElementaryStreamConsumer, PesHeader};
+///
+/// struct Pts(Vec<Option<u64>>);
+/// impl AdtsConsumer2 for Pts {
+///     fn on_frame(&mut self, frame: &FrameContext<'_>) {
+///         self.0.push(frame.pts);
+///     }
+/// }
+/// let mut consumer = AdtsElementaryStreamConsumer::new(AdtsParser::new(Pts(vec![])));
+/// // a PES packet with a PTS of 9000, holding a frame and the start of the next
+/// let packet = [
+///     0x00, 0x00, 0x01, 0xc0, 0x00, 0x13, 0x80, 0x80, 0x05, 0x21, 0x00, 0x01, 0x46, 0x51,
+///     0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21, 0xff, 0xf1, 0x4c,
+/// ];
+/// consumer.start_stream(&mut ());
+/// consumer.begin_packet(&mut (), PesHeader::from_bytes(&packet).unwrap());
+/// consumer.continue_packet(&mut (), &[0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// consumer.end_packet(&mut ());
+/// assert_eq!(consumer.parser.consumer.0, [Some(9000), Some(10920)]);
+/// ```
+pub struct AdtsElementaryStreamConsumer<C, K = SystemClock>
+where
+    C: AdtsConsumer2,
+    K: Clock,
+{
+    pub parser: AdtsParser<C, K>,
+}
+impl<C, K> AdtsElementaryStreamConsumer<C, K>
+where
+    C: AdtsConsumer2,
+    K: Clock,
+{
+    /// Wraps the given parser, which may have been configured with any of its builder methods
+    pub fn new(parser: AdtsParser<C, K>) -> Self {
+        AdtsElementaryStreamConsumer { parser }
+    }
+}
+impl<Ctx, C, K> ElementaryStreamConsumer<Ctx> for AdtsElementaryStreamConsumer<C, K>
+where
+    C: AdtsConsumer2,
+    K: Clock,
+{
+    fn start_stream(&mut self, _ctx: &mut Ctx) {
+        self.parser.start();
+    }
+
+    fn begin_packet(&mut self, _ctx: &mut Ctx, header: PesHeader<'_>) {
+        match header.contents() {
+            PesContents::Parsed(Some(parsed)) => {
+                let pts = match parsed.pts_dts() {
+                    Ok(PtsDts::PtsOnly(Ok(pts))) | Ok(PtsDts::Both { pts: Ok(pts), .. }) => {
+                        Some(pts.value())
+                    }
+                    _ => None,
+                };
+                self.parser.begin_pes_packet(pts);
+                self.parser.push(parsed.payload());
+            }
+            PesContents::Parsed(None) => {
+                // the packet's payload can't be located, so is lost
+                self.parser.start();
+            }
+            PesContents::Payload(payload) => {
+                self.parser.begin_pes_packet(None);
+                self.parser.push(payload);
+            }
+        }
+    }
+
+    fn continue_packet(&mut self, _ctx: &mut Ctx, data: &[u8]) {
+        self.parser.push(data);
+    }
+
+    fn end_packet(&mut self, _ctx: &mut Ctx) {}
+
+    fn continuity_error(&mut self, _ctx: &mut Ctx) {
+        self.parser.start();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::{AdtsParseError, FrameContext};
+
+    #[derive(Default)]
+    struct Frames {
+        pts: Vec<Option<u64>>,
+        errors: Vec<AdtsParseError>,
+    }
+    impl AdtsConsumer2 for Frames {
+        fn on_frame(&mut self, frame: &FrameContext<'_>) {
+            self.pts.push(frame.pts);
+        }
+        fn on_error(&mut self, err: AdtsParseError) {
+            self.errors.push(err);
+        }
+    }
+
+    /// A PES packet for an audio stream, with the given PTS, holding the given data
+    fn pes_packet(pts: Option<u64>, data: &[u8]) -> Vec<u8> {
+        let mut optional = vec![];
+        if let Some(pts) = pts {
+            optional = vec![
+                0x21 | (pts >> 29) as u8 & 0x0e,
+                (pts >> 22) as u8,
+                0x01 | (pts >> 14) as u8 & 0xfe,
+                (pts >> 7) as u8,
+                0x01 | (pts << 1) as u8,
+            ];
+        }
+        let len = 3 + optional.len() + data.len();
+        let mut packet = vec![0x00, 0x00, 0x01, 0xc0, (len >> 8) as u8, len as u8];
+        packet.extend_from_slice(&[0x80, if pts.is_some() { 0x80 } else { 0x00 }]);
+        packet.push(optional.len() as u8);
+        packet.extend(optional);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn pes_packets() {
+        // 11 byte frames of 1920 ticks each
+        let data = StreamBuilder::new()
+            .frames(5, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut consumer = AdtsElementaryStreamConsumer::new(AdtsParser::new(Frames::default()));
+        let ctx = &mut ();
+        consumer.start_stream(ctx);
+        // a packet holding a frame and part of the next, continued in a second TS packet
+        let packet = pes_packet(Some(1000), &data[..15]);
+        consumer.begin_packet(ctx, PesHeader::from_bytes(&packet).unwrap());
+        consumer.continue_packet(ctx, &data[15..22]);
+        consumer.end_packet(ctx);
+        // a packet without a PTS, holding the third frame
+        let packet = pes_packet(None, &data[22..33]);
+        consumer.begin_packet(ctx, PesHeader::from_bytes(&packet).unwrap());
+        consumer.end_packet(ctx);
+        // a packet whose header is cut short, and part of the fourth frame followed by lost
+        // data, then a packet holding the last frame
+        consumer.begin_packet(ctx, PesHeader::from_bytes(&packet[..4 + 2]).unwrap());
+        consumer.continue_packet(ctx, &data[33..40]);
+        consumer.continuity_error(ctx);
+        let packet = pes_packet(Some(50_000), &data[44..]);
+        consumer.begin_packet(ctx, PesHeader::from_bytes(&packet).unwrap());
+        consumer.end_packet(ctx);
+
+        let frames = consumer.parser.consumer;
+        assert_eq!(
+            frames.pts,
+            [Some(1000), Some(2920), Some(4840), Some(50_000)]
+        );
+        assert_eq!(frames.errors, []);
+    }
+}
@@ -0,0 +1,140 @@
+//! Compact per-interval summaries of a stream, for drawing overview strips of a whole archive in
+//! a user interface, in the manner of a waveform display.
+//!
+//! Everything is derived from the framing and from cheap statistics on the payload bytes, so
+//! nothing is decoded; even long archives can be summarised quickly.
+
+use crate::{AdtsFrameIter, FrameLength, MediaTime, PayloadProfile};
+use std::time::Duration;
+
+/// Options for [`overview()`](fn.overview.html).
+#[derive(Debug, Clone)]
+pub struct OverviewOptions {
+    /// The span of media time summarised by each bucket (default 1 second)
+    pub bucket: Duration,
+    /// Number of samples per `raw_data_block`, used to calculate media time (default
+    /// `FrameLength::Samples1024`)
+    pub frame_length: FrameLength,
+}
+impl Default for OverviewOptions {
+    fn default() -> Self {
+        OverviewOptions {
+            bucket: Duration::from_secs(1),
+            frame_length: FrameLength::default(),
+        }
+    }
+}
+
+/// Aggregate values for the frames starting within one interval of media time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct OverviewBucket {
+    /// Number of frames starting in the interval
+    pub frames: u32,
+    /// Total size of those frames, including headers
+    pub bytes: u32,
+    /// Size of the smallest frame
+    pub min_frame_len: u16,
+    /// Size of the largest frame
+    pub max_frame_len: u16,
+    /// `true` if every frame's payload looks like encoded silence, judged by
+    /// [`PayloadProfile::likely_silence()`](../struct.PayloadProfile.html#method.likely_silence)
+    pub silent: bool,
+}
+
+/// Summarises the frames in the given buffer, giving one bucket for each interval of media time
+/// (the first bucket starting at the first frame).  Data which isn't part of a frame is skipped.
+///
+/// ```rust
+/// use adts_reader::overview::{overview, OverviewOptions};
+/// # let archive = vec![];
+/// for (second, bucket) in overview(&archive, &OverviewOptions::default()).iter().enumerate() {
+///     println!("{}s: {} bytes{}", second, bucket.bytes, if bucket.silent { " (silent)" } else { "" });
+/// }
+/// ```
+pub fn overview(buf: &[u8], options: &OverviewOptions) -> Vec<OverviewBucket> {
+    let interval = options.bucket.as_nanos().max(1);
+    let mut buckets: Vec<OverviewBucket> = vec![];
+    let mut elapsed = MediaTime::ZERO;
+    for header in AdtsFrameIter::new(buf).filter_map(Result::ok) {
+        // compare elapsed.samples / elapsed.rate against multiples of interval / 10^9, exactly
+        let index = (u128::from(elapsed.samples()) * 1_000_000_000
+            / (u128::from(elapsed.rate()) * interval)) as usize;
+        if buckets.len() <= index {
+            buckets.resize(index + 1, OverviewBucket::default());
+        }
+        let bucket = &mut buckets[index];
+        let len = header.frame_length();
+        let silent = header
+            .payload()
+            .is_ok_and(|p| PayloadProfile::of(p).likely_silence());
+        if bucket.frames == 0 {
+            bucket.min_frame_len = len;
+            bucket.silent = silent;
+        }
+        bucket.frames += 1;
+        bucket.bytes += u32::from(len);
+        bucket.min_frame_len = bucket.min_frame_len.min(len);
+        bucket.max_frame_len = bucket.max_frame_len.max(len);
+        bucket.silent &= silent;
+        // frames with a reserved sampling frequency have unknown duration, and are taken to have
+        // none
+        if let Some(rate) = header.sampling_frequency().freq() {
+            let blocks = header.number_of_raw_data_blocks_in_frame();
+            elapsed =
+                elapsed + MediaTime::from_blocks_of(blocks.into(), options.frame_length, rate);
+        }
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn buckets() {
+        // at 8kHz, each frame is 128ms, so 7.8125 frames per second
+        let mut builder = StreamBuilder::new().config(AdtsStreamConfig {
+            sampling_frequency: SamplingFrequency::Freq8000,
+            ..StreamBuilder::DEFAULT_CONFIG
+        });
+        for i in 0..16u8 {
+            let payload: Vec<u8> = if i < 8 {
+                vec![0; 4]
+            } else {
+                (0..=i).map(|b| b.wrapping_mul(37)).collect()
+            };
+            builder = builder.frame(&payload);
+        }
+        let data = builder.build();
+        let buckets = overview(&data, &OverviewOptions::default());
+        assert_eq!(buckets.len(), 2);
+        // frames 0..=7 start before 1s (frame 7 starts at 896ms)
+        assert_eq!(
+            buckets[0],
+            OverviewBucket {
+                frames: 8,
+                bytes: 8 * 11,
+                min_frame_len: 11,
+                max_frame_len: 11,
+                silent: true,
+            }
+        );
+        // frames 8..=15 start between 1.024s and 1.920s
+        assert_eq!(buckets[1].frames, 8);
+        assert_eq!(buckets[1].min_frame_len, 16);
+        assert_eq!(buckets[1].max_frame_len, 23);
+        assert!(!buckets[1].silent);
+
+        let options = OverviewOptions {
+            bucket: Duration::from_millis(500),
+            ..OverviewOptions::default()
+        };
+        let frames: Vec<u32> = overview(&data, &options).iter().map(|b| b.frames).collect();
+        assert_eq!(frames, vec![4, 4, 4, 4]);
+    }
+}
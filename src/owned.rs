@@ -0,0 +1,164 @@
+//! Frames held in their own buffers, for readers which hand out frames that outlive the data they
+//! were read into.
+
+use crate::{AdtsConsumer2, AdtsHeader, FrameContext, FrameError};
+
+/// A complete ADTS frame, header included, held in its own buffer.
+///
+/// ```rust
+/// use adts_reader::OwnedAdtsFrame;
+/// let frame = OwnedAdtsFrame::new(vec![0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]).unwrap();
+/// assert_eq!(frame.header().frame_length(), 8);
+/// assert_eq!(frame.payload(), &[0x21]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedAdtsFrame {
+    buf: Vec<u8>,
+}
+impl OwnedAdtsFrame {
+    /// Takes ownership of the bytes of a frame, which must hold exactly one complete frame.
+    ///
+    /// Only the framing is checked, as by `AdtsHeader::from_bytes()`; use
+    /// [`validate_frame()`](fn.validate_frame.html) for stricter checks.
+    pub fn new(buf: Vec<u8>) -> Result<OwnedAdtsFrame, FrameError> {
+        let expected = AdtsHeader::from_bytes(&buf)?.frame_len();
+        if buf.len() < expected {
+            return Err(FrameError::Truncated {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        if buf.len() > expected {
+            return Err(FrameError::TrailingData {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        Ok(OwnedAdtsFrame { buf })
+    }
+
+    /// Wraps a buffer already known to hold exactly one frame with a valid header
+    pub(crate) fn from_frame(buf: Vec<u8>) -> OwnedAdtsFrame {
+        debug_assert!(matches!(
+            AdtsHeader::from_bytes(&buf),
+            Ok(header) if header.frame_len() == buf.len()
+        ));
+        OwnedAdtsFrame { buf }
+    }
+
+    pub fn header(&self) -> AdtsHeader<'_> {
+        AdtsHeader::from_bytes(&self.buf).expect("header checked on construction")
+    }
+
+    /// The frame payload, following the header fields
+    pub fn payload(&self) -> &[u8] {
+        self.header()
+            .payload()
+            .expect("length checked on construction")
+    }
+
+    /// The bytes of the whole frame, header included
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+impl AsRef<[u8]> for OwnedAdtsFrame {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// An [`AdtsConsumer2`](trait.AdtsConsumer2.html) which copies each frame the parser finds into
+/// an [`OwnedAdtsFrame`](struct.OwnedAdtsFrame.html), and passes it to the given closure.
+///
+/// This is a parser mode emitting owned frames, for handing them to another thread (a decoder,
+/// say) without the borrowed payload of `on_frame()` getting in the way.  Other parser events
+/// are ignored; implement `AdtsConsumer2` directly, calling
+/// [`FrameContext::to_owned()`](struct.FrameContext.html#method.to_owned), to handle those too.
+///
+/// ```rust
+/// use adts_reader::{AdtsParser, OwnedFrames};
+/// use std::sync::mpsc;
+/// let (tx, rx) = mpsc::channel();
+/// let mut parser = AdtsParser::new(OwnedFrames::new(move |frame| tx.send(frame).unwrap()));
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// let decoder = std::thread::spawn(move || rx.recv().unwrap().payload().to_vec());
+/// assert_eq!(decoder.join().unwrap(), [0x21]);
+/// ```
+pub struct OwnedFrames<F: FnMut(OwnedAdtsFrame)> {
+    f: F,
+}
+impl<F: FnMut(OwnedAdtsFrame)> OwnedFrames<F> {
+    pub fn new(f: F) -> OwnedFrames<F> {
+        OwnedFrames { f }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.f
+    }
+}
+impl<F: FnMut(OwnedAdtsFrame)> AdtsConsumer2 for OwnedFrames<F> {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        (self.f)(frame.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdtsHeaderError;
+
+    #[test]
+    fn framing_checked() {
+        let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+        assert_eq!(
+            OwnedAdtsFrame::new(frame[..7].to_vec()),
+            Err(FrameError::Truncated {
+                expected: 8,
+                actual: 7
+            })
+        );
+        let mut long = frame.to_vec();
+        long.push(0);
+        assert_eq!(
+            OwnedAdtsFrame::new(long),
+            Err(FrameError::TrailingData {
+                expected: 8,
+                actual: 9
+            })
+        );
+        assert_eq!(
+            OwnedAdtsFrame::new(vec![0; 8]),
+            Err(FrameError::Header(AdtsHeaderError::BadSyncWord(0)))
+        );
+        let owned = OwnedAdtsFrame::new(frame.to_vec()).unwrap();
+        assert_eq!(owned.as_bytes(), frame);
+        assert_eq!(owned.into_bytes(), frame);
+    }
+
+    #[test]
+    fn from_borrowed() {
+        let buf = vec![0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21, 0xff, 0xf1];
+        let header = AdtsHeader::from_bytes(&buf).unwrap();
+        let owned = header.to_owned().unwrap();
+        assert_eq!(owned.as_bytes(), &buf[..8]);
+        assert!(AdtsHeader::from_bytes(&buf[..7])
+            .unwrap()
+            .to_owned()
+            .is_err());
+
+        let mut frames = vec![];
+        let mut parser = crate::AdtsParser::new(OwnedFrames::new(|frame| frames.push(frame)));
+        parser.push(&buf[..5]);
+        parser.push(&buf[5..8]);
+        parser.push(&buf[..8]);
+        drop(parser);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], owned);
+        assert_eq!(frames[1], owned);
+    }
+}
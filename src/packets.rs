@@ -0,0 +1,187 @@
+//! Delivery of frames as timestamped packets, for feeding media frameworks.
+//!
+//! Available when the `packets` feature is enabled.
+//!
+//! Frameworks such as GStreamer (via an `appsrc` element) expect a sequence of encoded packets,
+//! each with a presentation timestamp and duration.  [`PacketAdapter`](struct.PacketAdapter.html)
+//! produces exactly that from the output of an `AdtsParser`, so the only glue code needed is the
+//! closure handing each packet to the framework.
+//!
+//! ```rust
+//! use adts_reader::AdtsParser;
+//! use adts_reader::packets::PacketAdapter;
+//!
+//! let mut parser = AdtsParser::new(PacketAdapter::new(|packet| {
+//!     // e.g. buffer.set_pts(ClockTime::from_nseconds(packet.pts_nanos())); appsrc.push_buffer(..)
+//!     println!("{:?} {} bytes", packet.pts, packet.data.len());
+//! }));
+//! parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+//! ```
+
+use crate::{
+    AdtsConsumer, AdtsParseError, AudioObjectType, ChannelConfiguration, FrameLength, MediaTime,
+    MpegVersion, Originality, ProtectionIndicator, SamplingFrequency,
+};
+use std::time::Duration;
+
+/// An encoded AAC access unit (the payload of an ADTS frame, without the ADTS header) together
+/// with its timing.
+#[derive(Debug)]
+pub struct TimedPacket<'a> {
+    /// Presentation time of the first sample in the packet, relative to the start of the stream
+    pub pts: Duration,
+    /// The exact presentation time, from which `pts` is derived
+    pub start: MediaTime,
+    pub duration: Duration,
+    /// Number of samples (per channel) preceding this packet in the stream
+    pub sample_offset: u64,
+    /// Number of samples (per channel) coded in this packet
+    pub samples: u64,
+    pub sample_rate: u32,
+    pub data: &'a [u8],
+}
+impl<'a> TimedPacket<'a> {
+    /// The presentation time in nanoseconds, as used by GStreamer's `ClockTime`
+    pub fn pts_nanos(&self) -> u64 {
+        self.pts.as_nanos() as u64
+    }
+
+    /// The duration in nanoseconds
+    pub fn duration_nanos(&self) -> u64 {
+        self.duration.as_nanos() as u64
+    }
+}
+
+/// An `AdtsConsumer` which passes each frame payload to a closure as a
+/// [`TimedPacket`](struct.TimedPacket.html).
+///
+/// Timestamps are derived by counting samples from the start of the stream.  Frames that arrive
+/// before any configuration with a valid sampling frequency (which shouldn't happen with a
+/// well-formed stream) can't be timed, and are dropped.  Errors reported by the parser are passed
+/// to an optional error handler set with [`on_error()`](#method.on_error).
+pub struct PacketAdapter<F>
+where
+    F: FnMut(TimedPacket<'_>),
+{
+    on_packet: F,
+    on_error: Option<Box<dyn FnMut(AdtsParseError)>>,
+    sample_rate: Option<u32>,
+    /// stream time at which the next packet starts
+    elapsed: MediaTime,
+    /// samples since the stream started
+    sample_offset: u64,
+    frame_length: FrameLength,
+}
+impl<F> PacketAdapter<F>
+where
+    F: FnMut(TimedPacket<'_>),
+{
+    pub fn new(on_packet: F) -> PacketAdapter<F> {
+        PacketAdapter {
+            on_packet,
+            on_error: None,
+            sample_rate: None,
+            elapsed: MediaTime::ZERO,
+            sample_offset: 0,
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to calculate packet timestamps and
+    /// durations (by default, 1024)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Sets a closure to receive any errors the parser reports
+    pub fn on_error(mut self, on_error: impl FnMut(AdtsParseError) + 'static) -> Self {
+        self.on_error = Some(Box::new(on_error));
+        self
+    }
+}
+impl<F> AdtsConsumer for PacketAdapter<F>
+where
+    F: FnMut(TimedPacket<'_>),
+{
+    fn new_config(
+        &mut self,
+        _mpeg_version: MpegVersion,
+        _protection: ProtectionIndicator,
+        _aot: AudioObjectType,
+        freq: SamplingFrequency,
+        _private_bit: u8,
+        _channels: ChannelConfiguration,
+        _originality: Originality,
+        _home: u8,
+    ) {
+        self.sample_rate = freq.freq();
+    }
+
+    fn payload(&mut self, _buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) {
+        let rate = match self.sample_rate {
+            Some(rate) => rate,
+            None => return,
+        };
+        let length = MediaTime::from_blocks_of(number_of_blocks.into(), self.frame_length, rate);
+        let start = self.elapsed;
+        let end = start + length;
+        (self.on_packet)(TimedPacket {
+            pts: start.to_duration(),
+            start,
+            duration: end.to_duration() - start.to_duration(),
+            sample_offset: self.sample_offset,
+            samples: length.samples(),
+            sample_rate: rate,
+            data: buf,
+        });
+        self.elapsed = end;
+        self.sample_offset += length.samples();
+    }
+
+    fn error(&mut self, err: AdtsParseError) {
+        if let Some(ref mut on_error) = self.on_error {
+            on_error(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[test]
+    fn timestamps() {
+        let frame = StreamBuilder::new()
+            .config(AdtsStreamConfig {
+                sampling_frequency: SamplingFrequency::Freq44100,
+                ..StreamBuilder::DEFAULT_CONFIG
+            })
+            .frame(&[1, 2])
+            .build();
+        let mut packets = vec![];
+        {
+            let mut parser = AdtsParser::new(PacketAdapter::new(|p| {
+                packets.push((p.pts, p.duration, p.sample_offset, p.data.to_vec()))
+            }));
+            for _ in 0..44100 {
+                parser.push(&frame);
+            }
+        }
+        assert_eq!(packets.len(), 44100);
+        assert_eq!(
+            packets[0],
+            (Duration::ZERO, Duration::new(0, 23219954), 0, vec![1, 2])
+        );
+        // 44100 frames of 1024 samples is exactly 1024 seconds, with no accumulated error
+        let (pts, duration, offset, _) = packets[44099];
+        assert_eq!(pts + duration, Duration::from_secs(1024));
+        assert_eq!(offset, 44099 * 1024);
+        // durations are computed so that consecutive packets abut exactly
+        for pair in packets.windows(2) {
+            assert_eq!(pair[0].0 + pair[0].1, pair[1].0);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Timestamps from the PES packets carrying an ADTS stream in MPEG transport streams.
+
+use crate::{AdtsHeader, FrameLength, MediaTime, PtsCounter};
+use std::collections::VecDeque;
+
+/// The largest number of PES packets whose first frame is still awaited; more than this means
+/// the packets hold no frames at all, and only the most recent are kept
+const MAX_PENDING: usize = 16;
+
+/// Assigns the PTS of each PES packet to the first frame starting within it, and extrapolates
+/// timestamps for the frames which follow
+#[derive(Debug, Default)]
+pub(crate) struct PesTiming {
+    /// the stream offset at which each PES packet began, and its PTS, for packets in which no
+    /// frame has yet been found
+    pending: VecDeque<(u64, Option<u64>)>,
+    /// counts on from the most recent PTS, once a packet has given one
+    counter: Option<PtsCounter>,
+}
+impl PesTiming {
+    /// Notes that a PES packet with the given PTS begins at the given stream offset
+    pub fn begin_packet(&mut self, offset: u64, pts: Option<u64>) {
+        if self.pending.len() == MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((offset, pts));
+    }
+
+    /// The PTS of the frame with the given header, starting at the given stream offset
+    pub fn frame(
+        &mut self,
+        offset: u64,
+        header: &AdtsHeader<'_>,
+        frame_length: FrameLength,
+    ) -> Option<u64> {
+        // packets starting before this frame, other than the one it starts in, hold no frames,
+        // so their timestamps (which should be absent anyway) can't be used
+        let mut packet = None;
+        while let Some(&(start, pts)) = self.pending.front() {
+            if start > offset {
+                break;
+            }
+            packet = Some(pts);
+            self.pending.pop_front();
+        }
+        if let Some(Some(pts)) = packet {
+            let start = MediaTime::new(pts & ((1 << 33) - 1), 90_000);
+            self.counter = Some(PtsCounter::new(start).frame_length(frame_length));
+        }
+        self.counter
+            .as_mut()
+            .map(|counter| counter.next_90khz(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[derive(Default)]
+    struct Timestamps(Vec<Option<u64>>);
+    impl AdtsConsumer2 for Timestamps {
+        fn on_frame(&mut self, frame: &FrameContext<'_>) {
+            self.0.push(frame.pts);
+        }
+    }
+
+    #[test]
+    fn pes_timestamps() {
+        // 11 byte frames of 1920 ticks each
+        let data = StreamBuilder::new()
+            .frames(6, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut parser = AdtsParser::new(Timestamps::default());
+        // no PTS known for the first frame
+        parser.push(&data[..11]);
+        // a packet holding a frame and the start of the next
+        parser.begin_pes_packet(Some(1000));
+        parser.push(&data[11..27]);
+        // a packet without a PTS, where the frames are extrapolated
+        parser.begin_pes_packet(None);
+        parser.push(&data[27..40]);
+        // a packet in which no frame starts, whose PTS is unused
+        parser.begin_pes_packet(Some(5));
+        parser.push(&data[40..44]);
+        assert_eq!(parser.pending_bytes(), 0);
+        // the PTS wraps at 33 bits
+        parser.begin_pes_packet(Some((1 << 33) - 100));
+        parser.push(&data[44..]);
+        assert_eq!(
+            parser.consumer.0,
+            [
+                None,
+                Some(1000),
+                Some(2920),
+                Some(4840),
+                Some((1 << 33) - 100),
+                Some(1820)
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Workarounds for known-broken encoders, which the parser can apply to each frame as it is
+//! found.
+//!
+//! Some encoders in the wild consistently get a header field wrong in a recognisable way.  Left
+//! alone, a wrong `frame_length` loses sync with every frame, and a wrong `adts_buffer_fullness`
+//! misleads anything doing rate control.  Enabling the matching [`Quirk`](enum.Quirk.html) with
+//! [`AdtsParser::with_quirks()`](../struct.AdtsParser.html#method.with_quirks) corrects the
+//! header before the frame reaches the consumer, and the corrections made are reported in
+//! `FrameContext::quirks` and counted in `ParserStats::quirk_corrected_frames`.
+//!
+//! Quirks have names, so that the set to apply can come from configuration:
+//!
+//! ```rust
+//! use adts_reader::quirks::{Quirk, Quirks};
+//! let quirks = "frame_length_excludes_crc, buffer_fullness_byte_swapped"
+//!     .split(',')
+//!     .map(|name| name.trim().parse::<Quirk>())
+//!     .collect::<Result<Quirks, _>>()
+//!     .unwrap();
+//! assert!(quirks.contains(Quirk::FrameLengthExcludesCrc));
+//! ```
+
+use crate::{fields, AdtsHeader, ProtectionIndicator};
+use std::fmt;
+use std::str::FromStr;
+
+/// A known encoder bug that the parser can work around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Quirk {
+    /// In frames with a CRC, `frame_length` counts only the 7 byte header and the payload,
+    /// leaving out the 2 byte `crc_check` field
+    FrameLengthExcludesCrc,
+    /// The 11 bit `adts_buffer_fullness` value was written with its low 8 bits first, followed by
+    /// its high 3 bits.  The VBR marker value `0x7FF` reads the same either way.
+    BufferFullnessByteSwapped,
+}
+impl Quirk {
+    /// Every quirk known to this release of the crate
+    pub const ALL: &'static [Quirk] = &[
+        Quirk::FrameLengthExcludesCrc,
+        Quirk::BufferFullnessByteSwapped,
+    ];
+
+    /// The name used for this quirk in configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            Quirk::FrameLengthExcludesCrc => "frame_length_excludes_crc",
+            Quirk::BufferFullnessByteSwapped => "buffer_fullness_byte_swapped",
+        }
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+impl fmt::Display for Quirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+impl FromStr for Quirk {
+    type Err = UnknownQuirk;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Quirk::ALL
+            .iter()
+            .copied()
+            .find(|q| q.name() == s)
+            .ok_or_else(|| UnknownQuirk(s.to_string()))
+    }
+}
+
+/// Error given when parsing a name that isn't one of [`Quirk::ALL`](enum.Quirk.html#associatedconstant.ALL).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownQuirk(pub String);
+impl fmt::Display for UnknownQuirk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown quirk {:?}", self.0)
+    }
+}
+impl std::error::Error for UnknownQuirk {}
+
+/// A set of [`Quirk`s](enum.Quirk.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    bits: u32,
+}
+impl Quirks {
+    /// The empty set, applying no workarounds
+    pub const NONE: Quirks = Quirks { bits: 0 };
+
+    /// Returns this set with the given quirk added
+    pub fn with(mut self, quirk: Quirk) -> Quirks {
+        self.insert(quirk);
+        self
+    }
+
+    pub fn insert(&mut self, quirk: Quirk) {
+        self.bits |= quirk.bit();
+    }
+
+    pub fn contains(&self, quirk: Quirk) -> bool {
+        self.bits & quirk.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// The quirks in this set, in the order of [`Quirk::ALL`](enum.Quirk.html#associatedconstant.ALL)
+    pub fn iter(&self) -> impl Iterator<Item = Quirk> + '_ {
+        Quirk::ALL.iter().copied().filter(|&q| self.contains(q))
+    }
+
+    /// The length of the frame with the given header as it is actually present in the stream
+    pub(crate) fn frame_len(&self, header: &AdtsHeader<'_>) -> usize {
+        if self.excludes_crc(header) {
+            header.frame_len() + 2
+        } else {
+            header.frame_len()
+        }
+    }
+
+    /// The subset of these quirks whose correction would change the given frame's header
+    pub(crate) fn applicable(&self, header: &AdtsHeader<'_>) -> Quirks {
+        let mut applicable = Quirks::NONE;
+        if self.excludes_crc(header) {
+            applicable.insert(Quirk::FrameLengthExcludesCrc);
+        }
+        if self.contains(Quirk::BufferFullnessByteSwapped) {
+            let raw = header.adts_buffer_fullness();
+            if unswap_fullness(raw) != raw {
+                applicable.insert(Quirk::BufferFullnessByteSwapped);
+            }
+        }
+        applicable
+    }
+
+    fn excludes_crc(&self, header: &AdtsHeader<'_>) -> bool {
+        self.contains(Quirk::FrameLengthExcludesCrc)
+            && header.protection() == ProtectionIndicator::CrcPresent
+            // a frame_length this large can't be corrected, so the encoder can't have written it
+            && u32::from(header.frame_length()) + 2 <= fields::FRAME_LENGTH.max()
+    }
+
+    /// Corrects the header fields at the start of the given frame for each quirk in this set
+    pub(crate) fn correct(&self, frame: &mut [u8]) {
+        if self.contains(Quirk::FrameLengthExcludesCrc) {
+            let frame_length = fields::FRAME_LENGTH.get(frame);
+            fields::FRAME_LENGTH.set(frame, frame_length + 2);
+        }
+        if self.contains(Quirk::BufferFullnessByteSwapped) {
+            let raw = fields::ADTS_BUFFER_FULLNESS.get(frame) as u16;
+            fields::ADTS_BUFFER_FULLNESS.set(frame, unswap_fullness(raw).into());
+        }
+    }
+}
+impl FromIterator<Quirk> for Quirks {
+    fn from_iter<I: IntoIterator<Item = Quirk>>(iter: I) -> Self {
+        iter.into_iter().fold(Quirks::NONE, Quirks::with)
+    }
+}
+
+/// Recovers the fullness value from one whose low 8 bits were written ahead of its high 3 bits
+fn unswap_fullness(raw: u16) -> u16 {
+    (raw & 0x7) << 8 | raw >> 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for &quirk in Quirk::ALL {
+            assert_eq!(quirk.name().parse::<Quirk>(), Ok(quirk));
+        }
+        assert_eq!(
+            "frame_length".parse::<Quirk>(),
+            Err(UnknownQuirk("frame_length".to_string()))
+        );
+        let quirks: Quirks = Quirk::ALL.iter().copied().collect();
+        assert_eq!(quirks.iter().collect::<Vec<_>>(), Quirk::ALL);
+        assert!(Quirks::NONE.is_empty());
+    }
+
+    #[test]
+    fn fullness() {
+        // 0x123 written as 0x23 then 0x1
+        assert_eq!(unswap_fullness(0x23 << 3 | 0x1), 0x123);
+        assert_eq!(unswap_fullness(0x7ff), 0x7ff);
+    }
+}
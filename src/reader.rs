@@ -0,0 +1,277 @@
+//! Reading frames from a blocking `io::Read`, such as a file.
+
+use crate::follow::FrameBuffer;
+use crate::{AdtsHeader, AdtsHeaderError, OwnedAdtsFrame};
+use std::io::{self, Read};
+use std::iter::FusedIterator;
+
+/// Errors yielded by [`AdtsFrameReader`](struct.AdtsFrameReader.html)
+#[derive(Debug)]
+pub enum AdtsReadError {
+    /// Reading the input failed; iteration may be continued, to retry
+    Io(io::Error),
+    /// The input ended part-way through a frame, of which only `actual` of the `expected` bytes
+    /// were present.  This is the last item yielded.
+    Truncated { expected: usize, actual: usize },
+}
+impl From<io::Error> for AdtsReadError {
+    fn from(e: io::Error) -> Self {
+        AdtsReadError::Io(e)
+    }
+}
+impl From<AdtsReadError> for io::Error {
+    fn from(e: AdtsReadError) -> Self {
+        match e {
+            AdtsReadError::Io(e) => e,
+            AdtsReadError::Truncated { expected, actual } => io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "input ended after {} of the {} bytes of a frame",
+                    actual, expected
+                ),
+            ),
+        }
+    }
+}
+
+/// Yields the complete ADTS frames read from an input, taking care of frames which span the
+/// boundaries between reads.
+///
+/// Bytes which aren't part of any frame (e.g. an ID3 tag at the start of a file) are skipped, and
+/// counted by [`skipped_bytes()`](#method.skipped_bytes).  Unlike
+/// [`FollowingAdtsReader`](follow/struct.FollowingAdtsReader.html), the end of the input is taken
+/// to be the end of the stream, so an incomplete frame found there is reported as
+/// `AdtsReadError::Truncated`.
+///
+/// ```rust
+/// use adts_reader::AdtsFrameReader;
+/// # let file: &[u8] = &[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// for frame in AdtsFrameReader::new(file) {
+///     let frame = frame?;
+///     println!("frame of {} bytes", frame.as_bytes().len());
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct AdtsFrameReader<R: Read> {
+    input: R,
+    buf: FrameBuffer,
+    skipped_bytes: u64,
+    ended: bool,
+    id3_tags: bool,
+    /// the HLS timestamp from the ID3 tag most recently skipped, for the next frame
+    tag_timestamp: Option<u64>,
+    /// the HLS timestamp of the frame most recently returned
+    timestamp: Option<u64>,
+}
+impl<R: Read> AdtsFrameReader<R> {
+    pub fn new(input: R) -> AdtsFrameReader<R> {
+        AdtsFrameReader {
+            input,
+            buf: FrameBuffer::default(),
+            skipped_bytes: 0,
+            ended: false,
+            id3_tags: false,
+            tag_timestamp: None,
+            timestamp: None,
+        }
+    }
+
+    /// When `true`, ID3v2 tags found between frames are examined for the MPEG-TS timestamp with
+    /// which each HLS packed audio segment starts, given by
+    /// [`id3_timestamp()`](#method.id3_timestamp), before being skipped (by default, `false`, so
+    /// that tags are skipped along with any other junk)
+    pub fn with_id3_tags(mut self, id3_tags: bool) -> Self {
+        self.id3_tags = id3_tags;
+        self
+    }
+
+    /// The MPEG-TS timestamp (in 90kHz ticks) given by the
+    /// `com.apple.streaming.transportStreamTimestamp` ID3v2 tag preceding the frame most recently
+    /// returned, or `None` if the frame had no such tag before it.  Only available when enabled
+    /// with [`with_id3_tags()`](#method.with_id3_tags).
+    pub fn id3_timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Number of bytes read which were not part of any frame
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Number of bytes read but not yet returned as part of a frame
+    pub fn pending_bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+
+    /// Discards any buffered data, giving access to the input so that reading can restart from
+    /// some other position
+    #[cfg(feature = "symphonia")]
+    pub(crate) fn reset(&mut self) -> &mut R {
+        self.buf.clear();
+        self.ended = false;
+        &mut self.input
+    }
+
+    /// Appends the next read from the input to the buffer, noting the end of the input
+    fn fill(&mut self) -> io::Result<()> {
+        let n = self.buf.read_from(&mut self.input)?;
+        self.ended = n == 0;
+        Ok(())
+    }
+}
+impl<R: Read> Iterator for AdtsFrameReader<R> {
+    type Item = Result<OwnedAdtsFrame, AdtsReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ready = !self.id3_tags
+                || self
+                    .buf
+                    .skip_id3_tags(&mut self.skipped_bytes, &mut self.tag_timestamp);
+            if ready {
+                if let Some(frame) = self.buf.take_frame(&mut self.skipped_bytes) {
+                    self.timestamp = self.tag_timestamp.take();
+                    return Some(Ok(OwnedAdtsFrame::from_frame(frame)));
+                }
+            }
+            if self.ended {
+                if self.buf.is_empty() {
+                    return None;
+                }
+                if !ready {
+                    // the input ended part way through a tag
+                    self.skipped_bytes += self.buf.len() as u64;
+                    self.buf.clear();
+                    return None;
+                }
+                // take_frame() leaves only the start of a frame unconsumed
+                let expected = match AdtsHeader::from_bytes(self.buf.pending()) {
+                    Ok(header) => header.frame_len(),
+                    Err(AdtsHeaderError::NotEnoughData { expected, .. }) => expected,
+                    Err(_) => unreachable!("take_frame() skips false sync-words"),
+                };
+                let actual = self.buf.len();
+                self.buf.clear();
+                return Some(Err(AdtsReadError::Truncated { expected, actual }));
+            }
+            if let Err(e) = self.fill() {
+                return Some(Err(AdtsReadError::Io(e)));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.ended {
+            // every frame is at least 7 bytes, and may be followed by a final Truncated error
+            let remaining = self.buf.len();
+            (0, Some(remaining.div_ceil(7)))
+        } else {
+            (0, None)
+        }
+    }
+}
+// once the input has ended and the buffer is drained, every later call to next() returns None
+impl<R: Read> FusedIterator for AdtsFrameReader<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+
+    /// Gives out its data a few bytes at a time, failing once part way through
+    struct Trickle {
+        data: Vec<u8>,
+        pos: usize,
+        fail_at: Option<usize>,
+    }
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.fail_at == Some(self.pos) {
+                self.fail_at = None;
+                return Err(io::Error::other("flaky"));
+            }
+            let n = buf.len().min(5).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn frames_across_reads() {
+        let builder = StreamBuilder::new()
+            .junk(3)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG);
+        let offsets = builder.offsets().to_vec();
+        let mut data = builder.build();
+        data.truncate(data.len() - 2);
+        let mut reader = AdtsFrameReader::new(Trickle {
+            data: data.clone(),
+            pos: 0,
+            fail_at: Some(10),
+        });
+        let mut frames = vec![];
+        let mut errors = vec![];
+        for item in reader.by_ref() {
+            match item {
+                Ok(frame) => frames.push(frame.into_bytes()),
+                Err(e) => errors.push(e),
+            }
+        }
+        assert_eq!(
+            frames,
+            vec![
+                data[offsets[0]..offsets[1]].to_vec(),
+                data[offsets[1]..offsets[2]].to_vec(),
+            ]
+        );
+        assert!(matches!(errors[0], AdtsReadError::Io(_)));
+        assert!(matches!(
+            errors[1],
+            AdtsReadError::Truncated {
+                expected: 11,
+                actual: 9
+            }
+        ));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(reader.skipped_bytes(), 3);
+        assert_eq!(reader.pending_bytes(), 0);
+        assert!(reader.next().is_none());
+        assert_eq!(reader.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn id3_timestamps() {
+        use crate::id3::tests::timestamp_tag;
+        // two segments, each of a 73 byte tag and two 11 byte frames, the second segment being
+        // cut short part way through its tag
+        let mut data = vec![];
+        for timestamp in [9000, 20000] {
+            data.extend(timestamp_tag(timestamp));
+            data.extend(
+                StreamBuilder::new()
+                    .frames(2, StreamBuilder::DEFAULT_CONFIG)
+                    .build(),
+            );
+        }
+        data.extend(&timestamp_tag(30000)[..40]);
+        let mut reader = AdtsFrameReader::new(Trickle {
+            data,
+            pos: 0,
+            fail_at: None,
+        })
+        .with_id3_tags(true);
+        let mut timestamps = vec![];
+        while let Some(frame) = reader.next() {
+            frame.unwrap();
+            timestamps.push(reader.id3_timestamp());
+        }
+        assert_eq!(timestamps, [Some(9000), None, Some(20000), None]);
+        assert_eq!(reader.skipped_bytes(), 73 * 2 + 40);
+        assert_eq!(reader.pending_bytes(), 0);
+    }
+}
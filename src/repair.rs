@@ -0,0 +1,812 @@
+//! Streaming repair of damaged or inconsistent ADTS streams, as used by the `adts-fix` tool.
+//!
+//! Dropping junk or truncated frames (or repairing a stream made by concatenating several) joins
+//! frames which an encoder never wrote next to each other, and the `adts_buffer_fullness` values
+//! either side of such a join are often impossible for a single encoder's bit reservoir.  Strict
+//! decoders may complain, so [`FullnessRepair`](enum.FullnessRepair.html) can rewrite the field
+//! in every output frame.
+//!
+//! For changes beyond the built-in repairs, [`rewrite()`](fn.rewrite.html) lets a closure edit
+//! the header of every frame as it is copied.
+//!
+//! [`concat()`](fn.concat.html) joins several inputs into one output, applying the same repairs,
+//! and can drop the frames which segmenters often repeat either side of a joint.
+//!
+//! The same pass can also filter frames, dropping those which fail their CRC check, or keeping
+//! only those within a range of media time, to cut an excerpt from a long recording.
+
+use crate::crc::{self, CrcAlgorithm};
+use crate::writer::BitReservoir;
+use crate::{
+    encode_header, fields, id3, AdtsHeader, AdtsHeaderError, AdtsStreamConfig,
+    ChannelConfiguration, FrameLength, FrameOptions, MediaTime, ProtectionIndicator,
+};
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Range;
+use std::time::Duration;
+
+/// How [`repair()`](fn.repair.html) should treat CRC protection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrcRepair {
+    /// Leave frames with or without a CRC as they are
+    Keep,
+    /// Remove the CRC from frames that have one
+    Strip,
+    /// Calculate and add a CRC to frames that lack one
+    Add,
+}
+
+/// How [`repair()`](fn.repair.html) should treat the `adts_buffer_fullness` header field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FullnessRepair {
+    /// Leave the value in each frame as it is
+    Keep,
+    /// Set every frame to `0x7FF`, signalling a variable rate stream
+    Vbr,
+    /// Recompute the value for a constant rate stream of the given bit rate (in bits per second,
+    /// counting whole frames, headers included), by modelling the encoder's bit reservoir over
+    /// the actual sizes of the output frames.
+    ///
+    /// The reservoir starts full, and is assumed to hold up to 6144 bits per channel less one
+    /// frame's share of the bit rate.  Frames with a `channel_configuration` of zero (whose
+    /// channel count is given in-band) are left as they are, and `raw_data_block`s are taken to
+    /// hold [`RepairOptions::frame_length`](struct.RepairOptions.html#structfield.frame_length)
+    /// samples.
+    Reservoir { bitrate: u32 },
+}
+
+/// Selects the repairs that [`repair()`](fn.repair.html) applies.
+///
+/// Further fields may be added in future releases, so start from `RepairOptions::default()` and
+/// change the fields of interest.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RepairOptions {
+    /// Drop ID3v2 tags found between frames (default `true`)
+    pub skip_id3: bool,
+    /// Drop bytes which aren't part of any frame, rather than copying them to the output (default
+    /// `true`)
+    pub skip_junk: bool,
+    /// Drop an incomplete frame at the end of the input, rather than copying it to the output
+    /// (default `true`)
+    pub trim_truncated: bool,
+    /// (default `CrcRepair::Keep`)
+    pub crc: CrcRepair,
+    /// Rewrite the `channel_configuration` of every frame to this value (default `None`)
+    pub force_channels: Option<ChannelConfiguration>,
+    /// How to rewrite the `adts_buffer_fullness` field of each output frame, which splicing
+    /// or dropping frames can leave inconsistent with the encoder's bit reservoir; either signal
+    /// a variable rate stream, or recompute the values for a constant bit rate (default
+    /// `FullnessRepair::Keep`, leaving the field as it is)
+    pub buffer_fullness: FullnessRepair,
+    /// The CRC implementation used when adding or checking CRCs (default
+    /// [`crc::DEFAULT`](../crc/static.DEFAULT.html))
+    pub crc_algorithm: &'static dyn CrcAlgorithm,
+    /// Drop frames with a CRC which doesn't match their contents (default `false`).  Frames
+    /// without a CRC are always kept.
+    pub drop_corrupt: bool,
+    /// Keep only the frames starting within this range of media time, measured from the start
+    /// of the input (or of the first input to [`concat()`](fn.concat.html)) (default `None`,
+    /// keeping every frame).  Dropped frames still count towards the media time of those
+    /// following them.
+    pub time_range: Option<Range<Duration>>,
+    /// The number of samples in each `raw_data_block`, which ADTS headers don't record, for
+    /// measuring `time_range` and modelling `FullnessRepair::Reservoir` (default
+    /// `FrameLength::Samples1024`)
+    pub frame_length: FrameLength,
+    /// The most frames by which consecutive inputs to [`concat()`](fn.concat.html) may overlap.
+    /// Where the first frames of an input have byte-identical payloads to the last frames of
+    /// the input before it, up to this many of them are dropped as duplicates (default `0`,
+    /// leaving every frame in place).
+    ///
+    /// Frames of digital silence are often identical to one another, so a window much larger
+    /// than the segmenter's real overlap may also drop some silence at a joint.
+    pub splice_duplicates: usize,
+}
+impl Default for RepairOptions {
+    fn default() -> Self {
+        RepairOptions {
+            skip_id3: true,
+            skip_junk: true,
+            trim_truncated: true,
+            crc: CrcRepair::Keep,
+            force_channels: None,
+            buffer_fullness: FullnessRepair::Keep,
+            crc_algorithm: &crc::DEFAULT,
+            drop_corrupt: false,
+            time_range: None,
+            frame_length: FrameLength::Samples1024,
+            splice_duplicates: 0,
+        }
+    }
+}
+
+/// Counts of what [`repair()`](fn.repair.html) did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// Frames written to the output
+    pub frames: u64,
+    /// Frames whose header was changed on the way to the output
+    pub frames_rewritten: u64,
+    pub id3_bytes_skipped: u64,
+    pub junk_bytes_skipped: u64,
+    pub truncated_bytes_trimmed: u64,
+    /// Frames dropped as duplicates at the joints between inputs to [`concat()`](fn.concat.html)
+    pub duplicate_frames_dropped: u64,
+    /// Frames dropped because their CRC didn't match, with `drop_corrupt` set
+    pub corrupt_frames_dropped: u64,
+    /// Frames dropped because they started outside of the `time_range`
+    pub frames_outside_range: u64,
+}
+
+/// The header values of a frame, as given to the closure passed to [`rewrite()`](fn.rewrite.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderFields {
+    pub config: AdtsStreamConfig,
+    /// The per-frame values; `crc` may be changed to add or remove the frame's CRC, but the
+    /// payload is copied untouched, so `number_of_raw_data_blocks` should be left alone
+    pub options: FrameOptions,
+}
+
+const READ_SIZE: usize = 64 * 1024;
+
+/// Copies ADTS data from `input` to `output`, applying the repairs selected in `options`.
+///
+/// Input is consumed incrementally and each frame is written as soon as it is complete, so this
+/// works as a filter over live pipes.  Memory use is bounded by the largest possible frame
+/// (8191 bytes) plus a fixed-size read buffer, however long the input.
+///
+/// ```rust
+/// use adts_reader::repair::{repair, RepairOptions};
+/// let input = b"junk\xff\xf1\x4c\x80\x01\x1f\xfc\x21\xff\xf1\x4c";
+/// let mut output = vec![];
+/// let report = repair(&input[..], &mut output, &RepairOptions::default()).unwrap();
+/// assert_eq!(output, b"\xff\xf1\x4c\x80\x01\x1f\xfc\x21");
+/// assert_eq!(report.junk_bytes_skipped, 4);
+/// assert_eq!(report.truncated_bytes_trimmed, 3);
+/// ```
+pub fn repair<R: io::Read, W: io::Write>(
+    input: R,
+    output: W,
+    options: &RepairOptions,
+) -> io::Result<RepairReport> {
+    run(std::iter::once(input), output, options, None)
+}
+
+/// Copies each of the `inputs` in turn to `output` as a single stream, applying the repairs
+/// selected in `options`, as for [`repair()`](fn.repair.html).
+///
+/// Each input is treated as a separate stream: an incomplete frame at the end of one is not
+/// completed by data from the next.  With `options.splice_duplicates` set, frames repeated at the
+/// start of an input from the end of the input before it are dropped, and counted in
+/// `RepairReport::duplicate_frames_dropped`.
+///
+/// ```rust
+/// use adts_reader::repair::{concat, RepairOptions};
+/// let a = b"\xff\xf1\x4c\x80\x01\x1f\xfc\x21";
+/// let b = b"\xff\xf1\x4c\x80\x01\x1f\xfc\x22";
+/// // segments overlapping by one frame
+/// let first = [&a[..], &b[..]].concat();
+/// let second = [&b[..], &a[..]].concat();
+/// let mut options = RepairOptions::default();
+/// options.splice_duplicates = 2;
+/// let mut output = vec![];
+/// let report = concat([&first[..], &second[..]], &mut output, &options).unwrap();
+/// assert_eq!(output, [&a[..], &b[..], &a[..]].concat());
+/// assert_eq!(report.duplicate_frames_dropped, 1);
+/// ```
+pub fn concat<I, R, W>(inputs: I, output: W, options: &RepairOptions) -> io::Result<RepairReport>
+where
+    I: IntoIterator<Item = R>,
+    R: io::Read,
+    W: io::Write,
+{
+    run(inputs, output, options, None)
+}
+
+/// Copies ADTS data from `input` to `output`, calling `edit` with the header values of each frame
+/// so that it may change any of them.
+///
+/// Frames whose values are changed are written with a new header, with `frame_length` and any
+/// CRC recalculated; payloads, unchanged frames, and any data that isn't part of a frame are
+/// copied untouched.  As for [`repair()`](fn.repair.html), the input is processed in a single
+/// streaming pass.  Fails with `io::ErrorKind::InvalidInput` if `edit` gives a value that can't
+/// be written (an out of range field, or a CRC on a frame of several `raw_data_block`s).
+///
+/// ```rust
+/// use adts_reader::repair::rewrite;
+/// use adts_reader::Originality;
+/// let input = b"\xff\xf1\x4c\x80\x01\x1f\xfc\x21";
+/// let mut output = vec![];
+/// let report = rewrite(&input[..], &mut output, |header| {
+///     header.config.originality = Originality::Copy;
+/// })
+/// .unwrap();
+/// assert_eq!(output, b"\xff\xf1\x4c\xa0\x01\x1f\xfc\x21");
+/// assert_eq!(report.frames_rewritten, 1);
+/// ```
+pub fn rewrite<R, W, F>(input: R, output: W, mut edit: F) -> io::Result<RepairReport>
+where
+    R: io::Read,
+    W: io::Write,
+    F: FnMut(&mut HeaderFields),
+{
+    let options = RepairOptions {
+        skip_id3: false,
+        skip_junk: false,
+        trim_truncated: false,
+        ..RepairOptions::default()
+    };
+    run(std::iter::once(input), output, &options, Some(&mut edit))
+}
+
+fn run<'a, I, R, W>(
+    inputs: I,
+    mut output: W,
+    options: &'a RepairOptions,
+    edit: Option<&'a mut dyn FnMut(&mut HeaderFields)>,
+) -> io::Result<RepairReport>
+where
+    I: IntoIterator<Item = R>,
+    R: io::Read,
+    W: io::Write,
+{
+    let mut repairer = Repairer {
+        options,
+        edit,
+        report: RepairReport::default(),
+        skip_remaining: 0,
+        reservoir: None,
+        recent: VecDeque::new(),
+        held: None,
+        elapsed: MediaTime::ZERO,
+    };
+    let mut buf = Vec::new();
+    let mut chunk = vec![0; READ_SIZE];
+    for mut input in inputs {
+        repairer.start_input();
+        loop {
+            let n = match input.read(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let eof = n == 0;
+            buf.extend_from_slice(&chunk[..n]);
+            let consumed = repairer.process(&buf, eof, &mut output)?;
+            buf.drain(..consumed);
+            if eof {
+                break;
+            }
+        }
+        repairer.end_splice(&mut output)?;
+        if !buf.is_empty() {
+            if options.trim_truncated {
+                repairer.report.truncated_bytes_trimmed += buf.len() as u64;
+            } else {
+                output.write_all(&buf)?;
+            }
+            buf.clear();
+        }
+        // an ID3 tag cut short by the end of an input doesn't continue into the next
+        repairer.skip_remaining = 0;
+    }
+    output.flush()?;
+    Ok(repairer.report)
+}
+
+struct Repairer<'a> {
+    options: &'a RepairOptions,
+    edit: Option<&'a mut dyn FnMut(&mut HeaderFields)>,
+    report: RepairReport,
+    skip_remaining: usize,
+    /// the model for `FullnessRepair::Reservoir`, created with the first frame
+    reservoir: Option<BitReservoir>,
+    /// payloads of the last `splice_duplicates` frames written, oldest first
+    recent: VecDeque<Vec<u8>>,
+    /// frames from the start of the current input, held back until it's known whether they
+    /// repeat `recent`; `None` when not at a joint
+    held: Option<Vec<Vec<u8>>>,
+    /// total duration of the complete frames found so far, whether written or dropped
+    elapsed: MediaTime,
+}
+impl<'a> Repairer<'a> {
+    /// Prepares for the next input, which is joined to any frames already written
+    fn start_input(&mut self) {
+        if self.options.splice_duplicates > 0 && !self.recent.is_empty() {
+            self.held = Some(vec![]);
+        }
+    }
+
+    /// Writes the frames held back at a joint, less those repeating the frames before the joint
+    fn end_splice<W: io::Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let held = match self.held.take() {
+            Some(held) => held,
+            None => return Ok(()),
+        };
+        let payload = |frame: &[u8]| {
+            let header = AdtsHeader::from_bytes(frame).expect("held frames are complete");
+            header.payload().expect("held frames are complete").to_vec()
+        };
+        // the longest run of held frames matching the same number of frames written last
+        let overlap = (1..=held.len().min(self.recent.len()))
+            .rev()
+            .find(|&n| {
+                let tail = self.recent.iter().skip(self.recent.len() - n);
+                held[..n]
+                    .iter()
+                    .zip(tail)
+                    .all(|(frame, recent)| payload(frame) == *recent)
+            })
+            .unwrap_or(0);
+        self.report.duplicate_frames_dropped += overlap as u64;
+        for frame in &held[overlap..] {
+            let header = AdtsHeader::from_bytes(frame).expect("held frames are complete");
+            self.emit_frame(&header, frame, out)?;
+        }
+        Ok(())
+    }
+
+    /// Handles as much of `buf` as possible, returning the number of bytes consumed.  Anything
+    /// left unconsumed once `eof` is true is an incomplete frame.
+    fn process<W: io::Write>(&mut self, buf: &[u8], eof: bool, out: &mut W) -> io::Result<usize> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            if self.skip_remaining > 0 {
+                let n = self.skip_remaining.min(buf.len() - pos);
+                self.skip_remaining -= n;
+                pos += n;
+                continue;
+            }
+            let rem = &buf[pos..];
+            if self.options.skip_id3 {
+                if id3::maybe_tag_prefix(rem) && !eof {
+                    break;
+                }
+                if let Some(len) = id3::tag_len(rem) {
+                    self.skip_remaining = len;
+                    self.report.id3_bytes_skipped += len as u64;
+                    continue;
+                }
+            }
+            if rem[0] == 0xff {
+                match AdtsHeader::from_bytes(rem) {
+                    Ok(header) if plausible(&header) => {
+                        let len = header.frame_len();
+                        if rem.len() < len {
+                            break;
+                        }
+                        self.write_frame(&header, &rem[..len], out)?;
+                        pos += len;
+                        continue;
+                    }
+                    Err(AdtsHeaderError::NotEnoughData { .. }) => break,
+                    _ => (),
+                }
+            }
+            // not the start of a frame; discard (or pass through) everything up to the next
+            // possible sync-word
+            let len = rem[1..]
+                .iter()
+                .position(|&b| b == 0xff)
+                .map_or(rem.len(), |p| p + 1);
+            if self.options.skip_junk {
+                self.report.junk_bytes_skipped += len as u64;
+            } else {
+                if self.held.as_ref().is_some_and(|held| !held.is_empty()) {
+                    self.end_splice(out)?;
+                }
+                out.write_all(&rem[..len])?;
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn write_frame<W: io::Write>(
+        &mut self,
+        header: &AdtsHeader<'_>,
+        frame: &[u8],
+        out: &mut W,
+    ) -> io::Result<()> {
+        let start = self.elapsed;
+        if let Some(duration) = header.frame_duration_of(self.options.frame_length) {
+            self.elapsed = self.elapsed + duration;
+        }
+        if let Some(range) = &self.options.time_range {
+            if !range.contains(&start.to_duration()) {
+                self.report.frames_outside_range += 1;
+                return Ok(());
+            }
+        }
+        if self.options.drop_corrupt
+            && header.protection() == ProtectionIndicator::CrcPresent
+            && header.verify_crc_with(self.options.crc_algorithm).is_err()
+        {
+            self.report.corrupt_frames_dropped += 1;
+            return Ok(());
+        }
+        if let Some(held) = self.held.as_mut() {
+            held.push(frame.to_vec());
+            if held.len() >= self.options.splice_duplicates {
+                self.end_splice(out)?;
+            }
+            return Ok(());
+        }
+        self.emit_frame(header, frame, out)
+    }
+
+    fn emit_frame<W: io::Write>(
+        &mut self,
+        header: &AdtsHeader<'_>,
+        frame: &[u8],
+        out: &mut W,
+    ) -> io::Result<()> {
+        if self.options.splice_duplicates > 0 {
+            if self.recent.len() == self.options.splice_duplicates {
+                self.recent.pop_front();
+            }
+            if let Ok(payload) = header.payload() {
+                self.recent.push_back(payload.to_vec());
+            }
+        }
+        self.report.frames += 1;
+        match self.rewrite(header)? {
+            Some(rewritten) => {
+                self.report.frames_rewritten += 1;
+                out.write_all(&rewritten)
+            }
+            None => out.write_all(frame),
+        }
+    }
+
+    fn rewrite(&mut self, header: &AdtsHeader<'_>) -> io::Result<Option<Vec<u8>>> {
+        let mut config = header.stream_config();
+        let mut frame_options = header.frame_options();
+        let mut changed = false;
+        if let Some(channels) = self.options.force_channels {
+            changed |= config.channel_configuration != channels;
+            config.channel_configuration = channels;
+        }
+        match self.options.crc {
+            CrcRepair::Keep => (),
+            CrcRepair::Strip => {
+                changed |= frame_options.crc;
+                frame_options.crc = false;
+            }
+            CrcRepair::Add => {
+                changed |= !frame_options.crc;
+                frame_options.crc = true;
+            }
+        }
+        let fullness = match self.options.buffer_fullness {
+            FullnessRepair::Keep => None,
+            FullnessRepair::Vbr => Some(0x7ff),
+            FullnessRepair::Reservoir { bitrate } => {
+                // the frame as it will be written, since CRC repairs may change its size
+                let crc_len = if frame_options.crc { 2 } else { 0 };
+                let len = match header.payload() {
+                    Ok(payload) => payload.len() + 7 + crc_len,
+                    Err(_) => return Ok(None),
+                };
+                self.reservoir
+                    .get_or_insert_with(|| BitReservoir::new(bitrate, self.options.frame_length))
+                    .fill(&config, header.number_of_raw_data_blocks_in_frame(), len)
+            }
+        };
+        if let Some(fullness) = fullness {
+            changed |= frame_options.buffer_fullness != fullness;
+            frame_options.buffer_fullness = fullness;
+        }
+        let mut edited = false;
+        if let Some(edit) = self.edit.as_mut() {
+            let mut fields = HeaderFields {
+                config,
+                options: frame_options.clone(),
+            };
+            edit(&mut fields);
+            edited = fields.config != config || fields.options != frame_options;
+            config = fields.config;
+            frame_options = fields.options;
+        }
+        if !changed && !edited {
+            return Ok(None);
+        }
+        let payload = match header.payload() {
+            Ok(payload) => payload,
+            Err(_) => return Ok(None),
+        };
+        let mut new_header = match encode_header(&config, &frame_options, payload.len()) {
+            Ok(new_header) => new_header,
+            Err(e) if edited => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?}", e),
+                ))
+            }
+            // frames which can't be repaired (e.g. multi-block frames, where adding a CRC is not
+            // supported) are passed through unchanged
+            Err(_) => return Ok(None),
+        };
+        new_header.fill_crc_with(payload, self.options.crc_algorithm);
+        let mut frame = Vec::with_capacity(new_header.len() + payload.len());
+        frame.extend_from_slice(&new_header);
+        frame.extend_from_slice(payload);
+        Ok(Some(frame))
+    }
+}
+
+/// Extra checks on a header beyond those done by `AdtsHeader::from_bytes()`, to reduce the
+/// chance of treating junk which happens to contain a sync-word as a frame
+fn plausible(header: &AdtsHeader<'_>) -> bool {
+    let layer = fields::LAYER.get(header.buf) as u8;
+    layer == 0 && header.sampling_frequency().freq().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+    use std::io::Read;
+
+    const CONFIG: AdtsStreamConfig = AdtsStreamConfig {
+        sampling_frequency: SamplingFrequency::Freq44100,
+        channel_configuration: ChannelConfiguration::Mono,
+        ..StreamBuilder::DEFAULT_CONFIG
+    };
+
+    /// `count` separate frames, the `i`th having the payload `[i, i]`
+    fn numbered_frames(count: usize, crc: bool) -> Vec<Vec<u8>> {
+        let stream = StreamBuilder::new()
+            .crc(crc)
+            .payload_len(2)
+            .frames(count, CONFIG)
+            .build();
+        stream
+            .chunks(stream.len() / count)
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+
+    #[test]
+    fn id3_junk_and_crc() {
+        let input = StreamBuilder::new()
+            .config(CONFIG)
+            .bytes(b"ID3\x04\x00\x00\x00\x00\x00\x02ab")
+            .crc(true)
+            .frame(&[1, 2, 3])
+            .bytes(b"\x00\xff\x00")
+            .crc(false)
+            .frame(&[4, 5])
+            .build();
+        let options = RepairOptions {
+            crc: CrcRepair::Strip,
+            ..RepairOptions::default()
+        };
+        let mut output = vec![];
+        let report = repair(&input[..], &mut output, &options).unwrap();
+        let expected = StreamBuilder::new()
+            .config(CONFIG)
+            .frame(&[1, 2, 3])
+            .frame(&[4, 5])
+            .build();
+        assert_eq!(output, expected);
+        assert_eq!(
+            report,
+            RepairReport {
+                frames: 2,
+                frames_rewritten: 1,
+                id3_bytes_skipped: 12,
+                junk_bytes_skipped: 3,
+                truncated_bytes_trimmed: 0,
+                duplicate_frames_dropped: 0,
+                corrupt_frames_dropped: 0,
+                frames_outside_range: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn long_junk_run() {
+        // many read-buffers worth of junk, with no sync-words, is discarded as it is read
+        let input = io::repeat(0x55).take(READ_SIZE as u64 * 10);
+        let mut output = vec![];
+        let report = repair(input, &mut output, &RepairOptions::default()).unwrap();
+        assert!(output.is_empty());
+        assert_eq!(report.junk_bytes_skipped, READ_SIZE as u64 * 10);
+    }
+
+    #[test]
+    fn force_channels_small_reads() {
+        struct Trickle<'a>(&'a [u8]);
+        impl<'a> io::Read for Trickle<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(3);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let input = StreamBuilder::new()
+            .config(CONFIG)
+            .frame(&[1; 20])
+            .crc(true)
+            .frame(&[2; 20])
+            .build();
+        let options = RepairOptions {
+            force_channels: Some(ChannelConfiguration::Stereo),
+            crc: CrcRepair::Add,
+            ..RepairOptions::default()
+        };
+        let mut output = vec![];
+        let report = repair(Trickle(&input), &mut output, &options).unwrap();
+        assert_eq!(report.frames, 2);
+        assert_eq!(report.frames_rewritten, 2);
+        let first = validate_frame_with(
+            &output,
+            &ValidateOptions {
+                allow_trailing_data: true,
+                ..ValidateOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(first.crc_verified);
+        assert_eq!(
+            first.header.channel_configuration(),
+            ChannelConfiguration::Stereo
+        );
+    }
+
+    #[test]
+    fn buffer_fullness() {
+        // 128 byte frames of 1024 samples at 44.1kHz make exactly 44100 bits/s
+        let sizes = [128, 128, 228, 128, 28];
+        let input = sizes
+            .iter()
+            .fold(StreamBuilder::new().config(CONFIG), |builder, &len| {
+                builder.frame(&vec![0; len - 7])
+            })
+            .build();
+        let fullness = |options: &RepairOptions| {
+            let mut output = vec![];
+            repair(&input[..], &mut output, options).unwrap();
+            AdtsFrameIter::new(&output)
+                .map(|h| h.unwrap().adts_buffer_fullness())
+                .collect::<Vec<_>>()
+        };
+        let options = RepairOptions {
+            buffer_fullness: FullnessRepair::Reservoir { bitrate: 44100 },
+            ..RepairOptions::default()
+        };
+        // a full reservoir of (6144 - 1024) bits is 160 units of 32 bits, which the large frame
+        // draws 800 bits from, and the small frame returns 800 bits to
+        assert_eq!(fullness(&options), vec![160, 160, 135, 135, 160]);
+        let options = RepairOptions {
+            buffer_fullness: FullnessRepair::Vbr,
+            ..RepairOptions::default()
+        };
+        assert_eq!(fullness(&options), vec![0x7ff; 5]);
+    }
+
+    #[test]
+    fn rewrite_headers() {
+        let input = StreamBuilder::new()
+            .config(CONFIG)
+            .bytes(b"ID3junk")
+            .crc(true)
+            .frame(&[1, 2, 3])
+            .crc(false)
+            .frame(&[4, 5])
+            .frame(&[6])
+            .truncate_last_frame()
+            .build();
+        let mut seen = vec![];
+        let mut output = vec![];
+        let report = rewrite(&input[..], &mut output, |header| {
+            seen.push(header.options.crc);
+            if header.options.crc {
+                header.config.private_bit = 1;
+            }
+        })
+        .unwrap();
+        assert_eq!(seen, vec![true, false]);
+        assert_eq!(report.frames, 2);
+        assert_eq!(report.frames_rewritten, 1);
+        // everything but the edited header is copied as it was
+        assert_eq!(output.len(), input.len());
+        assert_eq!(output[..7], input[..7]);
+        assert_eq!(output[16..], input[16..]);
+        let first = validate_frame_with(
+            &output[7..],
+            &ValidateOptions {
+                allow_trailing_data: true,
+                ..ValidateOptions::default()
+            },
+        )
+        .unwrap();
+        assert!(first.crc_verified);
+        assert_eq!(first.header.private_bit(), 1);
+
+        let err = rewrite(&input[..], io::sink(), |header| header.config.home = 2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn splice_duplicates() {
+        let frames = numbered_frames(8, false);
+        let segment = |range: std::ops::Range<usize>| frames[range].concat();
+        let options = RepairOptions {
+            splice_duplicates: 3,
+            ..RepairOptions::default()
+        };
+        // overlaps of two frames, one frame, and none, with a final input shorter than the window
+        let inputs = [segment(0..3), segment(1..5), segment(4..6), segment(6..8)];
+        let mut output = vec![];
+        let report = concat(inputs.iter().map(|i| &i[..]), &mut output, &options).unwrap();
+        assert_eq!(output, segment(0..8));
+        assert_eq!(report.frames, 8);
+        assert_eq!(report.duplicate_frames_dropped, 3);
+
+        // repeated frames which aren't at the end of the previous input are kept
+        let inputs = [segment(0..3), segment(1..2)];
+        let mut output = vec![];
+        let report = concat(inputs.iter().map(|i| &i[..]), &mut output, &options).unwrap();
+        assert_eq!(output, [segment(0..3), segment(1..2)].concat());
+        assert_eq!(report.duplicate_frames_dropped, 0);
+
+        // without the option, every frame is kept, and a truncated frame ends with its input
+        let inputs = [segment(0..2), segment(1..2)[..5].to_vec(), segment(1..3)];
+        let mut output = vec![];
+        let report = concat(
+            inputs.iter().map(|i| &i[..]),
+            &mut output,
+            &RepairOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(output, [segment(0..2), segment(1..3)].concat());
+        assert_eq!(report.duplicate_frames_dropped, 0);
+        assert_eq!(report.truncated_bytes_trimmed, 5);
+    }
+
+    #[test]
+    fn filters() {
+        let mut frames = numbered_frames(10, true);
+        *frames[4].last_mut().unwrap() ^= 1;
+        let input = frames.concat();
+        // frames at 44.1kHz last 23.2ms, so frames 3 to 6 start within the range
+        let options = RepairOptions {
+            drop_corrupt: true,
+            time_range: Some(Duration::from_millis(50)..Duration::from_millis(150)),
+            ..RepairOptions::default()
+        };
+        let mut output = vec![];
+        let report = repair(&input[..], &mut output, &options).unwrap();
+        assert_eq!(output, [&frames[3][..], &frames[5], &frames[6]].concat());
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.frames_outside_range, 6);
+        assert_eq!(report.corrupt_frames_dropped, 1);
+
+        // frames of 960 samples last 21.8ms, so the third starts before the range
+        let range = Duration::from_millis(45)..Duration::from_millis(60);
+        let options = RepairOptions {
+            time_range: Some(range.clone()),
+            ..RepairOptions::default()
+        };
+        let report = repair(&input[..], io::sink(), &options).unwrap();
+        assert_eq!(report.frames, 1);
+        let options = RepairOptions {
+            time_range: Some(range),
+            frame_length: FrameLength::Samples960,
+            ..RepairOptions::default()
+        };
+        let report = repair(&input[..], io::sink(), &options).unwrap();
+        assert_eq!(report.frames, 0);
+
+        // corrupt frames are kept by default
+        let mut output = vec![];
+        repair(&input[..], &mut output, &RepairOptions::default()).unwrap();
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,106 @@
+//! Versioned serialisation of the crate's report types, for consumption by external QC systems.
+//!
+//! Available when the `serde` feature is enabled.
+//!
+//! The reports ([`HealthReport`](../analysis/struct.HealthReport.html),
+//! [`DiffReport`](../compare/struct.DiffReport.html),
+//! [`ParserStats`](../struct.ParserStats.html), and lists of
+//! [`Violation`](../conformance/enum.Violation.html)s) can be wrapped in a
+//! [`Versioned`](struct.Versioned.html) envelope recording the
+//! [`FORMAT_VERSION`](constant.FORMAT_VERSION.html) they were written with.
+//!
+//! The format version only changes when a property is removed, renamed, or changes meaning.
+//! Additions -- new properties, and new kinds of anomaly such as new
+//! [`Warning`](../analysis/enum.Warning.html) variants -- may arrive in any release without a
+//! version change, so consumers should ignore properties they don't recognise, and treat unknown
+//! anomaly kinds as a generic failure rather than rejecting the report.
+//!
+//! With the `schema` feature enabled, [`schema()`](fn.schema.html) gives a JSON Schema describing
+//! the envelope and report, for use in validating reports downstream.
+
+use serde::{Deserialize, Serialize};
+
+/// The version of the serialised report format produced by this release of the crate.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A report, together with the version of the format it's serialised in.
+///
+/// ```rust
+/// use adts_reader::analysis::{analyze, AnalysisOptions};
+/// use adts_reader::report::Versioned;
+/// let report = analyze(&[], &AnalysisOptions::default());
+/// let json = serde_json::to_string(&Versioned::new(report)).unwrap();
+/// assert!(json.starts_with(r#"{"format_version":1,"report":{"frames":0,"#));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Versioned<T> {
+    pub format_version: u32,
+    pub report: T,
+}
+impl<T> Versioned<T> {
+    /// Wraps the given report, marking it with the current `FORMAT_VERSION`
+    pub fn new(report: T) -> Versioned<T> {
+        Versioned {
+            format_version: FORMAT_VERSION,
+            report,
+        }
+    }
+}
+
+/// Produces a JSON Schema for a [`Versioned`](struct.Versioned.html) report of the given type.
+///
+/// Available when the `schema` feature is enabled.
+///
+/// ```rust
+/// use adts_reader::analysis::HealthReport;
+/// let schema = adts_reader::report::schema::<HealthReport>();
+/// println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+/// ```
+#[cfg(feature = "schema")]
+pub fn schema<T: schemars::JsonSchema>() -> serde_json::Value {
+    let mut schema = schemars::schema_for!(Versioned<T>);
+    // pin the version, so that validation rejects reports in other formats
+    if let Some(ref mut object) = schema.schema.object {
+        if let Some(schemars::schema::Schema::Object(version)) =
+            object.properties.get_mut("format_version")
+        {
+            version.const_value = Some(FORMAT_VERSION.into());
+        }
+    }
+    serde_json::to_value(schema).expect("schema serialisation failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{HealthReport, Warning};
+
+    #[test]
+    fn round_trip() {
+        let report = Versioned::new(HealthReport {
+            frames: 3,
+            warnings: vec![Warning::CrcAbsent { frames: 3 }],
+            ..HealthReport::default()
+        });
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Versioned<HealthReport>>(&json).unwrap(),
+            report
+        );
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema_pins_version() {
+        let health = schema::<HealthReport>();
+        let properties = &health["properties"];
+        assert_eq!(properties["format_version"]["const"], FORMAT_VERSION);
+        assert!(health["definitions"]["HealthReport"]["properties"]["warnings"].is_object());
+        assert!(health["definitions"]["Warning"].is_object());
+        let stats = schema::<crate::ParserStats>();
+        assert!(stats["definitions"]["ParserStats"]["properties"]
+            .get("last_error_at")
+            .is_none());
+    }
+}
@@ -0,0 +1,639 @@
+//! Carriage of AAC over RTP in the `mpeg4-generic` payload format of RFC 3640, as negotiated by
+//! SIP and RTSP applications.
+//!
+//! Each RTP payload starts with a list of AU-headers, giving the size of each access unit (the
+//! payload of an ADTS frame) that follows.  Several small access units may be aggregated into a
+//! single packet, and an access unit too large for one packet is fragmented across several.
+//! [`RtpPacketizer`](struct.RtpPacketizer.html) produces such payloads from the frames found by
+//! an `AdtsParser`, and [`RtpDepacketizer`](struct.RtpDepacketizer.html) reassembles the access
+//! units from received payloads, which [`AccessUnit::to_adts()`](struct.AccessUnit.html#method.to_adts)
+//! can frame as ADTS once more.
+//!
+//! Interleaving isn't supported, so the AU-index of every access unit must be zero.
+//!
+//! ```rust
+//! use adts_reader::AdtsParser;
+//! use adts_reader::rtp::{AuHeaderConfig, RtpDepacketizer, RtpPacketizer};
+//!
+//! let mut payloads = vec![];
+//! let packetizer = RtpPacketizer::new(AuHeaderConfig::AAC_HBR, 1400, |payload| {
+//!     payloads.push((payload.data.to_vec(), payload.timestamp));
+//! });
+//! let mut parser = AdtsParser::new(packetizer);
+//! parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+//! parser.consumer.flush();
+//! drop(parser);
+//! // a 16 bit AU-header, giving a size of 1 and an index of 0
+//! assert_eq!(payloads, [(vec![0x00, 0x10, 0x00, 0x08, 0x21], 0)]);
+//!
+//! let mut depacketizer = RtpDepacketizer::new(AuHeaderConfig::AAC_HBR);
+//! let (data, timestamp) = &payloads[0];
+//! depacketizer
+//!     .push(0, &adts_reader::rtp::RtpPayload { data, timestamp: *timestamp, marker: true }, |au| {
+//!         assert_eq!(au.data, [0x21]);
+//!     })
+//!     .unwrap();
+//! ```
+
+use crate::asc::{AscError, AudioSpecificConfig};
+use crate::bits::{BitReader, BitWriter};
+use crate::{
+    write_frame, AdtsConsumer2, AdtsStreamConfig, FrameContext, FrameLength, FrameOptions,
+    ProtectionIndicator, WriteError,
+};
+
+/// The sizes of the fields of each AU-header, as given by the `sizeLength`, `indexLength` and
+/// `indexDeltaLength` parameters of the SDP `fmtp` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuHeaderConfig {
+    /// Bits giving the size of each access unit
+    pub size_length: u8,
+    /// Bits giving the AU-index of the first access unit in a payload
+    pub index_length: u8,
+    /// Bits giving the AU-index-delta of each access unit after the first
+    pub index_delta_length: u8,
+}
+impl AuHeaderConfig {
+    /// The `AAC-hbr` mode, allowing access units of up to 8191 bytes
+    pub const AAC_HBR: AuHeaderConfig = AuHeaderConfig {
+        size_length: 13,
+        index_length: 3,
+        index_delta_length: 3,
+    };
+    /// The `AAC-lbr` mode, allowing access units of up to 63 bytes
+    pub const AAC_LBR: AuHeaderConfig = AuHeaderConfig {
+        size_length: 6,
+        index_length: 2,
+        index_delta_length: 2,
+    };
+
+    /// The largest access unit whose size the AU-header can give
+    pub fn max_access_unit_len(&self) -> usize {
+        (1 << self.size_length) - 1
+    }
+
+    /// The parameters of the SDP `fmtp` attribute describing a stream with the given
+    /// configuration, such as
+    /// `streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=1190`
+    pub fn fmtp(&self, asc: &AudioSpecificConfig) -> Result<String, AscError> {
+        let config: String = asc
+            .to_bytes()?
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let mode = match *self {
+            AuHeaderConfig::AAC_HBR => "AAC-hbr",
+            AuHeaderConfig::AAC_LBR => "AAC-lbr",
+            _ => "generic",
+        };
+        Ok(format!(
+            "streamtype=5;profile-level-id=1;mode={};sizelength={};indexlength={};indexdeltalength={};config={}",
+            mode, self.size_length, self.index_length, self.index_delta_length, config
+        ))
+    }
+
+    /// Bits in the AU-headers of a payload holding the given number of access units
+    fn headers_bits(&self, count: usize) -> usize {
+        let first = usize::from(self.size_length + self.index_length);
+        let rest = usize::from(self.size_length + self.index_delta_length);
+        first + rest * count.saturating_sub(1)
+    }
+}
+
+/// Error indicating that an access unit couldn't be packetized, or a payload couldn't be
+/// depacketized.
+#[derive(Debug, PartialEq)]
+pub enum RtpError {
+    /// The access unit is larger than the AU-header's `size_length` can describe
+    AccessUnitTooLarge { len: usize, max: usize },
+    /// The payload ended before the AU-headers or the access units they describe
+    Truncated,
+    /// An AU-index or AU-index-delta was non-zero, signalling interleaving, which isn't supported
+    Interleaved,
+    /// Packets carrying fragments of an access unit were lost, so the fragments received were
+    /// discarded
+    MissingFragment,
+}
+
+/// The payload of an RTP packet, without the RTP header.
+#[derive(Debug, PartialEq)]
+pub struct RtpPayload<'a> {
+    /// The AU-headers section, followed by the access units or fragment
+    pub data: &'a [u8],
+    /// The RTP timestamp of the first access unit, in units of the sampling frequency
+    pub timestamp: u32,
+    /// The RTP header's marker bit, set on each packet which ends an access unit
+    pub marker: bool,
+}
+
+/// Splits the frames of an ADTS stream into RTP payloads, passing each to the given closure.
+///
+/// Timestamps start at zero, counting samples at the stream's sampling frequency; RTP senders
+/// add a random offset of their own.  Frames with several `raw_data_block`s give an access unit
+/// for each block, which needs the frames to have CRCs (or the `elements` feature).  Frames which
+/// can't be packetized are counted by [`dropped()`](#method.dropped).
+pub struct RtpPacketizer<F>
+where
+    F: FnMut(RtpPayload<'_>),
+{
+    on_payload: F,
+    config: AuHeaderConfig,
+    max_payload_len: usize,
+    max_access_units: usize,
+    frame_length: FrameLength,
+    /// the access units awaiting aggregation, one after another
+    pending: Vec<u8>,
+    pending_sizes: Vec<usize>,
+    pending_timestamp: u32,
+    /// the timestamp of the next access unit from the parser
+    timestamp: u32,
+    dropped: u64,
+}
+impl<F> RtpPacketizer<F>
+where
+    F: FnMut(RtpPayload<'_>),
+{
+    /// Creates a packetizer which produces payloads of at most `max_payload_len` bytes, each
+    /// holding a single access unit, or a fragment of one
+    pub fn new(config: AuHeaderConfig, max_payload_len: usize, on_payload: F) -> RtpPacketizer<F> {
+        RtpPacketizer {
+            on_payload,
+            config,
+            max_payload_len,
+            max_access_units: 1,
+            frame_length: FrameLength::default(),
+            pending: vec![],
+            pending_sizes: vec![],
+            pending_timestamp: 0,
+            timestamp: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Aggregates up to the given number of consecutive access units into each payload, as
+    /// space allows (by default, 1).  This saves on packet overheads, but delays each payload
+    /// until its last access unit is available.
+    pub fn with_aggregation(mut self, max_access_units: usize) -> Self {
+        self.max_access_units = max_access_units.max(1);
+        self
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to calculate timestamps (by
+    /// default, 1024)
+    pub fn with_frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Number of frames which couldn't be packetized
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Packetizes the given access unit, having the given RTP timestamp.  Aggregated access units
+    /// must be consecutive, as their timestamps are implied by that of the first.
+    pub fn push(&mut self, access_unit: &[u8], timestamp: u32) -> Result<(), RtpError> {
+        let max = self.config.max_access_unit_len();
+        if access_unit.len() > max {
+            return Err(RtpError::AccessUnitTooLarge {
+                len: access_unit.len(),
+                max,
+            });
+        }
+        let count = self.pending_sizes.len() + 1;
+        let len = 2
+            + self.config.headers_bits(count).div_ceil(8)
+            + self.pending.len()
+            + access_unit.len();
+        if !self.pending_sizes.is_empty()
+            && (count > self.max_access_units || len > self.max_payload_len)
+        {
+            self.flush();
+        }
+        if self.pending_sizes.is_empty() {
+            self.pending_timestamp = timestamp;
+        }
+        self.pending.extend_from_slice(access_unit);
+        self.pending_sizes.push(access_unit.len());
+        if self.pending_sizes.len() >= self.max_access_units {
+            self.flush();
+        }
+        Ok(())
+    }
+
+    /// Passes on any access units awaiting aggregation, as at the end of the stream
+    pub fn flush(&mut self) {
+        if self.pending_sizes.is_empty() {
+            return;
+        }
+        let headers_len = 2 + self
+            .config
+            .headers_bits(self.pending_sizes.len())
+            .div_ceil(8);
+        let [size] = self.pending_sizes[..] else {
+            self.emit(&self.pending_sizes.clone(), 0, self.pending.len(), true);
+            return self.clear();
+        };
+        // a single access unit, which may need fragmenting
+        let space = self.max_payload_len.saturating_sub(headers_len).max(1);
+        let mut start = 0;
+        while start < size {
+            let end = size.min(start + space);
+            self.emit(&[size], start, end, end == size);
+            start = end;
+        }
+        if size == 0 {
+            self.emit(&[0], 0, 0, true);
+        }
+        self.clear();
+    }
+
+    /// Passes on a payload of the given AU-headers, followed by the given range of the pending
+    /// access unit data
+    fn emit(&mut self, sizes: &[usize], start: usize, end: usize, marker: bool) {
+        let mut w = BitWriter::new();
+        w.write(16, self.config.headers_bits(sizes.len()) as u32);
+        for (i, &size) in sizes.iter().enumerate() {
+            w.write(self.config.size_length.into(), size as u32);
+            let index_bits = if i == 0 {
+                self.config.index_length
+            } else {
+                self.config.index_delta_length
+            };
+            w.write(index_bits.into(), 0);
+        }
+        let mut data = w.into_bytes();
+        data.extend_from_slice(&self.pending[start..end]);
+        (self.on_payload)(RtpPayload {
+            data: &data,
+            timestamp: self.pending_timestamp,
+            marker,
+        });
+    }
+
+    fn clear(&mut self) {
+        self.pending.clear();
+        self.pending_sizes.clear();
+    }
+}
+impl<F> AdtsConsumer2 for RtpPacketizer<F>
+where
+    F: FnMut(RtpPayload<'_>),
+{
+    fn on_config(&mut self, _config: AdtsStreamConfig, _protection: ProtectionIndicator) {
+        // access units of different configurations can't share a payload
+        self.flush();
+    }
+
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        let blocks = match frame.header.raw_data_blocks() {
+            Ok(blocks) => blocks,
+            Err(_) => {
+                self.dropped += 1;
+                return;
+            }
+        };
+        let timestamp = self.timestamp;
+        for block in blocks {
+            if self.push(block.data, self.timestamp).is_err() {
+                self.dropped += 1;
+                self.timestamp = timestamp.wrapping_add(
+                    frame.header.number_of_raw_data_blocks_in_frame() as u32
+                        * self.frame_length.samples() as u32,
+                );
+                return;
+            }
+            self.timestamp = self
+                .timestamp
+                .wrapping_add(self.frame_length.samples() as u32);
+        }
+    }
+}
+
+/// An access unit reassembled by [`RtpDepacketizer`](struct.RtpDepacketizer.html).
+#[derive(Debug, PartialEq)]
+pub struct AccessUnit<'a> {
+    /// The access unit, which is the payload of an ADTS frame
+    pub data: &'a [u8],
+    /// The RTP timestamp of the access unit
+    pub timestamp: u32,
+}
+impl AccessUnit<'_> {
+    /// Frames the access unit as ADTS, with the given configuration (as may be found from the
+    /// `config` parameter of the SDP `fmtp` attribute, with
+    /// [`AudioSpecificConfig::to_adts_config()`](../asc/struct.AudioSpecificConfig.html#method.to_adts_config))
+    pub fn to_adts(&self, config: &AdtsStreamConfig) -> Result<Vec<u8>, WriteError> {
+        write_frame(config, self.data, &FrameOptions::default())
+    }
+}
+
+/// A partly received fragmented access unit
+struct Fragment {
+    data: Vec<u8>,
+    size: usize,
+    timestamp: u32,
+}
+
+/// Reassembles access units from the payloads of received RTP packets.
+pub struct RtpDepacketizer {
+    config: AuHeaderConfig,
+    frame_length: FrameLength,
+    fragment: Option<Fragment>,
+    /// the sequence number and timestamp of the previous packet
+    last: Option<(u16, u32)>,
+}
+impl RtpDepacketizer {
+    pub fn new(config: AuHeaderConfig) -> RtpDepacketizer {
+        RtpDepacketizer {
+            config,
+            frame_length: FrameLength::default(),
+            fragment: None,
+            last: None,
+        }
+    }
+
+    /// Sets the number of samples per access unit used to calculate the timestamps of
+    /// aggregated access units after the first (by default, 1024)
+    pub fn with_frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Passes each access unit completed by the given payload, from the RTP packet with the given
+    /// sequence number, to `on_access_unit`.  Packets must be given in sequence order; a gap in
+    /// the sequence numbers discards any partly received access unit.
+    pub fn push(
+        &mut self,
+        sequence_number: u16,
+        payload: &RtpPayload<'_>,
+        mut on_access_unit: impl FnMut(AccessUnit<'_>),
+    ) -> Result<(), RtpError> {
+        let (continuous, same_timestamp) = match self.last {
+            Some((seq, ts)) => (
+                seq.wrapping_add(1) == sequence_number,
+                ts == payload.timestamp,
+            ),
+            None => (true, false),
+        };
+        self.last = Some((sequence_number, payload.timestamp));
+        let (sizes, data) = self.parse(payload.data)?;
+        if let Some(mut fragment) = self.fragment.take() {
+            if continuous && fragment.timestamp == payload.timestamp && sizes == [fragment.size] {
+                let needed = fragment.size - fragment.data.len();
+                if data.len() > needed {
+                    return Err(RtpError::Truncated);
+                }
+                fragment.data.extend_from_slice(data);
+                if data.len() == needed {
+                    on_access_unit(AccessUnit {
+                        data: &fragment.data,
+                        timestamp: fragment.timestamp,
+                    });
+                } else {
+                    self.fragment = Some(fragment);
+                }
+                return Ok(());
+            }
+            // the rest of the fragmented access unit was lost
+            self.accept(payload, &sizes, data, same_timestamp, &mut on_access_unit)?;
+            return Err(RtpError::MissingFragment);
+        }
+        if !continuous && same_timestamp {
+            // possibly the continuation of a fragmented access unit whose start was lost
+            if let [size] = sizes[..] {
+                if size > data.len() {
+                    return Err(RtpError::MissingFragment);
+                }
+            }
+        }
+        self.accept(payload, &sizes, data, same_timestamp, &mut on_access_unit)
+    }
+
+    /// Handles a payload which doesn't continue a fragmented access unit
+    fn accept(
+        &mut self,
+        payload: &RtpPayload<'_>,
+        sizes: &[usize],
+        data: &[u8],
+        same_timestamp: bool,
+        on_access_unit: &mut impl FnMut(AccessUnit<'_>),
+    ) -> Result<(), RtpError> {
+        if let [size] = sizes[..] {
+            if size > data.len() {
+                if same_timestamp {
+                    // the later fragment of an access unit already abandoned
+                    return Err(RtpError::MissingFragment);
+                }
+                self.fragment = Some(Fragment {
+                    data: data.to_vec(),
+                    size,
+                    timestamp: payload.timestamp,
+                });
+                return Ok(());
+            }
+        }
+        if sizes.iter().sum::<usize>() > data.len() {
+            return Err(RtpError::Truncated);
+        }
+        let mut data = data;
+        let mut timestamp = payload.timestamp;
+        for &size in sizes {
+            let (access_unit, rest) = data.split_at(size);
+            on_access_unit(AccessUnit {
+                data: access_unit,
+                timestamp,
+            });
+            data = rest;
+            timestamp = timestamp.wrapping_add(self.frame_length.samples() as u32);
+        }
+        Ok(())
+    }
+
+    /// Reads the AU-headers at the start of the payload, giving the size of each access unit,
+    /// and the data following the headers
+    fn parse<'a>(&self, payload: &'a [u8]) -> Result<(Vec<usize>, &'a [u8]), RtpError> {
+        let mut r = BitReader::new(payload);
+        let headers_bits = r.read(16).ok_or(RtpError::Truncated)? as usize;
+        let headers_end = 2 + headers_bits.div_ceil(8);
+        if payload.len() < headers_end {
+            return Err(RtpError::Truncated);
+        }
+        let mut sizes = vec![];
+        while r.position() < 16 + headers_bits {
+            let size = r
+                .read(self.config.size_length.into())
+                .ok_or(RtpError::Truncated)?;
+            let index_bits = if sizes.is_empty() {
+                self.config.index_length
+            } else {
+                self.config.index_delta_length
+            };
+            if r.read(index_bits.into()).ok_or(RtpError::Truncated)? != 0 {
+                return Err(RtpError::Interleaved);
+            }
+            sizes.push(size as usize);
+        }
+        if r.position() != 16 + headers_bits || sizes.is_empty() {
+            return Err(RtpError::Truncated);
+        }
+        Ok((sizes, &payload[headers_end..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::AdtsParser;
+
+    type Payloads = Vec<(Vec<u8>, u32, bool)>;
+    type AccessUnits = Vec<(Vec<u8>, u32)>;
+
+    /// Packetizes the given stream, and reassembles it
+    fn round_trip(
+        data: &[u8],
+        max_payload_len: usize,
+        aggregation: usize,
+    ) -> (Payloads, AccessUnits) {
+        let mut payloads = vec![];
+        let packetizer = RtpPacketizer::new(AuHeaderConfig::AAC_HBR, max_payload_len, |p| {
+            payloads.push((p.data.to_vec(), p.timestamp, p.marker));
+        })
+        .with_aggregation(aggregation);
+        let mut parser = AdtsParser::new(packetizer);
+        parser.push(data);
+        parser.consumer.flush();
+        assert_eq!(parser.consumer.dropped(), 0);
+        drop(parser);
+
+        let mut depacketizer = RtpDepacketizer::new(AuHeaderConfig::AAC_HBR);
+        let mut access_units = vec![];
+        for (seq, (data, timestamp, marker)) in payloads.iter().enumerate() {
+            let payload = RtpPayload {
+                data,
+                timestamp: *timestamp,
+                marker: *marker,
+            };
+            depacketizer
+                .push(seq as u16, &payload, |au| {
+                    access_units.push((au.data.to_vec(), au.timestamp))
+                })
+                .unwrap();
+        }
+        (payloads, access_units)
+    }
+
+    #[test]
+    fn aggregation_and_fragmentation() {
+        // four frames with 20 byte payloads
+        let data = StreamBuilder::new()
+            .payload_len(20)
+            .frames(4, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let expected: Vec<_> = (0..4)
+            .map(|i| {
+                let frame = &data[i * 27..];
+                let payload = crate::AdtsHeader::from_bytes(frame)
+                    .unwrap()
+                    .payload()
+                    .unwrap();
+                (payload.to_vec(), i as u32 * 1024)
+            })
+            .collect();
+
+        let (payloads, access_units) = round_trip(&data, 1400, 1);
+        assert_eq!(payloads.len(), 4);
+        assert_eq!(payloads[1].0[..4], [0x00, 0x10, 20 >> 5, 20 << 3]);
+        assert_eq!(access_units, expected);
+
+        // three access units fit in 2 + 6 + 60 bytes, so the fourth goes in a second payload
+        let (payloads, access_units) = round_trip(&data, 70, 4);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0].0[..2], [0x00, 0x30]);
+        assert_eq!(payloads[0].0.len(), 68);
+        assert_eq!(payloads[1].1, 3072);
+        assert_eq!(access_units, expected);
+
+        // each access unit split into fragments of at most 8 bytes
+        let (payloads, access_units) = round_trip(&data, 12, 1);
+        assert_eq!(payloads.len(), 12);
+        let markers: Vec<_> = payloads[..3].iter().map(|p| p.2).collect();
+        assert_eq!(markers, [false, false, true]);
+        assert_eq!(access_units, expected);
+        let config = StreamBuilder::DEFAULT_CONFIG;
+        let au = AccessUnit {
+            data: &access_units[0].0,
+            timestamp: 0,
+        };
+        assert_eq!(au.to_adts(&config).unwrap(), &data[..27]);
+    }
+
+    #[test]
+    fn lost_fragments() {
+        let data = StreamBuilder::new()
+            .payload_len(20)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let (payloads, _) = round_trip(&data, 12, 1);
+        let mut depacketizer = RtpDepacketizer::new(AuHeaderConfig::AAC_HBR);
+        let mut results = vec![];
+        let mut timestamps = vec![];
+        // the second fragment of the second access unit is lost
+        for (seq, (data, timestamp, marker)) in payloads.iter().enumerate() {
+            if seq == 4 {
+                continue;
+            }
+            let payload = RtpPayload {
+                data,
+                timestamp: *timestamp,
+                marker: *marker,
+            };
+            results
+                .push(depacketizer.push(seq as u16, &payload, |au| timestamps.push(au.timestamp)));
+        }
+        assert_eq!(timestamps, [0, 2048]);
+        assert_eq!(results[4], Err(RtpError::MissingFragment));
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        let mut depacketizer = RtpDepacketizer::new(AuHeaderConfig::AAC_HBR);
+        let push = |depacketizer: &mut RtpDepacketizer, data: &[u8]| {
+            let payload = RtpPayload {
+                data,
+                timestamp: 0,
+                marker: true,
+            };
+            depacketizer.push(0, &payload, |_| ())
+        };
+        assert_eq!(push(&mut depacketizer, &[0x00]), Err(RtpError::Truncated));
+        assert_eq!(
+            push(&mut depacketizer, &[0x00, 0x10, 0x00, 0x09, 0x21]),
+            Err(RtpError::Interleaved)
+        );
+        // two access units said to total 3 bytes, with only 2 present
+        assert_eq!(
+            push(
+                &mut depacketizer,
+                &[0x00, 0x20, 0x00, 0x08, 0x00, 0x10, 1, 2]
+            ),
+            Err(RtpError::Truncated)
+        );
+
+        let mut packetizer = RtpPacketizer::new(AuHeaderConfig::AAC_LBR, 1400, |_| ());
+        assert_eq!(
+            packetizer.push(&[0; 64], 0),
+            Err(RtpError::AccessUnitTooLarge { len: 64, max: 63 })
+        );
+    }
+
+    #[test]
+    fn fmtp() {
+        let asc = AudioSpecificConfig::parse(&[0x11, 0x90]).unwrap();
+        assert_eq!(
+            AuHeaderConfig::AAC_HBR.fmtp(&asc).unwrap(),
+            "streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=1190"
+        );
+    }
+}
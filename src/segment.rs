@@ -0,0 +1,154 @@
+//! Help for choosing segment boundaries aligned to media time.
+//!
+//! Every AAC frame is independently decodable, so a segment may start at any frame; segmenters
+//! still usually want boundaries at (or just before) whole seconds of media time.  Frame start
+//! times are compared against the target using integer sample counts, never floating point, so
+//! the same frame is chosen however long the stream.
+
+use crate::{AdtsHeader, FrameLength, MediaTime};
+use std::time::Duration;
+
+/// The frame chosen by [`frame_at_or_before()`](fn.frame_at_or_before.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundaryFrame {
+    /// Zero-based index of the frame within the buffer
+    pub index: u64,
+    /// Byte offset of the frame within the buffer
+    pub offset: usize,
+    /// The start time of the frame
+    pub start: MediaTime,
+    /// The sampling frequency of the frame
+    pub sample_rate: u32,
+}
+
+/// Finds the last frame in the given buffer of ADTS data whose start time is not later than
+/// `target`, where the first frame starts at time zero.
+///
+/// Returns `None` if the buffer holds no frames.  If the target is beyond the end of the data,
+/// the final frame is returned.  Scanning stops at the first data which is not a valid frame, or
+/// at a frame with a reserved sampling frequency (whose duration can't be known).
+///
+/// ```rust
+/// use adts_reader::segment::frame_at_or_before;
+/// use std::time::Duration;
+/// # let stream: Vec<u8> = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21].repeat(100);
+/// // 48kHz frames of 1024 samples; frame 46 starts at 0.98133..s and frame 47 at 1.00266..s
+/// let frame = frame_at_or_before(&stream, Duration::from_secs(1)).unwrap();
+/// assert_eq!(frame.index, 46);
+/// ```
+pub fn frame_at_or_before(buf: &[u8], target: Duration) -> Option<BoundaryFrame> {
+    frame_at_or_before_with(buf, target, FrameLength::Samples1024)
+}
+
+/// As for [`frame_at_or_before()`](fn.frame_at_or_before.html), for streams whose
+/// `raw_data_block`s have the given number of samples.
+pub fn frame_at_or_before_with(
+    buf: &[u8],
+    target: Duration,
+    frame_length: FrameLength,
+) -> Option<BoundaryFrame> {
+    // targets too distant to represent are later than any frame could be
+    let target = MediaTime::from_duration(target);
+    let mut found = None;
+    let mut pos = 0;
+    let mut index = 0;
+    let mut elapsed = MediaTime::ZERO;
+    while pos < buf.len() {
+        let header = match AdtsHeader::from_bytes(&buf[pos..]) {
+            Ok(header) if header.payload().is_ok() => header,
+            _ => break,
+        };
+        let rate = match header.sampling_frequency().freq() {
+            Some(rate) => rate,
+            None => break,
+        };
+        if matches!(target, Some(target) if elapsed > target) {
+            break;
+        }
+        found = Some(BoundaryFrame {
+            index,
+            offset: pos,
+            start: elapsed,
+            sample_rate: rate,
+        });
+        let blocks = header.number_of_raw_data_blocks_in_frame();
+        elapsed = elapsed + MediaTime::from_blocks_of(blocks.into(), frame_length, rate);
+        index += 1;
+        pos += header.frame_len();
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    /// `count` 8 byte frames at the given sampling frequency
+    fn frames(freq: SamplingFrequency, count: usize) -> StreamBuilder {
+        let config = AdtsStreamConfig {
+            sampling_frequency: freq,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        StreamBuilder::new().payload_len(1).frames(count, config)
+    }
+
+    #[test]
+    fn exact_boundaries() {
+        // at 8kHz each frame is exactly 128ms, so frame 125 starts at exactly 16s
+        let data = frames(SamplingFrequency::Freq8000, 200).build();
+        let at = |nanos| frame_at_or_before(&data, Duration::from_nanos(nanos)).unwrap();
+        assert_eq!(at(16_000_000_000).index, 125);
+        assert_eq!(at(16_000_000_000).offset, 125 * 8);
+        assert_eq!(at(15_999_999_999).index, 124);
+        assert_eq!(at(0).index, 0);
+        // beyond the end
+        assert_eq!(at(u64::MAX).index, 199);
+        assert_eq!(frame_at_or_before(&[], Duration::ZERO), None);
+    }
+
+    #[test]
+    fn no_float_drift() {
+        // at 44.1kHz, frame 11025 starts at exactly 256s (11025 * 1024 / 44100), a boundary
+        // which accumulated floating point frame durations tend to land either side of
+        let data = frames(SamplingFrequency::Freq44100, 11100).build();
+        let frame = frame_at_or_before(&data, Duration::from_secs(256)).unwrap();
+        assert_eq!(frame.index, 11025);
+        assert_eq!(frame.start, MediaTime::new(11025 * 1024, 44100));
+    }
+
+    #[test]
+    fn rate_change() {
+        let data = frames(SamplingFrequency::Freq8000, 10)
+            .frames(
+                10,
+                AdtsStreamConfig {
+                    sampling_frequency: SamplingFrequency::Freq16000,
+                    ..StreamBuilder::DEFAULT_CONFIG
+                },
+            )
+            .build();
+        // the first 10 frames last 1.28s, after which frames are 64ms
+        let frame = frame_at_or_before(&data, Duration::from_millis(1280 + 64 * 3)).unwrap();
+        assert_eq!(frame.index, 13);
+        assert_eq!(frame.sample_rate, 16000);
+    }
+
+    #[test]
+    fn frame_length_960() {
+        // at 48kHz, 960 sample frames are 20ms, so frame 50 starts at exactly 1s (whereas 1024
+        // sample frames would put frame 46 just before it)
+        let data = frames(SamplingFrequency::Freq48000, 100).build();
+        let frame = frame_at_or_before_with(&data, Duration::from_secs(1), FrameLength::Samples960)
+            .unwrap();
+        assert_eq!(frame.index, 50);
+        assert_eq!(frame.start.to_duration(), Duration::from_secs(1));
+        assert_eq!(
+            frame_at_or_before(&data, Duration::from_secs(1))
+                .unwrap()
+                .index,
+            46
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! Counters describing how an `AdtsParser` has coped with damaged input, and summarising the
+//! frames it found.
+
+use crate::{
+    copyright, crc, AdtsConsumer2, AdtsHeader, AdtsParseError, AdtsStreamConfig, FrameContext,
+    FrameLength, MediaTime, ProtectionIndicator, StreamBoundary,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Error recovery statistics for a single [`AdtsParser`](struct.AdtsParser.html), retrieved with
+/// [`AdtsParser::stats()`](struct.AdtsParser.html#method.stats).
+///
+/// A steadily rising `resync_events` or `garbage_bytes` count points at a problem upstream of the
+/// parser (a misbehaving encoder, or lossy transport), rather than at isolated corruption.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ParserStats {
+    /// Number of errors reported to the `AdtsConsumer`.  A single run of garbage produces one
+    /// error, however long it is.
+    pub errors: u64,
+    /// Number of times the parser regained sync with the ADTS bitstream after an error
+    pub resync_events: u64,
+    /// Total number of bytes discarded while searching for the next frame after errors
+    pub garbage_bytes: u64,
+    /// The largest number of bytes discarded in a single run of garbage
+    pub longest_garbage_run: u64,
+    /// Number of bytes (of frames and garbage alike) processed since the most recent error, or
+    /// `None` if there has been no error
+    pub bytes_since_last_error: Option<u64>,
+    /// When the most recent error was found, or `None` if there has been no error (not
+    /// serialised, since an `Instant` has no meaning outside of this process)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub last_error_at: Option<Instant>,
+    /// Position within all the data pushed into the parser at which the most recent error was
+    /// found, or `None` if there has been no error
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub last_error_offset: Option<u64>,
+    /// Media time at which the most recent error was found (the total duration of the frames
+    /// preceding it), or `None` if there has been no error
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub last_error_media_time: Option<Duration>,
+    /// Number of frames whose header was corrected by one of the parser's
+    /// [`quirks`](quirks/index.html)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub quirk_corrected_frames: u64,
+    /// Number of frames whose CRC didn't match, when checked according to the parser's
+    /// [`CrcPolicy`](crc/enum.CrcPolicy.html)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub crc_mismatches: u64,
+    /// Number of frames consisting only of a header, which are passed to the consumer with an
+    /// empty payload
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub empty_frames: u64,
+    /// Number of [`StreamBoundary`](struct.StreamBoundary.html) events reported to the consumer
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stream_boundaries: u64,
+    /// Number of ID3v2 tags skipped between frames, when enabled with
+    /// [`AdtsParser::with_id3_tags()`](struct.AdtsParser.html#method.with_id3_tags) (otherwise,
+    /// tags are counted as garbage)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub id3_tags: u64,
+    /// The label given to [`AdtsParser::with_label()`](struct.AdtsParser.html#method.with_label),
+    /// if any, identifying the stream these statistics describe
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub label: Option<String>,
+}
+impl ParserStats {
+    /// Time elapsed since the most recent error, or `None` if there has been no error.
+    ///
+    /// This is measured with the system clock; for a parser created with
+    /// [`AdtsParser::with_clock()`](struct.AdtsParser.html#method.with_clock), use
+    /// [`time_since_last_error_at()`](#method.time_since_last_error_at) instead.
+    pub fn time_since_last_error(&self) -> Option<Duration> {
+        self.time_since_last_error_at(Instant::now())
+    }
+
+    /// Time elapsed between the most recent error and `now`, or `None` if there has been no error
+    pub fn time_since_last_error_at(&self, now: Instant) -> Option<Duration> {
+        self.last_error_at.map(|t| now.saturating_duration_since(t))
+    }
+}
+
+/// The parser's error recovery state, maintaining the `ParserStats` as it goes.
+#[derive(Default)]
+pub(crate) struct Recovery {
+    pub stats: ParserStats,
+    /// `true` from an error until the next frame is successfully found
+    resyncing: bool,
+    /// bytes discarded since the current run of garbage started
+    garbage_run: u64,
+    /// frames found so far
+    frames: u64,
+    /// bytes of frames and garbage processed so far
+    position: u64,
+    /// total duration of the frames found so far
+    elapsed: MediaTime,
+    pub frame_length: FrameLength,
+    /// the configuration of the most recent frame, and whether it signalled a variable rate
+    last_frame: Option<(AdtsStreamConfig, ProtectionIndicator, bool)>,
+    /// `true` if the current run of garbage started with an ID3v2 tag
+    leading_id3: bool,
+    /// bytes of ID3v2 tags skipped (rather than discarded as garbage) since the last frame
+    tag_run: u64,
+    /// `true` if the data skipped since the last frame started with an ID3v2 tag, rather than
+    /// with garbage
+    leading_tag: bool,
+    /// the copyright identifier being collected from successive frames
+    pub copyright: copyright::Accumulator,
+}
+impl Recovery {
+    /// Notes a bitstream error, returning `true` if it should be reported to the consumer, or
+    /// `false` if it is part of a run of garbage which has already been reported.
+    pub fn error(&mut self, now: Instant) -> bool {
+        if self.resyncing {
+            return false;
+        }
+        self.resyncing = true;
+        self.copyright.reset();
+        self.garbage_run = 0;
+        self.leading_id3 = false;
+        self.stats.errors += 1;
+        self.stats.bytes_since_last_error = Some(0);
+        self.stats.last_error_at = Some(now);
+        self.stats.last_error_offset = Some(self.position);
+        self.stats.last_error_media_time = Some(self.elapsed.to_duration());
+        true
+    }
+
+    pub fn garbage(&mut self, len: usize) {
+        self.garbage_run += len as u64;
+        self.stats.garbage_bytes += len as u64;
+        self.stats.longest_garbage_run = self.stats.longest_garbage_run.max(self.garbage_run);
+        self.bytes(len);
+    }
+
+    /// Adds the duration of the given frame to the media time elapsed
+    pub fn elapse(&mut self, header: &AdtsHeader<'_>) {
+        // frames with a reserved sampling frequency have unknown duration, and are taken to have
+        // none
+        if let Some(duration) = header.frame_duration_of(self.frame_length) {
+            self.elapsed = self.elapsed + duration;
+        }
+    }
+
+    /// Notes that the data about to be discarded holds an ID3v2 tag, which is only of interest if
+    /// it is at the start of a run of garbage
+    pub fn id3(&mut self) {
+        if self.resyncing && self.garbage_run == 0 {
+            self.leading_id3 = true;
+        }
+    }
+
+    /// Notes an ID3v2 tag of the given length, skipped between frames
+    pub fn id3_tag(&mut self, len: usize) {
+        if self.tag_run == 0 && !self.resyncing {
+            self.leading_tag = true;
+        }
+        self.tag_run += len as u64;
+        self.stats.id3_tags += 1;
+        self.bytes(len);
+    }
+
+    /// Describes the stream boundary preceding the frame with the given header (whose
+    /// configuration differs from that of the previous frame), if there is one.
+    ///
+    /// A configuration change alone may just be a malformed stream, so a boundary also needs a
+    /// discontinuity: data discarded or ID3v2 tags skipped ahead of the frame, or a switch
+    /// between variable and constant rate signalling in `adts_buffer_fullness`.
+    pub fn stream_boundary(&mut self, header: &AdtsHeader<'_>) -> Option<StreamBoundary> {
+        let (previous, protection, was_vbr) = self.last_frame?;
+        let config = header.stream_config();
+        if previous == config && protection == header.protection() {
+            return None;
+        }
+        let fullness_changed = was_vbr != (header.adts_buffer_fullness() == 0x7ff);
+        if !self.resyncing && self.tag_run == 0 && !fullness_changed {
+            return None;
+        }
+        let garbage = if self.resyncing { self.garbage_run } else { 0 };
+        let discarded = garbage + self.tag_run;
+        self.stats.stream_boundaries += 1;
+        Some(StreamBoundary {
+            offset: self.position - discarded,
+            discarded,
+            id3: self.leading_tag || (self.resyncing && self.leading_id3),
+            index: self.frames,
+            time: self.elapsed.to_duration(),
+            previous,
+            config,
+            fullness_changed,
+            label: self.stats.label.clone(),
+        })
+    }
+
+    /// Remembers the configuration of a frame passed to the consumer
+    pub fn last_frame(&mut self, header: &AdtsHeader<'_>) {
+        self.last_frame = Some((
+            header.stream_config(),
+            header.protection(),
+            header.adts_buffer_fullness() == 0x7ff,
+        ));
+    }
+
+    pub fn frame(&mut self, len: usize) {
+        self.frames += 1;
+        self.tag_run = 0;
+        self.leading_tag = false;
+        if self.resyncing {
+            self.resyncing = false;
+            self.stats.resync_events += 1;
+        }
+        self.bytes(len);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn elapsed(&self) -> MediaTime {
+        self.elapsed
+    }
+
+    /// `true` from an error until the next frame is found
+    pub fn resyncing(&self) -> bool {
+        self.resyncing
+    }
+
+    /// Abandons any resync in progress, as when the parser is explicitly restarted
+    pub fn reset(&mut self) {
+        self.resyncing = false;
+        self.copyright.reset();
+    }
+
+    fn bytes(&mut self, len: usize) {
+        self.position += len as u64;
+        if let Some(ref mut n) = self.stats.bytes_since_last_error {
+            *n += len as u64;
+        }
+    }
+}
+
+/// Summary statistics of the frames in a stream, of the kind QC tooling reports for a capture.
+///
+/// `AdtsStreamStats` implements [`AdtsConsumer2`](trait.AdtsConsumer2.html), so it can be given
+/// to an `AdtsParser` directly.  To collect the statistics alongside other processing, call
+/// [`frame()`](#method.frame) and [`error()`](#method.error) from another consumer instead.
+///
+/// ```rust
+/// use adts_reader::{AdtsParser, AdtsStreamStats};
+/// let mut parser = AdtsParser::new(AdtsStreamStats::new());
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// let stats = &parser.consumer;
+/// assert_eq!(stats.frames, 1);
+/// assert_eq!(stats.samples, 1024);
+/// assert_eq!(stats.mean_frame_length(), Some(8.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdtsStreamStats {
+    pub frames: u64,
+    /// Total bytes of all frames, including headers
+    pub bytes: u64,
+    /// Total PCM samples coded in all frames, per channel
+    pub samples: u64,
+    /// Total duration of all frames (except any with a reserved sampling frequency, whose
+    /// duration is unknown)
+    pub duration: MediaTime,
+    /// The configuration of the most recent frame, or `None` if there have been no frames
+    pub config: Option<AdtsStreamConfig>,
+    /// Number of times the configuration was seen to differ from that of the previous frame
+    pub config_changes: u64,
+    /// Smallest `frame_length` seen, or `None` if there have been no frames
+    pub min_frame_length: Option<u16>,
+    /// Largest `frame_length` seen, or `None` if there have been no frames
+    pub max_frame_length: Option<u16>,
+    /// Counts of frames by `adts_buffer_fullness`, in 16 buckets of 128 values each, so that
+    /// bucket `n` counts values from `n * 128` to `n * 128 + 127`.  The variable rate marker
+    /// value `0x7FF` is counted in `vbr_frames` rather than here.
+    pub fullness_histogram: [u64; 16],
+    /// Number of frames with the `adts_buffer_fullness` value `0x7FF`, which signals a variable
+    /// rate stream
+    pub vbr_frames: u64,
+    /// Number of errors in the bitstream
+    pub errors: u64,
+    /// Number of frames whose CRC didn't match
+    pub crc_errors: u64,
+    frame_length: FrameLength,
+    window: Duration,
+    /// the duration and length of the most recent frames, covering at least `window` once
+    /// there is enough media
+    recent: VecDeque<(Duration, u16)>,
+    recent_duration: Duration,
+    recent_bytes: u64,
+}
+impl Default for AdtsStreamStats {
+    fn default() -> Self {
+        AdtsStreamStats {
+            frames: 0,
+            bytes: 0,
+            samples: 0,
+            duration: MediaTime::ZERO,
+            config: None,
+            config_changes: 0,
+            min_frame_length: None,
+            max_frame_length: None,
+            fullness_histogram: [0; 16],
+            vbr_frames: 0,
+            errors: 0,
+            crc_errors: 0,
+            frame_length: FrameLength::default(),
+            window: Duration::from_secs(1),
+            recent: VecDeque::new(),
+            recent_duration: Duration::ZERO,
+            recent_bytes: 0,
+        }
+    }
+}
+impl AdtsStreamStats {
+    pub fn new() -> AdtsStreamStats {
+        AdtsStreamStats::default()
+    }
+
+    /// Sets the number of samples per `raw_data_block` used to count samples and to time the
+    /// bitrate window (by default, 1024)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// Sets the span of media time over which [`bitrate()`](#method.bitrate) is measured (by
+    /// default, one second)
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Adds the frame with the given header to the statistics
+    pub fn frame(&mut self, header: &AdtsHeader<'_>) {
+        let len = header.frame_length();
+        self.frames += 1;
+        self.bytes += u64::from(len);
+        self.samples += header.samples_per_frame_of(self.frame_length);
+        let config = header.stream_config();
+        if self.config.is_some_and(|previous| previous != config) {
+            self.config_changes += 1;
+        }
+        self.config = Some(config);
+        self.min_frame_length = Some(self.min_frame_length.map_or(len, |min| min.min(len)));
+        self.max_frame_length = Some(self.max_frame_length.map_or(len, |max| max.max(len)));
+        match header.adts_buffer_fullness() {
+            0x7ff => self.vbr_frames += 1,
+            fullness => self.fullness_histogram[usize::from(fullness >> 7)] += 1,
+        }
+
+        // frames with a reserved sampling frequency have unknown duration, and are taken to have
+        // none
+        let duration = header.frame_duration_of(self.frame_length);
+        if let Some(duration) = duration {
+            self.duration = self.duration + duration;
+        }
+        let duration = duration.map_or(Duration::ZERO, |d| d.to_duration());
+        self.recent.push_back((duration, len));
+        self.recent_duration += duration;
+        self.recent_bytes += u64::from(len);
+        while let Some(&(oldest, oldest_len)) = self.recent.front() {
+            if self.recent_duration - oldest < self.window {
+                break;
+            }
+            self.recent.pop_front();
+            self.recent_duration -= oldest;
+            self.recent_bytes -= u64::from(oldest_len);
+        }
+    }
+
+    /// Counts an error in the bitstream
+    pub fn error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// The mean `frame_length`, or `None` if there have been no frames
+    pub fn mean_frame_length(&self) -> Option<f64> {
+        if self.frames == 0 {
+            None
+        } else {
+            Some(self.bytes as f64 / self.frames as f64)
+        }
+    }
+
+    /// The mean bitrate of all the frames, in bits per second, headers included, or `None` if
+    /// there are no frames of known duration
+    pub fn mean_bitrate(&self) -> Option<f64> {
+        if self.duration.is_zero() {
+            None
+        } else {
+            Some(self.bytes as f64 * 8.0 / self.duration.to_duration().as_secs_f64())
+        }
+    }
+
+    /// The bitrate, in bits per second, of the most recent frames covering the
+    /// [`window()`](#method.window) of media time, headers included.  Until that much media has
+    /// been seen, this covers all the frames so far.  `None` if there are no frames of known
+    /// duration.
+    pub fn bitrate(&self) -> Option<f64> {
+        if self.recent_duration.is_zero() {
+            None
+        } else {
+            Some(self.recent_bytes as f64 * 8.0 / self.recent_duration.as_secs_f64())
+        }
+    }
+}
+impl AdtsConsumer2 for AdtsStreamStats {
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        self.frame(&frame.header);
+    }
+
+    fn on_error(&mut self, _err: AdtsParseError) {
+        self.error();
+    }
+
+    fn on_crc_error(&mut self, _frame: &FrameContext<'_>, _err: crc::CrcError) {
+        self.crc_errors += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::{AdtsParser, FrameOptions};
+
+    #[test]
+    fn stream_stats() {
+        let data = StreamBuilder::new()
+            .frames(50, StreamBuilder::DEFAULT_CONFIG)
+            .junk(5)
+            .options(FrameOptions {
+                buffer_fullness: 0x100,
+                ..FrameOptions::default()
+            })
+            .payload_len(14)
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut parser = AdtsParser::new(AdtsStreamStats::new());
+        for chunk in data.chunks(7) {
+            parser.push(chunk);
+        }
+        let stats = &parser.consumer;
+        assert_eq!(stats.frames, 52);
+        assert_eq!(stats.bytes, 50 * 11 + 2 * 21);
+        assert_eq!(stats.samples, 52 * 1024);
+        assert_eq!(stats.duration, MediaTime::new(52 * 1024, 48000));
+        assert_eq!(stats.config, Some(StreamBuilder::DEFAULT_CONFIG));
+        assert_eq!(stats.config_changes, 0);
+        let mean = 592.0 * 8.0 / (52.0 * 1024.0 / 48000.0);
+        assert!((stats.mean_bitrate().unwrap() - mean).abs() < 0.01);
+        assert_eq!(stats.min_frame_length, Some(11));
+        assert_eq!(stats.max_frame_length, Some(21));
+        assert_eq!(stats.mean_frame_length(), Some(592.0 / 52.0));
+        assert_eq!(stats.vbr_frames, 50);
+        let mut histogram = [0; 16];
+        histogram[2] = 2;
+        assert_eq!(stats.fullness_histogram, histogram);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.crc_errors, 0);
+
+        // 47 frames of 1024 samples at 48kHz are needed to cover one second
+        let expected = (45 * 11 + 2 * 21) as f64 * 8.0 / (47.0 * 1024.0 / 48000.0);
+        assert!((stats.bitrate().unwrap() - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn bitrate_window() {
+        let mut stats = AdtsStreamStats::new().window(Duration::from_millis(100));
+        assert_eq!(stats.bitrate(), None);
+        assert_eq!(stats.mean_frame_length(), None);
+        let data = StreamBuilder::new()
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let header = AdtsHeader::from_bytes(&data).unwrap();
+        stats.frame(&header);
+        assert!((stats.bitrate().unwrap() - 4125.0).abs() < 0.01);
+        for _ in 0..10 {
+            stats.frame(&header);
+        }
+        // the five most recent frames cover 106.7ms
+        assert_eq!(stats.recent.len(), 5);
+        assert!((stats.bitrate().unwrap() - 4125.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,40 @@
+//! Sequences of frames: the push parser, readers for blocking and async input, and the tools
+//! built on them for monitoring, repairing and analysing whole streams.
+//!
+//! The async front-ends, [`async_reader`](async_reader/index.html) and
+//! [`broadcast`](broadcast/index.html), need the `tokio` feature.
+//!
+//! ```rust
+//! use adts_reader::stream::follow::FollowingAdtsReader;
+//! # let file = std::io::Cursor::new(vec![]);
+//! let reader = FollowingAdtsReader::new(file);
+//! ```
+
+pub use crate::{
+    AdtsConsumer, AdtsConsumer2, AdtsFrameIter, AdtsFrameReader, AdtsParseError, AdtsParser,
+    AdtsReadError, AdtsStreamStats, AdtsWriter, Callbacks, ConfigChange, ConfigField, ErrorContext,
+    FrameContext, HeaderContext, Id3Tag, OwnedAdtsFrame, OwnedFrames, ParserLimits, ParserState,
+    ParserStats, PushSummary, RawUnsupported, StreamBoundary, StreamMetadata, Unsupported,
+    WithOffsets,
+};
+
+pub use crate::analysis;
+pub use crate::arena;
+#[cfg(feature = "tokio")]
+pub use crate::async_reader;
+#[cfg(feature = "tokio")]
+pub use crate::broadcast;
+pub use crate::chunk;
+pub use crate::clock;
+pub use crate::compare;
+pub use crate::dedup;
+pub use crate::descriptor;
+pub use crate::digest;
+pub use crate::follow;
+pub use crate::index;
+pub use crate::monitor;
+pub use crate::overview;
+pub use crate::quirks;
+pub use crate::repair;
+pub use crate::segment;
+pub use crate::tagged;
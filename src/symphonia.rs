@@ -0,0 +1,376 @@
+//! A [Symphonia](https://github.com/pdeljanov/Symphonia) `FormatReader` for ADTS streams, so that
+//! applications built on Symphonia can use this crate's framing in place of Symphonia's own.
+//!
+//! Available when the `symphonia` feature is enabled.
+//!
+//! [`AdtsFormatReader`](struct.AdtsFormatReader.html) produces one packet per `raw_data_block`,
+//! without the ADTS header, ready for an AAC decoder.  Each packet is timestamped in samples from
+//! the start of the stream, and the track's codec parameters carry the equivalent
+//! `AudioSpecificConfig` as their `extra_data`.  Data which isn't part of a frame, such as an ID3
+//! tag at the start of a file, is skipped.
+//!
+//! ```rust
+//! use adts_reader::symphonia::AdtsFormatReader;
+//! use symphonia_core::formats::{FormatOptions, FormatReader};
+//! use symphonia_core::io::MediaSourceStream;
+//! use std::io::Cursor;
+//!
+//! let frame = vec![0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+//! let source = MediaSourceStream::new(Box::new(Cursor::new(frame.repeat(2))), Default::default());
+//! let mut reader = AdtsFormatReader::try_new(source, &FormatOptions::default()).unwrap();
+//! assert_eq!(reader.tracks()[0].codec_params.sample_rate, Some(48000));
+//! let packet = reader.next_packet().unwrap();
+//! assert_eq!((packet.ts, packet.dur), (0, 1024));
+//! assert_eq!(&packet.data[..], &[0x21]);
+//! assert_eq!(reader.next_packet().unwrap().ts, 1024);
+//! assert!(reader.next_packet().is_err());
+//! ```
+//!
+//! To have a Symphonia `Probe` choose this reader for ADTS input, register it in place of
+//! Symphonia's own ADTS support, with `probe.register_all::<AdtsFormatReader>()`.
+
+use crate::asc::AudioSpecificConfig;
+use crate::{
+    AdtsFrameReader, AdtsReadError, AdtsStreamConfig, ChannelConfiguration, OwnedAdtsFrame,
+};
+use std::collections::VecDeque;
+use std::io::{self, Seek, SeekFrom};
+use symphonia_core::audio::Channels;
+use symphonia_core::codecs::{CodecParameters, CODEC_TYPE_AAC};
+use symphonia_core::errors::{decode_error, seek_error, Error, Result, SeekErrorKind};
+use symphonia_core::formats::{
+    Cue, FormatOptions, FormatReader, Packet, SeekMode, SeekTo, SeekedTo, Track,
+};
+use symphonia_core::io::{MediaSource, MediaSourceStream, ReadBytes};
+use symphonia_core::meta::{Metadata, MetadataLog};
+use symphonia_core::probe::{Descriptor, Instantiate, QueryDescriptor};
+use symphonia_core::support_format;
+use symphonia_core::units::TimeBase;
+
+/// The ID of the single track of an ADTS stream
+const TRACK_ID: u32 = 0;
+
+/// Reads packets from an ADTS stream, implementing Symphonia's `FormatReader`.
+///
+/// The blocks of a frame holding several `raw_data_block`s are returned as separate packets
+/// where they can be located, which is only in frames with a CRC; otherwise the frame's whole
+/// payload is returned as one packet, with the duration of all its blocks.
+///
+/// A change of stream configuration part way through is reported by `next_packet()` as
+/// `Error::ResetRequired`, after which the track gives the new codec parameters.  Timestamps
+/// continue to count samples, at whatever the sampling frequency was when they were taken.
+///
+/// Seeking is to the packet containing the requested time.  Seeking backwards rereads the stream
+/// from the start, so needs a seekable source.
+pub struct AdtsFormatReader {
+    frames: AdtsFrameReader<MediaSourceStream>,
+    /// the position in the source at which reading began
+    start: u64,
+    config: AdtsStreamConfig,
+    tracks: Vec<Track>,
+    cues: Vec<Cue>,
+    metadata: MetadataLog,
+    /// a frame read ahead, whose configuration differs from the packets already returned
+    pending: Option<OwnedAdtsFrame>,
+    /// packets from the frames read so far, not yet returned
+    packets: VecDeque<Packet>,
+    /// the timestamp of the first packet of the next frame read
+    next_ts: u64,
+}
+impl AdtsFormatReader {
+    /// Reads the next frame from the source, failing at the end of the stream
+    fn read_frame(&mut self) -> Result<OwnedAdtsFrame> {
+        match self.frames.next() {
+            Some(Ok(frame)) => Ok(frame),
+            Some(Err(AdtsReadError::Io(e))) => Err(Error::IoError(e)),
+            Some(Err(e)) => Err(Error::IoError(e.into())),
+            None => Err(Error::IoError(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "end of stream",
+            ))),
+        }
+    }
+
+    /// Queues the packets of the given frame
+    fn queue(&mut self, frame: &OwnedAdtsFrame) {
+        let header = frame.header();
+        let block_samples =
+            header.samples_per_frame() / u64::from(header.number_of_raw_data_blocks_in_frame());
+        match header.raw_data_blocks() {
+            Ok(blocks) => {
+                for block in blocks {
+                    self.packets.push_back(Packet::new_from_slice(
+                        TRACK_ID,
+                        self.next_ts,
+                        block_samples,
+                        block.data,
+                    ));
+                    self.next_ts += block_samples;
+                }
+            }
+            Err(_) => {
+                let dur = header.samples_per_frame();
+                self.packets.push_back(Packet::new_from_slice(
+                    TRACK_ID,
+                    self.next_ts,
+                    dur,
+                    frame.payload(),
+                ));
+                self.next_ts += dur;
+            }
+        }
+    }
+
+    /// Makes sure there is a packet waiting in `packets`, reading the next frame if need be
+    fn fill(&mut self) -> Result<()> {
+        if !self.packets.is_empty() {
+            return Ok(());
+        }
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => self.read_frame()?,
+        };
+        let config = frame.header().stream_config();
+        if config != self.config {
+            self.tracks = vec![Track::new(TRACK_ID, codec_params(&config))];
+            self.config = config;
+            self.pending = Some(frame);
+            return Err(Error::ResetRequired);
+        }
+        self.queue(&frame);
+        Ok(())
+    }
+
+    /// Restarts reading from the start of the stream
+    fn rewind(&mut self) -> Result<()> {
+        let source = self.frames.reset();
+        if !source.is_seekable() {
+            return seek_error(SeekErrorKind::ForwardOnly);
+        }
+        source.seek(SeekFrom::Start(self.start))?;
+        self.pending = None;
+        self.packets.clear();
+        self.next_ts = 0;
+        Ok(())
+    }
+}
+
+/// Symphonia's codec parameters for the given stream configuration
+fn codec_params(config: &AdtsStreamConfig) -> CodecParameters {
+    let mut params = CodecParameters::new();
+    params.for_codec(CODEC_TYPE_AAC);
+    if let Some(freq) = config.sampling_frequency.freq() {
+        params
+            .with_sample_rate(freq)
+            .with_time_base(TimeBase::new(1, freq));
+    }
+    if let Some(channels) = channels(config.channel_configuration) {
+        params.with_channels(channels);
+    }
+    let asc = AudioSpecificConfig::from_adts_config(config, false).and_then(|asc| asc.to_bytes());
+    if let Ok(asc) = asc {
+        params.with_extra_data(asc.into_boxed_slice());
+    }
+    params
+}
+
+/// The channel layout of the given configuration, as Symphonia's AAC decoder maps them
+fn channels(config: ChannelConfiguration) -> Option<Channels> {
+    let front = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+    let surround = Channels::FRONT_CENTRE | front | Channels::SIDE_LEFT | Channels::SIDE_RIGHT;
+    match config {
+        ChannelConfiguration::ObjectTypeSpecificConfig => None,
+        ChannelConfiguration::Mono => Some(Channels::FRONT_LEFT),
+        ChannelConfiguration::Stereo => Some(front),
+        ChannelConfiguration::Three => Some(Channels::FRONT_CENTRE | front),
+        ChannelConfiguration::Four => Some(Channels::FRONT_CENTRE | front | Channels::REAR_CENTRE),
+        ChannelConfiguration::Five => Some(surround),
+        ChannelConfiguration::FiveOne => Some(surround | Channels::LFE1),
+        ChannelConfiguration::SevenOne => {
+            Some(surround | Channels::FRONT_LEFT_WIDE | Channels::FRONT_RIGHT_WIDE | Channels::LFE1)
+        }
+    }
+}
+
+impl QueryDescriptor for AdtsFormatReader {
+    fn query() -> &'static [Descriptor] {
+        &[support_format!(
+            "adts",
+            "Audio Data Transport Stream (via adts-reader)",
+            &["aac", "adts"],
+            &["audio/aac", "audio/aacp"],
+            &[&[0xff, 0xf1], &[0xff, 0xf9]]
+        )]
+    }
+
+    fn score(_context: &[u8]) -> u8 {
+        255
+    }
+}
+
+impl FormatReader for AdtsFormatReader {
+    fn try_new(source: MediaSourceStream, _options: &FormatOptions) -> Result<Self> {
+        let start = source.pos();
+        let mut frames = AdtsFrameReader::new(source);
+        let first = match frames.next() {
+            Some(Ok(frame)) => frame,
+            Some(Err(AdtsReadError::Io(e))) => return Err(Error::IoError(e)),
+            _ => return decode_error("adts: no frames found"),
+        };
+        let config = first.header().stream_config();
+        let mut reader = AdtsFormatReader {
+            frames,
+            start,
+            config,
+            tracks: vec![Track::new(TRACK_ID, codec_params(&config))],
+            cues: vec![],
+            metadata: MetadataLog::default(),
+            pending: None,
+            packets: VecDeque::new(),
+            next_ts: 0,
+        };
+        reader.queue(&first);
+        Ok(reader)
+    }
+
+    fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    fn metadata(&mut self) -> Metadata<'_> {
+        self.metadata.metadata()
+    }
+
+    fn seek(&mut self, _mode: SeekMode, to: SeekTo) -> Result<SeekedTo> {
+        let required_ts = match to {
+            SeekTo::TimeStamp { ts, .. } => ts,
+            SeekTo::Time { time, .. } => match self.tracks[0].codec_params.time_base {
+                Some(time_base) => time_base.calc_timestamp(time),
+                None => return seek_error(SeekErrorKind::Unseekable),
+            },
+        };
+        let next_ts = self.packets.front().map_or(self.next_ts, |p| p.ts);
+        if required_ts < next_ts {
+            self.rewind()?;
+        }
+        loop {
+            self.fill()?;
+            let packet = self.packets.front().expect("filled");
+            if packet.ts + packet.dur > required_ts {
+                return Ok(SeekedTo {
+                    track_id: TRACK_ID,
+                    required_ts,
+                    actual_ts: packet.ts,
+                });
+            }
+            self.packets.pop_front();
+        }
+    }
+
+    fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    fn next_packet(&mut self) -> Result<Packet> {
+        self.fill()?;
+        Ok(self.packets.pop_front().expect("filled"))
+    }
+
+    /// Returns the source, positioned after whatever data has been read from it (which may be
+    /// beyond the last packet returned)
+    fn into_inner(self: Box<Self>) -> MediaSourceStream {
+        self.frames.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::{FrameOptions, SamplingFrequency};
+    use std::io::Cursor;
+    use symphonia_core::units::Time;
+
+    fn reader(data: Vec<u8>) -> AdtsFormatReader {
+        let source = MediaSourceStream::new(Box::new(Cursor::new(data)), Default::default());
+        AdtsFormatReader::try_new(source, &FormatOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn packets() {
+        let data = StreamBuilder::new()
+            .junk(5)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .options(FrameOptions {
+                crc: true,
+                ..FrameOptions::default()
+            })
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut reader = reader(data);
+        let params = &reader.tracks()[0].codec_params;
+        assert_eq!(
+            params.channels,
+            Some(Channels::FRONT_LEFT | Channels::FRONT_RIGHT)
+        );
+        // AAC-LC, 48kHz, stereo
+        assert_eq!(params.extra_data.as_deref(), Some(&[0x11, 0x90][..]));
+        let mut ts = vec![];
+        while let Ok(packet) = reader.next_packet() {
+            assert_eq!(packet.data.len(), 4);
+            ts.push(packet.ts);
+        }
+        assert_eq!(ts, [0, 1024, 2048, 3072, 4096]);
+    }
+
+    #[test]
+    fn config_change() {
+        let config = StreamBuilder::DEFAULT_CONFIG;
+        let data = StreamBuilder::new()
+            .frames(1, config)
+            .frames(
+                1,
+                AdtsStreamConfig {
+                    sampling_frequency: SamplingFrequency::Freq44100,
+                    ..config
+                },
+            )
+            .build();
+        let mut reader = reader(data);
+        reader.next_packet().unwrap();
+        assert!(matches!(reader.next_packet(), Err(Error::ResetRequired)));
+        assert_eq!(reader.tracks()[0].codec_params.sample_rate, Some(44100));
+        assert_eq!(reader.next_packet().unwrap().ts, 1024);
+    }
+
+    #[test]
+    fn seek() {
+        let data = StreamBuilder::new()
+            .frames(10, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let mut reader = reader(data);
+        let seeked = reader
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: 5000,
+                    track_id: TRACK_ID,
+                },
+            )
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 4096);
+        assert_eq!(reader.next_packet().unwrap().ts, 4096);
+        // backwards, by time
+        let seeked = reader
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::new(0, 0.05),
+                    track_id: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(seeked.actual_ts, 2048);
+        assert_eq!(reader.next_packet().unwrap().ts, 2048);
+    }
+}
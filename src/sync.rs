@@ -0,0 +1,73 @@
+//! Searching for the sync-word which starts each frame.
+
+/// Finds the first ADTS sync-word in the given data, returning its position.
+///
+/// A sync-word is 12 set bits starting on a byte boundary, followed after the `ID` bit by a
+/// `layer` field of 0, as every ADTS header has.  That is only a candidate for the start of a
+/// frame: the same bits can turn up by chance within a frame's payload, or in junk preceding the
+/// stream, so parsing the header from that position may still fail.
+///
+/// The search looks for `0xff` bytes first (using the `memchr` crate when the `memchr` feature is
+/// enabled), and checks the bits following only at those positions, so long runs of data without
+/// frames are skipped quickly.
+///
+/// ```rust
+/// use adts_reader::find_syncword;
+/// assert_eq!(find_syncword(&[0x49, 0x44, 0xff, 0x00, 0xff, 0xf1, 0x4c]), Some(4));
+/// assert_eq!(find_syncword(&[0x00, 0xff]), None);
+/// ```
+pub fn find_syncword(buf: &[u8]) -> Option<usize> {
+    find(buf, false)
+}
+
+/// As for `find_syncword()`, but also accepting a `0xff` in the final byte, where the rest of a
+/// sync-word may follow in data not yet seen
+pub(crate) fn find_possible_sync(buf: &[u8]) -> Option<usize> {
+    find(buf, true)
+}
+
+fn find(buf: &[u8], partial: bool) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(i) = find_ff(&buf[pos..]) {
+        let i = pos + i;
+        match buf.get(i + 1) {
+            Some(b) if b & 0xf6 == 0xf0 => return Some(i),
+            Some(_) => pos = i + 1,
+            None => return Some(i).filter(|_| partial),
+        }
+    }
+    None
+}
+
+#[cfg(feature = "memchr")]
+fn find_ff(buf: &[u8]) -> Option<usize> {
+    memchr::memchr(0xff, buf)
+}
+
+#[cfg(not(feature = "memchr"))]
+fn find_ff(buf: &[u8]) -> Option<usize> {
+    buf.iter().position(|&b| b == 0xff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maybe_sync;
+
+    #[test]
+    fn matches_maybe_sync() {
+        let mut data = vec![];
+        for b in 0..=255u8 {
+            data.extend_from_slice(&[0xff, b, 0xff, 0xff, b, 0x12]);
+        }
+        for start in 0..data.len() {
+            let buf = &data[start..];
+            let expected = (0..buf.len()).find(|&i| maybe_sync(&buf[i..]));
+            assert_eq!(find_possible_sync(buf), expected);
+            let complete = expected.filter(|&i| i + 1 < buf.len());
+            assert_eq!(find_syncword(buf), complete);
+        }
+        assert_eq!(find_syncword(&[]), None);
+        assert_eq!(find_possible_sync(&[0x00, 0xff]), Some(1));
+    }
+}
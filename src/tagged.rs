@@ -0,0 +1,203 @@
+//! Propagation of caller-supplied metadata (arrival time, network source, and so on) from the
+//! chunks of data pushed into a parser to the frames they complete.
+//!
+//! A frame may be split across several chunks, for example several UDP datagrams.  Each frame is
+//! tagged with the metadata of the chunk holding its final byte, since that is when the frame
+//! became available.
+
+use crate::clock::{Clock, SystemClock};
+use crate::{
+    AdtsConsumer, AdtsParseError, AdtsParser, AdtsStreamConfig, AudioObjectType,
+    ChannelConfiguration, MpegVersion, Originality, ParserStats, ProtectionIndicator,
+    SamplingFrequency,
+};
+
+/// Receives events from a [`TaggedParser`](struct.TaggedParser.html), each with the metadata of
+/// the chunk of data whose arrival produced it.
+pub trait TaggedConsumer<T> {
+    /// Called when a new configuration is found within the ADTS bitstream, as for
+    /// `AdtsConsumer::new_config()`
+    fn new_config(&mut self, tag: &T, config: AdtsStreamConfig, protection: ProtectionIndicator);
+
+    /// Called with the ADTS frame payload, and frame-specific header values
+    fn payload(&mut self, tag: &T, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]);
+
+    /// Called if the parser encounters an error in the ADTS bitstream
+    fn error(&mut self, tag: &T, err: AdtsParseError);
+}
+
+/// Adapts a `TaggedConsumer` to the `AdtsConsumer` interface, supplying the tag of the chunk
+/// currently being pushed
+struct Tagging<C, T> {
+    consumer: C,
+    tag: Option<T>,
+}
+impl<C: TaggedConsumer<T>, T> AdtsConsumer for Tagging<C, T> {
+    fn new_config(
+        &mut self,
+        mpeg_version: MpegVersion,
+        protection: ProtectionIndicator,
+        aot: AudioObjectType,
+        freq: SamplingFrequency,
+        private_bit: u8,
+        channels: ChannelConfiguration,
+        originality: Originality,
+        home: u8,
+    ) {
+        let config = AdtsStreamConfig {
+            mpeg_version,
+            audio_object_type: aot,
+            sampling_frequency: freq,
+            private_bit,
+            channel_configuration: channels,
+            originality,
+            home,
+        };
+        let tag = self.tag.as_ref().expect("events only occur during push()");
+        self.consumer.new_config(tag, config, protection);
+    }
+
+    fn payload(&mut self, buffer_fullness: u16, number_of_blocks: u8, buf: &[u8]) {
+        let tag = self.tag.as_ref().expect("events only occur during push()");
+        self.consumer
+            .payload(tag, buffer_fullness, number_of_blocks, buf);
+    }
+
+    fn error(&mut self, err: AdtsParseError) {
+        let tag = self.tag.as_ref().expect("events only occur during push()");
+        self.consumer.error(tag, err);
+    }
+}
+
+/// An [`AdtsParser`](../struct.AdtsParser.html) accepting a tag with each chunk of data pushed
+/// into it, which is passed on to the [`TaggedConsumer`](trait.TaggedConsumer.html) with every
+/// event that chunk produces.
+///
+/// ```rust
+/// use adts_reader::tagged::{TaggedConsumer, TaggedParser};
+/// use adts_reader::{AdtsParseError, AdtsStreamConfig, ProtectionIndicator};
+/// use std::net::SocketAddr;
+///
+/// struct Receiver;
+/// impl TaggedConsumer<SocketAddr> for Receiver {
+///     fn new_config(&mut self, _: &SocketAddr, _: AdtsStreamConfig, _: ProtectionIndicator) {}
+///     fn payload(&mut self, source: &SocketAddr, _: u16, _: u8, buf: &[u8]) {
+///         println!("{} byte frame completed by datagram from {}", buf.len(), source);
+///     }
+///     fn error(&mut self, source: &SocketAddr, err: AdtsParseError) {}
+/// }
+///
+/// let mut parser = TaggedParser::new(Receiver);
+/// let source: SocketAddr = "192.0.2.1:5004".parse().unwrap();
+/// parser.push(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21], source);
+/// ```
+pub struct TaggedParser<C, T, K = SystemClock>
+where
+    C: TaggedConsumer<T>,
+    K: Clock,
+{
+    parser: AdtsParser<Tagging<C, T>, K>,
+}
+impl<C, T> TaggedParser<C, T>
+where
+    C: TaggedConsumer<T>,
+{
+    pub fn new(consumer: C) -> TaggedParser<C, T> {
+        TaggedParser::with_clock(consumer, SystemClock)
+    }
+}
+impl<C, T, K> TaggedParser<C, T, K>
+where
+    C: TaggedConsumer<T>,
+    K: Clock,
+{
+    /// As for [`AdtsParser::with_clock()`](../struct.AdtsParser.html#method.with_clock)
+    pub fn with_clock(consumer: C, clock: K) -> TaggedParser<C, T, K> {
+        TaggedParser {
+            parser: AdtsParser::with_clock(
+                Tagging {
+                    consumer,
+                    tag: None,
+                },
+                clock,
+            ),
+        }
+    }
+
+    pub fn consumer(&self) -> &C {
+        &self.parser.consumer.consumer
+    }
+
+    pub fn consumer_mut(&mut self) -> &mut C {
+        &mut self.parser.consumer.consumer
+    }
+
+    /// Error recovery statistics for the data pushed into this parser so far
+    pub fn stats(&self) -> &ParserStats {
+        self.parser.stats()
+    }
+
+    /// As for [`AdtsParser::start()`](../struct.AdtsParser.html#method.start)
+    pub fn start(&mut self) {
+        self.parser.start()
+    }
+
+    /// Parses the given chunk of data, as for
+    /// [`AdtsParser::push()`](../struct.AdtsParser.html#method.push), passing `tag` to the
+    /// consumer with each resulting event.  Frames begun in earlier chunks but completed by this
+    /// one are given this chunk's tag.
+    pub fn push(&mut self, buf: &[u8], tag: T) {
+        self.parser.consumer.tag = Some(tag);
+        self.parser.push(buf);
+        self.parser.consumer.tag = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::StreamBuilder;
+    use crate::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        configs: Vec<u32>,
+        payloads: Vec<(u32, Vec<u8>)>,
+        errors: Vec<u32>,
+    }
+    impl TaggedConsumer<u32> for Recorder {
+        fn new_config(&mut self, tag: &u32, _: AdtsStreamConfig, _: ProtectionIndicator) {
+            self.configs.push(*tag);
+        }
+        fn payload(&mut self, tag: &u32, _: u16, _: u8, buf: &[u8]) {
+            self.payloads.push((*tag, buf.to_vec()));
+        }
+        fn error(&mut self, tag: &u32, _: AdtsParseError) {
+            self.errors.push(*tag);
+        }
+    }
+
+    #[test]
+    fn tags_follow_completing_chunk() {
+        // frames of 9, 8 and 8 bytes
+        let data = StreamBuilder::new()
+            .frame(&[1, 1])
+            .frame(&[2])
+            .frame(&[3])
+            .build();
+        let mut parser = TaggedParser::new(Recorder::default());
+        // the first frame split over two chunks, the second chunk also holding the second frame
+        parser.push(&data[..4], 10);
+        parser.push(&data[4..17], 20);
+        parser.push(&[0; 8], 30);
+        parser.push(&data[17..], 40);
+        let recorder = parser.consumer();
+        assert_eq!(recorder.configs, vec![20]);
+        assert_eq!(
+            recorder.payloads,
+            vec![(20, vec![1, 1]), (20, vec![2]), (40, vec![3])]
+        );
+        assert_eq!(recorder.errors, vec![30]);
+        assert_eq!(parser.stats().garbage_bytes, 8);
+    }
+}
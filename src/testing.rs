@@ -0,0 +1,456 @@
+//! Assembly of synthetic ADTS streams, for tests of code which consumes them.
+//!
+//! Available when the `testing` feature is enabled.
+//!
+//! ```rust
+//! use adts_reader::testing::StreamBuilder;
+//! use adts_reader::*;
+//! let mono = AdtsStreamConfig {
+//!     channel_configuration: ChannelConfiguration::Mono,
+//!     ..StreamBuilder::DEFAULT_CONFIG
+//! };
+//! let stream = StreamBuilder::new()
+//!     .frames(10, StreamBuilder::DEFAULT_CONFIG)
+//!     .junk(5)
+//!     .config_change(mono)
+//!     .truncate_last_frame()
+//!     .build();
+//! assert_eq!(AdtsFrameIter::new(&stream).filter(Result::is_ok).count(), 10);
+//! ```
+//!
+//! [`boundary_corpus()`](fn.boundary_corpus.html) goes on to divide a stream into the buffers of
+//! successive `AdtsParser::push()` calls in many different ways, for the fuzz targets.
+
+use crate::{
+    fields, write_frame, AdtsFrameIter, AdtsStreamConfig, AudioObjectType, ChannelConfiguration,
+    FrameOptions, MpegVersion, Originality, SamplingFrequency,
+};
+
+/// Builds a buffer of ADTS data from a sequence of frames, junk and other damage.
+///
+/// Unless given explicitly, the payload of each frame is `payload_len` copies of the frame's
+/// index within the stream (truncated to a byte), so that frames can be told apart.
+#[derive(Debug, Clone)]
+pub struct StreamBuilder {
+    data: Vec<u8>,
+    config: AdtsStreamConfig,
+    options: FrameOptions,
+    payload_len: usize,
+    /// start of each frame written, in order
+    offsets: Vec<usize>,
+    /// `true` if the last thing appended was a frame
+    ends_with_frame: bool,
+}
+impl StreamBuilder {
+    /// MPEG-4 AAC-LC, 48kHz stereo
+    pub const DEFAULT_CONFIG: AdtsStreamConfig = AdtsStreamConfig {
+        mpeg_version: MpegVersion::Mpeg4,
+        audio_object_type: AudioObjectType::AacLC,
+        sampling_frequency: SamplingFrequency::Freq48000,
+        private_bit: 0,
+        channel_configuration: ChannelConfiguration::Stereo,
+        originality: Originality::Original,
+        home: 0,
+    };
+
+    /// Starts an empty stream, whose frames will use
+    /// [`DEFAULT_CONFIG`](#associatedconstant.DEFAULT_CONFIG) until told otherwise
+    pub fn new() -> StreamBuilder {
+        StreamBuilder {
+            data: vec![],
+            config: StreamBuilder::DEFAULT_CONFIG,
+            options: FrameOptions::default(),
+            payload_len: 4,
+            offsets: vec![],
+            ends_with_frame: false,
+        }
+    }
+
+    /// Sets the payload length of later frames (default `4`)
+    pub fn payload_len(mut self, len: usize) -> Self {
+        self.payload_len = len;
+        self
+    }
+
+    /// Sets the per-frame header values of later frames, such as whether they have a CRC
+    pub fn options(mut self, options: FrameOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets whether later frames have a CRC, leaving their other options unchanged
+    pub fn crc(mut self, crc: bool) -> Self {
+        self.options.crc = crc;
+        self
+    }
+
+    /// Sets the configuration of later frames, without appending any
+    pub fn config(mut self, config: AdtsStreamConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Appends `count` frames with the given configuration, which later frames also use
+    pub fn frames(mut self, count: usize, config: AdtsStreamConfig) -> Self {
+        self.config = config;
+        for _ in 0..count {
+            let payload = vec![self.offsets.len() as u8; self.payload_len];
+            self = self.frame(&payload);
+        }
+        self
+    }
+
+    /// Appends a single frame with the given configuration, which later frames also use
+    pub fn config_change(self, config: AdtsStreamConfig) -> Self {
+        self.frames(1, config)
+    }
+
+    /// Appends a single frame holding the given payload
+    pub fn frame(mut self, payload: &[u8]) -> Self {
+        let frame = write_frame(&self.config, payload, &self.options)
+            .expect("frame can be written with the builder's settings");
+        self.offsets.push(self.data.len());
+        self.data.extend(frame);
+        self.ends_with_frame = true;
+        self
+    }
+
+    /// Appends `len` bytes of junk, holding no sync-word
+    pub fn junk(mut self, len: usize) -> Self {
+        self.data.extend(std::iter::repeat_n(0x55, len));
+        self.ends_with_frame = false;
+        self
+    }
+
+    /// Appends the given bytes as they are, for damage which the other methods can't produce
+    pub fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.data.extend_from_slice(bytes);
+        self.ends_with_frame = false;
+        self
+    }
+
+    /// Removes the final byte of the stream, leaving the last frame incomplete.  Panics if the
+    /// last thing appended was not a frame.
+    pub fn truncate_last_frame(mut self) -> Self {
+        assert!(self.ends_with_frame, "stream doesn't end with a frame");
+        self.data.pop();
+        self.ends_with_frame = false;
+        self
+    }
+
+    /// The byte offset of each frame appended so far, including any truncated frame
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.data
+    }
+}
+impl Default for StreamBuilder {
+    fn default() -> Self {
+        StreamBuilder::new()
+    }
+}
+
+/// A stream of data divided into the buffers given to successive calls to `AdtsParser::push()`,
+/// as read from its input by the `push_schedule` fuzz target.
+///
+/// The encoding is a big-endian `u16` count of chunks, then that many big-endian `u16` chunk
+/// lengths, then the data.  Any input decodes to some schedule: chunks reaching beyond the end
+/// of the data are cut short, and data left over after the last chunk is pushed as a final
+/// buffer.
+///
+/// ```rust
+/// use adts_reader::testing::PushSchedule;
+/// let schedule = PushSchedule {
+///     chunks: vec![2, 0],
+///     data: vec![1, 2, 3, 4],
+/// };
+/// let bytes = schedule.to_bytes();
+/// assert_eq!(bytes, [0, 2, 0, 2, 0, 0, 1, 2, 3, 4]);
+/// assert_eq!(PushSchedule::from_bytes(&bytes), schedule);
+/// assert_eq!(schedule.pushes(), [&[1, 2][..], &[], &[3, 4]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushSchedule {
+    /// The length of each buffer to push, in order
+    pub chunks: Vec<u16>,
+    pub data: Vec<u8>,
+}
+impl PushSchedule {
+    pub fn from_bytes(input: &[u8]) -> PushSchedule {
+        if input.len() < 2 {
+            return PushSchedule {
+                chunks: vec![],
+                data: input.to_vec(),
+            };
+        }
+        let count = usize::from(u16::from_be_bytes([input[0], input[1]]));
+        let lengths = input[2..].chunks_exact(2).take(count);
+        let chunks: Vec<u16> = lengths.map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+        PushSchedule {
+            data: input[2 + chunks.len() * 2..].to_vec(),
+            chunks,
+        }
+    }
+
+    /// Encodes the schedule, as read by [`from_bytes()`](#method.from_bytes).  Panics if there
+    /// are more chunks than the `u16` count can describe.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let count = u16::try_from(self.chunks.len()).expect("too many chunks to encode");
+        let mut bytes = count.to_be_bytes().to_vec();
+        for len in &self.chunks {
+            bytes.extend_from_slice(&len.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// The buffers to push, in order
+    pub fn pushes(&self) -> Vec<&[u8]> {
+        let mut rest = &self.data[..];
+        let mut pushes = vec![];
+        for &len in &self.chunks {
+            let (chunk, tail) = rest.split_at(usize::from(len).min(rest.len()));
+            pushes.push(chunk);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            pushes.push(rest);
+        }
+        pushes
+    }
+}
+
+/// Generates `count` variants of the given stream (normally a fixture of valid ADTS data), each
+/// damaged in a different way and divided into buffers differently, as a corpus for the
+/// `push_schedule` fuzz target.
+///
+/// Buffer boundaries are placed at random, at every byte, or close to the ends of frame headers
+/// (where the CRC field makes the header length vary); damage includes flipped bits, inserted
+/// junk and false sync-words, corrupted `frame_length` fields, and truncation.  The output is
+/// the same for the same `seed`.
+pub fn boundary_corpus(fixture: &[u8], count: usize, seed: u64) -> Vec<PushSchedule> {
+    let mut rng = Rng(seed | 1);
+    (0..count)
+        .map(|i| {
+            let data = Damage::ALL[i % Damage::ALL.len()].apply(fixture, &mut rng);
+            let split = Split::ALL[i / Damage::ALL.len() % Split::ALL.len()];
+            PushSchedule {
+                chunks: split.chunks(&data, &mut rng),
+                data,
+            }
+        })
+        .collect()
+}
+
+/// xorshift64*, so that corpus generation needs no extra dependencies and is reproducible
+struct Rng(u64);
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A value in `0..n`, or `0` when `n` is `0`
+    fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next() % n as u64) as usize
+        }
+    }
+}
+
+/// Start of each complete frame found in the data
+fn frame_offsets(data: &[u8]) -> Vec<usize> {
+    AdtsFrameIter::new(data)
+        .with_offsets()
+        .filter(|(_, frame)| frame.is_ok())
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum Damage {
+    None,
+    FlipBit,
+    InsertJunk,
+    FalseSync,
+    FrameLength,
+    Truncate,
+}
+impl Damage {
+    const ALL: [Damage; 6] = [
+        Damage::None,
+        Damage::FlipBit,
+        Damage::InsertJunk,
+        Damage::FalseSync,
+        Damage::FrameLength,
+        Damage::Truncate,
+    ];
+
+    fn apply(self, fixture: &[u8], rng: &mut Rng) -> Vec<u8> {
+        let mut data = fixture.to_vec();
+        if data.is_empty() {
+            return data;
+        }
+        let frames = frame_offsets(&data);
+        let frame = frames.get(rng.below(frames.len())).copied();
+        match self {
+            Damage::None => (),
+            Damage::FlipBit => {
+                let pos = rng.below(data.len());
+                data[pos] ^= 1 << rng.below(8);
+            }
+            Damage::InsertJunk => {
+                let pos = rng.below(data.len() + 1);
+                let junk: Vec<u8> = (0..1 + rng.below(8)).map(|_| rng.next() as u8).collect();
+                data.splice(pos..pos, junk);
+            }
+            Damage::FalseSync => {
+                let pos = frame.unwrap_or(0);
+                data.splice(pos..pos, [0xff, 0xf1]);
+            }
+            Damage::FrameLength => {
+                if let Some(pos) = frame {
+                    let len = rng.below(fields::FRAME_LENGTH.max() as usize + 1) as u32;
+                    fields::FRAME_LENGTH.set(&mut data[pos..], len);
+                }
+            }
+            Damage::Truncate => data.truncate(rng.below(data.len())),
+        }
+        data
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Split {
+    Whole,
+    Random,
+    HeaderEdges,
+    Bytewise,
+}
+impl Split {
+    const ALL: [Split; 4] = [
+        Split::Whole,
+        Split::Random,
+        Split::HeaderEdges,
+        Split::Bytewise,
+    ];
+
+    fn chunks(self, data: &[u8], rng: &mut Rng) -> Vec<u16> {
+        let max_chunks = usize::from(u16::MAX);
+        match self {
+            Split::Whole => vec![],
+            Split::Random => {
+                let mut chunks = vec![];
+                let mut pos = 0;
+                while pos < data.len() && chunks.len() < max_chunks {
+                    // occasionally empty, and sometimes longer than a frame
+                    let len = rng.below(64).pow(2) / 16;
+                    chunks.push(len as u16);
+                    pos += len;
+                }
+                chunks
+            }
+            Split::HeaderEdges => {
+                let mut chunks = vec![];
+                let mut pos = 0;
+                for start in frame_offsets(data) {
+                    // just short of, at, or just past the end of a 7 or 9 byte header
+                    let end = start + 6 + rng.below(5);
+                    while end > pos {
+                        let len = (end - pos).min(usize::from(u16::MAX));
+                        chunks.push(len as u16);
+                        pos += len;
+                    }
+                }
+                chunks.truncate(max_chunks);
+                chunks
+            }
+            Split::Bytewise => vec![1; data.len().min(max_chunks)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn assembly() {
+        let mono = AdtsStreamConfig {
+            channel_configuration: ChannelConfiguration::Mono,
+            ..StreamBuilder::DEFAULT_CONFIG
+        };
+        let builder = StreamBuilder::new()
+            .payload_len(2)
+            .frames(2, StreamBuilder::DEFAULT_CONFIG)
+            .junk(3)
+            .config_change(mono)
+            .frame(&[7])
+            .truncate_last_frame();
+        assert_eq!(builder.offsets(), [0, 9, 21, 30]);
+        let data = builder.build();
+        assert_eq!(data.len(), 30 + 8 - 1);
+        assert_eq!(&data[18..21], [0x55; 3]);
+        let frames: Vec<_> = AdtsFrameIter::new(&data).collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[1].as_ref().unwrap().payload(), Ok(&[1, 1][..]));
+        assert!(frames[2].is_err());
+        let third = frames[3].as_ref().unwrap();
+        assert_eq!(third.channel_configuration(), ChannelConfiguration::Mono);
+        assert_eq!(third.payload(), Ok(&[2, 2][..]));
+    }
+
+    #[derive(Default)]
+    struct Frames(Vec<Vec<u8>>);
+    impl AdtsConsumer2 for Frames {
+        fn on_frame(&mut self, frame: &FrameContext<'_>) {
+            self.0.push(frame.payload.to_vec());
+        }
+    }
+
+    #[test]
+    fn corpus() {
+        let fixture = StreamBuilder::new()
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .crc(true)
+            .frames(3, StreamBuilder::DEFAULT_CONFIG)
+            .build();
+        let corpus = boundary_corpus(&fixture, 48, 7);
+        assert_eq!(corpus.len(), 48);
+        assert_eq!(boundary_corpus(&fixture, 48, 7), corpus);
+        for (i, schedule) in corpus.iter().enumerate() {
+            assert_eq!(PushSchedule::from_bytes(&schedule.to_bytes()), *schedule);
+            assert_eq!(schedule.pushes().concat(), schedule.data);
+            let mut parser = AdtsParser::new(Frames::default());
+            for buf in schedule.pushes() {
+                parser.push(buf);
+            }
+            // the undamaged variants parse the same however they are divided
+            if i % 6 == 0 {
+                assert_eq!(schedule.data, fixture);
+                assert_eq!(parser.consumer.0.len(), 6, "variant {}", i);
+            }
+        }
+        // each division of the stream is used
+        assert!(corpus[0].chunks.is_empty());
+        assert_eq!(corpus[18].chunks, vec![1; fixture.len()]);
+        assert!(corpus[12].pushes().iter().all(|buf| buf.len() <= 15));
+    }
+
+    #[test]
+    fn arbitrary_schedule() {
+        assert_eq!(PushSchedule::from_bytes(&[1]).pushes(), [&[1][..]]);
+        let schedule = PushSchedule::from_bytes(&[0, 3, 0, 5, 9]);
+        assert_eq!(schedule.chunks, vec![5]);
+        assert_eq!(schedule.data, vec![9]);
+        assert_eq!(schedule.pushes(), [&[9][..]]);
+    }
+}
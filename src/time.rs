@@ -0,0 +1,470 @@
+//! Exact media time arithmetic.
+
+use crate::AdtsHeader;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Add;
+use std::time::Duration;
+
+/// Number of PCM samples coded in each AAC `raw_data_block`, as signalled by the
+/// `frameLengthFlag` of an `AudioSpecificConfig`.
+///
+/// The ADTS header has no way to signal this, and almost all ADTS streams use 1024 sample
+/// frames.  Where a stream is known (out of band) to come from an encoder producing 960 sample
+/// frames, the crate's timing helpers can be told so, to give correct durations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameLength {
+    /// 1024 samples per `raw_data_block` (`frameLengthFlag` of 0)
+    #[default]
+    Samples1024,
+    /// 960 samples per `raw_data_block` (`frameLengthFlag` of 1)
+    Samples960,
+}
+impl FrameLength {
+    pub fn from_frame_length_flag(flag: bool) -> FrameLength {
+        if flag {
+            FrameLength::Samples960
+        } else {
+            FrameLength::Samples1024
+        }
+    }
+
+    pub fn frame_length_flag(self) -> bool {
+        self == FrameLength::Samples960
+    }
+
+    /// Number of samples per `raw_data_block`
+    pub const fn samples(self) -> u64 {
+        match self {
+            FrameLength::Samples1024 => 1024,
+            FrameLength::Samples960 => 960,
+        }
+    }
+}
+
+/// How [`MediaTime::to_timescale()`](struct.MediaTime.html#method.to_timescale) treats times
+/// falling between two ticks of the target timescale.
+///
+/// Whichever is chosen, use it consistently: mixing rounding modes between (for example) audio
+/// and video timestamps is a classic cause of off-by-one drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards zero (truncation)
+    Down,
+    /// To the nearest tick, with times exactly half way between ticks rounded up
+    Nearest,
+    /// Away from zero
+    Up,
+}
+
+/// A point in (or span of) media time, held exactly as a count of samples at a given sampling
+/// rate.
+///
+/// Timing calculations in this crate are done with `MediaTime` values, converting to `Duration`
+/// or clock ticks only at the last moment, so that rounding errors can't accumulate over long
+/// streams.  Values at different rates compare and add exactly; the sum of values at two
+/// different rates is expressed at the least common multiple of the rates (for the rates ADTS
+/// can signal this never exceeds 28.224MHz).
+///
+/// ```rust
+/// use adts_reader::MediaTime;
+/// use std::time::Duration;
+/// let a = MediaTime::new(1024, 44100);
+/// let b = MediaTime::new(1024, 48000);
+/// assert!(a > b);
+/// // 44100 frames of 1024 samples at 44.1kHz is exactly 1024 seconds
+/// let total = (0..44100).fold(MediaTime::ZERO, |t, _| t + a);
+/// assert_eq!(total.to_duration(), Duration::from_secs(1024));
+/// ```
+#[derive(Clone, Copy)]
+pub struct MediaTime {
+    samples: u64,
+    /// Samples per second; never zero
+    rate: u32,
+}
+impl MediaTime {
+    /// Time zero (at a nominal rate of 1Hz)
+    pub const ZERO: MediaTime = MediaTime {
+        samples: 0,
+        rate: 1,
+    };
+
+    /// Panics if `rate` is zero.
+    pub const fn new(samples: u64, rate: u32) -> MediaTime {
+        assert!(rate != 0, "MediaTime rate must not be zero");
+        MediaTime { samples, rate }
+    }
+
+    /// The number of samples, at [`rate()`](#method.rate)
+    pub const fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Samples per second, which is never zero
+    pub const fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// The duration of the given number of `raw_data_block`s (1024 samples each) at the given
+    /// rate.  Panics if `rate` is zero.
+    pub const fn from_blocks(blocks: u64, rate: u32) -> MediaTime {
+        MediaTime::from_blocks_of(blocks, FrameLength::Samples1024, rate)
+    }
+
+    /// The duration of the given number of `raw_data_block`s of the given length at the given
+    /// rate.  Panics if `rate` is zero.
+    ///
+    /// ```rust
+    /// use adts_reader::{FrameLength, MediaTime};
+    /// use std::time::Duration;
+    /// let frames = MediaTime::from_blocks_of(50, FrameLength::Samples960, 48000);
+    /// assert_eq!(frames.to_duration(), Duration::from_secs(1));
+    /// ```
+    pub const fn from_blocks_of(blocks: u64, frame_length: FrameLength, rate: u32) -> MediaTime {
+        MediaTime::new(blocks * frame_length.samples(), rate)
+    }
+
+    /// Converts a `Duration` exactly, at a rate of 1GHz.  Returns `None` for durations of more
+    /// than `u64::MAX` nanoseconds (around 584 years).
+    pub fn from_duration(duration: Duration) -> Option<MediaTime> {
+        Some(MediaTime::new(
+            u64::try_from(duration.as_nanos()).ok()?,
+            1_000_000_000,
+        ))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.samples == 0
+    }
+
+    /// Converts to a `Duration`, rounding down to the nearest nanosecond.
+    pub fn to_duration(&self) -> Duration {
+        let rate = u64::from(self.rate);
+        Duration::new(
+            self.samples / rate,
+            (self.samples % rate * 1_000_000_000 / rate) as u32,
+        )
+    }
+
+    /// Converts to ticks of the 90kHz clock used for MPEG transport stream timestamps, rounding
+    /// down.
+    ///
+    /// Note that PTS/DTS values in a transport stream are only 33 bits wide; wrap the result with
+    /// `% (1 << 33)` when writing them.
+    pub fn to_90khz(&self) -> u64 {
+        self.to_timescale(90_000, Rounding::Down)
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Converts to ticks of the given timescale (e.g. the `timescale` of an MP4 track), rounding
+    /// as specified.  Returns `None` if the result would overflow a `u64`, or if `timescale` is
+    /// zero.
+    ///
+    /// ```rust
+    /// use adts_reader::{MediaTime, Rounding};
+    /// // frame 3 of a 44.1kHz stream, in a 1kHz timescale
+    /// let start = MediaTime::from_blocks(3, 44100);
+    /// assert_eq!(start.to_timescale(1000, Rounding::Down), Some(69));
+    /// assert_eq!(start.to_timescale(1000, Rounding::Nearest), Some(70));
+    /// assert_eq!(start.to_timescale(1000, Rounding::Up), Some(70));
+    /// ```
+    pub fn to_timescale(&self, timescale: u32, rounding: Rounding) -> Option<u64> {
+        if timescale == 0 {
+            return None;
+        }
+        let num = u128::from(self.samples) * u128::from(timescale);
+        let den = u128::from(self.rate);
+        let ticks = match rounding {
+            Rounding::Down => num / den,
+            Rounding::Nearest => (num * 2 + den) / (den * 2),
+            Rounding::Up => num.div_ceil(den),
+        };
+        u64::try_from(ticks).ok()
+    }
+
+    /// Adds two times, returning `None` on overflow.
+    pub fn checked_add(self, other: MediaTime) -> Option<MediaTime> {
+        if self.rate == other.rate {
+            return Some(MediaTime {
+                samples: self.samples.checked_add(other.samples)?,
+                rate: self.rate,
+            });
+        }
+        // zero values shouldn't force a change of rate
+        if other.is_zero() {
+            return Some(self);
+        }
+        if self.is_zero() {
+            return Some(other);
+        }
+        let rate = lcm(self.rate, other.rate)?;
+        let a = self.samples.checked_mul(u64::from(rate / self.rate))?;
+        let b = other.samples.checked_mul(u64::from(rate / other.rate))?;
+        Some(MediaTime {
+            samples: a.checked_add(b)?,
+            rate,
+        })
+    }
+
+    /// `samples * other.rate`, for comparisons by cross-multiplication
+    fn cross(&self, other: &MediaTime) -> u128 {
+        u128::from(self.samples) * u128::from(other.rate)
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: u32, b: u32) -> Option<u32> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+impl Add for MediaTime {
+    type Output = MediaTime;
+
+    /// Panics on overflow
+    fn add(self, other: MediaTime) -> MediaTime {
+        self.checked_add(other).expect("MediaTime overflow")
+    }
+}
+impl PartialEq for MediaTime {
+    fn eq(&self, other: &MediaTime) -> bool {
+        self.cross(other) == other.cross(self)
+    }
+}
+impl Eq for MediaTime {}
+impl PartialOrd for MediaTime {
+    fn partial_cmp(&self, other: &MediaTime) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for MediaTime {
+    fn cmp(&self, other: &MediaTime) -> Ordering {
+        self.cross(other).cmp(&other.cross(self))
+    }
+}
+impl Default for MediaTime {
+    fn default() -> Self {
+        MediaTime::ZERO
+    }
+}
+impl fmt::Debug for MediaTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MediaTime({}/{}Hz)", self.samples, self.rate)
+    }
+}
+
+/// Accumulates the timestamps of successive frames, for muxing them into containers such as
+/// MPEG-TS or MP4.
+///
+/// The time is kept exactly as a [`MediaTime`](struct.MediaTime.html), and only converted to
+/// 90kHz ticks or nanoseconds as each frame's timestamp is taken, so rounding errors don't build
+/// up over long streams.
+///
+/// ```rust
+/// use adts_reader::{AdtsFrameIter, MediaTime, PtsCounter};
+/// # let stream = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21].repeat(3);
+/// // continuing from a stream position already at 90kHz tick 900
+/// let mut pts = PtsCounter::new(MediaTime::new(900, 90_000));
+/// let ticks: Vec<u64> = AdtsFrameIter::new(&stream)
+///     .map(|header| pts.next_90khz(&header.unwrap()))
+///     .collect();
+/// assert_eq!(ticks, [900, 2820, 4740]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PtsCounter {
+    next: MediaTime,
+    frame_length: FrameLength,
+}
+impl PtsCounter {
+    /// Starts counting with the first frame given timestamp `start`
+    pub fn new(start: MediaTime) -> PtsCounter {
+        PtsCounter {
+            next: start,
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Sets the number of samples per `raw_data_block` (default 1024), since ADTS headers don't
+    /// say
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// The timestamp that the next frame will be given
+    pub fn peek(&self) -> MediaTime {
+        self.next
+    }
+
+    /// Returns the timestamp of the given frame, and advances past it.  Frames with a reserved
+    /// sampling frequency have unknown duration, and are taken to have none.
+    ///
+    /// Where the running time can't be kept exactly at a rate which is also a multiple of the
+    /// frame's sampling frequency (for instance when started from
+    /// [`MediaTime::from_duration()`](struct.MediaTime.html#method.from_duration) on a 44.1kHz
+    /// stream), it is first rounded to the nearest sample at the frame's sampling frequency.  The
+    /// timestamp returned for this frame is unaffected, but later ones may differ from the exact
+    /// sum by up to half a sample.
+    ///
+    /// Panics if the time overflows even at the frame's sampling frequency.
+    pub fn next(&mut self, header: &AdtsHeader<'_>) -> MediaTime {
+        let pts = self.next;
+        if let Some(duration) = header.frame_duration_of(self.frame_length) {
+            self.next = self
+                .next
+                .checked_add(duration)
+                .or_else(|| {
+                    let rate = duration.rate();
+                    let samples = self.next.to_timescale(rate, Rounding::Nearest)?;
+                    MediaTime::new(samples, rate).checked_add(duration)
+                })
+                .expect("MediaTime overflow");
+        }
+        pts
+    }
+
+    /// As for [`next()`](#method.next), giving the timestamp in ticks of the 90kHz MPEG-TS clock,
+    /// wrapped to the 33 bits of a PES header's PTS field
+    pub fn next_90khz(&mut self, header: &AdtsHeader<'_>) -> u64 {
+        self.next(header).to_90khz() % (1 << 33)
+    }
+
+    /// As for [`next()`](#method.next), giving the timestamp in nanoseconds (saturating at
+    /// `u64::MAX`)
+    pub fn next_nanos(&mut self, header: &AdtsHeader<'_>) -> u64 {
+        self.next(header)
+            .to_timescale(1_000_000_000, Rounding::Down)
+            .unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixed_rates() {
+        let rates = [
+            96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+        ];
+        let mut total = MediaTime::ZERO;
+        let mut nanos = 0u128;
+        for &rate in rates.iter().cycle().take(1000) {
+            let block = MediaTime::from_blocks(1, rate);
+            total = total + block;
+            nanos += 1024 * 1_000_000_000 / u128::from(rate);
+        }
+        assert_eq!(total.rate(), 28_224_000);
+        assert_eq!(total.samples() % 1024, 0);
+        // summing Durations truncated to the nanosecond per frame drifts, the exact sum does not
+        assert!(total.to_duration().as_nanos() > nanos);
+        assert!(total.to_duration().as_nanos() - nanos < 1000);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(MediaTime::new(1024, 48000), MediaTime::new(2048, 96000));
+        assert!(MediaTime::new(1024, 44100) > MediaTime::new(1024, 48000));
+        assert_eq!(MediaTime::ZERO, MediaTime::new(0, 44100));
+        assert_eq!(MediaTime::new(90, 48000).to_90khz(), 168);
+        assert_eq!(MediaTime::new(48000, 48000).to_90khz(), 90_000);
+        assert_eq!(
+            MediaTime::new(u64::MAX, 7350).to_duration(),
+            Duration::new(
+                u64::MAX / 7350,
+                (u64::MAX % 7350 * 1_000_000_000 / 7350) as u32
+            )
+        );
+        assert_eq!(
+            MediaTime::new(u64::MAX, 1).checked_add(MediaTime::new(1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn frame_lengths() {
+        for frame_length in [FrameLength::Samples1024, FrameLength::Samples960] {
+            let flag = frame_length.frame_length_flag();
+            assert_eq!(FrameLength::from_frame_length_flag(flag), frame_length);
+        }
+        assert_eq!(FrameLength::default().samples(), 1024);
+        assert_eq!(
+            MediaTime::from_blocks_of(4, FrameLength::Samples960, 48000),
+            MediaTime::new(3840, 48000)
+        );
+        assert_eq!(
+            MediaTime::from_blocks_of(4, FrameLength::Samples1024, 48000),
+            MediaTime::from_blocks(4, 48000)
+        );
+    }
+
+    #[test]
+    fn timescales() {
+        use Rounding::*;
+        // 1024 samples at 44.1kHz is 2089.795.. ticks of 90kHz
+        let frame = MediaTime::from_blocks(1, 44100);
+        assert_eq!(frame.to_90khz(), 2089);
+        assert_eq!(frame.to_timescale(90_000, Nearest), Some(2090));
+        assert_eq!(frame.to_timescale(90_000, Up), Some(2090));
+        // exact values are unaffected by rounding
+        let second = MediaTime::new(48000, 48000);
+        for rounding in [Down, Nearest, Up] {
+            assert_eq!(second.to_timescale(90_000, rounding), Some(90_000));
+            assert_eq!(second.to_timescale(1, rounding), Some(1));
+        }
+        // ties round up
+        assert_eq!(MediaTime::new(1, 2).to_timescale(1, Nearest), Some(1));
+        assert_eq!(MediaTime::new(1, 3).to_timescale(1, Nearest), Some(0));
+        assert_eq!(MediaTime::new(u64::MAX, 1).to_timescale(2, Down), None);
+        assert_eq!(second.to_timescale(0, Down), None);
+        // converting each frame start independently gives tick deltas that sum exactly to the
+        // converted total, which summing independently rounded frame durations would not
+        let starts: Vec<u64> = (0..=1000)
+            .map(|i| MediaTime::from_blocks(i, 44100).to_90khz())
+            .collect();
+        let deltas: u64 = starts.windows(2).map(|w| w[1] - w[0]).sum();
+        assert_eq!(deltas, MediaTime::from_blocks(1000, 44100).to_90khz());
+    }
+
+    #[test]
+    fn pts_counter() {
+        let config = crate::AdtsStreamConfig {
+            sampling_frequency: crate::SamplingFrequency::Freq44100,
+            ..crate::testing::StreamBuilder::DEFAULT_CONFIG
+        };
+        let frame = crate::write_frame(&config, &[0], &crate::FrameOptions::default()).unwrap();
+        let header = AdtsHeader::from_bytes(&frame).unwrap();
+        let mut pts = PtsCounter::new(MediaTime::ZERO);
+        let ticks: Vec<u64> = (0..3).map(|_| pts.next_90khz(&header)).collect();
+        // 2089.795.. ticks per frame, rounded down only once per timestamp
+        assert_eq!(ticks, [0, 2089, 4179]);
+        assert_eq!(pts.peek(), MediaTime::from_blocks(3, 44100));
+        assert_eq!(pts.next_nanos(&header), 69_659_863);
+
+        let mut pts = PtsCounter::new(MediaTime::ZERO).frame_length(FrameLength::Samples960);
+        pts.next(&header);
+        assert_eq!(pts.peek(), MediaTime::new(960, 44100));
+
+        // timestamps wrap as the 33 bit PTS field does
+        let mut pts = PtsCounter::new(MediaTime::new((1 << 33) - 1, 90_000));
+        assert_eq!(pts.next_90khz(&header), (1 << 33) - 1);
+        assert_eq!(pts.next_90khz(&header), 2088);
+
+        // a start at 1GHz has no common rate with 44.1kHz that fits in a u32, so is rounded to
+        // the nearest sample
+        let start = MediaTime::from_duration(Duration::from_millis(10)).unwrap();
+        let mut pts = PtsCounter::new(start);
+        assert_eq!(pts.next(&header), start);
+        assert_eq!(pts.peek(), MediaTime::new(441 + 1024, 44100));
+        let start = MediaTime::from_duration(Duration::new(1, 11_000)).unwrap();
+        let mut pts = PtsCounter::new(start);
+        assert_eq!(pts.next(&header), start);
+        assert_eq!(pts.next(&header), MediaTime::new(44100 + 1024, 44100));
+        assert_eq!(pts.next_nanos(&header), 1_046_439_909);
+    }
+}
@@ -0,0 +1,236 @@
+//! Checks for a single, standalone ADTS frame.
+//!
+//! Some transports deliver exactly one ADTS frame per message (a WebSocket message, or an RTP
+//! payload after reassembly, for example), in which case the streaming state kept by
+//! [`AdtsParser`](../struct.AdtsParser.html) is just overhead.
+//! [`validate_frame()`](fn.validate_frame.html) checks that such a buffer holds one complete, sane
+//! frame and nothing else.
+
+use crate::crc::{self, CrcAlgorithm};
+use crate::encryption::PayloadEncryption;
+use crate::fields;
+use crate::{AdtsHeader, AdtsHeaderError, ProtectionIndicator, SamplingFrequency};
+
+/// Problems that [`validate_frame()`](fn.validate_frame.html) may find in a buffer.
+///
+/// Further variants may be added in future releases.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FrameError {
+    /// The header fields could not be parsed at all
+    Header(AdtsHeaderError),
+    /// The 2-bit `layer` field is required to be `0` in ADTS
+    BadLayer(u8),
+    /// The sampling frequency index holds one of the reserved values
+    ReservedSamplingFrequency(SamplingFrequency),
+    /// The buffer is shorter than the `frame_length` header field says the frame should be
+    Truncated { expected: usize, actual: usize },
+    /// The buffer holds more data than the `frame_length` header field accounts for
+    TrailingData { expected: usize, actual: usize },
+    /// The CRC calculated for the frame does not match the value stored in the header
+    CrcMismatch { expected: u16, actual: u16 },
+    /// The frame consists only of its header, and `ValidateOptions::allow_empty_payload` is
+    /// `false`
+    EmptyPayload,
+}
+impl From<AdtsHeaderError> for FrameError {
+    fn from(e: AdtsHeaderError) -> Self {
+        FrameError::Header(e)
+    }
+}
+
+/// Options controlling which checks [`validate_frame_with()`](fn.validate_frame_with.html)
+/// performs.
+///
+/// Further fields may be added in future releases, so start from `ValidateOptions::default()` and
+/// change the fields of interest.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ValidateOptions {
+    /// Verify the `crc_check` value, for frames that have one (default `true`)
+    pub check_crc: bool,
+    /// Accept buffers that continue past the end of the frame (default `false`)
+    pub allow_trailing_data: bool,
+    /// The CRC implementation used when checking frames (default
+    /// [`crc::DEFAULT`](crc/static.DEFAULT.html))
+    pub crc_algorithm: &'static dyn CrcAlgorithm,
+    /// How the payload is encrypted; the CRC is not checked for encrypted payloads (default
+    /// `PayloadEncryption::Clear`)
+    pub encryption: PayloadEncryption,
+    /// Accept frames consisting only of a header, with an empty payload (default `true`, since
+    /// some encoders emit these for priming or padding)
+    pub allow_empty_payload: bool,
+}
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        ValidateOptions {
+            check_crc: true,
+            allow_trailing_data: false,
+            crc_algorithm: &crc::DEFAULT,
+            encryption: PayloadEncryption::Clear,
+            allow_empty_payload: true,
+        }
+    }
+}
+
+/// A frame which passed the checks performed by [`validate_frame()`](fn.validate_frame.html).
+#[derive(Debug)]
+pub struct ValidFrame<'buf> {
+    /// The header of the frame, giving access to all header field values
+    pub header: AdtsHeader<'buf>,
+    /// The frame payload, following the header fields
+    pub payload: &'buf [u8],
+    /// `true` if the frame has a CRC and it was checked
+    pub crc_verified: bool,
+}
+
+/// Checks that the given buffer contains exactly one complete ADTS frame, with sane header
+/// values and (if present) a correct CRC.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// let buf = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21];
+/// let frame = validate_frame(&buf).unwrap();
+/// assert_eq!(frame.header.sampling_frequency(), SamplingFrequency::Freq48000);
+/// assert_eq!(frame.payload, &[0x21]);
+/// ```
+pub fn validate_frame(buf: &[u8]) -> Result<ValidFrame<'_>, FrameError> {
+    validate_frame_with(buf, &ValidateOptions::default())
+}
+
+/// As for [`validate_frame()`](fn.validate_frame.html), but with control over the checks
+/// applied.
+pub fn validate_frame_with<'buf>(
+    buf: &'buf [u8],
+    options: &ValidateOptions,
+) -> Result<ValidFrame<'buf>, FrameError> {
+    if buf.is_empty() {
+        return Err(FrameError::Header(AdtsHeaderError::NotEnoughData {
+            expected: 7,
+            actual: 0,
+        }));
+    }
+    let header = AdtsHeader::from_bytes(buf)?;
+    let layer = fields::LAYER.get(header.buf) as u8;
+    if layer != 0 {
+        return Err(FrameError::BadLayer(layer));
+    }
+    let freq = header.sampling_frequency();
+    if freq.freq().is_none() {
+        return Err(FrameError::ReservedSamplingFrequency(freq));
+    }
+    let frame_length = header.frame_len();
+    if buf.len() < frame_length {
+        return Err(FrameError::Truncated {
+            expected: frame_length,
+            actual: buf.len(),
+        });
+    }
+    if buf.len() > frame_length && !options.allow_trailing_data {
+        return Err(FrameError::TrailingData {
+            expected: frame_length,
+            actual: buf.len(),
+        });
+    }
+    let payload = header.payload().expect("length already checked");
+    if payload.is_empty() && !options.allow_empty_payload {
+        return Err(FrameError::EmptyPayload);
+    }
+    let mut crc_verified = false;
+    // for frames with multiple raw_data_blocks, the header CRC covers a raw_data_block_position
+    // table which we don't yet model, so only single-block frames are checked
+    if options.check_crc
+        && !options.encryption.is_encrypted()
+        && header.protection() == ProtectionIndicator::CrcPresent
+        && header.number_of_raw_data_blocks_in_frame() == 1
+    {
+        let actual = options.crc_algorithm.frame_crc(buf, payload);
+        let expected = header.crc().unwrap();
+        if actual != expected {
+            return Err(FrameError::CrcMismatch { expected, actual });
+        }
+        crc_verified = true;
+    }
+    Ok(ValidFrame {
+        header,
+        payload,
+        crc_verified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{make_test_data, write_frame};
+    use bitstream_io::BitWrite;
+
+    #[test]
+    fn valid() {
+        let buf = make_test_data(|mut w| write_frame(&mut w));
+        let frame = validate_frame(&buf).unwrap();
+        assert_eq!(frame.payload, &[0b10000001]);
+        assert!(!frame.crc_verified);
+    }
+
+    #[test]
+    fn length_bounds() {
+        let buf = make_test_data(|mut w| {
+            write_frame(&mut w)?;
+            w.write(8, 0)
+        });
+        assert_eq!(
+            validate_frame(&buf).unwrap_err(),
+            FrameError::TrailingData {
+                expected: 8,
+                actual: 9
+            }
+        );
+        let opts = ValidateOptions {
+            allow_trailing_data: true,
+            ..Default::default()
+        };
+        assert!(validate_frame_with(&buf, &opts).is_ok());
+        assert_eq!(
+            validate_frame(&buf[..7]).unwrap_err(),
+            FrameError::Truncated {
+                expected: 8,
+                actual: 7
+            }
+        );
+    }
+
+    #[test]
+    fn empty_payload() {
+        let buf = [0xff, 0xf1, 0x4c, 0x80, 0x00, 0xff, 0xfc];
+        let frame = validate_frame(&buf).unwrap();
+        assert!(frame.payload.is_empty());
+        assert!(!frame.header.has_payload());
+        let strict = ValidateOptions {
+            allow_empty_payload: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_frame_with(&buf, &strict).unwrap_err(),
+            FrameError::EmptyPayload
+        );
+    }
+
+    #[test]
+    fn crc() {
+        let mut buf = vec![0xff, 0xf0, 0x4c, 0x80, 0x01, 0x5f, 0xfc, 0, 0, 0x21];
+        let crc = crc::frame_crc(&buf, &buf[9..]);
+        buf[7] = (crc >> 8) as u8;
+        buf[8] = crc as u8;
+        assert!(validate_frame(&buf).unwrap().crc_verified);
+        buf[9] ^= 1;
+        assert!(matches!(
+            validate_frame(&buf),
+            Err(FrameError::CrcMismatch { .. })
+        ));
+        let opts = ValidateOptions {
+            encryption: PayloadEncryption::SampleAes,
+            ..Default::default()
+        };
+        assert!(!validate_frame_with(&buf, &opts).unwrap().crc_verified);
+    }
+}
@@ -0,0 +1,496 @@
+//! Production of ADTS frames from a stream configuration and an AAC payload.
+
+use crate::crc::{self, CrcAlgorithm};
+use crate::encryption::PayloadEncryption;
+use crate::fields;
+use crate::{
+    AdtsStreamConfig, AudioObjectType, CopyrightIdentificationStart, MpegVersion, Originality,
+};
+
+/// Largest value that the 13-bit `frame_length` field can hold.
+const MAX_FRAME_LENGTH: usize = fields::FRAME_LENGTH.max() as usize;
+
+/// Error indicating that a frame could not be written with the given values.
+#[derive(Debug, PartialEq)]
+pub enum WriteError {
+    /// The payload is too large for the total frame length to be representable in the 13-bit
+    /// `frame_length` field
+    PayloadTooLarge { max: usize, actual: usize },
+    /// The named header field was given a value that can't be represented in the bits the
+    /// field has available
+    FieldOutOfRange { field: &'static str, value: u32 },
+    /// Writing a CRC for frames holding more than one `raw_data_block` is not supported
+    MultiBlockCrcUnsupported,
+}
+
+/// Per-frame values for the header fields that are not part of
+/// [`AdtsStreamConfig`](struct.AdtsStreamConfig.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameOptions {
+    /// Include a `crc_check` field in the header (default `false`)
+    pub crc: bool,
+    /// Value for the 11-bit `adts_buffer_fullness` field (default `0x7ff`, signalling VBR)
+    pub buffer_fullness: u16,
+    /// Number of `raw_data_block`s in the frame, between 1 and 4 inclusive (default `1`)
+    pub number_of_raw_data_blocks: u8,
+    /// either 1 or 0 (default `0`)
+    pub copyright_identification_bit: u8,
+    /// (default `Other`)
+    pub copyright_identification_start: CopyrightIdentificationStart,
+}
+impl Default for FrameOptions {
+    fn default() -> Self {
+        FrameOptions {
+            crc: false,
+            buffer_fullness: 0x7ff,
+            number_of_raw_data_blocks: 1,
+            copyright_identification_bit: 0,
+            copyright_identification_start: CopyrightIdentificationStart::Other,
+        }
+    }
+}
+
+/// Produces a single, complete ADTS frame holding the given payload.
+///
+/// The `frame_length` field is derived from the payload size, and if `options.crc` is set then
+/// the `crc_check` value is calculated over the frame.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// let config = AdtsStreamConfig {
+///     mpeg_version: MpegVersion::Mpeg4,
+///     audio_object_type: AudioObjectType::AacLC,
+///     sampling_frequency: SamplingFrequency::Freq48000,
+///     private_bit: 0,
+///     channel_configuration: ChannelConfiguration::Stereo,
+///     originality: Originality::Original,
+///     home: 0,
+/// };
+/// let frame = write_frame(&config, &[0x21], &FrameOptions::default()).unwrap();
+/// assert_eq!(frame, [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21]);
+/// ```
+pub fn write_frame(
+    config: &AdtsStreamConfig,
+    payload: &[u8],
+    options: &FrameOptions,
+) -> Result<Vec<u8>, WriteError> {
+    let mut header = encode_header(config, options, payload.len())?;
+    header.fill_crc(payload);
+    let mut frame = Vec::with_capacity(header.len() + payload.len());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+/// As for [`write_frame()`](fn.write_frame.html), but with the payload encrypted as described by
+/// `encryption`, for packaging such as HLS SAMPLE-AES.
+///
+/// The `encrypt` callback is given the part of the payload which must be encrypted (as given by
+/// [`PayloadEncryption::encrypted_range()`](encryption/enum.PayloadEncryption.html#method.encrypted_range))
+/// to transform in place.  For SAMPLE-AES this is a whole number of 16 byte blocks, to be
+/// encrypted with AES-128 in CBC mode, restarting from the key's IV for every frame.  The
+/// callback is not called for frames too short to have any encrypted part.
+///
+/// The header, including the `frame_length`, is left in the clear, and any `crc_check` value is
+/// calculated over the payload before encryption, as the decoder will see it.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// use adts_reader::encryption::PayloadEncryption;
+/// # let config = AdtsStreamConfig {
+/// #     mpeg_version: MpegVersion::Mpeg4,
+/// #     audio_object_type: AudioObjectType::AacLC,
+/// #     sampling_frequency: SamplingFrequency::Freq48000,
+/// #     private_bit: 0,
+/// #     channel_configuration: ChannelConfiguration::Stereo,
+/// #     originality: Originality::Original,
+/// #     home: 0,
+/// # };
+/// let payload = [0x21; 40];
+/// let frame = write_encrypted_frame(
+///     &config,
+///     &payload,
+///     &FrameOptions::default(),
+///     PayloadEncryption::SampleAes,
+///     |blocks| {
+///         // cipher.encrypt_cbc(blocks), for example
+///         assert_eq!(blocks.len(), 16);
+///     },
+/// )
+/// .unwrap();
+/// assert_eq!(frame.len(), 7 + 40);
+/// ```
+pub fn write_encrypted_frame<F>(
+    config: &AdtsStreamConfig,
+    payload: &[u8],
+    options: &FrameOptions,
+    encryption: PayloadEncryption,
+    mut encrypt: F,
+) -> Result<Vec<u8>, WriteError>
+where
+    F: FnMut(&mut [u8]),
+{
+    let mut frame = write_frame(config, payload, options)?;
+    let header_len = frame.len() - payload.len();
+    let range = encryption.encrypted_range(payload.len());
+    if !range.is_empty() {
+        encrypt(&mut frame[header_len + range.start..header_len + range.end]);
+    }
+    Ok(frame)
+}
+
+/// The header bytes produced by [`encode_header()`](fn.encode_header.html); dereferences to a
+/// slice of either 7 bytes, or 9 if a CRC is included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodedHeader {
+    bytes: [u8; 9],
+    len: usize,
+}
+impl EncodedHeader {
+    /// Calculates the `crc_check` value for the given payload and stores it in the header.  Has
+    /// no effect on headers without a CRC.
+    pub fn fill_crc(&mut self, payload: &[u8]) {
+        self.fill_crc_with(payload, &crc::DEFAULT)
+    }
+
+    /// As for [`fill_crc()`](#method.fill_crc), but using the given CRC implementation.
+    pub fn fill_crc_with(&mut self, payload: &[u8], algorithm: &dyn CrcAlgorithm) {
+        if self.len == 9 {
+            let crc = algorithm.frame_crc(&self.bytes, payload);
+            fields::CRC_CHECK.set(&mut self.bytes, crc.into());
+        }
+    }
+}
+impl std::ops::Deref for EncodedHeader {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Produces the header for a frame that will hold a payload of the given length, without needing
+/// the payload itself.
+///
+/// This is a pure function, useful for writing headers directly into pre-allocated buffers.  If
+/// `options.crc` is set, the `crc_check` field is left zeroed, since it depends on the payload
+/// bytes; use [`EncodedHeader::fill_crc()`](struct.EncodedHeader.html#method.fill_crc) once the
+/// payload is available.
+///
+/// ```rust
+/// # use adts_reader::*;
+/// # let config = AdtsStreamConfig {
+/// #     mpeg_version: MpegVersion::Mpeg4,
+/// #     audio_object_type: AudioObjectType::AacLC,
+/// #     sampling_frequency: SamplingFrequency::Freq48000,
+/// #     private_bit: 0,
+/// #     channel_configuration: ChannelConfiguration::Stereo,
+/// #     originality: Originality::Original,
+/// #     home: 0,
+/// # };
+/// let header = encode_header(&config, &FrameOptions::default(), 1).unwrap();
+/// assert_eq!(&header[..], &[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc]);
+/// ```
+pub fn encode_header(
+    config: &AdtsStreamConfig,
+    options: &FrameOptions,
+    payload_len: usize,
+) -> Result<EncodedHeader, WriteError> {
+    let header_len: usize = if options.crc { 9 } else { 7 };
+    let frame_length = match header_len.checked_add(payload_len) {
+        Some(len) if len <= MAX_FRAME_LENGTH => len,
+        _ => {
+            return Err(WriteError::PayloadTooLarge {
+                max: MAX_FRAME_LENGTH - header_len,
+                actual: payload_len,
+            })
+        }
+    };
+    let mut bytes = [0; 9];
+    bytes[..7].copy_from_slice(&pack_header(config, options, frame_length as u16)?);
+    Ok(EncodedHeader {
+        bytes,
+        len: header_len,
+    })
+}
+
+pub(crate) fn check_range(field: &fields::Field, value: u32) -> Result<(), WriteError> {
+    if value > field.max() {
+        Err(WriteError::FieldOutOfRange {
+            field: field.name,
+            value,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Packs the 7 bytes of fixed and variable header which precede any `crc_check` field.
+pub(crate) fn pack_header(
+    config: &AdtsStreamConfig,
+    options: &FrameOptions,
+    frame_length: u16,
+) -> Result<[u8; 7], WriteError> {
+    check_range(&fields::PRIVATE_BIT, config.private_bit.into())?;
+    check_range(&fields::HOME, config.home.into())?;
+    check_range(
+        &fields::COPYRIGHT_IDENTIFICATION_BIT,
+        options.copyright_identification_bit.into(),
+    )?;
+    check_range(
+        &fields::ADTS_BUFFER_FULLNESS,
+        options.buffer_fullness.into(),
+    )?;
+    check_range(&fields::FRAME_LENGTH, frame_length.into())?;
+    let blocks = options.number_of_raw_data_blocks;
+    if !(1..=4).contains(&blocks) {
+        return Err(WriteError::FieldOutOfRange {
+            field: "number_of_raw_data_blocks",
+            value: blocks.into(),
+        });
+    }
+    if options.crc && blocks > 1 {
+        return Err(WriteError::MultiBlockCrcUnsupported);
+    }
+
+    let mpeg_version = match config.mpeg_version {
+        MpegVersion::Mpeg2 => 1,
+        MpegVersion::Mpeg4 => 0,
+    };
+    let profile = match config.audio_object_type {
+        AudioObjectType::AacMain => 0,
+        AudioObjectType::AacLC => 1,
+        AudioObjectType::AacSSR => 2,
+        AudioObjectType::AacLTP => 3,
+    };
+    let original_copy = match config.originality {
+        Originality::Original => 0,
+        Originality::Copy => 1,
+    };
+    let copyright_start = match options.copyright_identification_start {
+        CopyrightIdentificationStart::Start => 1,
+        CopyrightIdentificationStart::Other => 0,
+    };
+    let mut buf = [0; 7];
+    fields::SYNCWORD.set(&mut buf, fields::SYNCWORD.max());
+    fields::ID.set(&mut buf, mpeg_version);
+    fields::LAYER.set(&mut buf, 0);
+    fields::PROTECTION_ABSENT.set(&mut buf, if options.crc { 0 } else { 1 });
+    fields::PROFILE.set(&mut buf, profile);
+    fields::SAMPLING_FREQUENCY_INDEX.set(&mut buf, config.sampling_frequency as u32);
+    fields::PRIVATE_BIT.set(&mut buf, config.private_bit.into());
+    fields::CHANNEL_CONFIGURATION.set(&mut buf, config.channel_configuration as u32);
+    fields::ORIGINAL_COPY.set(&mut buf, original_copy);
+    fields::HOME.set(&mut buf, config.home.into());
+    fields::COPYRIGHT_IDENTIFICATION_BIT.set(&mut buf, options.copyright_identification_bit.into());
+    fields::COPYRIGHT_IDENTIFICATION_START.set(&mut buf, copyright_start);
+    fields::FRAME_LENGTH.set(&mut buf, frame_length.into());
+    fields::ADTS_BUFFER_FULLNESS.set(&mut buf, options.buffer_fullness.into());
+    fields::NUMBER_OF_RAW_DATA_BLOCKS_IN_FRAME.set(&mut buf, u32::from(blocks - 1));
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    fn config() -> AdtsStreamConfig {
+        AdtsStreamConfig {
+            mpeg_version: MpegVersion::Mpeg2,
+            audio_object_type: AudioObjectType::AacLTP,
+            sampling_frequency: SamplingFrequency::Freq22050,
+            private_bit: 1,
+            channel_configuration: ChannelConfiguration::FiveOne,
+            originality: Originality::Copy,
+            home: 1,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let options = FrameOptions {
+            crc: true,
+            buffer_fullness: 0x123,
+            copyright_identification_bit: 1,
+            copyright_identification_start: CopyrightIdentificationStart::Start,
+            ..FrameOptions::default()
+        };
+        let frame = write_frame(&config(), &[1, 2, 3], &options).unwrap();
+        let valid = validate_frame(&frame).unwrap();
+        assert!(valid.crc_verified);
+        assert_eq!(valid.payload, &[1, 2, 3]);
+        let header = valid.header;
+        assert_eq!(header.stream_config(), config());
+        assert_eq!(header.frame_length(), 12);
+        assert_eq!(header.adts_buffer_fullness(), 0x123);
+        assert_eq!(header.copyright_identification_bit(), 1);
+        assert_eq!(
+            header.copyright_identification_start(),
+            CopyrightIdentificationStart::Start
+        );
+    }
+
+    #[test]
+    fn round_trip_all_values() {
+        // every combination of header field values written must be read back unchanged; fullness
+        // and frame length are covered by values exercising each of their bits
+        let mut count = 0;
+        for mpeg_version in [MpegVersion::Mpeg2, MpegVersion::Mpeg4] {
+            for audio_object_type in [
+                AudioObjectType::AacMain,
+                AudioObjectType::AacLC,
+                AudioObjectType::AacSSR,
+                AudioObjectType::AacLTP,
+            ] {
+                for freq in 0..16u8 {
+                    for channels in 0..8 {
+                        for bits in 0..16u8 {
+                            let config = AdtsStreamConfig {
+                                mpeg_version,
+                                audio_object_type,
+                                sampling_frequency: SamplingFrequency::try_from(freq).unwrap(),
+                                private_bit: bits & 1,
+                                channel_configuration: ChannelConfiguration::try_from(channels)
+                                    .unwrap(),
+                                originality: if bits & 2 == 0 {
+                                    Originality::Original
+                                } else {
+                                    Originality::Copy
+                                },
+                                home: bits >> 2 & 1,
+                            };
+                            for blocks in 1..=4 {
+                                let options = FrameOptions {
+                                    crc: blocks == 1 && bits & 8 != 0,
+                                    buffer_fullness: [0, 0x555, 0x2aa, 0x7ff][blocks as usize - 1],
+                                    number_of_raw_data_blocks: blocks,
+                                    copyright_identification_bit: bits >> 3 & 1,
+                                    copyright_identification_start: if bits & 4 == 0 {
+                                        CopyrightIdentificationStart::Other
+                                    } else {
+                                        CopyrightIdentificationStart::Start
+                                    },
+                                };
+                                let payload_len = [0, 0x1555, 0xaaa, 0x1ff0][blocks as usize - 1];
+                                let header = encode_header(&config, &options, payload_len).unwrap();
+                                let parsed = AdtsHeader::from_bytes(&header).unwrap();
+                                assert_eq!(parsed.stream_config(), config);
+                                assert_eq!(parsed.frame_options(), options);
+                                assert_eq!(
+                                    usize::from(parsed.payload_length().unwrap()),
+                                    payload_len
+                                );
+                                count += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(count, 2 * 4 * 16 * 8 * 16 * 4);
+    }
+
+    #[test]
+    fn header_then_crc() {
+        let options = FrameOptions {
+            crc: true,
+            ..FrameOptions::default()
+        };
+        let payload = [9, 8, 7, 6];
+        let mut header = encode_header(&config(), &options, payload.len()).unwrap();
+        assert_eq!(header.len(), 9);
+        assert_eq!(&header[7..], &[0, 0]);
+        header.fill_crc(&payload);
+        let mut frame = header.to_vec();
+        frame.extend_from_slice(&payload);
+        assert_eq!(frame, write_frame(&config(), &payload, &options).unwrap());
+        assert!(validate_frame(&frame).unwrap().crc_verified);
+    }
+
+    #[test]
+    fn encrypted() {
+        let options = FrameOptions {
+            crc: true,
+            ..FrameOptions::default()
+        };
+        let payload: Vec<u8> = (0..50).collect();
+        let clear = write_frame(&config(), &payload, &options).unwrap();
+        let mut calls = 0;
+        let frame = write_encrypted_frame(
+            &config(),
+            &payload,
+            &options,
+            PayloadEncryption::SampleAes,
+            |blocks| {
+                calls += 1;
+                assert_eq!(blocks.len(), 32);
+                blocks.iter_mut().for_each(|b| *b ^= 0xff);
+            },
+        )
+        .unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(frame.len(), clear.len());
+        // header (with the CRC of the clear payload), 16 byte leader and 2 byte tail are clear
+        assert_eq!(frame[..9 + 16], clear[..9 + 16]);
+        assert_eq!(frame[9 + 48..], clear[9 + 48..]);
+        assert!(frame[9 + 16..9 + 48]
+            .iter()
+            .zip(&clear[9 + 16..9 + 48])
+            .all(|(e, c)| *e == !*c));
+        let opts = ValidateOptions {
+            encryption: PayloadEncryption::SampleAes,
+            ..ValidateOptions::default()
+        };
+        assert!(validate_frame_with(&frame, &opts).is_ok());
+
+        // too short for any encrypted block
+        let frame = write_encrypted_frame(
+            &config(),
+            &payload[..20],
+            &options,
+            PayloadEncryption::SampleAes,
+            |_| panic!("nothing to encrypt"),
+        )
+        .unwrap();
+        assert_eq!(
+            frame,
+            write_frame(&config(), &payload[..20], &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let payload = vec![0; MAX_FRAME_LENGTH - 7];
+        assert!(write_frame(&config(), &payload, &FrameOptions::default()).is_ok());
+        let options = FrameOptions {
+            crc: true,
+            ..FrameOptions::default()
+        };
+        assert_eq!(
+            write_frame(&config(), &payload, &options),
+            Err(WriteError::PayloadTooLarge {
+                max: MAX_FRAME_LENGTH - 9,
+                actual: payload.len()
+            })
+        );
+        assert_eq!(
+            encode_header(&config(), &options, usize::MAX),
+            Err(WriteError::PayloadTooLarge {
+                max: MAX_FRAME_LENGTH - 9,
+                actual: usize::MAX
+            })
+        );
+        let options = FrameOptions {
+            buffer_fullness: 0x800,
+            ..FrameOptions::default()
+        };
+        assert_eq!(
+            write_frame(&config(), &[], &options),
+            Err(WriteError::FieldOutOfRange {
+                field: "adts_buffer_fullness",
+                value: 0x800
+            })
+        );
+    }
+}
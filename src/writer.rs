@@ -0,0 +1,385 @@
+//! Framing of raw AAC access units (as carried in MP4, for example) as an ADTS stream.
+
+use crate::asc::{AscError, AudioSpecificConfig};
+use crate::{
+    encode_header, AdtsStreamConfig, AudioObjectType, ChannelConfiguration,
+    CopyrightIdentificationStart, EncodedHeader, FrameLength, FrameOptions, MpegVersion,
+    Originality, SamplingFrequency, WriteError,
+};
+use std::io;
+
+/// Builds the header for each frame of a stream, from values set once for the whole stream.
+///
+/// By default, frames are MPEG-4, without a CRC, and signal a variable rate stream with an
+/// `adts_buffer_fullness` of `0x7FF`.
+///
+/// ```rust
+/// use adts_reader::*;
+/// let builder = AdtsHeaderBuilder::new(
+///     AudioObjectType::AacLC,
+///     SamplingFrequency::Freq48000,
+///     ChannelConfiguration::Stereo,
+/// );
+/// let header = builder.build(1).unwrap();
+/// assert_eq!(&header[..], [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdtsHeaderBuilder {
+    config: AdtsStreamConfig,
+    options: FrameOptions,
+    bitrate: Option<u32>,
+    frame_length: FrameLength,
+}
+impl AdtsHeaderBuilder {
+    pub fn new(
+        audio_object_type: AudioObjectType,
+        sampling_frequency: SamplingFrequency,
+        channel_configuration: ChannelConfiguration,
+    ) -> AdtsHeaderBuilder {
+        AdtsHeaderBuilder::from_config(AdtsStreamConfig {
+            mpeg_version: MpegVersion::Mpeg4,
+            audio_object_type,
+            sampling_frequency,
+            private_bit: 0,
+            channel_configuration,
+            originality: Originality::Original,
+            home: 0,
+        })
+    }
+
+    /// Starts from all the values of an existing stream's configuration
+    pub fn from_config(config: AdtsStreamConfig) -> AdtsHeaderBuilder {
+        AdtsHeaderBuilder {
+            config,
+            options: FrameOptions::default(),
+            bitrate: None,
+            frame_length: FrameLength::default(),
+        }
+    }
+
+    /// Starts from the configuration described by an MPEG-4 `AudioSpecificConfig`, such as the
+    /// `DecoderSpecificInfo` of an MP4 `esds` box, for repackaging MP4 audio as ADTS, including
+    /// its frame length.
+    ///
+    /// Fails if ADTS can't describe the audio, as explained for
+    /// [`AudioSpecificConfig::to_adts_config()`](asc/struct.AudioSpecificConfig.html#method.to_adts_config).
+    ///
+    /// ```rust
+    /// use adts_reader::asc::AudioSpecificConfig;
+    /// use adts_reader::AdtsHeaderBuilder;
+    /// // AAC-LC, 44.1kHz, stereo
+    /// let asc = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+    /// let header = AdtsHeaderBuilder::from_asc(&asc).unwrap().build(1).unwrap();
+    /// assert_eq!(&header[..], [0xff, 0xf1, 0x50, 0x80, 0x01, 0x1f, 0xfc]);
+    /// ```
+    pub fn from_asc(asc: &AudioSpecificConfig) -> Result<AdtsHeaderBuilder, AscError> {
+        let builder = AdtsHeaderBuilder::from_config(asc.to_adts_config()?);
+        Ok(builder.frame_length(asc.frame_length))
+    }
+
+    pub fn mpeg_version(mut self, mpeg_version: MpegVersion) -> Self {
+        self.config.mpeg_version = mpeg_version;
+        self
+    }
+
+    /// Include a `crc_check` field in every frame (default `false`)
+    pub fn crc(mut self, crc: bool) -> Self {
+        self.options.crc = crc;
+        self
+    }
+
+    /// either 1 or 0 (default `0`)
+    pub fn private_bit(mut self, private_bit: u8) -> Self {
+        self.config.private_bit = private_bit;
+        self
+    }
+
+    pub fn originality(mut self, originality: Originality) -> Self {
+        self.config.originality = originality;
+        self
+    }
+
+    /// either 1 or 0 (default `0`)
+    pub fn home(mut self, home: u8) -> Self {
+        self.config.home = home;
+        self
+    }
+
+    /// Sets the copyright identification fields of every frame
+    pub fn copyright_identification(
+        mut self,
+        bit: u8,
+        start: CopyrightIdentificationStart,
+    ) -> Self {
+        self.options.copyright_identification_bit = bit;
+        self.options.copyright_identification_start = start;
+        self
+    }
+
+    /// Uses the given `adts_buffer_fullness` value for every frame (default `0x7FF`, signalling a
+    /// variable rate stream)
+    pub fn buffer_fullness(mut self, buffer_fullness: u16) -> Self {
+        self.options.buffer_fullness = buffer_fullness;
+        self.bitrate = None;
+        self
+    }
+
+    /// Declares the stream constant rate, at the given bit rate (in bits per second, counting
+    /// whole frames, headers included).  An [`AdtsWriter`](struct.AdtsWriter.html) then
+    /// calculates each frame's `adts_buffer_fullness` by modelling the encoder's bit reservoir
+    /// over the sizes of the frames it writes.  `build()` is unaffected.
+    pub fn constant_bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// Sets the number of samples in each access unit (default 1024), which ADTS headers don't
+    /// record, but which determines each frame's share of a
+    /// [`constant_bitrate()`](#method.constant_bitrate)
+    pub fn frame_length(mut self, frame_length: FrameLength) -> Self {
+        self.frame_length = frame_length;
+        self
+    }
+
+    /// The stream configuration that will be written in every frame header
+    pub fn config(&self) -> &AdtsStreamConfig {
+        &self.config
+    }
+
+    /// Encodes the header for a frame holding a single `raw_data_block` of the given length.  Any
+    /// CRC must be filled in with
+    /// [`EncodedHeader::fill_crc()`](struct.EncodedHeader.html#method.fill_crc) once the payload
+    /// is known.
+    pub fn build(&self, payload_len: usize) -> Result<EncodedHeader, WriteError> {
+        encode_header(&self.config, &self.options, payload_len)
+    }
+
+    /// Creates a writer framing access units with headers from this builder
+    pub fn writer<W: io::Write>(self, inner: W) -> AdtsWriter<W> {
+        AdtsWriter::new(inner, self)
+    }
+}
+
+/// Writes each raw AAC access unit it is given as a complete ADTS frame, with `frame_length`,
+/// `adts_buffer_fullness` and any CRC filled in.
+///
+/// ```rust
+/// use adts_reader::*;
+/// # fn example(access_units: Vec<Vec<u8>>) -> std::io::Result<()> {
+/// let builder = AdtsHeaderBuilder::new(
+///     AudioObjectType::AacLC,
+///     SamplingFrequency::Freq44100,
+///     ChannelConfiguration::Stereo,
+/// )
+/// .crc(true);
+/// let mut writer = builder.writer(std::fs::File::create("out.aac")?);
+/// for access_unit in access_units {
+///     writer.write_access_unit(&access_unit)?;
+/// }
+/// writer.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AdtsWriter<W: io::Write> {
+    inner: W,
+    builder: AdtsHeaderBuilder,
+    reservoir: Option<BitReservoir>,
+    frames: u64,
+}
+impl<W: io::Write> AdtsWriter<W> {
+    pub fn new(inner: W, builder: AdtsHeaderBuilder) -> AdtsWriter<W> {
+        AdtsWriter {
+            inner,
+            reservoir: builder
+                .bitrate
+                .map(|bitrate| BitReservoir::new(bitrate, builder.frame_length)),
+            builder,
+            frames: 0,
+        }
+    }
+
+    /// Writes one access unit (a single `raw_data_block`) as an ADTS frame.
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if the access unit is too large for a frame, or
+    /// if the builder was given a field value that can't be represented in the header.
+    pub fn write_access_unit(&mut self, access_unit: &[u8]) -> io::Result<()> {
+        let mut options = self.builder.options.clone();
+        if let Some(ref mut reservoir) = self.reservoir {
+            let header_len = if options.crc { 9 } else { 7 };
+            if let Some(fullness) =
+                reservoir.fill(&self.builder.config, 1, header_len + access_unit.len())
+            {
+                options.buffer_fullness = fullness;
+            }
+        }
+        let mut header = encode_header(&self.builder.config, &options, access_unit.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", e)))?;
+        header.fill_crc(access_unit);
+        self.inner.write_all(&header)?;
+        self.inner.write_all(access_unit)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Number of frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Models the bit reservoir of a constant rate encoder, to give the `adts_buffer_fullness` of
+/// each frame from the frame sizes.
+///
+/// The reservoir starts full, and is assumed to hold up to 6144 bits per channel less one frame's
+/// share of the bit rate, with `raw_data_block`s of the given length.
+#[derive(Debug, Clone)]
+pub(crate) struct BitReservoir {
+    bitrate: u32,
+    frame_length: FrameLength,
+    /// the reservoir level in bits multiplied by the sampling frequency (so that each frame's
+    /// share of the bit rate is a whole number), or `None` before the first frame
+    level: Option<u64>,
+}
+impl BitReservoir {
+    pub fn new(bitrate: u32, frame_length: FrameLength) -> BitReservoir {
+        BitReservoir {
+            bitrate,
+            frame_length,
+            level: None,
+        }
+    }
+
+    /// Updates the model with a frame of the given length, returning the resulting buffer
+    /// fullness value, or `None` if the configuration doesn't give the channel count and
+    /// sampling frequency
+    pub fn fill(&mut self, config: &AdtsStreamConfig, blocks: u8, frame_len: usize) -> Option<u16> {
+        let channels = match config.channel_configuration {
+            ChannelConfiguration::ObjectTypeSpecificConfig => return None,
+            ChannelConfiguration::SevenOne => 8,
+            other => other as u64,
+        };
+        let rate = u64::from(config.sampling_frequency.freq()?);
+        let share = u64::from(self.bitrate) * self.frame_length.samples() * u64::from(blocks);
+        let capacity = (6144 * channels * rate).saturating_sub(share);
+        let level = (self.level.unwrap_or(capacity) + share)
+            .saturating_sub(frame_len as u64 * 8 * rate)
+            .min(capacity);
+        self.level = Some(level);
+        // in units of 32 bits per channel; 0x7ff is reserved to signal variable rate
+        Some((level / rate / (32 * channels)).min(0x7fe) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn write_access_units() {
+        let builder = AdtsHeaderBuilder::new(
+            AudioObjectType::AacLC,
+            SamplingFrequency::Freq44100,
+            ChannelConfiguration::Mono,
+        )
+        .mpeg_version(MpegVersion::Mpeg2)
+        .crc(true)
+        .constant_bitrate(44100);
+        let mut writer = builder.writer(vec![]);
+        // 128 byte frames of 1024 samples at 44.1kHz make exactly 44100 bits/s
+        for len in [119, 119, 219, 119] {
+            writer.write_access_unit(&vec![1; len]).unwrap();
+        }
+        assert_eq!(writer.frames(), 4);
+        let err = writer.write_access_unit(&[0; 8192]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let output = writer.into_inner();
+        let frames: Vec<_> = AdtsFrameIter::new(&output).map(Result::unwrap).collect();
+        assert_eq!(frames.len(), 4);
+        for frame in &frames {
+            assert_eq!(frame.mpeg_version(), MpegVersion::Mpeg2);
+            assert_eq!(frame.verify_crc(), Ok(()));
+        }
+        assert_eq!(
+            frames
+                .iter()
+                .map(|f| f.adts_buffer_fullness())
+                .collect::<Vec<_>>(),
+            vec![160, 160, 135, 135]
+        );
+        assert_eq!(frames[2].payload().unwrap(), &[1; 219][..]);
+
+        // at 48kbit/s, a frame of 960 samples at 48kHz is allotted 120 bytes, so these 130 byte
+        // frames each draw 80 bits from the reservoir, which then holds less than with 1024
+        let mut writer = AdtsHeaderBuilder::new(
+            AudioObjectType::AacLC,
+            SamplingFrequency::Freq48000,
+            ChannelConfiguration::Mono,
+        )
+        .constant_bitrate(48000)
+        .frame_length(FrameLength::Samples960)
+        .writer(vec![]);
+        for _ in 0..2 {
+            writer.write_access_unit(&[1; 123]).unwrap();
+        }
+        let output = writer.into_inner();
+        let fullness: Vec<_> = AdtsFrameIter::new(&output)
+            .map(|f| f.unwrap().adts_buffer_fullness())
+            .collect();
+        assert_eq!(fullness, vec![159, 157]);
+    }
+
+    #[test]
+    fn fixed_fullness() {
+        let builder = AdtsHeaderBuilder::from_config(
+            AdtsHeader::from_bytes(&[0xff, 0xf1, 0x4c, 0x80, 0x01, 0x1f, 0xfc, 0x21])
+                .unwrap()
+                .stream_config(),
+        )
+        .constant_bitrate(128_000)
+        .buffer_fullness(0x100);
+        let mut writer = builder.writer(vec![]);
+        writer.write_access_unit(&[0x21]).unwrap();
+        let output = writer.into_inner();
+        let header = AdtsHeader::from_bytes(&output).unwrap();
+        assert_eq!(header.adts_buffer_fullness(), 0x100);
+        assert_eq!(header.frame_length(), 8);
+    }
+
+    #[test]
+    fn from_asc() {
+        // HE-AAC, 24kHz core, mono, with explicit SBR signalling
+        let asc = asc::AudioSpecificConfig::parse(&[0x2b, 0x09, 0x88, 0x00]).unwrap();
+        assert_eq!(asc.output_sampling_frequency(), 48000);
+        let header = AdtsHeaderBuilder::from_asc(&asc).unwrap().build(4).unwrap();
+        let header = AdtsHeader::from_bytes(&header).unwrap();
+        assert_eq!(header.audio_object_type(), AudioObjectType::AacLC);
+        assert_eq!(header.sampling_frequency(), SamplingFrequency::Freq24000);
+        assert_eq!(header.channel_configuration(), ChannelConfiguration::Mono);
+        assert_eq!(header.frame_length(), 11);
+        let eld = asc::AudioSpecificConfig::parse(&[0xf8, 0xe6, 0x20, 0x00]).unwrap();
+        assert_eq!(
+            AdtsHeaderBuilder::from_asc(&eld).unwrap_err(),
+            asc::AscError::LowDelay {
+                audio_object_type: 39
+            }
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! End-to-end tests of the command-line tools, run against generated input.
+//!
+//! These hold the tools' command-line interface and output to the same standard of stability as
+//! the library API.
+
+#![cfg(all(feature = "cli", feature = "testing"))]
+
+use adts_reader::testing::StreamBuilder;
+use adts_reader::*;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const ADTS_FIX: &str = env!("CARGO_BIN_EXE_adts-fix");
+const ADTS_TOOL: &str = env!("CARGO_BIN_EXE_adts-tool");
+
+/// `count` frames of 12 bytes (14 with a CRC), each holding its index
+fn numbered(count: usize, crc: bool) -> Vec<u8> {
+    StreamBuilder::new()
+        .crc(crc)
+        .payload_len(5)
+        .frames(count, StreamBuilder::DEFAULT_CONFIG)
+        .build()
+}
+
+fn frames(crc: bool) -> Vec<u8> {
+    numbered(3, crc)
+}
+
+/// Frames preceded by an ID3 tag and junk, and followed by the first 10 bytes of a frame
+fn damaged() -> Vec<u8> {
+    let mut data = StreamBuilder::new()
+        .bytes(b"ID3\x04\x00\x00\x00\x00\x00\x02ab")
+        .bytes(b"junk")
+        .payload_len(5)
+        .frames(3, StreamBuilder::DEFAULT_CONFIG)
+        .frame(&[9; 20])
+        .build();
+    data.truncate(12 + 4 + 3 * 12 + 10);
+    data
+}
+
+/// A path in the temporary directory which is unique to the calling test
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("adts-reader-cli-{}-{}", std::process::id(), name))
+}
+
+fn run_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+    run_program_with_stdin(ADTS_FIX, args, stdin)
+}
+
+fn run_program_with_stdin(program: &str, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn fix_file_to_file() {
+    let input = temp_path("fix-in.aac");
+    let output = temp_path("fix-out.aac");
+    fs::write(&input, damaged()).unwrap();
+    let result = Command::new(ADTS_FIX)
+        .arg(&input)
+        .arg(&output)
+        .output()
+        .unwrap();
+    let written = fs::read(&output);
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+    assert!(result.status.success());
+    assert_eq!(written.unwrap(), frames(false));
+    assert_eq!(
+        String::from_utf8_lossy(&result.stderr).trim(),
+        "3 frames written (0 rewritten); dropped 12 bytes of ID3 tags, 4 bytes of junk, 10 bytes of truncated frame"
+    );
+}
+
+#[test]
+fn fix_as_filter() {
+    let result = run_with_stdin(&["--add-crc", "-", "-"], &damaged());
+    assert!(result.status.success());
+    assert_eq!(result.stdout, frames(true));
+    for f in result.stdout.chunks(7 + 2 + 5) {
+        assert!(validate_frame(f).is_ok());
+    }
+
+    let result = run_with_stdin(&["--strip-crc", "-", "-"], &frames(true));
+    assert!(result.status.success());
+    assert_eq!(result.stdout, frames(false));
+}
+
+#[test]
+fn fix_keeping_damage() {
+    let input = damaged();
+    let args = ["--keep-id3", "--keep-junk", "--keep-truncated", "-", "-"];
+    let result = run_with_stdin(&args, &input);
+    assert!(result.status.success());
+    assert_eq!(result.stdout, input);
+}
+
+#[test]
+fn fix_joining_inputs() {
+    // the second input repeats the last frame of the first
+    let first = temp_path("join-1.aac");
+    let second = temp_path("join-2.aac");
+    let all = numbered(4, false);
+    fs::write(&first, &all[..3 * 12]).unwrap();
+    fs::write(&second, &all[2 * 12..]).unwrap();
+    let result = Command::new(ADTS_FIX)
+        .args(["--splice-duplicates", "2"])
+        .arg(&first)
+        .arg(&second)
+        .arg("-")
+        .output()
+        .unwrap();
+    fs::remove_file(&first).unwrap();
+    fs::remove_file(&second).unwrap();
+    assert!(result.status.success());
+    assert_eq!(result.stdout, all);
+    assert!(String::from_utf8_lossy(&result.stderr).ends_with(", 1 duplicate frames\n"));
+}
+
+#[test]
+fn fix_force_channels() {
+    let result = run_with_stdin(&["--force-channels", "1", "-", "-"], &frames(false));
+    assert!(result.status.success());
+    let header = AdtsHeader::from_bytes(&result.stdout).unwrap();
+    assert_eq!(header.channel_configuration(), ChannelConfiguration::Mono);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("(3 rewritten)"));
+}
+
+#[test]
+fn fix_time_range() {
+    let input = frames(false);
+    let result = run_with_stdin(&["--end", "0.001", "-", "-"], &input);
+    assert!(result.status.success());
+    let first = AdtsHeader::from_bytes(&input).unwrap().frame_len();
+    assert_eq!(result.stdout, &input[..first]);
+    assert!(String::from_utf8_lossy(&result.stderr).ends_with(", 2 frames out of range\n"));
+}
+
+#[test]
+fn fix_usage_errors() {
+    for args in [
+        &["-"][..],
+        &["--no-such-option", "-", "-"],
+        &["--force-channels", "8", "-", "-"],
+        &["--start", "soon", "-", "-"],
+    ] {
+        let result = run_with_stdin(args, &[]);
+        assert_eq!(result.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&result.stderr).starts_with("usage: adts-fix"));
+    }
+    let missing = temp_path("missing.aac");
+    let result = Command::new(ADTS_FIX)
+        .arg(&missing)
+        .arg("-")
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&result.stderr).starts_with("adts-fix: "));
+}
+
+#[test]
+fn tool_info() {
+    let result = run_program_with_stdin(ADTS_TOOL, &["info", "-"], &damaged());
+    assert!(result.status.success());
+    let text = String::from_utf8(result.stdout).unwrap();
+    assert!(text.starts_with("configuration:    Mpeg4 AacLC Freq48000 Stereo\n"));
+    assert!(text.contains("frames:           3\n"));
+    assert!(text.contains("duration:         0.064s\n"));
+
+    let result = run_program_with_stdin(ADTS_TOOL, &["info", "--json", "-"], &frames(false));
+    assert!(result.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+    assert_eq!(json["frames"], 3);
+    assert_eq!(json["bytes"], 36);
+    assert_eq!(json["config"]["sampling_frequency"], "Freq48000");
+}
+
+#[test]
+fn tool_frames() {
+    let mut input = frames(true);
+    let second = input.len() / 3;
+    input[second + 9] ^= 1;
+    let result = run_program_with_stdin(ADTS_TOOL, &["frames", "-"], &input);
+    assert!(result.status.success());
+    let text = String::from_utf8(result.stdout).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        "   index       offset  length  blocks  fullness  crc"
+    );
+    assert_eq!(
+        lines[1],
+        "       0            0      14       1     0x7ff  ok"
+    );
+    assert_eq!(
+        lines[2],
+        "       1           14      14       1     0x7ff  MISMATCH"
+    );
+    assert_eq!(lines.len(), 4);
+
+    let result = run_program_with_stdin(ADTS_TOOL, &["frames", "--json", "-"], &input);
+    let text = String::from_utf8(result.stdout).unwrap();
+    let rows: Vec<serde_json::Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1]["offset"], 14);
+    assert_eq!(rows[1]["crc"], "mismatch");
+}
+
+#[test]
+fn tool_verify() {
+    let result = run_program_with_stdin(ADTS_TOOL, &["info", "--verify", "-"], &frames(true));
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&result.stderr),
+        "verify: 0 CRC mismatches, 0 bitstream errors, 0 configuration changes, 0 bytes of truncated frame\n"
+    );
+
+    let result = run_program_with_stdin(ADTS_TOOL, &["info", "--verify", "-"], &damaged());
+    assert_eq!(result.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&result.stderr),
+        "verify: 0 CRC mismatches, 1 bitstream errors, 0 configuration changes, 10 bytes of truncated frame\n"
+    );
+
+    for args in [&["info"][..], &["dump", "-"], &["frames", "--nope", "-"]] {
+        let result = run_program_with_stdin(ADTS_TOOL, args, &[]);
+        assert_eq!(result.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&result.stderr).starts_with("usage: adts-tool"));
+    }
+}
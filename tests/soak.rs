@@ -0,0 +1,139 @@
+//! Long-running soak test of `AdtsParser`, feeding it synthetic live input: millions of frames in
+//! randomly sized chunks, with periodic junk and configuration changes.
+//!
+//! Ignored by default, since it is slow in debug builds; run it with
+//!
+//! ```text
+//! cargo test --release --features testing --test soak -- --ignored
+//! ```
+//!
+//! The number of frames may be set with the `ADTS_SOAK_FRAMES` environment variable, and the
+//! random seed with `ADTS_SOAK_SEED`, so that a failure can be reproduced.
+
+#![cfg(feature = "testing")]
+
+use adts_reader::testing::StreamBuilder;
+use adts_reader::*;
+
+/// Largest value of the 13-bit `frame_length` field
+const MAX_FRAME_LEN: usize = 8191;
+const BATCH_FRAMES: u64 = 1000;
+
+/// xorshift64*, so that the test needs no extra dependencies and runs are reproducible
+struct Rng(u64);
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A value in `1..=max`
+    fn up_to(&mut self, max: u64) -> usize {
+        (self.next() % max + 1) as usize
+    }
+
+    /// `true` one time in `n`, on average
+    fn one_in(&mut self, n: u64) -> bool {
+        self.next() % n == 0
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    configs: u64,
+    frames: u64,
+    payload_bytes: u64,
+    errors: u64,
+}
+impl AdtsConsumer2 for Counts {
+    fn on_config(&mut self, _config: AdtsStreamConfig, _protection: ProtectionIndicator) {
+        self.configs += 1;
+    }
+
+    fn on_frame(&mut self, frame: &FrameContext<'_>) {
+        assert_eq!(frame.index, self.frames, "frame index");
+        self.frames += 1;
+        self.payload_bytes += frame.payload.len() as u64;
+    }
+
+    fn on_error(&mut self, _err: AdtsParseError) {
+        self.errors += 1;
+    }
+}
+
+fn env(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .map(|v| v.parse().expect("numeric value"))
+        .unwrap_or(default)
+}
+
+#[test]
+#[ignore]
+fn soak() {
+    let total_frames = env("ADTS_SOAK_FRAMES", 2_000_000);
+    let seed = env("ADTS_SOAK_SEED", 0x5eed);
+    let mut rng = Rng(seed | 1);
+    let configs = [
+        StreamBuilder::DEFAULT_CONFIG,
+        AdtsStreamConfig {
+            sampling_frequency: SamplingFrequency::Freq44100,
+            channel_configuration: ChannelConfiguration::Mono,
+            ..StreamBuilder::DEFAULT_CONFIG
+        },
+    ];
+    let mut config = 0;
+
+    let mut parser = AdtsParser::new(Counts::default());
+    let mut frames = 0;
+    let mut config_changes = 1;
+    let mut payload_bytes = 0;
+    let mut junk_runs = 0;
+    let mut junk_bytes = 0;
+    while frames < total_frames {
+        let mut builder = StreamBuilder::new();
+        for _ in 0..BATCH_FRAMES.min(total_frames - frames) {
+            if rng.one_in(500) {
+                config = 1 - config;
+                config_changes += 1;
+            }
+            let len = rng.up_to(600);
+            builder = builder.payload_len(len).frames(1, configs[config]);
+            frames += 1;
+            payload_bytes += len as u64;
+            // junk only ever follows a frame, so each run is reported as a single error
+            if rng.one_in(97) {
+                let len = rng.up_to(50);
+                builder = builder.junk(len);
+                junk_runs += 1;
+                junk_bytes += len as u64;
+            }
+        }
+        let data = builder.build();
+
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            // mostly small chunks, like network packets, with occasional large reads
+            let max = if rng.one_in(10) { 16384 } else { 1500 };
+            let (chunk, tail) = rest.split_at(rng.up_to(max).min(rest.len()));
+            parser.push(chunk);
+            rest = tail;
+            assert!(
+                parser.pending_bytes() <= MAX_FRAME_LEN,
+                "parser holding {} bytes",
+                parser.pending_bytes()
+            );
+        }
+    }
+    let counts = &parser.consumer;
+    let stats = parser.stats();
+    assert_eq!(counts.frames, frames, "seed {}", seed);
+    assert_eq!(counts.payload_bytes, payload_bytes, "seed {}", seed);
+    assert_eq!(counts.configs, config_changes, "seed {}", seed);
+    assert_eq!(counts.errors, junk_runs, "seed {}", seed);
+    assert_eq!(stats.errors, junk_runs, "seed {}", seed);
+    assert_eq!(stats.resync_events, junk_runs, "seed {}", seed);
+    assert_eq!(stats.garbage_bytes, junk_bytes, "seed {}", seed);
+    assert!(stats.longest_garbage_run <= 50, "seed {}", seed);
+}